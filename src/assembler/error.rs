@@ -0,0 +1,41 @@
+use std::fmt::{Debug, Display, Formatter};
+#[derive(Debug, Clone)]
+pub enum AssemblerError {
+    // line, column, what was expected, what was found
+    UnexpectedToken(usize, usize, String, String),
+    // line, column, what was expected
+    UnexpectedEndOfLine(usize, usize, String),
+    // line, column, the offending literal
+    InvalidNumber(usize, usize, String),
+    // line, column, the offending statement head
+    UnknownStatement(usize, usize, String),
+}
+
+impl Display for AssemblerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssemblerError::UnexpectedToken(line, column, expected, found) => {
+                f.write_fmt(format_args!(
+                    "{}:{}: expected {}, found `{}`",
+                    line, column, expected, found
+                ))
+            }
+            AssemblerError::UnexpectedEndOfLine(line, column, expected) => {
+                f.write_fmt(format_args!(
+                    "{}:{}: expected {}, found end of line",
+                    line, column, expected
+                ))
+            }
+            AssemblerError::InvalidNumber(line, column, literal) => f.write_fmt(format_args!(
+                "{}:{}: `{}` is not a valid number",
+                line, column, literal
+            )),
+            AssemblerError::UnknownStatement(line, column, head) => f.write_fmt(format_args!(
+                "{}:{}: unknown statement starting with `{}`",
+                line, column, head
+            )),
+        }
+    }
+}
+
+impl std::error::Error for AssemblerError {}
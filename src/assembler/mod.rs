@@ -0,0 +1,964 @@
+mod error;
+
+pub use crate::assembler::error::AssemblerError;
+use crate::math::{Fp, Fp4, P};
+use crate::parser::{Code, LineNo};
+use crate::structures::{Parameter, ReadAddr, StructuredInstruction};
+
+struct Token {
+    text: String,
+    column: usize,
+}
+
+fn tokenize(line: &str) -> Vec<Token> {
+    let chars = line.chars().collect::<Vec<char>>();
+    let mut tokens = Vec::new();
+
+    let mut cur = 0;
+    while cur < chars.len() {
+        let c = chars[cur];
+        let start = cur;
+
+        if c.is_whitespace() {
+            cur += 1;
+            continue;
+        } else if c == '/' && chars.get(cur + 1) == Some(&'/') {
+            // a comment runs to the end of the line and is kept as one token
+            cur = chars.len();
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            while cur < chars.len() && (chars[cur].is_ascii_alphanumeric() || chars[cur] == '_') {
+                cur += 1;
+            }
+        } else if chars[cur..].starts_with(&['.', '.', '=']) {
+            cur += 3;
+        } else if chars[cur..].starts_with(&['.', '.'])
+            || chars[cur..].starts_with(&['<', '<'])
+            || chars[cur..].starts_with(&['+', '='])
+        {
+            cur += 2;
+        } else {
+            cur += 1;
+        }
+
+        tokens.push(Token {
+            text: chars[start..cur]
+                .iter()
+                .collect::<String>()
+                .trim()
+                .to_string(),
+            column: start + 1,
+        });
+    }
+
+    tokens
+}
+
+struct Line {
+    tokens: Vec<Token>,
+    pos: usize,
+    line_no: usize,
+    end_column: usize,
+}
+
+impl Line {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|t| t.text.as_str())
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&str> {
+        self.tokens.get(self.pos + offset).map(|t| t.text.as_str())
+    }
+
+    fn is(&self, s: &str) -> bool {
+        self.peek() == Some(s)
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        if self.is(s) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn column(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map(|t| t.column)
+            .unwrap_or(self.end_column)
+    }
+
+    fn unexpected(&self, expected: &str) -> AssemblerError {
+        match self.tokens.get(self.pos) {
+            Some(t) => AssemblerError::UnexpectedToken(
+                self.line_no,
+                t.column,
+                expected.to_string(),
+                t.text.clone(),
+            ),
+            None => AssemblerError::UnexpectedEndOfLine(
+                self.line_no,
+                self.end_column,
+                expected.to_string(),
+            ),
+        }
+    }
+
+    fn expect(&mut self, s: &str) -> Result<(), AssemblerError> {
+        if self.eat(s) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("`{}`", s)))
+        }
+    }
+
+    fn expect_all(&mut self, tokens: &[&str]) -> Result<(), AssemblerError> {
+        for s in tokens.iter() {
+            self.expect(s)?;
+        }
+        Ok(())
+    }
+
+    fn number(&mut self) -> Result<u32, AssemblerError> {
+        match self.tokens.get(self.pos) {
+            Some(t) if t.text.starts_with(|c: char| c.is_ascii_digit()) => {
+                let v = t.text.parse::<u32>().map_err(|_| {
+                    AssemblerError::InvalidNumber(self.line_no, t.column, t.text.clone())
+                })?;
+                self.pos += 1;
+                Ok(v)
+            }
+            _ => Err(self.unexpected("a number")),
+        }
+    }
+
+    // `m[a..=b]`, returns `(a, b)`
+    fn range(&mut self) -> Result<(u32, u32), AssemblerError> {
+        self.expect_all(&["m", "["])?;
+        let start = self.number()?;
+        self.expect("..=")?;
+        let end = self.number()?;
+        self.expect("]")?;
+        Ok((start, end))
+    }
+
+    // `poseidon.state[a..=b]` or `poseidon2.state[a..=b]`, returns whether it is the Poseidon2
+    // state and the group index
+    fn poseidon_state(&mut self) -> Result<(bool, u32), AssemblerError> {
+        let poseidon2 = self.eat("poseidon2");
+        if !poseidon2 {
+            self.expect("poseidon")?;
+        }
+        self.expect_all(&[".", "state", "["])?;
+        let start = self.number()?;
+        self.expect("..=")?;
+        self.number()?;
+        self.expect("]")?;
+        Ok((poseidon2, start / 8))
+    }
+
+    // `m[a]`, `m[a].i`, `c`, or a constant tuple `(c0, c1)` / `(c0, c1, c2, c3)`
+    fn operand(&mut self) -> Result<ReadAddr, AssemblerError> {
+        if self.eat("m") {
+            self.expect("[")?;
+            let v = self.number()?;
+            self.expect("]")?;
+            if self.eat(".") {
+                let idx = self.number()?;
+                Ok(ReadAddr::RefSub(v, idx))
+            } else {
+                Ok(ReadAddr::Ref(v))
+            }
+        } else if self.eat("(") {
+            let first = self.number()?;
+            self.const_tuple(first)
+        } else {
+            let v = self.number()?;
+            Ok(ReadAddr::Const(Fp4::new(
+                Fp::new(v),
+                Fp::ZERO,
+                Fp::ZERO,
+                Fp::ZERO,
+            )))
+        }
+    }
+
+    // the rest of a constant tuple after `(first`
+    fn const_tuple(&mut self, first: u32) -> Result<ReadAddr, AssemblerError> {
+        let mut v = vec![first];
+        while self.eat(",") {
+            v.push(self.number()?);
+        }
+        if v.len() != 2 && v.len() != 4 {
+            return Err(self.unexpected("a constant with 2 or 4 components"));
+        }
+        self.expect(")")?;
+        v.resize(4, 0);
+        Ok(ReadAddr::Const(Fp4::new(
+            Fp::new(v[0]),
+            Fp::new(v[1]),
+            Fp::new(v[2]),
+            Fp::new(v[3]),
+        )))
+    }
+
+    fn eight_operands(&mut self) -> Result<[ReadAddr; 8], AssemblerError> {
+        self.expect("(")?;
+        let mut next = |i: usize| {
+            if i != 0 {
+                self.expect(",")?;
+            }
+            Ok(component_0(self.operand()?))
+        };
+        let res = [
+            next(0)?,
+            next(1)?,
+            next(2)?,
+            next(3)?,
+            next(4)?,
+            next(5)?,
+            next(6)?,
+            next(7)?,
+        ];
+        self.expect(")")?;
+        Ok(res)
+    }
+
+    fn statement(&mut self) -> Result<StructuredInstruction, AssemblerError> {
+        let column = self.column();
+        let head = match self.peek() {
+            Some(head) => head.to_string(),
+            None => return Err(self.unexpected("a statement")),
+        };
+
+        let zero_arg = [
+            ("sha_init_start", StructuredInstruction::SHA_INIT_START),
+            ("sha_init_padding", StructuredInstruction::SHA_INIT_PADDING),
+            ("sha_mix", StructuredInstruction::SHA_MIX),
+            ("sha_fini_padding", StructuredInstruction::SHA_FINI_PADDING),
+            ("wom_init", StructuredInstruction::WOM_INIT),
+            ("wom_fini", StructuredInstruction::WOM_FINI),
+            ("nop", StructuredInstruction::NOP),
+            ("padding", StructuredInstruction::PADDING),
+            ("sha_init", StructuredInstruction::__SHA_INIT__),
+        ];
+        for (name, insn) in zero_arg {
+            if head == name {
+                self.expect_all(&[name, "(", ")", ";"])?;
+                return Ok(insn);
+            }
+        }
+
+        match head.as_str() {
+            "m" => self.assignment(),
+            "poseidon" => self.poseidon(),
+            "poseidon2" => self.poseidon2(),
+            "sha_load_from_montgomery" => {
+                self.expect_all(&[&head, "("])?;
+                let r = component_0(self.operand()?);
+                self.expect_all(&[")", ";"])?;
+                Ok(StructuredInstruction::SHA_LOAD_FROM_MONTGOMERY(r))
+            }
+            "sha_load" => {
+                self.expect_all(&[&head, "("])?;
+                let r0 = self.operand()?;
+                self.expect("+")?;
+                let r1 = self.operand()?;
+                self.expect_all(&["<<", "16", ")", ";"])?;
+                Ok(StructuredInstruction::SHA_LOAD(component_01(r0, r1)))
+            }
+            "sha_fini_start" | "sha_fini" => {
+                self.expect_all(&[&head, "(", "&", "mut"])?;
+                let (ws, _) = self.range()?;
+                self.expect_all(&[")", ";"])?;
+                if head == "sha_fini" {
+                    Ok(StructuredInstruction::__SHA_FINI__(ws))
+                } else {
+                    Ok(StructuredInstruction::SHA_FINI_START(ws))
+                }
+            }
+            "set_global" => {
+                self.expect_all(&[&head, "("])?;
+                let mut next = |i: usize| {
+                    self.expect(if i == 0 { "(" } else { "," })?;
+                    let r0 = self.operand()?;
+                    self.expect(",")?;
+                    let r1 = self.operand()?;
+                    Ok(component_01(r0, r1))
+                };
+                let (r1, r2, r3, r4) = (next(0)?, next(1)?, next(2)?, next(3)?);
+                self.expect_all(&[")", ","])?;
+                let idx = self.number()?;
+                self.expect_all(&[")", ";"])?;
+                Ok(StructuredInstruction::SET_GLOBAL(r1, r2, r3, r4, idx))
+            }
+            "assert_eq" => {
+                self.expect_all(&[&head, "!", "("])?;
+                let r1 = self.operand()?;
+                self.expect(",")?;
+                let r2 = self.operand()?;
+                self.expect_all(&[")", ";"])?;
+                Ok(StructuredInstruction::EQ(r1, r2))
+            }
+            "iop" => {
+                self.expect("iop")?;
+                if self.eat(".") {
+                    self.expect_all(&["write", "("])?;
+                    let (ws, we) = self.range()?;
+                    self.expect_all(&[")", ";"])?;
+                    Ok(StructuredInstruction::__READ_IOP_BODY_BATCH__(ws, we + 1))
+                } else {
+                    self.expect_all(&["=", "read_iop", "(", "IOP_Header", "{", "count", ":"])?;
+                    let count = self.number()?;
+                    self.expect_all(&[",", "k_and_flip_flag", ":"])?;
+                    let k_and_flip_flag = self.number()?;
+                    self.expect_all(&["}", ")", ";"])?;
+                    Ok(StructuredInstruction::READ_IOP_HEADER(
+                        count,
+                        k_and_flip_flag,
+                    ))
+                }
+            }
+            "panic" => {
+                self.expect_all(&["panic", "!", "(", ")", ";"])?;
+                Ok(StructuredInstruction::__PANIC__)
+            }
+            "for" => {
+                self.expect_all(&[
+                    "for", "_", "in", "0", "..", "48", "{", "sha_mix", "(", ")", ";", "}",
+                ])?;
+                Ok(StructuredInstruction::__SHA_MIX_48__)
+            }
+            _ => Err(AssemblerError::UnknownStatement(self.line_no, column, head)),
+        }
+    }
+
+    fn assignment(&mut self) -> Result<StructuredInstruction, AssemblerError> {
+        self.expect_all(&["m", "["])?;
+        let w = self.number()?;
+
+        if self.eat("..=") {
+            self.number()?;
+            self.expect_all(&["]", "="])?;
+            return if self.eat("to_montgomery") {
+                self.expect_all(&["!", "("])?;
+                let (poseidon2, idx) = self.poseidon_state()?;
+                self.expect_all(&[")", ";"])?;
+                if poseidon2 {
+                    Ok(StructuredInstruction::POSEIDON2_STORE_TO_MONTGOMERY(idx, w))
+                } else {
+                    Ok(StructuredInstruction::POSEIDON_STORE_TO_MONTGOMERY(idx, w))
+                }
+            } else {
+                let (poseidon2, idx) = self.poseidon_state()?;
+                self.expect(";")?;
+                if poseidon2 {
+                    Ok(StructuredInstruction::POSEIDON2_STORE(idx, w))
+                } else {
+                    Ok(StructuredInstruction::POSEIDON_STORE(idx, w))
+                }
+            };
+        }
+
+        self.expect_all(&["]", "="])?;
+
+        if self.eat("iop") {
+            self.expect_all(&[".", "pop", "(", ")", ";"])?;
+            return Ok(StructuredInstruction::READ_IOP_BODY(w));
+        }
+
+        if self.eat("if") {
+            let s = component_0(self.operand()?);
+            self.expect("{")?;
+            let r1 = self.operand()?;
+            self.expect_all(&["}", "else", "{"])?;
+            let r2 = self.operand()?;
+            self.expect_all(&["}", ";"])?;
+            return Ok(StructuredInstruction::SELECT(w, s, r1, r2));
+        }
+
+        if self.eat("!") {
+            let r = component_0(self.operand()?);
+            self.expect(";")?;
+            return Ok(StructuredInstruction::NOT(w, r));
+        }
+
+        let lhs = if self.eat("(") {
+            let first = self.operand()?;
+            if self.eat("^") {
+                // `(m[a].0 ^ m[b].0, m[a].1 ^ m[b].1)`
+                let a0 = first;
+                let b0 = self.operand()?;
+                self.expect(",")?;
+                let a1 = self.operand()?;
+                self.expect("^")?;
+                let b1 = self.operand()?;
+                self.expect_all(&[")", ";"])?;
+                return Ok(StructuredInstruction::BIT_XOR_SHORTS(
+                    w,
+                    component_01(a0, a1),
+                    component_01(b0, b1),
+                ));
+            }
+
+            let first = match first {
+                ReadAddr::Const(v) if v.1 == Fp::ZERO && v.2 == Fp::ZERO && v.3 == Fp::ZERO => {
+                    v.0 .0
+                }
+                _ => return Err(self.unexpected("`^`")),
+            };
+            if self.eat("*") {
+                // `(fp * m[w - 1].0) << 64 + m[a].1 << 48 + m[a].0 << 32 + m[b].1 << 16 + m[b].0`
+                let prev = component_0(self.operand()?);
+                self.expect_all(&[")", "<<", "64", "+"])?;
+                let (r1, r2) = self.mix_rng_tail()?;
+                return Ok(StructuredInstruction::MIX_RNG_WITH_PERV(
+                    w, first, prev, r1, r2,
+                ));
+            }
+            self.const_tuple(first)?
+        } else {
+            self.operand()?
+        };
+
+        match self.peek() {
+            Some(";") => {
+                self.pos += 1;
+                Ok(match lhs {
+                    ReadAddr::Const(v) if v.2 == Fp::ZERO && v.3 == Fp::ZERO && v.0 .0 < P => {
+                        StructuredInstruction::CONST(w, v.0 .0, v.1 .0)
+                    }
+                    ReadAddr::RefSub(v, idx) => {
+                        StructuredInstruction::EXTRACT(w, ReadAddr::Ref(v), idx)
+                    }
+                    _ => StructuredInstruction::__MOV__(w, lhs),
+                })
+            }
+            Some("+") | Some("-") | Some("*") => {
+                let op = self.peek().unwrap().to_string();
+                self.pos += 1;
+                let rhs = self.operand()?;
+                self.expect(";")?;
+                Ok(match op.as_str() {
+                    "+" => StructuredInstruction::ADD(w, lhs, rhs),
+                    "-" => StructuredInstruction::SUB(w, lhs, rhs),
+                    _ => StructuredInstruction::MUL(w, lhs, rhs),
+                })
+            }
+            Some("/") => {
+                if lhs != ReadAddr::Const(Fp4::from_fp(Fp::new(1))) {
+                    return Err(self.unexpected("`;` (only `1 / x` is an instruction)"));
+                }
+                self.pos += 1;
+                let r = self.operand()?;
+                self.expect(";")?;
+                Ok(StructuredInstruction::INV(w, r))
+            }
+            Some("&") => {
+                self.pos += 1;
+                let b0 = self.operand()?;
+                if self.eat(";") {
+                    return Ok(StructuredInstruction::BIT_AND_ELEM(
+                        w,
+                        component_0(lhs),
+                        component_0(b0),
+                    ));
+                }
+                self.expect_all(&["+", "("])?;
+                let a1 = self.operand()?;
+                self.expect("&")?;
+                let b1 = self.operand()?;
+                self.expect_all(&[")", "<<", "16", ";"])?;
+                Ok(StructuredInstruction::BIT_AND_SHORTS(
+                    w,
+                    component_01(lhs, a1),
+                    component_01(b0, b1),
+                ))
+            }
+            Some("<<") => {
+                // `m[a].1 << 48 + m[a].0 << 32 + m[b].1 << 16 + m[b].0`
+                self.expect_all(&["<<", "48", "+"])?;
+                let r1_0 = self.operand()?;
+                self.expect_all(&["<<", "32", "+"])?;
+                let r2_1 = self.operand()?;
+                self.expect_all(&["<<", "16", "+"])?;
+                let r2_0 = self.operand()?;
+                self.expect(";")?;
+                Ok(StructuredInstruction::MIX_RNG(
+                    w,
+                    component_01(r1_0, lhs),
+                    component_01(r2_0, r2_1),
+                ))
+            }
+            _ => Err(self.unexpected("`;` or an operator")),
+        }
+    }
+
+    // `m[a].1 << 48 + m[a].0 << 32 + m[b].1 << 16 + m[b].0;`
+    fn mix_rng_tail(&mut self) -> Result<(ReadAddr, ReadAddr), AssemblerError> {
+        let r1_1 = self.operand()?;
+        self.expect_all(&["<<", "48", "+"])?;
+        let r1_0 = self.operand()?;
+        self.expect_all(&["<<", "32", "+"])?;
+        let r2_1 = self.operand()?;
+        self.expect_all(&["<<", "16", "+"])?;
+        let r2_0 = self.operand()?;
+        self.expect(";")?;
+        Ok((component_01(r1_0, r1_1), component_01(r2_0, r2_1)))
+    }
+
+    fn poseidon(&mut self) -> Result<StructuredInstruction, AssemblerError> {
+        self.expect_all(&["poseidon", "."])?;
+
+        match self.peek() {
+            Some("full") | Some("partial") => {
+                let full = self.is("full");
+                self.pos += 1;
+                self.expect_all(&["(", ")", ";"])?;
+                if full {
+                    Ok(StructuredInstruction::POSEIDON_FULL)
+                } else {
+                    Ok(StructuredInstruction::POSEIDON_PARTIAL)
+                }
+            }
+            Some("permute") => {
+                self.expect_all(&["permute", "(", ")", ";"])?;
+                if !self.is("m") {
+                    return Ok(StructuredInstruction::__POSEIDON_PERMUTE__);
+                }
+                let (ws, _) = self.range()?;
+                self.expect("=")?;
+                let res = if self.eat("to_montgomery") {
+                    self.expect_all(&["!", "("])?;
+                    let (_, idx) = self.poseidon_state()?;
+                    self.expect(")")?;
+                    StructuredInstruction::__POSEIDON_PERMUTE_STORE_TO_MONTGOMERY__(idx, ws)
+                } else {
+                    let (_, idx) = self.poseidon_state()?;
+                    StructuredInstruction::__POSEIDON_PERMUTE_STORE__(idx, ws)
+                };
+                self.eat(";");
+                Ok(res)
+            }
+            // the listing does not print it, so loads read back with zero unless the text gives it
+            Some("add_consts") => {
+                self.expect_all(&["add_consts", "="])?;
+                let add_consts = self.number()?;
+                self.expect_all(&[";", "poseidon", "."])?;
+                self.poseidon_load(add_consts)
+            }
+            _ => self.poseidon_load(0),
+        }
+    }
+
+    // a Poseidon load after the leading `poseidon.`
+    fn poseidon_load(
+        &mut self,
+        add_consts: Parameter,
+    ) -> Result<StructuredInstruction, AssemblerError> {
+        self.expect("state")?;
+        let keep_state = if self.eat("=") {
+            self.expect_all(&["[", "0u32", ";", "24", "]", ";"])?;
+            self.expect_all(&["poseidon", ".", "state"])?;
+            false
+        } else {
+            true
+        };
+
+        let (idx, from_montgomery, [r1, r2, r3, r4, r5, r6, r7, r8]) =
+            self.load_tail(keep_state)?;
+
+        Ok(match (keep_state, from_montgomery) {
+            (false, true) => StructuredInstruction::POSEIDON_LOAD_FROM_MONTGOMERY(
+                add_consts, idx, r1, r2, r3, r4, r5, r6, r7, r8,
+            ),
+            (false, false) => StructuredInstruction::POSEIDON_LOAD(
+                add_consts, idx, r1, r2, r3, r4, r5, r6, r7, r8,
+            ),
+            (true, true) => StructuredInstruction::POSEIDON_ADD_LOAD_FROM_MONTGOMERY(
+                add_consts, idx, r1, r2, r3, r4, r5, r6, r7, r8,
+            ),
+            (true, false) => StructuredInstruction::POSEIDON_ADD_LOAD(
+                add_consts, idx, r1, r2, r3, r4, r5, r6, r7, r8,
+            ),
+        })
+    }
+
+    // `[a..=b] = ...;` or `[a..=b] += ...;` of a load, returns the group index, whether the
+    // operands are converted from Montgomery form, and the operands
+    fn load_tail(
+        &mut self,
+        keep_state: bool,
+    ) -> Result<(u32, bool, [ReadAddr; 8]), AssemblerError> {
+        self.expect("[")?;
+        let idx = self.number()? / 8;
+        self.expect("..=")?;
+        self.number()?;
+        self.expect("]")?;
+        self.expect(if keep_state { "+=" } else { "=" })?;
+
+        let from_montgomery = self.eat("from_montgomery");
+        if from_montgomery {
+            self.expect("!")?;
+        }
+        let operands = self.eight_operands()?;
+        self.expect(";")?;
+        Ok((idx, from_montgomery, operands))
+    }
+
+    fn poseidon2(&mut self) -> Result<StructuredInstruction, AssemblerError> {
+        self.expect_all(&["poseidon2", "."])?;
+
+        match self.peek() {
+            Some("full") | Some("partial") => {
+                let full = self.is("full");
+                self.pos += 1;
+                self.expect_all(&["(", ")", ";"])?;
+                if full {
+                    Ok(StructuredInstruction::POSEIDON2_FULL)
+                } else {
+                    Ok(StructuredInstruction::POSEIDON2_PARTIAL)
+                }
+            }
+            Some("permute") => {
+                self.expect_all(&["permute", "(", ")", ";"])?;
+                if !self.is("m") {
+                    return Ok(StructuredInstruction::__POSEIDON2_PERMUTE__);
+                }
+                let (ws, _) = self.range()?;
+                self.expect("=")?;
+                let res = if self.eat("to_montgomery") {
+                    self.expect_all(&["!", "("])?;
+                    let (_, idx) = self.poseidon_state()?;
+                    self.expect(")")?;
+                    StructuredInstruction::__POSEIDON2_PERMUTE_STORE_TO_MONTGOMERY__(idx, ws)
+                } else {
+                    let (_, idx) = self.poseidon_state()?;
+                    StructuredInstruction::__POSEIDON2_PERMUTE_STORE__(idx, ws)
+                };
+                self.eat(";");
+                Ok(res)
+            }
+            Some("prep_full") => {
+                self.expect_all(&["prep_full", "="])?;
+                let prep_full = self.number()?;
+                self.expect_all(&[";", "poseidon2", "."])?;
+                self.poseidon2_load(prep_full)
+            }
+            _ => self.poseidon2_load(0),
+        }
+    }
+
+    // a Poseidon2 load after the leading `poseidon2.`
+    fn poseidon2_load(
+        &mut self,
+        prep_full: Parameter,
+    ) -> Result<StructuredInstruction, AssemblerError> {
+        self.expect("state")?;
+        let (keep_state, keep_upper_state) = if self.eat("=") {
+            self.expect_all(&["[", "0u32", ";", "24", "]", ";"])?;
+            (false, 0)
+        } else if self.peek_at(1) == Some("0") && self.peek_at(3) == Some("15") {
+            self.expect_all(&[
+                "[", "0", "..=", "15", "]", "=", "[", "0u32", ";", "16", "]", ";",
+            ])?;
+            (false, 1)
+        } else {
+            (true, 0)
+        };
+        if !keep_state {
+            self.expect_all(&["poseidon2", ".", "state"])?;
+        }
+
+        let (idx, from_montgomery, [r1, r2, r3, r4, r5, r6, r7, r8]) =
+            self.load_tail(keep_state)?;
+
+        Ok(match (keep_state, from_montgomery) {
+            (false, true) => StructuredInstruction::POSEIDON2_LOAD_FROM_MONTGOMERY(
+                keep_upper_state,
+                prep_full,
+                idx,
+                r1,
+                r2,
+                r3,
+                r4,
+                r5,
+                r6,
+                r7,
+                r8,
+            ),
+            (false, false) => StructuredInstruction::POSEIDON2_LOAD(
+                keep_upper_state,
+                prep_full,
+                idx,
+                r1,
+                r2,
+                r3,
+                r4,
+                r5,
+                r6,
+                r7,
+                r8,
+            ),
+            (true, true) => StructuredInstruction::POSEIDON2_ADD_LOAD_FROM_MONTGOMERY(
+                prep_full, idx, r1, r2, r3, r4, r5, r6, r7, r8,
+            ),
+            (true, false) => StructuredInstruction::POSEIDON2_ADD_LOAD(
+                prep_full, idx, r1, r2, r3, r4, r5, r6, r7, r8,
+            ),
+        })
+    }
+}
+
+// undo `ReadAddr::_0()`: `m[a].0` is printed for a plain `m[a]`
+fn component_0(r: ReadAddr) -> ReadAddr {
+    match r {
+        ReadAddr::RefSub(v, 0) => ReadAddr::Ref(v),
+        _ => r,
+    }
+}
+
+// undo the pair `(ReadAddr::_0(), ReadAddr::_1())`
+fn component_01(r0: ReadAddr, r1: ReadAddr) -> ReadAddr {
+    match (&r0, &r1) {
+        (ReadAddr::RefSub(v0, 0), ReadAddr::RefSub(v1, 1)) if v0 == v1 => ReadAddr::Ref(*v0),
+        (ReadAddr::Const(c0), ReadAddr::Const(c1)) => {
+            ReadAddr::Const(Fp4::new(c0.0.clone(), c1.0.clone(), Fp::ZERO, Fp::ZERO))
+        }
+        _ => r0,
+    }
+}
+
+/// Parse the text printed by `Display for StructuredInstruction` back into a `Code`.
+///
+/// Every statement is attributed to the line it appears on and its position on that line, so
+/// several micro instructions written on one line share a row. A leading `{}: ` prefix, as written by the
+/// `decompile` tool, is skipped.
+impl TryFrom<&str> for Code {
+    type Error = AssemblerError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut out = Code::default();
+
+        for (idx, text) in value.lines().enumerate() {
+            let mut line = Line {
+                tokens: tokenize(text),
+                pos: 0,
+                line_no: idx + 1,
+                end_column: text.chars().count() + 1,
+            };
+
+            if line.peek_at(1) == Some(":") && line.number().is_ok() {
+                line.pos += 1;
+            }
+
+            let mut slot = 0;
+            while let Some(head) = line.peek() {
+                if head.starts_with("//") {
+                    if head == "// deleted" {
+                        out.push(
+                            StructuredInstruction::__DELETE__,
+                            LineNo::new(idx + 1, slot),
+                        );
+                    }
+                    break;
+                }
+                let insn = line.statement()?;
+                out.push(insn, LineNo::new(idx + 1, slot));
+                slot += 1;
+            }
+        }
+
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::structures::StructuredInstruction as I;
+
+    fn r(v: u32) -> ReadAddr {
+        ReadAddr::Ref(v)
+    }
+
+    // one instruction of every kind
+    pub(crate) fn instructions() -> Vec<StructuredInstruction> {
+        let c = |v: u32| ReadAddr::Const(Fp4::from_fp(Fp::new(v)));
+        vec![
+            I::BIT_AND_ELEM(3, r(1), r(2)),
+            I::BIT_AND_SHORTS(3, r(1), r(2)),
+            I::BIT_XOR_SHORTS(3, r(1), r(2)),
+            I::SHA_INIT_START,
+            I::SHA_INIT_PADDING,
+            I::SHA_LOAD_FROM_MONTGOMERY(r(4)),
+            I::SHA_LOAD(r(4)),
+            I::SHA_MIX,
+            I::SHA_FINI_START(8),
+            I::SHA_FINI_PADDING,
+            I::WOM_INIT,
+            I::WOM_FINI,
+            I::NOP,
+            I::PADDING,
+            I::SET_GLOBAL(r(5), r(6), r(7), r(8), 2),
+            I::CONST(9, 5, 7),
+            I::ADD(10, r(9), c(3)),
+            I::SUB(11, r(10), ReadAddr::RefSub(9, 2)),
+            I::MUL(12, c(P - 1), r(11)),
+            I::NOT(13, r(12)),
+            I::INV(14, r(13)),
+            I::EQ(r(13), r(14)),
+            I::READ_IOP_HEADER(2, 8),
+            I::READ_IOP_BODY(15),
+            I::MIX_RNG_WITH_PERV(16, 3, r(15), r(14), r(13)),
+            I::MIX_RNG(17, r(16), r(15)),
+            I::SELECT(18, r(17), r(16), r(15)),
+            I::EXTRACT(19, r(18), 3),
+            I::POSEIDON_LOAD_FROM_MONTGOMERY(0, 0, r(1), r(2), r(3), r(4), r(5), r(6), r(7), r(8)),
+            I::POSEIDON_LOAD(0, 1, r(1), r(2), r(3), r(4), r(5), r(6), r(7), r(8)),
+            I::POSEIDON_ADD_LOAD_FROM_MONTGOMERY(
+                0,
+                2,
+                r(1),
+                r(2),
+                r(3),
+                r(4),
+                r(5),
+                r(6),
+                r(7),
+                r(8),
+            ),
+            I::POSEIDON_ADD_LOAD(0, 1, r(1), r(2), r(3), r(4), r(5), r(6), r(7), r(8)),
+            I::POSEIDON_FULL,
+            I::POSEIDON_PARTIAL,
+            I::POSEIDON_STORE_TO_MONTGOMERY(0, 20),
+            I::POSEIDON_STORE(2, 28),
+            I::POSEIDON2_LOAD_FROM_MONTGOMERY(
+                1,
+                0,
+                0,
+                r(1),
+                r(2),
+                r(3),
+                r(4),
+                r(5),
+                r(6),
+                r(7),
+                r(8),
+            ),
+            I::POSEIDON2_LOAD(0, 1, 2, r(1), r(2), r(3), r(4), r(5), r(6), r(7), r(8)),
+            I::POSEIDON2_ADD_LOAD_FROM_MONTGOMERY(
+                1,
+                1,
+                r(1),
+                r(2),
+                r(3),
+                r(4),
+                r(5),
+                r(6),
+                r(7),
+                r(8),
+            ),
+            I::POSEIDON2_ADD_LOAD(0, 2, r(1), r(2), r(3), r(4), r(5), r(6), r(7), r(8)),
+            I::POSEIDON2_FULL,
+            I::POSEIDON2_PARTIAL,
+            I::POSEIDON2_STORE_TO_MONTGOMERY(1, 36),
+            I::POSEIDON2_STORE(0, 44),
+            I::__DELETE__,
+            I::__PANIC__,
+            I::__MOV__(52, r(51)),
+            I::__READ_IOP_BODY_BATCH__(53, 57),
+            I::__SHA_MIX_48__,
+            I::__POSEIDON_PERMUTE_STORE_TO_MONTGOMERY__(0, 60),
+            I::__POSEIDON_PERMUTE_STORE__(1, 68),
+            I::__POSEIDON_PERMUTE__,
+            I::__POSEIDON2_PERMUTE_STORE_TO_MONTGOMERY__(2, 76),
+            I::__POSEIDON2_PERMUTE_STORE__(0, 84),
+            I::__POSEIDON2_PERMUTE__,
+            I::__SHA_INIT__,
+            I::__SHA_FINI__(92),
+        ]
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for insn in instructions() {
+            let text = insn.to_string();
+            let code = Code::try_from(text.as_str())
+                .unwrap_or_else(|e| panic!("cannot parse {}: {}", text, e));
+            assert_eq!(code.0.len(), 1, "{}", text);
+            assert!(
+                code.0[0].0 == insn,
+                "{} reads back as {}",
+                text,
+                code.0[0].0
+            );
+        }
+    }
+
+    #[test]
+    fn listing_round_trip() {
+        let insns = instructions();
+        let text: String = insns
+            .iter()
+            .enumerate()
+            .map(|(i, insn)| format!("{}: {}\n", i + 1, insn))
+            .collect();
+        let code = Code::try_from(text.as_str()).unwrap();
+        assert_eq!(code.0.len(), insns.len());
+        for (i, ((insn, line_no), expected)) in code.0.iter().zip(insns.iter()).enumerate() {
+            assert!(insn == expected, "line {}: {}", i + 1, insn);
+            assert_eq!(*line_no, LineNo::new(i + 1, 0));
+        }
+    }
+
+    #[test]
+    fn add_consts_prefix() {
+        let text = "poseidon.add_consts = 1; poseidon.state[8..=15] += (m[1].0, m[2].0, m[3].0, m[4].0, m[5].0, m[6].0, m[7].0, m[8].0);";
+        let code = Code::try_from(text).unwrap();
+        assert!(
+            code.0[0].0
+                == I::POSEIDON_ADD_LOAD(1, 1, r(1), r(2), r(3), r(4), r(5), r(6), r(7), r(8))
+        );
+        // the listing prints the load without it
+        assert_eq!(
+            code.0[0].0.to_string(),
+            text["poseidon.add_consts = 1; ".len()..]
+        );
+    }
+
+    #[test]
+    fn micro_ops_on_one_line() {
+        let text = "m[1] = (5, 7); m[2] = m[1] + m[1]; assert_eq!(m[1], m[2]);\nwom_fini();\n";
+        let code = Code::try_from(text).unwrap();
+        let lines: Vec<LineNo> = code.0.iter().map(|(_, line_no)| *line_no).collect();
+        assert_eq!(
+            lines,
+            vec![
+                LineNo::new(1, 0),
+                LineNo::new(1, 1),
+                LineNo::new(1, 2),
+                LineNo::new(2, 0)
+            ]
+        );
+        assert!(code.0[1].0 == I::ADD(2, r(1), r(1)));
+    }
+
+    #[test]
+    fn constants_are_reduced() {
+        let text = "m[2] = m[1] + 2013265922; m[3] = m[1] * (2013265923, 4);\n";
+        let code = Code::try_from(text).unwrap();
+        let consts: Vec<u32> = code
+            .0
+            .iter()
+            .flat_map(|(insn, _)| match insn {
+                I::ADD(_, _, ReadAddr::Const(c)) | I::MUL(_, _, ReadAddr::Const(c)) => {
+                    vec![c.0 .0, c.1 .0, c.2 .0, c.3 .0]
+                }
+                _ => vec![],
+            })
+            .collect();
+        assert_eq!(consts, vec![1, 0, 0, 0, 2, 4, 0, 0]);
+    }
+}
@@ -0,0 +1,69 @@
+use clap::Parser;
+use ll0::encoder::encode;
+use ll0::layout::IsaLayout;
+use ll0::parser::Code;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+#[derive(Parser, Debug)]
+#[command(about = "Assemble a LL0 file back into a ZKR file", long_about = None)]
+struct Args {
+    // Filename of the LL0 file to be assembled
+    #[arg(short, long, required = true)]
+    file: String,
+
+    // Output file, default to [filename].zkr
+    #[arg(short, long)]
+    output: Option<String>,
+
+    // Column layout of the rows: the name of a built-in layout or a layout file
+    #[arg(long)]
+    layout: Option<String>,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let f = File::open(args.file.clone()).unwrap();
+    let mut buf_reader = BufReader::new(f);
+
+    let mut text = String::new();
+    buf_reader.read_to_string(&mut text).unwrap();
+
+    let code = match Code::try_from(text.as_str()) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{}: {}", args.file, e);
+            std::process::exit(1);
+        }
+    };
+    let layout = match args.layout {
+        Some(spec) => IsaLayout::load(&spec).unwrap(),
+        None => IsaLayout::default(),
+    };
+    let u32vec = match encode(&code, &layout) {
+        Ok(u32vec) => u32vec,
+        Err(e) => {
+            eprintln!("{}: {}", args.file, e);
+            std::process::exit(1);
+        }
+    };
+
+    let out_name = if let Some(output) = args.output {
+        output
+    } else {
+        let tmp = String::from(Path::new(&args.file).file_name().unwrap().to_str().unwrap());
+        if tmp.ends_with(".ll0") {
+            String::from(&tmp.as_str()[0..tmp.len() - 4]) + ".zkr"
+        } else {
+            tmp + ".zkr"
+        }
+    };
+
+    let ff = File::create(out_name).unwrap();
+    let mut buf_writer = BufWriter::new(ff);
+    buf_writer
+        .write_all(bytemuck::cast_slice(u32vec.as_slice()))
+        .unwrap();
+}
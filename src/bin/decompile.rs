@@ -1,70 +1,206 @@
-use clap::Parser;
-use ll0::parser::Code;
-use ll0::pass::const_pass::ConstPass;
-use ll0::pass::live_variable_analysis::LiveVariableAnalysisPass;
-use ll0::pass::merge_iop_pass::MergeIOPPass;
-use ll0::pass::poseidon_pass::PoseidonPass;
-use ll0::pass::reorder_pass::ReorderPass;
-use ll0::pass::sha_pass::ShaPass;
-use ll0::pass::Pass;
+use anyhow::Context;
+use clap::{Parser, ValueEnum};
+use ll0::layout::IsaLayout;
+use ll0::parser::{words_from_bytes, write_listing, write_source_map, Code};
+use ll0::pass::manager::PassManager;
+use ll0::pass::validation::Validator;
 use ll0::structures::StructuredInstruction;
+use ll0::wom;
 use std::fs::File;
 use std::io::{BufReader, BufWriter, Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug)]
 #[command(about = "Decompile a ZKR file", long_about = None)]
 struct Args {
-    // Filename of the ZKR file to be unzipped
+    // Filename of the ZKR file to be decompiled, or of a JSON or CBOR `Code`
     #[arg(short, long, required = true)]
     file: String,
 
-    // Output file, default to [filename].ll0
+    // Output file, default to [filename].ll0, [filename].json or [filename].cbor. The source map
+    // of a .ll0 file is written next to it, as [output].map
     #[arg(short, long)]
     output: Option<String>,
+
+    // Format of the output
+    #[arg(long, value_enum, default_value_t = Emit::Ll0)]
+    emit: Emit,
+
+    // Write the instructions as decoded, without running the passes
+    #[arg(long)]
+    no_passes: bool,
+
+    // The passes to run, in order, separated by commas, such as
+    // const,merge-iop,sha,poseidon,poseidon2,lva,reorder, which is the default
+    #[arg(long, value_delimiter = ',', conflicts_with = "no_passes")]
+    passes: Option<Vec<String>>,
+
+    // Print the time of each pass and the instructions it deletes, rewrites or renames
+    #[arg(long)]
+    stats: bool,
+
+    // Write the code after each pass to this directory, as [n]-[pass].ll0
+    #[arg(long)]
+    dump_passes: Option<PathBuf>,
+
+    // Reject rows that do not have the expected shape, and report all of them
+    #[arg(long)]
+    strict: bool,
+
+    // Column layout of the rows: "auto", "auto:[layout],[layout]" to also consider these layouts,
+    // the name of a built-in layout, or a layout file
+    #[arg(long, default_value = "auto")]
+    layout: String,
+
+    // Check that the code keeps to the write-once memory before decompiling it, and report every
+    // conflict
+    #[arg(long)]
+    check: bool,
+
+    // Run the program before and after each pass on this many random IOP inputs, 3 if not given,
+    // and stop at the first pass that changes what it computes
+    #[arg(long, num_args = 0..=1, default_missing_value = "3")]
+    validate: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Emit {
+    Ll0,
+    Json,
+    Cbor,
+}
+
+impl Emit {
+    fn extension(&self) -> &'static str {
+        match self {
+            Emit::Ll0 => ".ll0",
+            Emit::Json => ".json",
+            Emit::Cbor => ".cbor",
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+fn load_serialized(args: &Args, u8vec: &[u8]) -> anyhow::Result<Option<Code>> {
+    let code = if args.file.ends_with(".json") {
+        Code::from_json(std::str::from_utf8(u8vec)?)?
+    } else if args.file.ends_with(".cbor") {
+        Code::from_cbor(u8vec)?
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(code))
+}
+
+#[cfg(not(feature = "serde"))]
+fn load_serialized(args: &Args, _: &[u8]) -> anyhow::Result<Option<Code>> {
+    if args.file.ends_with(".json") || args.file.ends_with(".cbor") {
+        anyhow::bail!(
+            "reading {} needs ll0 built with the serde feature",
+            args.file
+        );
+    }
+    Ok(None)
+}
+
+#[cfg(feature = "serde")]
+fn write_serialized(code: &Code, emit: Emit, writer: &mut impl Write) -> anyhow::Result<()> {
+    match emit {
+        Emit::Json => writer.write_all(code.to_json()?.as_bytes())?,
+        _ => writer.write_all(&code.to_cbor()?)?,
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "serde"))]
+fn write_serialized(_: &Code, emit: Emit, _: &mut impl Write) -> anyhow::Result<()> {
+    anyhow::bail!("--emit {:?} needs ll0 built with the serde feature", emit);
+}
+
+fn load_zkr(args: &Args, u8vec: &[u8]) -> anyhow::Result<Code> {
+    let layout = IsaLayout::resolve(&args.layout, u8vec)?;
+
+    let u32vec =
+        words_from_bytes(u8vec, &layout).with_context(|| format!("cannot parse {}", args.file))?;
+    let code = if args.strict {
+        Code::parse_strict(u32vec.as_slice(), &layout)
+    } else {
+        Code::parse(u32vec.as_slice(), &layout)
+    }
+    .with_context(|| format!("cannot parse {} with layout {}", args.file, layout.name))?;
+
+    Ok(code)
 }
 
-fn main() {
+fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
-    let f = File::open(args.file.clone()).unwrap();
+    let f = File::open(args.file.clone())?;
     let mut buf_reader = BufReader::new(f);
 
     let mut u8vec: Vec<u8> = Vec::new();
-    buf_reader.read_to_end(&mut u8vec).unwrap();
+    buf_reader.read_to_end(&mut u8vec)?;
 
-    let u32vec: Vec<u32> = Vec::from(bytemuck::cast_slice(u8vec.as_slice()));
-    let mut code = Code::try_from(u32vec.as_slice()).unwrap();
+    let mut code = match load_serialized(&args, u8vec.as_slice())? {
+        Some(code) => code,
+        None => load_zkr(&args, u8vec.as_slice())?,
+    };
 
-    ConstPass::pass(&mut code).unwrap();
-    MergeIOPPass::pass(&mut code).unwrap();
-    LiveVariableAnalysisPass::pass(&mut code).unwrap();
-    ShaPass::pass(&mut code).unwrap();
-    PoseidonPass::pass(&mut code).unwrap();
-    ReorderPass::pass(&mut code).unwrap();
+    if args.check {
+        let issues = wom::check(&code);
+        for issue in issues.iter() {
+            eprintln!("{}", issue);
+        }
+        if !issues.is_empty() {
+            anyhow::bail!("{} does not keep to the write-once memory", args.file);
+        }
+    }
+
+    if !args.no_passes {
+        let mut manager = PassManager::default();
+        if let Some(passes) = &args.passes {
+            manager.set_pipeline(passes)?;
+        }
+        manager.set_dump_dir(args.dump_passes.clone());
 
-    let out_name = if args.output.is_some() {
-        args.output.unwrap()
+        let mut validator = args.validate.map(|n| Validator::new(&code, 0..n));
+        let stats = manager.run_with(&mut code, |name, code| {
+            if let Some(validator) = validator.as_mut() {
+                validator.check(name, code)?;
+            }
+            Ok(())
+        })?;
+        if args.stats {
+            for pass in stats.iter() {
+                eprintln!("{}", pass);
+            }
+        }
+    }
+    code.0
+        .retain(|(insn, _)| !matches!(insn, StructuredInstruction::__DELETE__));
+
+    let out_name = if let Some(output) = args.output.clone() {
+        output
     } else {
         let tmp = String::from(Path::new(&args.file).file_name().unwrap().to_str().unwrap());
-        if tmp.ends_with(".zkr") {
-            String::from(&tmp.as_str()[0..tmp.len() - 4]) + ".ll0"
-        } else {
-            tmp + ".ll0"
-        }
+        let stem = [".zkr", ".json", ".cbor"]
+            .iter()
+            .find_map(|ext| tmp.strip_suffix(ext))
+            .unwrap_or(tmp.as_str());
+        String::from(stem) + args.emit.extension()
     };
 
-    let ff = File::create(out_name).unwrap();
+    let ff = File::create(&out_name)?;
     let mut buf_writer = BufWriter::new(ff);
 
-    for (i, (insn, _)) in code
-        .0
-        .iter()
-        .filter(|(insn, _)| !matches!(insn, StructuredInstruction::__DELETE__))
-        .enumerate()
-    {
-        buf_writer
-            .write_fmt(format_args!("{}: {}\n", i + 1, insn))
-            .unwrap();
+    if args.emit == Emit::Ll0 {
+        write_listing(&code, &mut buf_writer)?;
+
+        let map = File::create(out_name + ".map")?;
+        write_source_map(&code, &mut BufWriter::new(map))?;
+    } else {
+        write_serialized(&code, args.emit, &mut buf_writer)?;
     }
+
+    Ok(())
 }
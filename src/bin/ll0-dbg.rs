@@ -0,0 +1,318 @@
+use anyhow::Context;
+use clap::Parser;
+use ll0::debugger::{Debugger, RowPos, Step, Stop};
+use ll0::iop::{self, IopSource};
+use ll0::layout::IsaLayout;
+use ll0::math::{Fp, Fp4};
+use ll0::parser::{words_from_bytes, Code};
+use ll0::pass::manager::PassManager;
+use std::io::{BufRead, Write};
+
+#[derive(Parser, Debug)]
+#[command(about = "Step through a ZKR or LL0 program", long_about = None)]
+struct Args {
+    // Filename of the ZKR file, or of a LL0 file whose source map, if any, is read from
+    // [filename].map
+    #[arg(short, long, required = true)]
+    file: String,
+
+    // IOP input, as little-endian u32 words, or as a JSON array of words if the name ends with
+    // .json
+    #[arg(long)]
+    iop: Option<String>,
+
+    // Run on a random IOP input generated from this seed, with the shape that the program expects
+    #[arg(long, conflicts_with = "iop")]
+    random_iop: Option<u64>,
+
+    // Step through the instructions of a ZKR file as decoded, without running the passes
+    #[arg(long)]
+    no_passes: bool,
+
+    // Column layout of the rows: "auto", "auto:[layout],[layout]" to also consider these layouts,
+    // the name of a built-in layout, or a layout file
+    #[arg(long, default_value = "auto")]
+    layout: String,
+}
+
+const HELP: &str = "\
+step [n]                  execute the next n instructions (s)
+continue                  run until a breakpoint, a watchpoint, an error or the end (c)
+until <row>[.<slot>]      run up to the instruction that covers this row of the zkr (u)
+until line <n>            run up to the instruction at this line of the listing
+break <row>[.<slot>]      stop before the instruction that covers this row (b)
+break <text>              stop before every instruction whose text contains <text>
+watch <addr>              stop after every instruction that writes m[addr] (w)
+delete [n]                remove breakpoint or watchpoint n, or all of them (d)
+info                      list the breakpoints and watchpoints (i)
+print <addr>[..<end>]     print m[addr], or the range of addresses up to <end> excluded (p)
+poseidon | poseidon2      print the Poseidon or Poseidon2 state
+sha                       print the SHA-256 state
+where                     print the current instruction and the rows it comes from
+list [n]                  print n instructions around the current one (l)
+restart                   start again from the first instruction (r)
+quit                      leave (q)
+An empty line repeats the last command.";
+
+// the command line front end of a `Debugger`
+struct Repl {
+    dbg: Debugger,
+}
+
+impl Repl {
+    fn describe(&self, idx: usize) -> String {
+        let (insn, line_no) = &self.dbg.code().0[idx];
+        format!("{:>6}: {}    [row {}]", self.dbg.line(idx), insn, line_no)
+    }
+
+    // print what the last step did, and where the program is now
+    fn report(&self, step: Step) {
+        match step {
+            Step::Halted => {
+                if let Some(e) = self.dbg.halted() {
+                    println!("{}", e);
+                }
+            }
+            Step::Watched(changes) => {
+                for change in changes.iter() {
+                    match &change.old {
+                        Some(old) => println!(
+                            "m[{}] changed from {} to {}",
+                            change.addr,
+                            old,
+                            show(change.new.as_ref())
+                        ),
+                        None => println!("m[{}] set to {}", change.addr, show(change.new.as_ref())),
+                    }
+                }
+            }
+            Step::Ran | Step::Done => {}
+        }
+        self.position();
+    }
+
+    fn position(&self) {
+        if self.dbg.halted().is_some() {
+            println!("halted at {}", self.describe(self.dbg.pc() - 1));
+        } else if self.dbg.pc() >= self.dbg.code().0.len() {
+            println!("the program has ended");
+        } else {
+            println!("{}", self.describe(self.dbg.pc()));
+        }
+    }
+
+    fn where_(&self) {
+        if self.dbg.pc() >= self.dbg.code().0.len() {
+            self.position();
+            return;
+        }
+        println!("{}", self.describe(self.dbg.pc()));
+        if let Some(covered) = self.dbg.origin(self.dbg.pc()) {
+            println!("from {} instruction(s) of the zkr:", covered.len());
+            for (insn, raw_line_no) in covered.iter().take(8) {
+                println!("    row {}: {}", raw_line_no, insn);
+            }
+            if covered.len() > 8 {
+                println!("    ...");
+            }
+        }
+    }
+
+    fn list(&self, n: usize) {
+        let pc = self.dbg.pc();
+        let start = pc.saturating_sub(n / 2);
+        let end = (start + n).min(self.dbg.code().0.len());
+        for idx in start..end {
+            let marker = if idx == pc {
+                "=>"
+            } else if self.dbg.breaks_at(idx) {
+                " *"
+            } else {
+                "  "
+            };
+            println!("{} {}", marker, self.describe(idx));
+        }
+    }
+
+    fn print_cells(name: &str, cells: &[Fp]) {
+        for (i, chunk) in cells.chunks(8).enumerate() {
+            let chunk: Vec<String> = chunk.iter().map(|c| c.to_string()).collect();
+            println!(
+                "{}[{}..={}] = ({})",
+                name,
+                i * 8,
+                i * 8 + 7,
+                chunk.join(", ")
+            );
+        }
+    }
+
+    fn add_stop(&mut self, stop: Stop) {
+        let text = stop.to_string();
+        println!("{}: {}", self.dbg.add_stop(stop), text);
+    }
+
+    fn command(&mut self, line: &str) -> anyhow::Result<bool> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let arg = words.get(1).copied();
+        match words.first().copied().unwrap_or_default() {
+            "s" | "step" => {
+                let n = arg.map(str::parse).transpose()?.unwrap_or(1usize);
+                let step = self.dbg.step_n(n);
+                self.report(step);
+            }
+            "c" | "continue" => {
+                let step = self.dbg.continue_();
+                self.report(step);
+            }
+            "u" | "until" => {
+                let step = match (arg, words.get(2)) {
+                    (Some("line"), Some(n)) => self.dbg.until_line(n.parse()?),
+                    (Some(pos), None) => {
+                        let pos = RowPos::parse(pos).context("expected <row>[.<slot>]")?;
+                        self.dbg.until_row(pos)
+                    }
+                    _ => anyhow::bail!("expected until <row>[.<slot>] or until line <n>"),
+                };
+                self.report(step);
+            }
+            "b" | "break" => {
+                let rest = line.trim_start()[words[0].len()..].trim();
+                anyhow::ensure!(!rest.is_empty(), "expected break <row>[.<slot>] or <text>");
+                self.add_stop(match RowPos::parse(rest) {
+                    Some(pos) => Stop::Row(pos),
+                    None => Stop::Text(rest.to_string()),
+                });
+            }
+            "w" | "watch" => {
+                let addr: u32 = arg.context("expected watch <addr>")?.parse()?;
+                self.add_stop(Stop::Watch(addr));
+            }
+            "d" | "delete" => match arg {
+                Some(n) => anyhow::ensure!(
+                    self.dbg.delete_stop(n.parse()?),
+                    "no such breakpoint or watchpoint"
+                ),
+                None => self.dbg.clear_stops(),
+            },
+            "i" | "info" => {
+                for (n, stop) in self.dbg.stops() {
+                    println!("{}: {}", n, stop);
+                }
+            }
+            "p" | "print" => {
+                let arg = arg.context("expected print <addr>[..<end>]")?;
+                let addr = arg.trim_start_matches("m[").trim_end_matches(']');
+                let (start, end) = match addr.split_once("..") {
+                    Some((start, end)) => (start.parse()?, end.parse()?),
+                    None => {
+                        let addr: u32 = addr.parse()?;
+                        (addr, addr + 1)
+                    }
+                };
+                for addr in start..end {
+                    println!("m[{}] = {}", addr, show(self.dbg.vm().get(addr)));
+                }
+            }
+            "poseidon" => {
+                let poseidon = self.dbg.vm().poseidon();
+                println!("next round: {}", poseidon.round());
+                Self::print_cells("poseidon.state", &poseidon.cells);
+            }
+            "poseidon2" => Self::print_cells("poseidon2.state", &self.dbg.vm().poseidon2().cells),
+            "sha" => {
+                let sha = self.dbg.vm().sha();
+                let state: Vec<String> = sha.state().iter().map(|w| format!("{:08x}", w)).collect();
+                println!("{}", sha.phase());
+                println!("state = {}", state.join(" "));
+            }
+            "where" => self.where_(),
+            "l" | "list" => {
+                let n = arg.map(str::parse).transpose()?.unwrap_or(11usize);
+                self.list(n);
+            }
+            "r" | "restart" => {
+                self.dbg.restart();
+                self.position();
+            }
+            "q" | "quit" => return Ok(false),
+            "h" | "help" => println!("{}", HELP),
+            other => anyhow::bail!("unknown command {}, try help", other),
+        }
+        Ok(true)
+    }
+}
+
+fn show(v: Option<&Fp4>) -> String {
+    match v {
+        Some(v) => v.to_string(),
+        None => String::from("(not written)"),
+    }
+}
+
+fn load_zkr(args: &Args, u8vec: &[u8]) -> anyhow::Result<Code> {
+    let layout = IsaLayout::resolve(&args.layout, u8vec)?;
+    let u32vec =
+        words_from_bytes(u8vec, &layout).with_context(|| format!("cannot parse {}", args.file))?;
+    Code::parse(u32vec.as_slice(), &layout)
+        .with_context(|| format!("cannot parse {} with layout {}", args.file, layout.name))
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let u8vec = std::fs::read(&args.file)?;
+
+    let iop = |code: &Code| -> anyhow::Result<IopSource> {
+        Ok(match (&args.iop, args.random_iop) {
+            (Some(path), _) => IopSource::load(path)?,
+            (None, Some(seed)) => IopSource::new(iop::generate(code, seed)),
+            (None, None) => IopSource::default(),
+        })
+    };
+    let dbg = if args.file.ends_with(".ll0") {
+        let code = Code::try_from(std::str::from_utf8(&u8vec)?)
+            .map_err(|e| anyhow::anyhow!("{}: {}", args.file, e))?;
+        let map = std::fs::read_to_string(args.file.clone() + ".map").ok();
+        let iop = iop(&code)?;
+        Debugger::from_listing(code, map.as_deref(), iop)?
+    } else if args.no_passes {
+        let code = load_zkr(&args, &u8vec)?;
+        let iop = iop(&code)?;
+        Debugger::from_zkr(code, iop)
+    } else {
+        let mut code = load_zkr(&args, &u8vec)?;
+        PassManager::default().run(&mut code)?;
+        let iop = iop(&code)?;
+        Debugger::from_passes(code, load_zkr(&args, &u8vec)?, iop)
+    };
+
+    let mut repl = Repl { dbg };
+    println!(
+        "{}: {} instructions, type help for the commands",
+        args.file,
+        repl.dbg.code().0.len()
+    );
+    repl.position();
+
+    let stdin = std::io::stdin();
+    let mut last = String::new();
+    loop {
+        print!("(ll0-dbg) ");
+        std::io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            break;
+        }
+        if line.trim().is_empty() {
+            line = last.clone();
+        }
+        match repl.command(&line) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => println!("{}", e),
+        }
+        last = line;
+    }
+
+    Ok(())
+}
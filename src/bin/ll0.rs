@@ -0,0 +1,291 @@
+use anyhow::Context;
+use clap::{Parser, Subcommand, ValueEnum};
+use ll0::control_id::{self, HashFn};
+use ll0::iop::{self, IopSource};
+use ll0::layout::IsaLayout;
+use ll0::parser::{words_from_bytes, Code};
+use ll0::pass::manager::PassManager;
+use ll0::profile::{self, Profile, RowClass};
+use ll0::provenance::Provenance;
+use ll0::structures::StructuredInstruction;
+
+#[derive(Parser, Debug)]
+#[command(about = "Tools to study a ZKR file", long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Count the rows of a ZKR file by class, with the permutations, SHA-256 blocks and IOP words
+    Profile {
+        file: String,
+
+        // Column layout of the rows: "auto", "auto:[layout],[layout]" to also consider these
+        // layouts, the name of a built-in layout, or a layout file
+        #[arg(long, default_value = "auto")]
+        layout: String,
+
+        // Also break the counts down over regions of this many rows
+        #[arg(long)]
+        window: Option<usize>,
+    },
+    /// Show where the value of an address or of a global comes from, down to the IOP reads and
+    /// the constants
+    Why {
+        file: String,
+
+        // The address to explain, as numbered in the decompiled listing, or in the raw
+        // instructions with --no-passes
+        #[arg(long, required_unless_present = "global", conflicts_with = "global")]
+        addr: Option<u32>,
+
+        // The index of the global to explain
+        #[arg(long)]
+        global: Option<u32>,
+
+        // Explain the address as the instruction at this line of the listing reads it, rather
+        // than after the last instruction
+        #[arg(long, requires = "addr")]
+        line: Option<usize>,
+
+        // Follow the raw instructions, without running the passes
+        #[arg(long)]
+        no_passes: bool,
+
+        // Show the values of a run on this IOP input, as little-endian u32 words, or as a JSON
+        // array of words if the name ends with .json
+        #[arg(long)]
+        iop: Option<String>,
+
+        // Show the values of a run on a random IOP input generated from this seed
+        #[arg(long, conflicts_with = "iop")]
+        random_iop: Option<u64>,
+
+        // Stop the tree at this depth
+        #[arg(long)]
+        depth: Option<usize>,
+
+        // Column layout of the rows: "auto", "auto:[layout],[layout]" to also consider these
+        // layouts, the name of a built-in layout, or a layout file
+        #[arg(long, default_value = "auto")]
+        layout: String,
+    },
+    /// Compute the control ID of a ZKR file, the Merkle root of its low-degree extended code
+    ControlId {
+        file: String,
+
+        // Pad the code to 2^po2 rows rather than to the next power of two
+        #[arg(long)]
+        po2: Option<usize>,
+
+        // The hash function of the Merkle tree
+        #[arg(long, value_enum, default_value_t = Hash::Poseidon)]
+        hash: Hash,
+
+        // Column layout of the rows: "auto", "auto:[layout],[layout]" to also consider these
+        // layouts, the name of a built-in layout, or a layout file
+        #[arg(long, default_value = "auto")]
+        layout: String,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Hash {
+    Poseidon,
+    Poseidon2,
+}
+
+impl Hash {
+    fn hash_fn(&self) -> HashFn {
+        match self {
+            Hash::Poseidon => HashFn::Poseidon,
+            Hash::Poseidon2 => HashFn::Poseidon2,
+        }
+    }
+}
+
+fn load_words(file: &str, layout: &str) -> anyhow::Result<(Vec<u32>, IsaLayout)> {
+    let u8vec = std::fs::read(file).with_context(|| format!("cannot read {}", file))?;
+    let layout = IsaLayout::resolve(layout, &u8vec)?;
+
+    let u32vec =
+        words_from_bytes(&u8vec, &layout).with_context(|| format!("cannot parse {}", file))?;
+    Ok((u32vec, layout))
+}
+
+fn load_zkr(file: &str, layout: &str) -> anyhow::Result<Code> {
+    let (u32vec, layout) = load_words(file, layout)?;
+    Code::parse(u32vec.as_slice(), &layout)
+        .with_context(|| format!("cannot parse {} with layout {}", file, layout.name))
+}
+
+fn run_passes(code: &mut Code) -> anyhow::Result<()> {
+    PassManager::default().run(code)?;
+    code.0
+        .retain(|(insn, _)| !matches!(insn, StructuredInstruction::__DELETE__));
+    Ok(())
+}
+
+// the words as a list of ranges, such as 0..=3, 8, 12..=15
+fn word_ranges(words: impl IntoIterator<Item = usize>) -> String {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for w in words {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == w => *end = w,
+            _ => ranges.push((w, w)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}..={}", start, end)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+// one line for each region, with the rows of the classes that occur in the program
+fn print_regions(code: &Code, total: &Profile, window: usize) {
+    let classes: Vec<RowClass> = RowClass::ALL
+        .into_iter()
+        .filter(|class| total.count(*class) != 0)
+        .collect();
+
+    print!("{:>17}", "rows");
+    for class in classes.iter() {
+        print!(" {:>w$}", class.name(), w = class.name().len().max(7));
+    }
+    println!(
+        " {:>12} {:>10} {:>9}",
+        "permutations", "sha blocks", "iop words"
+    );
+
+    for (range, profile) in profile::regions(code, window) {
+        print!("{:>17}", format!("{}..={}", range.start, range.end - 1));
+        for class in classes.iter() {
+            print!(
+                " {:>w$}",
+                profile.count(*class),
+                w = class.name().len().max(7)
+            );
+        }
+        println!(
+            " {:>12} {:>10} {:>9}",
+            profile.poseidon_permutations + profile.poseidon2_permutations,
+            profile.sha_blocks,
+            profile.iop_words
+        );
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    match args.command {
+        Command::Profile {
+            file,
+            layout,
+            window,
+        } => {
+            let code = load_zkr(&file, &layout)?;
+            let total = profile::profile(&code);
+            print!("{}", total);
+            if let Some(window) = window {
+                anyhow::ensure!(window != 0, "the window must have at least one row");
+                println!();
+                print_regions(&code, &total, window);
+            }
+        }
+        Command::Why {
+            file,
+            addr,
+            global,
+            line,
+            no_passes,
+            iop,
+            random_iop,
+            depth,
+            layout,
+        } => {
+            let mut code = load_zkr(&file, &layout)?;
+            if !no_passes {
+                run_passes(&mut code)?;
+            }
+            let iop = match (iop, random_iop) {
+                (Some(path), _) => Some(IopSource::load(&path)?),
+                (None, Some(seed)) => Some(IopSource::new(iop::generate(&code, seed))),
+                (None, None) => None,
+            };
+
+            let mut provenance = Provenance::new(&code);
+            provenance.set_max_depth(depth);
+            if let Some(iop) = iop {
+                if let Err(e) = provenance.run(iop) {
+                    eprintln!(
+                        "the run stops early, the values from there on are not shown: {}",
+                        e
+                    );
+                }
+            }
+
+            let node = match (addr, global) {
+                (Some(addr), _) => {
+                    if let Some(line) = line {
+                        anyhow::ensure!(
+                            (1..=code.0.len()).contains(&line),
+                            "the listing has no line {}",
+                            line
+                        );
+                    }
+                    provenance
+                        .of_addr(addr, line.map(|line| line - 1))
+                        .with_context(|| format!("m[{}] is never written", addr))?
+                }
+                (None, Some(global)) => provenance
+                    .of_global(global)
+                    .with_context(|| format!("global {} is never set", global))?,
+                (None, None) => unreachable!(),
+            };
+            print!("{}", node);
+            let words = node.iop_words();
+            if !words.is_empty() {
+                println!("iop words: {}", word_ranges(words));
+            }
+        }
+        Command::ControlId {
+            file,
+            po2,
+            hash,
+            layout,
+        } => {
+            let (words, layout) = load_words(&file, &layout)?;
+            let rows = words.len() / layout.row_width;
+            let min_po2 = control_id::min_po2(rows);
+            let po2 = po2.unwrap_or(min_po2);
+            anyhow::ensure!(
+                po2 >= min_po2,
+                "the {} rows of {} do not fit in 2^{} rows",
+                rows,
+                file,
+                po2
+            );
+            anyhow::ensure!(
+                (1 << po2) * control_id::INV_RATE <= 1 << 27,
+                "2^{} rows are too many to extend over BabyBear",
+                po2
+            );
+            println!(
+                "{}",
+                control_id::control_id(&words, layout.row_width, po2, hash.hash_fn())
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -29,6 +29,6 @@ fn main() {
         file.read_to_end(&mut buf).unwrap();
 
         let mut ff = File::create(format!("{}{}", args.output, file.name())).unwrap();
-        ff.write(&buf).unwrap();
+        ff.write_all(&buf).unwrap();
     }
 }
@@ -0,0 +1,356 @@
+use crate::math::poseidon::{poseidon_mix, CELLS};
+use crate::math::poseidon2::poseidon2_mix;
+use crate::math::Fp;
+use std::fmt::{Display, Formatter};
+
+/// The blowup of the low-degree extension, which evaluates the code columns over a domain four
+/// times the size of the trace.
+pub const INV_RATE: usize = 4;
+const LOG_INV_RATE: usize = 2;
+
+// the cells that a hash absorbs at a time, and the cells of a digest
+const CELLS_RATE: usize = 16;
+const CELLS_OUT: usize = 8;
+
+// a root of unity of order 2^27, the largest power of two that divides `P - 1`, and the one from
+// which `risc0-zkp` takes the roots of its NTTs
+const ROU_MAX: Fp = Fp(137);
+const ROU_MAX_PO2: usize = 27;
+
+// the offset of the coset over which the columns are extended, for zero knowledge
+const ZK_SHIFT: Fp = Fp(3);
+
+/// The hash function of a Merkle tree, as the hash suites of `risc0-zkp` use the permutations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashFn {
+    Poseidon,
+    Poseidon2,
+}
+
+impl HashFn {
+    fn mix(&self, cells: &mut [Fp; CELLS]) {
+        match self {
+            HashFn::Poseidon => poseidon_mix(cells),
+            HashFn::Poseidon2 => poseidon2_mix(cells),
+        }
+    }
+
+    /// Hash `elems` by blocks of 16. Poseidon adds each block to the state, and Poseidon2
+    /// overwrites the state with it, padding the last block with zeros.
+    pub fn hash_elems(&self, elems: &[Fp]) -> [Fp; CELLS_OUT] {
+        let mut cells: [Fp; CELLS] = Default::default();
+        let mut unmixed = 0;
+        for elem in elems {
+            match self {
+                HashFn::Poseidon => cells[unmixed] = &cells[unmixed] + elem,
+                HashFn::Poseidon2 => cells[unmixed] = elem.clone(),
+            }
+            unmixed += 1;
+            if unmixed == CELLS_RATE {
+                self.mix(&mut cells);
+                unmixed = 0;
+            }
+        }
+        if unmixed != 0 || elems.is_empty() {
+            if *self == HashFn::Poseidon2 {
+                for cell in cells[unmixed..CELLS_RATE].iter_mut() {
+                    *cell = Fp::ZERO;
+                }
+            }
+            self.mix(&mut cells);
+        }
+        cells[..CELLS_OUT].to_vec().try_into().unwrap()
+    }
+
+    /// Hash two digests into their parent in a Merkle tree.
+    pub fn hash_pair(&self, left: &[Fp; CELLS_OUT], right: &[Fp; CELLS_OUT]) -> [Fp; CELLS_OUT] {
+        self.hash_elems(&[left.as_slice(), right.as_slice()].concat())
+    }
+}
+
+/// A digest as `risc0-zkp` writes it, the Montgomery forms of its eight elements.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Digest(pub [u32; CELLS_OUT]);
+
+impl From<&[Fp; CELLS_OUT]> for Digest {
+    fn from(elems: &[Fp; CELLS_OUT]) -> Self {
+        Self(elems.clone().map(|e| e.to_montgomery()))
+    }
+}
+
+// the bytes of the words in little-endian order, in hex, as `risc0-zkp` prints a `Digest`
+impl Display for Digest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for word in self.0.iter() {
+            for byte in word.to_le_bytes() {
+                f.write_fmt(format_args!("{:02x}", byte))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// a root of unity of order 2^po2
+fn root_of_unity(po2: usize) -> Fp {
+    ROU_MAX.pow(1 << (ROU_MAX_PO2 - po2))
+}
+
+// evaluate in place the polynomial with coefficients `values`, from the constant one up, at the
+// powers of `root`, whose order is the length of `values`
+fn ntt(values: &mut [Fp], root: &Fp) {
+    let n = values.len();
+    let bits = n.trailing_zeros();
+    if bits == 0 {
+        return;
+    }
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if i < j {
+            values.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let step = root.pow((n / len) as u64);
+        for chunk in values.chunks_exact_mut(len) {
+            let (lo, hi) = chunk.split_at_mut(len / 2);
+            let mut w = Fp::ONE;
+            for (a, b) in lo.iter_mut().zip(hi.iter_mut()) {
+                let t = &w * &*b;
+                *b = &*a - &t;
+                *a = &*a + &t;
+                w *= &step;
+            }
+        }
+        len *= 2;
+    }
+}
+
+/// Extend a column of `2^k` values, taken as the evaluations of a polynomial over the roots of
+/// unity of order `2^k`, to its evaluations over `3` times the roots of unity of order
+/// `2^k * INV_RATE`, in the order of their powers.
+pub fn low_degree_extend(column: &[Fp]) -> Vec<Fp> {
+    let n = column.len();
+    assert!(n.is_power_of_two(), "the column has {} values", n);
+    let po2 = n.trailing_zeros() as usize;
+
+    let mut coeffs = column.to_vec();
+    ntt(&mut coeffs, &root_of_unity(po2).inv());
+    // interpolate, then shift the coset: the coefficient of x^k is multiplied by 3^k / n
+    let mut factor = Fp::from(n as u64).inv();
+    for coeff in coeffs.iter_mut() {
+        *coeff *= &factor;
+        factor *= &ZK_SHIFT;
+    }
+
+    coeffs.resize(n * INV_RATE, Fp::ZERO);
+    ntt(&mut coeffs, &root_of_unity(po2 + LOG_INV_RATE));
+    coeffs
+}
+
+/// The root of the Merkle tree whose leaves are the hashes of the rows of `columns`, which all
+/// have the same power-of-two length, the left child of each node being the even one.
+pub fn merkle_root(columns: &[Vec<Fp>], hash: HashFn) -> [Fp; CELLS_OUT] {
+    let rows = columns.first().map_or(0, |c| c.len());
+    assert!(rows.is_power_of_two(), "the tree has {} leaves", rows);
+
+    let mut layer: Vec<[Fp; CELLS_OUT]> = (0..rows)
+        .map(|r| {
+            let row: Vec<Fp> = columns.iter().map(|c| c[r].clone()).collect();
+            hash.hash_elems(&row)
+        })
+        .collect();
+    while layer.len() > 1 {
+        layer = layer
+            .chunks_exact(2)
+            .map(|pair| hash.hash_pair(&pair[0], &pair[1]))
+            .collect();
+    }
+    layer.pop().unwrap()
+}
+
+/// The smallest `po2` such that a program of `rows` rows fits in `2^po2` rows.
+pub fn min_po2(rows: usize) -> usize {
+    rows.next_power_of_two().trailing_zeros() as usize
+}
+
+/// The Merkle root of the code of a program, from the words of its zkr with `row_width` columns
+/// per row, following the steps of `compute_control_id` in `risc0-zkp`: the code is padded with
+/// zero rows to `2^po2` rows, each column is extended with `low_degree_extend`, and the rows of
+/// the extension are hashed into a Merkle tree.
+///
+/// The roots of unity are checked against those of `risc0-zkp`, and the permutations against
+/// its test vector for Poseidon2, but no result has been compared with the control ID of a
+/// released zkr yet, so it is not known to match one.
+///
+/// Panics if the program has more than `2^po2` rows, or if `2^po2 * INV_RATE` exceeds `2^27`.
+pub fn control_id(words: &[u32], row_width: usize, po2: usize, hash: HashFn) -> Digest {
+    let rows = words.len() / row_width;
+    let cycles = 1usize << po2;
+    assert!(rows <= cycles, "{} rows do not fit in 2^{} rows", rows, po2);
+    assert!(
+        po2 + LOG_INV_RATE <= ROU_MAX_PO2,
+        "2^{} rows are too many",
+        po2
+    );
+
+    let columns: Vec<Vec<Fp>> = (0..row_width)
+        .map(|c| {
+            let mut column: Vec<Fp> = (0..rows)
+                .map(|r| Fp::new(words[r * row_width + c]))
+                .collect();
+            column.resize(cycles, Fp::ZERO);
+            low_degree_extend(&column)
+        })
+        .collect();
+    Digest::from(&merkle_root(&columns, hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `ROU_FWD` of the BabyBear field of `risc0-zkp`, whose entry `k` is the root of unity of
+    // order 2^k that its NTTs use
+    const ROU_FWD: [u32; 28] = [
+        1, 2013265920, 284861408, 1801542727, 567209306, 740045640, 918899846, 1881002012,
+        1453957774, 65325759, 1538055801, 515192888, 483885487, 157393079, 1695124103, 2005211659,
+        1540072241, 88064245, 1542985445, 1269900459, 1461624142, 825701067, 682402162, 1311873874,
+        1164520853, 352275361, 18769, 137,
+    ];
+
+    fn column(n: usize, seed: u32) -> Vec<Fp> {
+        (0..n as u32)
+            .map(|i| {
+                Fp::new(
+                    seed.wrapping_mul(0x9e3779b9)
+                        .wrapping_add(i.wrapping_mul(0x85ebca6b)),
+                )
+            })
+            .collect()
+    }
+
+    // the evaluations of the polynomial that takes the values of `column` over the roots of
+    // unity of its order, at `points`, from its coefficients computed one by one
+    fn evaluate(column: &[Fp], points: &[Fp]) -> Vec<Fp> {
+        let n = column.len();
+        let w = root_of_unity(n.trailing_zeros() as usize);
+        let n_inv = Fp::from(n as u64).inv();
+        let coeffs: Vec<Fp> = (0..n)
+            .map(|k| {
+                let sum: Fp = (0..n)
+                    .map(|i| &column[i] * w.pow((i * k) as u64).inv())
+                    .sum();
+                sum * &n_inv
+            })
+            .collect();
+        points
+            .iter()
+            .map(|x| coeffs.iter().rev().fold(Fp::ZERO, |acc, c| acc * x + c))
+            .collect()
+    }
+
+    #[test]
+    fn roots_of_unity() {
+        for (po2, root) in ROU_FWD.iter().enumerate() {
+            assert_eq!(root_of_unity(po2), Fp(*root));
+        }
+        assert_eq!(ROU_MAX.pow(1 << (ROU_MAX_PO2 - 1)), -Fp::ONE);
+    }
+
+    #[test]
+    fn ntt_round_trip() {
+        for po2 in 0..8 {
+            let n = 1 << po2;
+            let values = column(n, po2 as u32);
+            let mut out = values.clone();
+            ntt(&mut out, &root_of_unity(po2));
+            // the evaluations at the powers of the root, in order
+            let w = root_of_unity(po2);
+            let points: Vec<Fp> = (0..n).map(|i| w.pow(i as u64)).collect();
+            let direct: Vec<Fp> = points
+                .iter()
+                .map(|x| values.iter().rev().fold(Fp::ZERO, |acc, c| acc * x + c))
+                .collect();
+            assert_eq!(out, direct);
+
+            ntt(&mut out, &w.inv());
+            let n_inv = Fp::from(n as u64).inv();
+            let back: Vec<Fp> = out.iter().map(|v| v * &n_inv).collect();
+            assert_eq!(back, values);
+        }
+    }
+
+    #[test]
+    fn low_degree_extension() {
+        for po2 in 0..6 {
+            let n = 1 << po2;
+            let values = column(n, 7 + po2 as u32);
+            let extended = low_degree_extend(&values);
+            let w = root_of_unity(po2 + LOG_INV_RATE);
+            let points: Vec<Fp> = (0..n * INV_RATE)
+                .map(|j| &ZK_SHIFT * w.pow(j as u64))
+                .collect();
+            assert_eq!(extended, evaluate(&values, &points));
+        }
+    }
+
+    #[test]
+    fn hashes_and_digests() {
+        let elems = column(24, 3);
+        for hash in [HashFn::Poseidon, HashFn::Poseidon2] {
+            let mut cells: [Fp; CELLS] = Default::default();
+            cells[..16].clone_from_slice(&elems[..16]);
+            hash.mix(&mut cells);
+            match hash {
+                HashFn::Poseidon => {
+                    for (cell, elem) in cells.iter_mut().zip(elems[16..].iter()) {
+                        *cell = &*cell + elem;
+                    }
+                }
+                HashFn::Poseidon2 => {
+                    cells[..8].clone_from_slice(&elems[16..]);
+                    cells[8..16].fill(Fp::ZERO);
+                }
+            }
+            hash.mix(&mut cells);
+            assert_eq!(hash.hash_elems(&elems).as_slice(), &cells[..CELLS_OUT]);
+        }
+
+        let digest = Digest::from(&[Fp::ONE, Fp(2), Fp(0), Fp(0), Fp(0), Fp(0), Fp(0), Fp(0)]);
+        assert_eq!(digest.0[0], 268435454);
+        assert!(digest.to_string().starts_with("feffff0ffcffff1f00000000"));
+    }
+
+    #[test]
+    fn control_id_of_a_small_program() {
+        // three rows of two columns, padded to four rows
+        let words = [1, 2, 3, 4, 5, 6];
+        for hash in [HashFn::Poseidon, HashFn::Poseidon2] {
+            let w = root_of_unity(2 + LOG_INV_RATE);
+            let points: Vec<Fp> = (0..16).map(|j| &ZK_SHIFT * w.pow(j as u64)).collect();
+            let columns: Vec<Vec<Fp>> = [[1, 3, 5, 0], [2, 4, 6, 0]]
+                .iter()
+                .map(|c| evaluate(&c.map(Fp), &points))
+                .collect();
+            let mut layer: Vec<[Fp; CELLS_OUT]> = (0..16)
+                .map(|r| hash.hash_elems(&[columns[0][r].clone(), columns[1][r].clone()]))
+                .collect();
+            while layer.len() > 1 {
+                layer = layer
+                    .chunks(2)
+                    .map(|p| hash.hash_elems(&[p[0].as_slice(), p[1].as_slice()].concat()))
+                    .collect();
+            }
+
+            let id = control_id(&words, 2, 2, hash);
+            assert_eq!(id, Digest::from(&layer[0]));
+            assert_eq!(
+                control_id(&[words.as_slice(), &[0, 0]].concat(), 2, 2, hash),
+                id
+            );
+            assert_ne!(control_id(&words, 2, 3, hash), id);
+        }
+    }
+}
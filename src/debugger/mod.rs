@@ -0,0 +1,500 @@
+use crate::iop::IopSource;
+use crate::math::Fp4;
+use crate::parser::{apply_source_map, Code, LineNo};
+use crate::structures::StructuredInstruction;
+use crate::vm::{Vm, VmError};
+use std::fmt::{Display, Formatter};
+
+/// A position in the zkr, as a row and optionally a micro op slot within the row.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RowPos {
+    pub row: usize,
+    pub slot: Option<usize>,
+}
+
+impl RowPos {
+    /// Read `<row>` or `<row>.<slot>`.
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.split_once('.') {
+            Some((row, slot)) => Some(Self {
+                row: row.parse().ok()?,
+                slot: Some(slot.parse().ok()?),
+            }),
+            None => Some(Self {
+                row: text.parse().ok()?,
+                slot: None,
+            }),
+        }
+    }
+
+    /// Whether the instruction at `line_no` comes from this position, or from the whole row when
+    /// no slot is given.
+    pub fn is_covered_by(&self, line_no: &LineNo) -> bool {
+        match self.slot {
+            None => line_no.row <= self.row && self.row <= line_no.end_row,
+            Some(slot) => {
+                (line_no.row, line_no.slot) <= (self.row, slot)
+                    && (self.row, slot) <= (line_no.end_row, line_no.end_slot)
+            }
+        }
+    }
+}
+
+impl Display for RowPos {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.slot {
+            Some(slot) => f.write_fmt(format_args!("row {}.{}", self.row, slot)),
+            None => f.write_fmt(format_args!("row {}", self.row)),
+        }
+    }
+}
+
+/// A breakpoint or a watchpoint.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Stop {
+    // before every instruction that covers this position
+    Row(RowPos),
+    // before every instruction whose text contains this text
+    Text(String),
+    // after every instruction that writes this address
+    Watch(u32),
+}
+
+impl Display for Stop {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stop::Row(pos) => f.write_fmt(format_args!("break at {}", pos)),
+            Stop::Text(text) => f.write_fmt(format_args!("break at \"{}\"", text)),
+            Stop::Watch(addr) => f.write_fmt(format_args!("watch m[{}]", addr)),
+        }
+    }
+}
+
+/// What a watched address held before and after an instruction wrote it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub addr: u32,
+    pub old: Option<Fp4>,
+    pub new: Option<Fp4>,
+}
+
+/// What happened to the program in the last `Debugger::step`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Step {
+    // the instruction ran, and the program goes on
+    Ran,
+    // the instruction wrote watched addresses
+    Watched(Vec<Change>),
+    // the instruction, or the end of the program, failed
+    Halted,
+    // there is no instruction left to run
+    Done,
+}
+
+impl Step {
+    /// Whether a run stops after this step.
+    pub fn stops(&self) -> bool {
+        !matches!(self, Step::Ran)
+    }
+}
+
+/// A program running one instruction at a time, with breakpoints and watchpoints.
+pub struct Debugger {
+    code: Code,
+    // the line of the listing for each instruction of `code`
+    lines: Vec<usize>,
+    // the code as decoded, when `code` is the result of the passes over a zkr
+    raw: Option<Code>,
+    iop: IopSource,
+    write_once: bool,
+    vm: Vm,
+    pc: usize,
+    halted: Option<VmError>,
+    stops: Vec<Option<Stop>>,
+}
+
+impl Debugger {
+    /// Step through `code`, where the instruction at index `i` is at line `lines[i]` of the
+    /// listing, and `raw`, if any, is the zkr as decoded that the passes turned into `code`.
+    pub fn new(
+        code: Code,
+        lines: Vec<usize>,
+        raw: Option<Code>,
+        iop: IopSource,
+        write_once: bool,
+    ) -> Self {
+        assert_eq!(code.0.len(), lines.len());
+        let mut dbg = Self {
+            code,
+            lines,
+            raw,
+            iop,
+            write_once,
+            vm: Vm::new(IopSource::default()),
+            pc: 0,
+            halted: None,
+            stops: Vec::new(),
+        };
+        dbg.restart();
+        dbg
+    }
+
+    /// Step through the instructions of a zkr as decoded.
+    pub fn from_zkr(code: Code, iop: IopSource) -> Self {
+        let lines = (1..=code.0.len()).collect();
+        Self::new(code, lines, None, iop, true)
+    }
+
+    /// Step through `code`, the result of the passes over the zkr `raw`.
+    pub fn from_passes(mut code: Code, raw: Code, iop: IopSource) -> Self {
+        code.0
+            .retain(|(insn, _)| !matches!(insn, StructuredInstruction::__DELETE__));
+        let lines = (1..=code.0.len()).collect();
+        Self::new(code, lines, Some(raw), iop, false)
+    }
+
+    /// Step through a listing, whose instructions speak of the rows of the zkr that `map`, if
+    /// any, gives them.
+    pub fn from_listing(mut code: Code, map: Option<&str>, iop: IopSource) -> anyhow::Result<Self> {
+        code.0
+            .retain(|(insn, _)| !matches!(insn, StructuredInstruction::__DELETE__));
+        let lines = code.0.iter().map(|(_, line_no)| line_no.row).collect();
+        if let Some(map) = map {
+            apply_source_map(&mut code, map)?;
+        }
+        // a decompiled listing may come from `LiveVariableAnalysisPass`, which reuses addresses
+        Ok(Self::new(code, lines, None, iop, false))
+    }
+
+    pub fn code(&self) -> &Code {
+        &self.code
+    }
+
+    pub fn vm(&self) -> &Vm {
+        &self.vm
+    }
+
+    /// The index in `code` of the next instruction to run.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// The error that stopped the program, if any.
+    pub fn halted(&self) -> Option<&VmError> {
+        self.halted.as_ref()
+    }
+
+    /// The line of the listing of the instruction at `idx`.
+    pub fn line(&self, idx: usize) -> usize {
+        self.lines[idx]
+    }
+
+    pub fn restart(&mut self) {
+        self.vm = Vm::new(self.iop.clone());
+        self.vm.set_write_once(self.write_once);
+        self.pc = 0;
+        self.halted = None;
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pc >= self.code.0.len() || self.halted.is_some()
+    }
+
+    /// Execute one instruction, and tell whether the program stops after it.
+    pub fn step(&mut self) -> Step {
+        if self.halted.is_some() {
+            return Step::Halted;
+        }
+        if self.pc >= self.code.0.len() {
+            return Step::Done;
+        }
+        let (insn, line_no) = &self.code.0[self.pc];
+        let writes = insn.writes();
+        let old: Vec<(u32, Option<Fp4>)> = self
+            .watches()
+            .filter(|addr| writes.contains(addr))
+            .map(|addr| (addr, self.vm.get(addr).cloned()))
+            .collect();
+
+        let mut res = self.vm.step(insn, *line_no);
+        self.pc += 1;
+        if res.is_ok() && self.pc == self.code.0.len() {
+            res = self.vm.finish(*line_no);
+        }
+        if let Err(e) = res {
+            self.halted = Some(e);
+            return Step::Halted;
+        }
+
+        if old.is_empty() {
+            return Step::Ran;
+        }
+        Step::Watched(
+            old.into_iter()
+                .map(|(addr, old)| Change {
+                    addr,
+                    old,
+                    new: self.vm.get(addr).cloned(),
+                })
+                .collect(),
+        )
+    }
+
+    /// Execute up to `n` instructions, stopping early as `step` tells.
+    pub fn step_n(&mut self, n: usize) -> Step {
+        let mut step = Step::Ran;
+        for _ in 0..n {
+            step = self.step();
+            if step.stops() {
+                break;
+            }
+        }
+        step
+    }
+
+    /// Run until `target` holds before an instruction, or a breakpoint or watchpoint stops it,
+    /// and give the last step taken.
+    pub fn run_until(&mut self, target: impl Fn(&Self) -> bool) -> Step {
+        let mut first = true;
+        let mut step = Step::Ran;
+        while !self.is_done() {
+            if target(self) || (!first && self.breaks_at(self.pc)) {
+                break;
+            }
+            first = false;
+            step = self.step();
+            if step.stops() {
+                break;
+            }
+        }
+        step
+    }
+
+    /// Run until a breakpoint, a watchpoint, an error or the end.
+    pub fn continue_(&mut self) -> Step {
+        self.run_until(|_| false)
+    }
+
+    /// Run up to the instruction that covers `pos`.
+    pub fn until_row(&mut self, pos: RowPos) -> Step {
+        self.run_until(|dbg| pos.is_covered_by(&dbg.code.0[dbg.pc].1))
+    }
+
+    /// Run up to the instruction at line `line` of the listing.
+    pub fn until_line(&mut self, line: usize) -> Step {
+        self.run_until(|dbg| dbg.lines[dbg.pc] >= line)
+    }
+
+    /// Add a breakpoint or watchpoint, and give its number.
+    pub fn add_stop(&mut self, stop: Stop) -> usize {
+        self.stops.push(Some(stop));
+        self.stops.len()
+    }
+
+    /// Remove breakpoint or watchpoint `n`, and tell whether there was one.
+    pub fn delete_stop(&mut self, n: usize) -> bool {
+        let stop = n.checked_sub(1).and_then(|i| self.stops.get_mut(i));
+        matches!(stop.map(Option::take), Some(Some(_)))
+    }
+
+    pub fn clear_stops(&mut self) {
+        self.stops.clear();
+    }
+
+    /// The breakpoints and watchpoints that are left, with their numbers.
+    pub fn stops(&self) -> impl Iterator<Item = (usize, &Stop)> + '_ {
+        self.stops
+            .iter()
+            .enumerate()
+            .filter_map(|(i, stop)| stop.as_ref().map(|stop| (i + 1, stop)))
+    }
+
+    fn watches(&self) -> impl Iterator<Item = u32> + '_ {
+        self.stops.iter().filter_map(|stop| match stop {
+            Some(Stop::Watch(addr)) => Some(*addr),
+            _ => None,
+        })
+    }
+
+    /// Whether a breakpoint stops the program before the instruction at `idx`.
+    pub fn breaks_at(&self, idx: usize) -> bool {
+        let (insn, line_no) = &self.code.0[idx];
+        self.stops.iter().any(|stop| match stop {
+            Some(Stop::Row(pos)) => pos.is_covered_by(line_no),
+            Some(Stop::Text(text)) => insn.to_string().contains(text.as_str()),
+            _ => false,
+        })
+    }
+
+    /// The instructions of the zkr as decoded that the instruction at `idx` comes from, when
+    /// stepping through the result of the passes.
+    pub fn origin(&self, idx: usize) -> Option<Vec<&(StructuredInstruction, LineNo)>> {
+        let line_no = self.code.0[idx].1;
+        let raw = self.raw.as_ref()?;
+        Some(
+            raw.0
+                .iter()
+                .filter(|(_, raw_line_no)| {
+                    RowPos {
+                        row: raw_line_no.row,
+                        slot: Some(raw_line_no.slot),
+                    }
+                    .is_covered_by(&line_no)
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Fp;
+    use crate::structures::{ReadAddr, StructuredInstruction as I};
+
+    fn r(v: u32) -> ReadAddr {
+        ReadAddr::Ref(v)
+    }
+
+    // (3 + 4)^2 == 49, over the micro ops of rows 2 and 3
+    fn program() -> Code {
+        let mut code = Code::default();
+        code.push(I::WOM_INIT, LineNo::new(1, 0));
+        code.push(I::CONST(1, 3, 0), LineNo::new(2, 0));
+        code.push(I::CONST(2, 4, 0), LineNo::new(2, 1));
+        code.push(I::ADD(3, r(1), r(2)), LineNo::new(2, 2));
+        code.push(I::MUL(4, r(3), r(3)), LineNo::new(3, 0));
+        code.push(I::CONST(5, 49, 0), LineNo::new(3, 1));
+        code.push(I::EQ(r(4), r(5)), LineNo::new(3, 2));
+        code.push(I::WOM_FINI, LineNo::new(4, 0));
+        code
+    }
+
+    fn fp4(v: u32) -> Fp4 {
+        Fp4::from(Fp::new(v))
+    }
+
+    #[test]
+    fn stepping() {
+        let mut dbg = Debugger::from_zkr(program(), IopSource::default());
+        assert_eq!(dbg.step_n(3), Step::Ran);
+        assert_eq!(dbg.pc(), 3);
+        assert_eq!(dbg.vm().get(2), Some(&fp4(4)));
+        assert_eq!(dbg.vm().get(3), None);
+        assert_eq!(dbg.continue_(), Step::Ran);
+        assert!(dbg.is_done() && dbg.halted().is_none());
+        assert_eq!(dbg.vm().get(4), Some(&fp4(49)));
+        assert_eq!(dbg.step(), Step::Done);
+
+        dbg.restart();
+        assert_eq!(dbg.pc(), 0);
+        assert_eq!(dbg.vm().get(1), None);
+
+        // a failed assertion halts the program on its instruction
+        let mut code = program();
+        code.0[5].0 = I::CONST(5, 50, 0);
+        let mut dbg = Debugger::from_zkr(code, IopSource::default());
+        assert_eq!(dbg.continue_(), Step::Halted);
+        assert_eq!(dbg.pc(), 7);
+        assert!(dbg.halted().is_some());
+        assert_eq!(dbg.step(), Step::Halted);
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut dbg = Debugger::from_zkr(program(), IopSource::default());
+        assert_eq!(dbg.add_stop(Stop::Row(RowPos::parse("3").unwrap())), 1);
+        assert_eq!(dbg.add_stop(Stop::Text(String::from("wom_fini"))), 2);
+        assert!(!dbg.breaks_at(3) && dbg.breaks_at(4) && dbg.breaks_at(6));
+        assert!(dbg.breaks_at(7));
+
+        dbg.continue_();
+        assert_eq!(dbg.pc(), 4);
+        // a run leaves the instruction it stopped at before looking at the breakpoints
+        dbg.continue_();
+        assert_eq!(dbg.pc(), 5);
+        assert!(dbg.delete_stop(1));
+        assert!(!dbg.delete_stop(1) && !dbg.delete_stop(0) && !dbg.delete_stop(3));
+        dbg.continue_();
+        assert_eq!(dbg.pc(), 7);
+        assert_eq!(dbg.stops().map(|(n, _)| n).collect::<Vec<_>>(), vec![2]);
+        dbg.clear_stops();
+        assert_eq!(dbg.stops().count(), 0);
+
+        dbg.restart();
+        assert_eq!(dbg.until_row(RowPos::parse("3.1").unwrap()), Step::Ran);
+        assert_eq!(dbg.pc(), 5);
+        dbg.restart();
+        dbg.until_line(4);
+        assert_eq!(dbg.pc(), 3);
+
+        assert_eq!(
+            RowPos::parse("3.1"),
+            Some(RowPos {
+                row: 3,
+                slot: Some(1)
+            })
+        );
+        assert_eq!(RowPos::parse("3.x"), None);
+        assert_eq!(RowPos::parse("add"), None);
+        assert!(RowPos::parse("3.1")
+            .unwrap()
+            .is_covered_by(&LineNo::new(2, 1).to(&LineNo::new(3, 2))));
+        assert!(!RowPos::parse("3.2")
+            .unwrap()
+            .is_covered_by(&LineNo::new(2, 1).to(&LineNo::new(3, 1))));
+    }
+
+    #[test]
+    fn watchpoints() {
+        let mut dbg = Debugger::from_zkr(program(), IopSource::default());
+        dbg.add_stop(Stop::Watch(3));
+        assert_eq!(
+            dbg.continue_(),
+            Step::Watched(vec![Change {
+                addr: 3,
+                old: None,
+                new: Some(fp4(7)),
+            }])
+        );
+        // the instruction that wrote m[3] has run
+        assert_eq!(dbg.pc(), 4);
+        assert_eq!(dbg.continue_(), Step::Ran);
+        assert!(dbg.is_done());
+        assert_eq!(Stop::Watch(3).to_string(), "watch m[3]");
+    }
+
+    #[test]
+    fn source_map() {
+        let raw = program();
+        // the micro ops of row 2 as one instruction
+        let mut code = program();
+        code.0[1].1 = LineNo::new(2, 0).to(&LineNo::new(2, 2));
+        code.0[2].0 = I::__DELETE__;
+        code.0[3].0 = I::__DELETE__;
+        let dbg = Debugger::from_passes(code, raw, IopSource::default());
+        assert_eq!(dbg.code().0.len(), 6);
+        let origin = dbg.origin(1).unwrap();
+        assert_eq!(origin.len(), 3);
+        assert!(origin[2].0 == I::ADD(3, r(1), r(2)));
+        assert_eq!(dbg.origin(0).unwrap().len(), 1);
+
+        // a listing numbers its lines, and the map gives them the rows of the zkr
+        let listing = "1: wom_init();\n2: m[1] = 3;\n3: wom_fini();\n";
+        let map = "line,row,slot,end_row,end_slot\n1,1,0,1,0\n2,2,0,2,0\n3,9,0,9,0\n";
+        let code = Code::try_from(listing).unwrap();
+        let mut dbg = Debugger::from_listing(code, Some(map), IopSource::default()).unwrap();
+        assert!(dbg.origin(0).is_none());
+        assert_eq!((dbg.line(1), dbg.line(2)), (2, 3));
+        assert_eq!(dbg.code().0[2].1, LineNo::new(9, 0));
+        dbg.add_stop(Stop::Row(RowPos::parse("9").unwrap()));
+        dbg.continue_();
+        assert_eq!(dbg.pc(), 2);
+        dbg.restart();
+        dbg.until_line(2);
+        assert_eq!(dbg.pc(), 1);
+
+        let code = Code::try_from(listing).unwrap();
+        assert!(Debugger::from_listing(code, Some("line\n1,2\n"), IopSource::default()).is_err());
+    }
+}
@@ -0,0 +1,44 @@
+use crate::parser::{LineNo, ParserError};
+use std::fmt::{Debug, Display, Formatter};
+#[derive(Debug, Clone)]
+pub enum EncoderError {
+    UnsupportedInstruction(LineNo, String),
+    NonRawOperand(LineNo, String),
+    IncompleteMicroRow(LineNo, usize),
+    // a micro instruction whose write address is not the one of its slot in the row
+    MisplacedWrite(LineNo, String),
+    // the instruction needs a column that the layout, named by the last field, does not have
+    MissingColumn(LineNo, &'static str, String),
+    // the original rows given to `encode_exact` do not parse
+    Original(ParserError),
+}
+
+impl Display for EncoderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncoderError::UnsupportedInstruction(line_no, insn) => f.write_fmt(format_args!(
+                "Instruction at line {} has no raw encoding: {}",
+                line_no, insn
+            )),
+            EncoderError::NonRawOperand(line_no, insn) => f.write_fmt(format_args!(
+                "Instruction at line {} reads an operand that is not a plain address: {}",
+                line_no, insn
+            )),
+            EncoderError::IncompleteMicroRow(line_no, count) => f.write_fmt(format_args!(
+                "Micro row at line {} has {} instructions instead of 3",
+                line_no, count
+            )),
+            EncoderError::MisplacedWrite(line_no, insn) => f.write_fmt(format_args!(
+                "Micro instruction at line {} does not write to the address of its slot: {}",
+                line_no, insn
+            )),
+            EncoderError::MissingColumn(line_no, column, layout) => f.write_fmt(format_args!(
+                "Instruction at line {} needs the column {}, which layout {} does not have",
+                line_no, column, layout
+            )),
+            EncoderError::Original(e) => {
+                f.write_fmt(format_args!("The original rows do not parse: {}", e))
+            }
+        }
+    }
+}
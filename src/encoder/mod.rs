@@ -0,0 +1,814 @@
+mod error;
+
+pub use crate::encoder::error::EncoderError;
+use crate::layout::IsaLayout;
+use crate::math::P;
+use crate::parser::{Code, LineNo};
+use crate::structures::{ReadAddr, StructuredInstruction};
+use crate::{
+    MICRO_ADD, MICRO_CONST, MICRO_EQ, MICRO_EXTRACT, MICRO_INV, MICRO_MIX_RNG, MICRO_MUL,
+    MICRO_READ_IOP_BODY, MICRO_READ_IOP_HEADER, MICRO_SELECT, MICRO_SUB,
+};
+use std::ops::Range;
+
+type Row = Vec<u32>;
+
+struct GlobalState<'a> {
+    layout: &'a IsaLayout,
+    sha_fini_start: u32,
+    sha_fini_pos: u32,
+    // one past the highest address written so far, used as the base of micro rows
+    // whose three slots do not carry a write address (e.g. three `EQ`s)
+    next_addr: u32,
+}
+
+fn addr(r: &ReadAddr, line_no: LineNo, insn: &StructuredInstruction) -> Result<u32, EncoderError> {
+    match r {
+        ReadAddr::Ref(v) => Ok(*v),
+        _ => Err(EncoderError::NonRawOperand(line_no, insn.to_string())),
+    }
+}
+
+fn is_micro(insn: &StructuredInstruction) -> bool {
+    matches!(
+        insn,
+        StructuredInstruction::CONST(..)
+            | StructuredInstruction::ADD(..)
+            | StructuredInstruction::SUB(..)
+            | StructuredInstruction::MUL(..)
+            | StructuredInstruction::NOT(..)
+            | StructuredInstruction::INV(..)
+            | StructuredInstruction::EQ(..)
+            | StructuredInstruction::READ_IOP_HEADER(..)
+            | StructuredInstruction::READ_IOP_BODY(..)
+            | StructuredInstruction::MIX_RNG_WITH_PERV(..)
+            | StructuredInstruction::MIX_RNG(..)
+            | StructuredInstruction::SELECT(..)
+            | StructuredInstruction::EXTRACT(..)
+    )
+}
+
+fn micro_write_addr(insn: &StructuredInstruction) -> Option<u32> {
+    match insn {
+        StructuredInstruction::CONST(w, _, _)
+        | StructuredInstruction::ADD(w, _, _)
+        | StructuredInstruction::SUB(w, _, _)
+        | StructuredInstruction::MUL(w, _, _)
+        | StructuredInstruction::NOT(w, _)
+        | StructuredInstruction::INV(w, _)
+        | StructuredInstruction::READ_IOP_BODY(w)
+        | StructuredInstruction::MIX_RNG_WITH_PERV(w, _, _, _, _)
+        | StructuredInstruction::MIX_RNG(w, _, _)
+        | StructuredInstruction::SELECT(w, _, _, _)
+        | StructuredInstruction::EXTRACT(w, _, _) => Some(*w),
+        _ => None,
+    }
+}
+
+fn encode_micro_slot(
+    insn: &StructuredInstruction,
+    line_no: LineNo,
+) -> Result<[u32; 4], EncoderError> {
+    let res = match insn {
+        StructuredInstruction::CONST(_, v1, v2) => [MICRO_CONST, *v1, *v2, 0],
+        StructuredInstruction::ADD(_, r1, r2) => [
+            MICRO_ADD,
+            addr(r1, line_no, insn)?,
+            addr(r2, line_no, insn)?,
+            0,
+        ],
+        StructuredInstruction::SUB(_, r1, r2) => [
+            MICRO_SUB,
+            addr(r1, line_no, insn)?,
+            addr(r2, line_no, insn)?,
+            0,
+        ],
+        StructuredInstruction::MUL(_, r1, r2) => [
+            MICRO_MUL,
+            addr(r1, line_no, insn)?,
+            addr(r2, line_no, insn)?,
+            0,
+        ],
+        StructuredInstruction::NOT(_, r) => [MICRO_INV, addr(r, line_no, insn)?, 0, 0],
+        StructuredInstruction::INV(_, r) => [MICRO_INV, addr(r, line_no, insn)?, 1, 0],
+        StructuredInstruction::EQ(r1, r2) => [
+            MICRO_EQ,
+            addr(r1, line_no, insn)?,
+            addr(r2, line_no, insn)?,
+            0,
+        ],
+        StructuredInstruction::READ_IOP_HEADER(p1, p2) => [MICRO_READ_IOP_HEADER, *p1, *p2, 0],
+        StructuredInstruction::READ_IOP_BODY(_) => [MICRO_READ_IOP_BODY, 0, 0, 0],
+        StructuredInstruction::MIX_RNG_WITH_PERV(_, fp, _, r1, r2) => [
+            MICRO_MIX_RNG,
+            addr(r1, line_no, insn)?,
+            addr(r2, line_no, insn)?,
+            *fp,
+        ],
+        StructuredInstruction::MIX_RNG(_, r1, r2) => [
+            MICRO_MIX_RNG,
+            addr(r1, line_no, insn)?,
+            addr(r2, line_no, insn)?,
+            0,
+        ],
+        StructuredInstruction::SELECT(_, s, r1, r2) => {
+            let if_true = addr(r1, line_no, insn)?;
+            let if_false = addr(r2, line_no, insn)?;
+            // the machine stores the distance between the two addresses modulo p
+            let diff = if if_true >= if_false {
+                if_true - if_false
+            } else {
+                P - (if_false - if_true)
+            };
+            [MICRO_SELECT, addr(s, line_no, insn)?, if_false, diff]
+        }
+        StructuredInstruction::EXTRACT(_, r, idx) => {
+            [MICRO_EXTRACT, addr(r, line_no, insn)?, idx / 2, idx % 2]
+        }
+        _ => {
+            return Err(EncoderError::UnsupportedInstruction(
+                line_no,
+                insn.to_string(),
+            ))
+        }
+    };
+    Ok(res)
+}
+
+fn encode_micro(
+    global_state: &mut GlobalState,
+    insns: &[&(StructuredInstruction, LineNo)],
+) -> Result<Row, EncoderError> {
+    let line_no = insns[0].1;
+    if insns.len() != 3 {
+        return Err(EncoderError::IncompleteMicroRow(line_no, insns.len()));
+    }
+
+    // the slots write to consecutive addresses from the write address of the row
+    let mut base = None;
+    for (i, (insn, line_no)) in insns.iter().enumerate() {
+        if let Some(w) = micro_write_addr(insn) {
+            let slot_base = w
+                .checked_sub(i as u32)
+                .filter(|b| base.is_none_or(|base| base == *b))
+                .ok_or_else(|| EncoderError::MisplacedWrite(*line_no, insn.to_string()))?;
+            base = Some(slot_base);
+        }
+    }
+    let base = base.unwrap_or(global_state.next_addr);
+
+    let l = global_state.layout;
+    let mut row = vec![0u32; l.row_width];
+    row[l.write_addr] = base;
+    row[l.select_micro_ops] = 1;
+    for ((insn, line_no), slot) in insns.iter().zip(l.micro_slots) {
+        row[slot..slot + 4].copy_from_slice(&encode_micro_slot(insn, *line_no)?);
+    }
+    global_state.next_addr = global_state.next_addr.max(base.saturating_add(3));
+
+    Ok(row)
+}
+
+fn encode_macro(
+    global_state: &mut GlobalState,
+    insn: &StructuredInstruction,
+    line_no: LineNo,
+) -> Result<Row, EncoderError> {
+    let l = global_state.layout;
+    let mut row = vec![0u32; l.row_width];
+    row[l.select_macro_ops] = 1;
+
+    match insn {
+        StructuredInstruction::BIT_AND_ELEM(w, r1, r2) => {
+            row[l.write_addr] = *w;
+            row[l.macro_bit_and_elem] = 1;
+            row[l.macro_operand_0] = addr(r1, line_no, insn)?;
+            row[l.macro_operand_1] = addr(r2, line_no, insn)?;
+            global_state.next_addr = global_state.next_addr.max(w.saturating_add(1));
+        }
+        StructuredInstruction::BIT_AND_SHORTS(w, r1, r2)
+        | StructuredInstruction::BIT_XOR_SHORTS(w, r1, r2) => {
+            row[l.write_addr] = *w;
+            row[l.macro_bit_op_shorts] = 1;
+            row[l.macro_operand_0] = addr(r1, line_no, insn)?;
+            row[l.macro_operand_1] = addr(r2, line_no, insn)?;
+            row[l.macro_operand_2] =
+                matches!(insn, StructuredInstruction::BIT_AND_SHORTS(..)) as u32;
+            global_state.next_addr = global_state.next_addr.max(w.saturating_add(1));
+        }
+        StructuredInstruction::SHA_INIT_START | StructuredInstruction::SHA_INIT_PADDING => {
+            row[l.macro_sha_init] = 1;
+        }
+        StructuredInstruction::SHA_LOAD_FROM_MONTGOMERY(r) => {
+            row[l.macro_sha_load] = 1;
+            row[l.macro_operand_0] = addr(r, line_no, insn)?;
+        }
+        StructuredInstruction::SHA_LOAD(r) => {
+            row[l.macro_sha_load] = 1;
+            row[l.macro_operand_0] = addr(r, line_no, insn)?;
+            row[l.macro_operand_2] = 1;
+        }
+        StructuredInstruction::SHA_MIX => {
+            row[l.macro_sha_mix] = 1;
+        }
+        StructuredInstruction::SHA_FINI_START(ws) => {
+            global_state.sha_fini_start = *ws;
+            global_state.sha_fini_pos = 1;
+            row[l.macro_sha_fini] = 1;
+            row[l.macro_operand_0] = ws + 3;
+            global_state.next_addr = global_state.next_addr.max(ws.saturating_add(8));
+        }
+        StructuredInstruction::SHA_FINI_PADDING => {
+            // the four fini rows walk the output address down towards the start
+            row[l.macro_sha_fini] = 1;
+            row[l.macro_operand_0] = global_state.sha_fini_start + 3 - global_state.sha_fini_pos;
+            global_state.sha_fini_pos = (global_state.sha_fini_pos + 1) % 4;
+        }
+        StructuredInstruction::WOM_INIT => {
+            row[l.macro_wom_init] = 1;
+        }
+        StructuredInstruction::WOM_FINI => {
+            row[l.macro_wom_fini] = 1;
+        }
+        StructuredInstruction::NOP => {
+            row[l.macro_nop] = 1;
+        }
+        StructuredInstruction::SET_GLOBAL(r1, _, _, _, idx) => {
+            row[l.macro_set_global] = 1;
+            row[l.macro_operand_0] = addr(r1, line_no, insn)?;
+            row[l.macro_operand_1] = *idx;
+        }
+        _ => {
+            return Err(EncoderError::UnsupportedInstruction(
+                line_no,
+                insn.to_string(),
+            ))
+        }
+    }
+
+    Ok(row)
+}
+
+fn encode_poseidon(
+    global_state: &mut GlobalState,
+    insn: &StructuredInstruction,
+    line_no: LineNo,
+) -> Result<Row, EncoderError> {
+    let l = global_state.layout;
+    let mut row = vec![0u32; l.row_width];
+
+    let group_columns = |row: &mut Row, group: u32| {
+        row[l.poseidon_load_g0] = (group == 0) as u32;
+        row[l.poseidon_load_g1] = (group == 1) as u32;
+        row[l.poseidon_load_g2] = (group == 2) as u32;
+    };
+
+    match insn {
+        StructuredInstruction::POSEIDON_LOAD_FROM_MONTGOMERY(
+            add_consts,
+            group,
+            r1,
+            r2,
+            r3,
+            r4,
+            r5,
+            r6,
+            r7,
+            r8,
+        )
+        | StructuredInstruction::POSEIDON_LOAD(add_consts, group, r1, r2, r3, r4, r5, r6, r7, r8)
+        | StructuredInstruction::POSEIDON_ADD_LOAD_FROM_MONTGOMERY(
+            add_consts,
+            group,
+            r1,
+            r2,
+            r3,
+            r4,
+            r5,
+            r6,
+            r7,
+            r8,
+        )
+        | StructuredInstruction::POSEIDON_ADD_LOAD(
+            add_consts,
+            group,
+            r1,
+            r2,
+            r3,
+            r4,
+            r5,
+            r6,
+            r7,
+            r8,
+        ) => {
+            row[l.select_poseidon_load] = 1;
+            row[l.poseidon_do_mont] = matches!(
+                insn,
+                StructuredInstruction::POSEIDON_LOAD_FROM_MONTGOMERY(..)
+                    | StructuredInstruction::POSEIDON_ADD_LOAD_FROM_MONTGOMERY(..)
+            ) as u32;
+            row[l.poseidon_load_keep_state] = matches!(
+                insn,
+                StructuredInstruction::POSEIDON_ADD_LOAD_FROM_MONTGOMERY(..)
+                    | StructuredInstruction::POSEIDON_ADD_LOAD(..)
+            ) as u32;
+            row[l.poseidon_load_add_consts] = *add_consts;
+            group_columns(&mut row, *group);
+            for (i, r) in [r1, r2, r3, r4, r5, r6, r7, r8].iter().enumerate() {
+                row[l.poseidon_load_operands + i] = addr(r, line_no, insn)?;
+            }
+        }
+        StructuredInstruction::POSEIDON_FULL => {
+            row[l.select_poseidon_full] = 1;
+        }
+        StructuredInstruction::POSEIDON_PARTIAL => {
+            row[l.select_poseidon_partial] = 1;
+        }
+        StructuredInstruction::POSEIDON_STORE_TO_MONTGOMERY(group, ws)
+        | StructuredInstruction::POSEIDON_STORE(group, ws) => {
+            row[l.write_addr] = *ws;
+            row[l.select_poseidon_store] = 1;
+            row[l.poseidon_do_mont] = matches!(
+                insn,
+                StructuredInstruction::POSEIDON_STORE_TO_MONTGOMERY(..)
+            ) as u32;
+            group_columns(&mut row, *group);
+            global_state.next_addr = global_state.next_addr.max(ws.saturating_add(8));
+        }
+        _ => {
+            return Err(EncoderError::UnsupportedInstruction(
+                line_no,
+                insn.to_string(),
+            ))
+        }
+    }
+
+    Ok(row)
+}
+
+fn encode_poseidon2(
+    global_state: &mut GlobalState,
+    insn: &StructuredInstruction,
+    line_no: LineNo,
+) -> Result<Row, EncoderError> {
+    let l = global_state.layout;
+    let column = |c: Option<usize>, name: &'static str| {
+        c.ok_or_else(|| EncoderError::MissingColumn(line_no, name, l.name.clone()))
+    };
+    let mut row = vec![0u32; l.row_width];
+
+    let group_columns = |row: &mut Row, group: u32| {
+        row[l.poseidon_load_g0] = (group == 0) as u32;
+        row[l.poseidon_load_g1] = (group == 1) as u32;
+        row[l.poseidon_load_g2] = (group == 2) as u32;
+    };
+
+    match insn {
+        StructuredInstruction::POSEIDON2_LOAD_FROM_MONTGOMERY(
+            keep_upper_state,
+            prep_full,
+            group,
+            r1,
+            r2,
+            r3,
+            r4,
+            r5,
+            r6,
+            r7,
+            r8,
+        )
+        | StructuredInstruction::POSEIDON2_LOAD(
+            keep_upper_state,
+            prep_full,
+            group,
+            r1,
+            r2,
+            r3,
+            r4,
+            r5,
+            r6,
+            r7,
+            r8,
+        ) => {
+            row[column(l.select_poseidon2_load, "select_poseidon2_load")?] = 1;
+            row[l.poseidon_do_mont] = matches!(
+                insn,
+                StructuredInstruction::POSEIDON2_LOAD_FROM_MONTGOMERY(..)
+            ) as u32;
+            if *keep_upper_state != 0 {
+                row[column(
+                    l.poseidon2_load_keep_upper_state,
+                    "poseidon2_load_keep_upper_state",
+                )?] = *keep_upper_state;
+            }
+            if *prep_full != 0 {
+                row[column(l.poseidon2_load_prep_full, "poseidon2_load_prep_full")?] = *prep_full;
+            }
+            group_columns(&mut row, *group);
+            for (i, r) in [r1, r2, r3, r4, r5, r6, r7, r8].iter().enumerate() {
+                row[l.poseidon_load_operands + i] = addr(r, line_no, insn)?;
+            }
+        }
+        StructuredInstruction::POSEIDON2_ADD_LOAD_FROM_MONTGOMERY(
+            prep_full,
+            group,
+            r1,
+            r2,
+            r3,
+            r4,
+            r5,
+            r6,
+            r7,
+            r8,
+        )
+        | StructuredInstruction::POSEIDON2_ADD_LOAD(
+            prep_full,
+            group,
+            r1,
+            r2,
+            r3,
+            r4,
+            r5,
+            r6,
+            r7,
+            r8,
+        ) => {
+            row[column(l.select_poseidon2_load, "select_poseidon2_load")?] = 1;
+            row[l.poseidon_do_mont] = matches!(
+                insn,
+                StructuredInstruction::POSEIDON2_ADD_LOAD_FROM_MONTGOMERY(..)
+            ) as u32;
+            row[l.poseidon_load_keep_state] = 1;
+            if *prep_full != 0 {
+                row[column(l.poseidon2_load_prep_full, "poseidon2_load_prep_full")?] = *prep_full;
+            }
+            group_columns(&mut row, *group);
+            for (i, r) in [r1, r2, r3, r4, r5, r6, r7, r8].iter().enumerate() {
+                row[l.poseidon_load_operands + i] = addr(r, line_no, insn)?;
+            }
+        }
+        StructuredInstruction::POSEIDON2_FULL => {
+            row[column(l.select_poseidon2_full, "select_poseidon2_full")?] = 1;
+        }
+        StructuredInstruction::POSEIDON2_PARTIAL => {
+            row[column(l.select_poseidon2_partial, "select_poseidon2_partial")?] = 1;
+        }
+        StructuredInstruction::POSEIDON2_STORE_TO_MONTGOMERY(group, ws)
+        | StructuredInstruction::POSEIDON2_STORE(group, ws) => {
+            row[l.write_addr] = *ws;
+            row[column(l.select_poseidon2_store, "select_poseidon2_store")?] = 1;
+            row[l.poseidon_do_mont] = matches!(
+                insn,
+                StructuredInstruction::POSEIDON2_STORE_TO_MONTGOMERY(..)
+            ) as u32;
+            group_columns(&mut row, *group);
+            global_state.next_addr = global_state.next_addr.max(ws.saturating_add(8));
+        }
+        _ => {
+            return Err(EncoderError::UnsupportedInstruction(
+                line_no,
+                insn.to_string(),
+            ))
+        }
+    }
+
+    Ok(row)
+}
+
+fn is_poseidon2(insn: &StructuredInstruction) -> bool {
+    matches!(
+        insn,
+        StructuredInstruction::POSEIDON2_LOAD_FROM_MONTGOMERY(..)
+            | StructuredInstruction::POSEIDON2_LOAD(..)
+            | StructuredInstruction::POSEIDON2_ADD_LOAD_FROM_MONTGOMERY(..)
+            | StructuredInstruction::POSEIDON2_ADD_LOAD(..)
+            | StructuredInstruction::POSEIDON2_FULL
+            | StructuredInstruction::POSEIDON2_PARTIAL
+            | StructuredInstruction::POSEIDON2_STORE_TO_MONTGOMERY(..)
+            | StructuredInstruction::POSEIDON2_STORE(..)
+    )
+}
+
+fn is_poseidon(insn: &StructuredInstruction) -> bool {
+    matches!(
+        insn,
+        StructuredInstruction::POSEIDON_LOAD_FROM_MONTGOMERY(..)
+            | StructuredInstruction::POSEIDON_LOAD(..)
+            | StructuredInstruction::POSEIDON_ADD_LOAD_FROM_MONTGOMERY(..)
+            | StructuredInstruction::POSEIDON_ADD_LOAD(..)
+            | StructuredInstruction::POSEIDON_FULL
+            | StructuredInstruction::POSEIDON_PARTIAL
+            | StructuredInstruction::POSEIDON_STORE_TO_MONTGOMERY(..)
+            | StructuredInstruction::POSEIDON_STORE(..)
+    )
+}
+
+// the rows that a `Code` was parsed from, with the instructions parsed from each
+struct Original<'a> {
+    words: &'a [u32],
+    code: Code,
+    // for each row, the range of `code` that it was parsed into
+    rows: Vec<Range<usize>>,
+}
+
+impl Original<'_> {
+    // the words of row `row`, counting from 1, if it was parsed into `insns`
+    fn row(&self, row: usize, insns: &[(StructuredInstruction, LineNo)]) -> Option<&[u32]> {
+        let range = self.rows.get(row.checked_sub(1)?)?;
+        let parsed = &self.code.0[range.clone()];
+        if parsed.len() != insns.len() || parsed.iter().zip(insns).any(|(a, b)| a.0 != b.0) {
+            return None;
+        }
+        let width = self.words.len() / self.rows.len();
+        Some(&self.words[(row - 1) * width..row * width])
+    }
+}
+
+fn encode_rows(
+    value: &Code,
+    layout: &IsaLayout,
+    original: Option<&Original>,
+) -> Result<Vec<u32>, EncoderError> {
+    let mut global_state = GlobalState {
+        layout,
+        sha_fini_start: 0,
+        sha_fini_pos: 0,
+        next_addr: 0,
+    };
+    let mut out = Vec::with_capacity(value.0.len() * layout.row_width);
+
+    let mut cur = 0;
+    while cur < value.0.len() {
+        let start = cur;
+        let (insn, line_no) = &value.0[cur];
+
+        let row = if is_micro(insn) {
+            let mut end = cur + 1;
+            while end < value.0.len()
+                && value.0[end].1.row == line_no.row
+                && is_micro(&value.0[end].0)
+            {
+                end += 1;
+            }
+            let group = value.0[cur..end].iter().collect::<Vec<_>>();
+            cur = end;
+            encode_micro(&mut global_state, &group)?
+        } else if *insn == StructuredInstruction::PADDING {
+            cur += 1;
+            vec![0u32; layout.row_width]
+        } else if is_poseidon(insn) {
+            cur += 1;
+            encode_poseidon(&mut global_state, insn, *line_no)?
+        } else if is_poseidon2(insn) {
+            cur += 1;
+            encode_poseidon2(&mut global_state, insn, *line_no)?
+        } else {
+            cur += 1;
+            encode_macro(&mut global_state, insn, *line_no)?
+        };
+
+        match original.and_then(|o| o.row(line_no.row, &value.0[start..cur])) {
+            Some(words) => out.extend_from_slice(words),
+            None => out.extend_from_slice(&row),
+        }
+    }
+
+    Ok(out)
+}
+
+/// Encode a `Code` of raw instructions, as produced by `Code::parse`, back into rows placed
+/// as `layout` describes.
+///
+/// Micro instructions are packed three per row by the row of their `LineNo`. Instructions
+/// introduced by the passes have no row of their own and are rejected.
+///
+/// The encoding is canonical: the columns that the parser does not read are written as zero,
+/// and a micro row none of whose slots writes, such as a row of three `EQ`s, gets the first
+/// address above the ones written so far. `encode_exact` keeps those columns from the original
+/// rows instead.
+pub fn encode(value: &Code, layout: &IsaLayout) -> Result<Vec<u32>, EncoderError> {
+    encode_rows(value, layout, None)
+}
+
+/// Encode `value` like `encode`, but write each row whose instructions are still those that
+/// `original` decodes to at the same row as it is in `original`, so that the columns the parser
+/// does not read, the write address of a micro row that writes nothing and the operands it
+/// reads as flags, such as the nonzero operand of an `INV`, are kept. Parsing rows and encoding
+/// them again with `encode_exact` gives back the same words.
+pub fn encode_exact(
+    value: &Code,
+    layout: &IsaLayout,
+    original: &[u32],
+) -> Result<Vec<u32>, EncoderError> {
+    let code = Code::parse(original, layout).map_err(EncoderError::Original)?;
+    let mut rows: Vec<Range<usize>> = Vec::with_capacity(original.len() / layout.row_width);
+    for (idx, (_, line_no)) in code.0.iter().enumerate() {
+        match rows.get_mut(line_no.row - 1) {
+            Some(range) => range.end = idx + 1,
+            None => rows.push(idx..idx + 1),
+        }
+    }
+    let original = Original {
+        words: original,
+        code,
+        rows,
+    };
+    encode_rows(value, layout, Some(&original))
+}
+
+/// Encode with the default layout.
+impl TryFrom<&Code> for Vec<u32> {
+    type Error = EncoderError;
+
+    fn try_from(value: &Code) -> Result<Self, Self::Error> {
+        encode(value, &IsaLayout::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(code: &Code) -> Vec<String> {
+        code.0
+            .iter()
+            .map(|(insn, line_no)| format!("{}: {}", line_no, insn))
+            .collect()
+    }
+
+    fn micro_row(l: &IsaLayout, write_addr: u32, slots: [[u32; 4]; 3]) -> Row {
+        let mut row = vec![0u32; l.row_width];
+        row[l.write_addr] = write_addr;
+        row[l.select_micro_ops] = 1;
+        for (slot, base) in slots.iter().zip(l.micro_slots) {
+            row[base..base + 4].copy_from_slice(slot);
+        }
+        row
+    }
+
+    fn macro_row(l: &IsaLayout, select: usize, write_addr: u32, operands: [u32; 3]) -> Row {
+        let mut row = vec![0u32; l.row_width];
+        row[l.write_addr] = write_addr;
+        row[l.select_macro_ops] = 1;
+        row[select] = 1;
+        row[l.macro_operand_0] = operands[0];
+        row[l.macro_operand_1] = operands[1];
+        row[l.macro_operand_2] = operands[2];
+        row
+    }
+
+    fn rows(l: &IsaLayout) -> Vec<u32> {
+        let mut load = vec![0u32; l.row_width];
+        load[l.select_poseidon_load] = 1;
+        load[l.poseidon_load_g1] = 1;
+        for i in 0..8 {
+            load[l.poseidon_load_operands + i] = 20 + i as u32;
+        }
+        let mut full = vec![0u32; l.row_width];
+        full[l.select_poseidon_full] = 1;
+        // a column that the parser does not read
+        full[l.write_addr] = 77;
+        let mut partial = vec![0u32; l.row_width];
+        partial[l.select_poseidon_partial] = 1;
+        let mut store = vec![0u32; l.row_width];
+        store[l.select_poseidon_store] = 1;
+        store[l.poseidon_do_mont] = 1;
+        store[l.poseidon_load_g0] = 1;
+        store[l.write_addr] = 40;
+
+        [
+            // a write address and an operand that the parser does not read
+            macro_row(l, l.macro_wom_init, 7, [9, 0, 0]),
+            micro_row(
+                l,
+                10,
+                [
+                    [MICRO_CONST, 5, 7, 0],
+                    [MICRO_READ_IOP_HEADER, 2, 8, 0],
+                    [MICRO_READ_IOP_BODY, 0, 0, 0],
+                ],
+            ),
+            micro_row(
+                l,
+                13,
+                [
+                    [MICRO_ADD, 10, 12, 3],
+                    // an `INV`, like any nonzero operand
+                    [MICRO_INV, 13, 5, 0],
+                    [MICRO_SELECT, 10, 12, P - 2],
+                ],
+            ),
+            // a row that writes nothing, whose write address `encode` does not keep
+            micro_row(
+                l,
+                500,
+                [
+                    [MICRO_EQ, 10, 11, 0],
+                    [MICRO_EQ, 12, 13, 0],
+                    [MICRO_EQ, 14, 15, 0],
+                ],
+            ),
+            micro_row(
+                l,
+                16,
+                [
+                    [MICRO_EXTRACT, 13, 1, 1],
+                    [MICRO_MIX_RNG, 16, 10, 1],
+                    [MICRO_MUL, 17, 17, 0],
+                ],
+            ),
+            macro_row(l, l.macro_bit_and_elem, 19, [16, 17, 0]),
+            load,
+            full,
+            partial,
+            store,
+            macro_row(l, l.macro_set_global, 0, [40, 3, 0]),
+            macro_row(l, l.macro_wom_fini, 0, [0, 0, 0]),
+            vec![0u32; l.row_width],
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn parse_encode_parse() {
+        let layout = IsaLayout::default();
+        let words = rows(&layout);
+        let code = Code::parse(&words, &layout).unwrap();
+        let encoded = encode(&code, &layout).unwrap();
+        assert_ne!(encoded, words);
+        let reparsed = Code::parse(&encoded, &layout).unwrap();
+        assert_eq!(listing(&code), listing(&reparsed));
+
+        // the encoding is canonical
+        assert_eq!(encode(&reparsed, &layout).unwrap(), encoded);
+    }
+
+    #[test]
+    fn parse_encode_exact() {
+        let layout = IsaLayout::default();
+        let words = rows(&layout);
+        let code = Code::parse(&words, &layout).unwrap();
+        assert_eq!(encode_exact(&code, &layout, &words).unwrap(), words);
+
+        // a row whose instructions changed gets the canonical encoding, the others are kept
+        let mut changed = Code::parse(&words, &layout).unwrap();
+        changed.0[4].0 = StructuredInstruction::SUB(13, 10.into(), 12.into());
+        let encoded = encode_exact(&changed, &layout, &words).unwrap();
+        let row = |words: &[u32], r: usize| {
+            words[r * layout.row_width..(r + 1) * layout.row_width].to_vec()
+        };
+        assert_eq!(
+            row(&encoded, 2),
+            micro_row(
+                &layout,
+                13,
+                [
+                    [MICRO_SUB, 10, 12, 0],
+                    [MICRO_INV, 13, 1, 0],
+                    [MICRO_SELECT, 10, 12, P - 2],
+                ]
+            )
+        );
+        for r in (0..words.len() / layout.row_width).filter(|r| *r != 2) {
+            assert_eq!(row(&encoded, r), row(&words, r));
+        }
+        assert_eq!(
+            listing(&Code::parse(&encoded, &layout).unwrap()),
+            listing(&changed)
+        );
+
+        assert!(matches!(
+            encode_exact(&code, &layout, &words[1..]),
+            Err(EncoderError::Original(_))
+        ));
+    }
+
+    #[test]
+    fn misplaced_write() {
+        let layout = IsaLayout::default();
+        let mut code = Code::default();
+        code.push(
+            StructuredInstruction::EQ(1.into(), 2.into()),
+            LineNo::new(1, 0),
+        );
+        code.push(
+            StructuredInstruction::ADD(0, 1.into(), 2.into()),
+            LineNo::new(1, 1),
+        );
+        code.push(
+            StructuredInstruction::EQ(1.into(), 2.into()),
+            LineNo::new(1, 2),
+        );
+        assert!(matches!(
+            encode(&code, &layout),
+            Err(EncoderError::MisplacedWrite(line_no, _)) if line_no == LineNo::new(1, 1)
+        ));
+
+        code.0[1].0 = StructuredInstruction::ADD(7, 1.into(), 2.into());
+        code.0[2].0 = StructuredInstruction::NOT(9, 1.into());
+        assert!(matches!(
+            encode(&code, &layout),
+            Err(EncoderError::MisplacedWrite(line_no, _)) if line_no == LineNo::new(1, 2)
+        ));
+    }
+}
@@ -0,0 +1,42 @@
+use std::fmt::{Debug, Display, Formatter};
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IopError {
+    // the number of words that a header needs, and the number left in the stream
+    WordsExhausted(usize, usize),
+    // a READ_IOP_BODY when the last header has no element left
+    BodyExhausted,
+    // a READ_IOP_HEADER while elements of the last header are still to be read, with their number
+    PendingBody(usize),
+    // the position in the stream of a word that is not a field element in Montgomery form
+    InvalidWord(usize, u32),
+    // a header `(count, k_and_flip_flag)` whose elements have no coefficient, with `count > 0`
+    EmptyElements(u32, u32),
+}
+
+impl Display for IopError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IopError::WordsExhausted(needed, left) => f.write_fmt(format_args!(
+                "the header needs {} words, but only {} are left in the IOP input",
+                needed, left
+            )),
+            IopError::BodyExhausted => {
+                f.write_str("there is no element left to read since the last header")
+            }
+            IopError::PendingBody(left) => f.write_fmt(format_args!(
+                "{} elements of the last header have not been read",
+                left
+            )),
+            IopError::InvalidWord(pos, word) => f.write_fmt(format_args!(
+                "word {} of the IOP input, {}, is not below the modulus",
+                pos, word
+            )),
+            IopError::EmptyElements(count, k_and_flip_flag) => f.write_fmt(format_args!(
+                "the header ({}, {}) reads elements of no coefficient",
+                count, k_and_flip_flag
+            )),
+        }
+    }
+}
+
+impl std::error::Error for IopError {}
@@ -0,0 +1,258 @@
+mod error;
+
+pub use crate::iop::error::IopError;
+use crate::math::{Fp, Fp4, FROM_MONTGOMERY, P};
+use crate::parser::Code;
+use crate::structures::StructuredInstruction;
+use std::collections::VecDeque;
+
+/// The number of words of the stream that a header `(count, k_and_flip_flag)` takes.
+pub fn header_words(count: u32, k_and_flip_flag: u32) -> usize {
+    let k = k_and_flip_flag as usize / 2;
+    if k == 2 {
+        count as usize
+    } else {
+        k * count as usize
+    }
+}
+
+/// The IOP input of a program, a stream of u32 words from the seal, which `READ_IOP_HEADER`
+/// turns into the elements that the following `READ_IOP_BODY` read, as `exec.rs` does.
+///
+/// A header `(count, k_and_flip_flag)` reads `count` elements of `k = k_and_flip_flag / 2`
+/// coefficients. For `k = 2`, each element takes one word, split into its low and high 16 bits.
+/// Otherwise, the `k * count` words are field elements in Montgomery form, stored element after
+/// element when the low bit of `k_and_flip_flag` is set, and coefficient after coefficient when
+/// it is not. A header of `k = 0` would read `count` elements out of no words, and is rejected
+/// unless `count` is zero.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IopSource {
+    words: VecDeque<u32>,
+    // the position in the stream of the next word, for the errors
+    pos: usize,
+    body: VecDeque<Fp4>,
+}
+
+impl IopSource {
+    pub fn new(words: impl IntoIterator<Item = u32>) -> Self {
+        Self {
+            words: words.into_iter().collect(),
+            pos: 0,
+            body: VecDeque::new(),
+        }
+    }
+
+    /// Read the stream from little-endian u32 words; a trailing partial word is an error.
+    pub fn from_le_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            bytes.len() % 4 == 0,
+            "the IOP input has {} bytes, which is not a whole number of words",
+            bytes.len()
+        );
+        Ok(Self::new(
+            bytes
+                .chunks_exact(4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap())),
+        ))
+    }
+
+    /// Read the stream from a JSON array of words.
+    #[cfg(feature = "serde")]
+    pub fn from_json(text: &str) -> anyhow::Result<Self> {
+        Ok(Self::new(serde_json::from_str::<Vec<u32>>(text)?))
+    }
+
+    /// Read the stream from a file, as JSON if its name ends with `.json` and as little-endian
+    /// words otherwise.
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        if !path.ends_with(".json") {
+            return Self::from_le_bytes(&bytes);
+        }
+        #[cfg(feature = "serde")]
+        {
+            Self::from_json(std::str::from_utf8(&bytes)?)
+        }
+        #[cfg(not(feature = "serde"))]
+        {
+            anyhow::bail!("reading {} needs ll0 built with the serde feature", path)
+        }
+    }
+
+    /// The number of words not taken by a header yet.
+    pub fn words_left(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn read_header(&mut self, count: u32, k_and_flip_flag: u32) -> Result<(), IopError> {
+        if !self.body.is_empty() {
+            return Err(IopError::PendingBody(self.body.len()));
+        }
+        let needed = header_words(count, k_and_flip_flag);
+        let count = count as usize;
+        let k = k_and_flip_flag as usize / 2;
+        let flip = k_and_flip_flag & 1 == 1;
+        if k == 0 && count > 0 {
+            return Err(IopError::EmptyElements(count as u32, k_and_flip_flag));
+        }
+
+        if needed > self.words.len() {
+            return Err(IopError::WordsExhausted(needed, self.words.len()));
+        }
+        let words: Vec<u32> = self.words.drain(..needed).collect();
+        let start = self.pos;
+        self.pos += needed;
+
+        if k == 2 {
+            self.body.extend(
+                words
+                    .into_iter()
+                    .map(|w| Fp4::new(Fp(w & 0xffff), Fp(w >> 16), Fp::ZERO, Fp::ZERO)),
+            );
+            return Ok(());
+        }
+
+        if let Some(i) = words.iter().position(|w| *w >= P) {
+            return Err(IopError::InvalidWord(start + i, words[i]));
+        }
+        for i in 0..count {
+            // like the machine, keep at most four coefficients
+            let mut coeffs: [Fp; 4] = Default::default();
+            for (j, coeff) in coeffs.iter_mut().enumerate().take(k) {
+                let w = if flip {
+                    words[i * k + j]
+                } else {
+                    words[j * count + i]
+                };
+                *coeff = Fp(w) * FROM_MONTGOMERY;
+            }
+            let [c0, c1, c2, c3] = coeffs;
+            self.body.push_back(Fp4::new(c0, c1, c2, c3));
+        }
+        Ok(())
+    }
+
+    pub fn read_body(&mut self) -> Result<Fp4, IopError> {
+        self.body.pop_front().ok_or(IopError::BodyExhausted)
+    }
+}
+
+// SplitMix64, which is enough to fill a stream and keeps ll0 free of a random number crate
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Generate a random IOP input with the shape that the headers of `code` expect: one random word
+/// for each element of `k = 2`, and a random field element in Montgomery form for each
+/// coefficient otherwise. The same `seed` gives the same stream.
+///
+/// The stream lets a program run offline, but the values are not those of a real seal, so the
+/// checks that a verifier program makes on them, such as the Merkle paths, fail.
+pub fn generate(code: &Code, seed: u64) -> Vec<u32> {
+    let mut rng = SplitMix64(seed);
+    let mut words = Vec::new();
+    for (insn, _) in code.0.iter() {
+        if let StructuredInstruction::READ_IOP_HEADER(count, k_and_flip_flag) = insn {
+            let k = *k_and_flip_flag as usize / 2;
+            if k == 2 {
+                words.extend((0..*count).map(|_| rng.next() as u32));
+            } else {
+                words.extend((0..k * *count as usize).map(|_| (rng.next() % P as u64) as u32));
+            }
+        }
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::LineNo;
+
+    fn mont(v: u32) -> u32 {
+        Fp(v).to_montgomery()
+    }
+
+    #[test]
+    fn header_sizes() {
+        assert_eq!(header_words(3, 4), 3);
+        assert_eq!(header_words(3, 5), 3);
+        assert_eq!(header_words(3, 2), 3);
+        assert_eq!(header_words(3, 8), 12);
+        assert_eq!(header_words(3, 9), 12);
+        assert_eq!(header_words(3, 0), 0);
+        assert_eq!(header_words(0, 8), 0);
+    }
+
+    #[test]
+    fn read_elements() {
+        let mut iop = IopSource::new([0x0002_0001]);
+        iop.read_header(1, 4).unwrap();
+        assert_eq!(
+            iop.read_body(),
+            Ok(Fp4::new(Fp(1), Fp(2), Fp::ZERO, Fp::ZERO))
+        );
+
+        // two elements of three coefficients, element after element and then coefficient
+        // after coefficient
+        let words: Vec<u32> = (1..=6).map(mont).collect();
+        let mut iop = IopSource::new(words.iter().chain(words.iter()).copied());
+        let element = |a, b, c| Fp4::new(Fp(a), Fp(b), Fp(c), Fp::ZERO);
+        iop.read_header(2, 7).unwrap();
+        assert_eq!(iop.read_body(), Ok(element(1, 2, 3)));
+        assert_eq!(iop.read_body(), Ok(element(4, 5, 6)));
+        iop.read_header(2, 6).unwrap();
+        assert_eq!(iop.read_body(), Ok(element(1, 3, 5)));
+        assert_eq!(iop.read_body(), Ok(element(2, 4, 6)));
+        assert_eq!(iop.words_left(), 0);
+    }
+
+    #[test]
+    fn errors() {
+        let mut iop = IopSource::new([mont(1), mont(2), P]);
+        assert_eq!(iop.read_body(), Err(IopError::BodyExhausted));
+        assert_eq!(iop.read_header(4, 2), Err(IopError::WordsExhausted(4, 3)));
+        assert_eq!(iop.read_header(2, 3), Ok(()));
+        assert_eq!(iop.read_header(1, 3), Err(IopError::PendingBody(2)));
+        iop.read_body().unwrap();
+        iop.read_body().unwrap();
+        assert_eq!(iop.read_header(1, 3), Err(IopError::InvalidWord(2, P)));
+        assert_eq!(iop.read_header(2, 1), Err(IopError::EmptyElements(2, 1)));
+        assert_eq!(iop.read_header(0, 0), Ok(()));
+        assert_eq!(iop.read_body(), Err(IopError::BodyExhausted));
+    }
+
+    #[test]
+    fn generated_stream() {
+        let mut code = Code::default();
+        for (count, k_and_flip_flag) in [(2, 4), (3, 8), (1, 9)] {
+            code.push(
+                StructuredInstruction::READ_IOP_HEADER(count, k_and_flip_flag),
+                LineNo::new(1, 0),
+            );
+        }
+        let words = generate(&code, 0);
+        assert_eq!(words.len(), 2 + 12 + 4);
+        // the first output of SplitMix64 from 0
+        assert_eq!(words[0], 0x7b1dcdaf);
+        assert_eq!(generate(&code, 0), words);
+        assert_ne!(generate(&code, 1), words);
+
+        let mut iop = IopSource::new(words);
+        for (count, k_and_flip_flag) in [(2, 4), (3, 8), (1, 9)] {
+            iop.read_header(count, k_and_flip_flag).unwrap();
+            for _ in 0..count {
+                iop.read_body().unwrap();
+            }
+        }
+        assert_eq!(iop.words_left(), 0);
+    }
+}
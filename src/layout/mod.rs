@@ -0,0 +1,509 @@
+use crate::parser::{validate, words_from_bytes};
+use crate::{
+    MACRO_BIT_AND_ELEM, MACRO_BIT_OP_SHORTS, MACRO_NOP, MACRO_OPERAND_0, MACRO_OPERAND_1,
+    MACRO_OPERAND_2, MACRO_SET_GLOBAL, MACRO_SHA_FINI, MACRO_SHA_INIT, MACRO_SHA_LOAD,
+    MACRO_SHA_MIX, MACRO_WOM_FINI, MACRO_WOM_INIT, POSEIDON_DO_MONT, POSEIDON_LOAD_ADD_CONSTS,
+    POSEIDON_LOAD_G0, POSEIDON_LOAD_G1, POSEIDON_LOAD_G2, POSEIDON_LOAD_KEEP_STATE,
+    SELECT_MACRO_OPS, SELECT_MICRO_OPS, SELECT_POSEIDON_FULL, SELECT_POSEIDON_LOAD,
+    SELECT_POSEIDON_PARTIAL, SELECT_POSEIDON_STORE, WRITE_ADDR,
+};
+use anyhow::{anyhow, bail, Context};
+use std::str::FromStr;
+
+/// The column positions of one version of the recursion circuit's instruction rows.
+///
+/// The parser, the validator and the encoder take a layout as a parameter. The `Default`
+/// layout is the one described by the constants in `lib.rs`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IsaLayout {
+    pub name: String,
+    pub row_width: usize,
+    pub write_addr: usize,
+
+    pub select_micro_ops: usize,
+    pub select_macro_ops: usize,
+    pub select_poseidon_load: usize,
+    pub select_poseidon_full: usize,
+    pub select_poseidon_partial: usize,
+    pub select_poseidon_store: usize,
+
+    pub macro_nop: usize,
+    pub macro_wom_init: usize,
+    pub macro_wom_fini: usize,
+    pub macro_bit_and_elem: usize,
+    pub macro_bit_op_shorts: usize,
+    pub macro_sha_init: usize,
+    pub macro_sha_fini: usize,
+    pub macro_sha_load: usize,
+    pub macro_sha_mix: usize,
+    pub macro_set_global: usize,
+    pub macro_operand_0: usize,
+    pub macro_operand_1: usize,
+    pub macro_operand_2: usize,
+
+    // the first column of each of the three (opcode, operand, operand, operand) micro op groups
+    pub micro_slots: [usize; 3],
+
+    pub poseidon_do_mont: usize,
+    pub poseidon_load_keep_state: usize,
+    pub poseidon_load_add_consts: usize,
+    pub poseidon_load_g0: usize,
+    pub poseidon_load_g1: usize,
+    pub poseidon_load_g2: usize,
+    // the first of the eight read addresses of a Poseidon load
+    pub poseidon_load_operands: usize,
+
+    // the Poseidon2 instruction family, whose columns are not known for any release and which
+    // no built-in layout has; its rows are assumed to share the Montgomery, keep state, group
+    // and read address columns with the Poseidon rows
+    pub select_poseidon2_load: Option<usize>,
+    pub select_poseidon2_full: Option<usize>,
+    pub select_poseidon2_partial: Option<usize>,
+    pub select_poseidon2_store: Option<usize>,
+    pub poseidon2_load_keep_upper_state: Option<usize>,
+    pub poseidon2_load_prep_full: Option<usize>,
+}
+
+impl IsaLayout {
+    /// The layout of the recursion circuit of RISC Zero 0.19, which `preflight.rs` describes.
+    pub fn risc0_v0_19() -> Self {
+        Self {
+            name: String::from("risc0-0.19"),
+            row_width: 21,
+            write_addr: WRITE_ADDR,
+            select_micro_ops: SELECT_MICRO_OPS,
+            select_macro_ops: SELECT_MACRO_OPS,
+            select_poseidon_load: SELECT_POSEIDON_LOAD,
+            select_poseidon_full: SELECT_POSEIDON_FULL,
+            select_poseidon_partial: SELECT_POSEIDON_PARTIAL,
+            select_poseidon_store: SELECT_POSEIDON_STORE,
+            macro_nop: MACRO_NOP,
+            macro_wom_init: MACRO_WOM_INIT,
+            macro_wom_fini: MACRO_WOM_FINI,
+            macro_bit_and_elem: MACRO_BIT_AND_ELEM,
+            macro_bit_op_shorts: MACRO_BIT_OP_SHORTS,
+            macro_sha_init: MACRO_SHA_INIT,
+            macro_sha_fini: MACRO_SHA_FINI,
+            macro_sha_load: MACRO_SHA_LOAD,
+            macro_sha_mix: MACRO_SHA_MIX,
+            macro_set_global: MACRO_SET_GLOBAL,
+            macro_operand_0: MACRO_OPERAND_0,
+            macro_operand_1: MACRO_OPERAND_1,
+            macro_operand_2: MACRO_OPERAND_2,
+            micro_slots: [7, 11, 15],
+            poseidon_do_mont: POSEIDON_DO_MONT,
+            poseidon_load_keep_state: POSEIDON_LOAD_KEEP_STATE,
+            poseidon_load_add_consts: POSEIDON_LOAD_ADD_CONSTS,
+            poseidon_load_g0: POSEIDON_LOAD_G0,
+            poseidon_load_g1: POSEIDON_LOAD_G1,
+            poseidon_load_g2: POSEIDON_LOAD_G2,
+            poseidon_load_operands: 13,
+            select_poseidon2_load: None,
+            select_poseidon2_full: None,
+            select_poseidon2_partial: None,
+            select_poseidon2_store: None,
+            poseidon2_load_keep_upper_state: None,
+            poseidon2_load_prep_full: None,
+        }
+    }
+
+    /// The layouts that ship with ll0. Only the layout of risc0 0.19 has been checked against
+    /// real zkr files; the columns of other releases are given as layout files.
+    pub fn builtin() -> Vec<Self> {
+        vec![Self::risc0_v0_19()]
+    }
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        Self::builtin()
+            .into_iter()
+            .find(|layout| layout.name == name)
+    }
+
+    /// Find a built-in layout by name, or else read one from the file at `spec`.
+    pub fn load(spec: &str) -> anyhow::Result<Self> {
+        if let Some(layout) = Self::by_name(spec) {
+            return Ok(layout);
+        }
+        let text = std::fs::read_to_string(spec).with_context(|| {
+            let names = Self::builtin()
+                .into_iter()
+                .map(|layout| layout.name)
+                .collect::<Vec<String>>();
+            format!(
+                "{} is neither a built-in layout ({}) nor a readable layout file",
+                spec,
+                names.join(", ")
+            )
+        })?;
+        text.parse()
+    }
+
+    /// The layout that `spec` names for the raw bytes of a zkr file: `auto` detects it among the
+    /// built-in layouts, `auto:<a>,<b>` among the built-in layouts and the layouts `<a>` and
+    /// `<b>`, and anything else is given to `load`.
+    pub fn resolve(spec: &str, value: &[u8]) -> anyhow::Result<Self> {
+        if spec == "auto" {
+            return Ok(Self::detect(value));
+        }
+        match spec.strip_prefix("auto:") {
+            Some(specs) => {
+                let mut candidates = Self::builtin();
+                for spec in specs.split(',') {
+                    candidates.push(Self::load(spec)?);
+                }
+                Ok(Self::detect_among(value, candidates))
+            }
+            None => Self::load(spec),
+        }
+    }
+
+    /// Pick the built-in layout under which the raw bytes of a zkr file decode with the
+    /// fewest violations. If the length fits none of them, fall back to the default layout so
+    /// that the parser reports where the input is truncated.
+    pub fn detect(value: &[u8]) -> Self {
+        Self::detect_among(value, Self::builtin())
+    }
+
+    /// Like `detect`, among `candidates`, of which the first wins a tie.
+    pub fn detect_among(value: &[u8], candidates: Vec<Self>) -> Self {
+        candidates
+            .into_iter()
+            .filter_map(|layout| {
+                let words = words_from_bytes(value, &layout).ok()?;
+                Some((validate(&words, &layout).len(), layout))
+            })
+            .min_by_key(|(violations, _)| *violations)
+            .map(|(_, layout)| layout)
+            .unwrap_or_default()
+    }
+
+    pub fn row_bytes(&self) -> usize {
+        self.row_width * 4
+    }
+
+    pub fn selectors(&self) -> Vec<usize> {
+        let mut selectors = vec![
+            self.select_micro_ops,
+            self.select_macro_ops,
+            self.select_poseidon_load,
+            self.select_poseidon_full,
+            self.select_poseidon_partial,
+            self.select_poseidon_store,
+        ];
+        selectors.extend(self.poseidon2_selectors());
+        selectors
+    }
+
+    /// The Poseidon2 row selectors that this layout has.
+    pub fn poseidon2_selectors(&self) -> Vec<usize> {
+        [
+            self.select_poseidon2_load,
+            self.select_poseidon2_full,
+            self.select_poseidon2_partial,
+            self.select_poseidon2_store,
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    pub fn macro_selectors(&self) -> [usize; 10] {
+        [
+            self.macro_nop,
+            self.macro_wom_init,
+            self.macro_wom_fini,
+            self.macro_bit_and_elem,
+            self.macro_bit_op_shorts,
+            self.macro_sha_init,
+            self.macro_sha_fini,
+            self.macro_sha_load,
+            self.macro_sha_mix,
+            self.macro_set_global,
+        ]
+    }
+
+    /// The columns of a row that are not in `used`.
+    pub fn columns_outside(&self, used: &[usize]) -> Vec<usize> {
+        (0..self.row_width).filter(|c| !used.contains(c)).collect()
+    }
+
+    fn field_mut(&mut self, key: &str) -> Option<&mut usize> {
+        Some(match key {
+            "row_width" => &mut self.row_width,
+            "write_addr" => &mut self.write_addr,
+            "select_micro_ops" => &mut self.select_micro_ops,
+            "select_macro_ops" => &mut self.select_macro_ops,
+            "select_poseidon_load" => &mut self.select_poseidon_load,
+            "select_poseidon_full" => &mut self.select_poseidon_full,
+            "select_poseidon_partial" => &mut self.select_poseidon_partial,
+            "select_poseidon_store" => &mut self.select_poseidon_store,
+            "macro_nop" => &mut self.macro_nop,
+            "macro_wom_init" => &mut self.macro_wom_init,
+            "macro_wom_fini" => &mut self.macro_wom_fini,
+            "macro_bit_and_elem" => &mut self.macro_bit_and_elem,
+            "macro_bit_op_shorts" => &mut self.macro_bit_op_shorts,
+            "macro_sha_init" => &mut self.macro_sha_init,
+            "macro_sha_fini" => &mut self.macro_sha_fini,
+            "macro_sha_load" => &mut self.macro_sha_load,
+            "macro_sha_mix" => &mut self.macro_sha_mix,
+            "macro_set_global" => &mut self.macro_set_global,
+            "macro_operand_0" => &mut self.macro_operand_0,
+            "macro_operand_1" => &mut self.macro_operand_1,
+            "macro_operand_2" => &mut self.macro_operand_2,
+            "micro_slot_0" => &mut self.micro_slots[0],
+            "micro_slot_1" => &mut self.micro_slots[1],
+            "micro_slot_2" => &mut self.micro_slots[2],
+            "poseidon_do_mont" => &mut self.poseidon_do_mont,
+            "poseidon_load_keep_state" => &mut self.poseidon_load_keep_state,
+            "poseidon_load_add_consts" => &mut self.poseidon_load_add_consts,
+            "poseidon_load_g0" => &mut self.poseidon_load_g0,
+            "poseidon_load_g1" => &mut self.poseidon_load_g1,
+            "poseidon_load_g2" => &mut self.poseidon_load_g2,
+            "poseidon_load_operands" => &mut self.poseidon_load_operands,
+            _ => return None,
+        })
+    }
+
+    fn optional_field_mut(&mut self, key: &str) -> Option<&mut Option<usize>> {
+        Some(match key {
+            "select_poseidon2_load" => &mut self.select_poseidon2_load,
+            "select_poseidon2_full" => &mut self.select_poseidon2_full,
+            "select_poseidon2_partial" => &mut self.select_poseidon2_partial,
+            "select_poseidon2_store" => &mut self.select_poseidon2_store,
+            "poseidon2_load_keep_upper_state" => &mut self.poseidon2_load_keep_upper_state,
+            "poseidon2_load_prep_full" => &mut self.poseidon2_load_prep_full,
+            _ => return None,
+        })
+    }
+
+    fn check(&self) -> anyhow::Result<()> {
+        let mut columns = vec![self.write_addr, self.poseidon_load_operands + 7];
+        columns.extend(self.selectors());
+        columns.extend(self.macro_selectors());
+        columns.extend([
+            self.macro_operand_0,
+            self.macro_operand_1,
+            self.macro_operand_2,
+            self.poseidon_do_mont,
+            self.poseidon_load_keep_state,
+            self.poseidon_load_add_consts,
+            self.poseidon_load_g0,
+            self.poseidon_load_g1,
+            self.poseidon_load_g2,
+        ]);
+        columns.extend(self.micro_slots.iter().map(|c| c + 3));
+        columns.extend(self.poseidon2_load_keep_upper_state);
+        columns.extend(self.poseidon2_load_prep_full);
+        if let Some(c) = columns.iter().find(|c| **c >= self.row_width) {
+            bail!(
+                "layout {} uses column {} but rows are only {} words wide",
+                self.name,
+                c,
+                self.row_width
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Default for IsaLayout {
+    fn default() -> Self {
+        Self::risc0_v0_19()
+    }
+}
+
+/// Read a layout from `key = value` lines.
+///
+/// Keys that are not given keep the value of the default layout, so a file only needs to
+/// list the columns that moved. Optional columns take `none` to remove them. `#` starts a
+/// comment.
+impl FromStr for IsaLayout {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut layout = IsaLayout {
+            name: String::from("custom"),
+            ..IsaLayout::default()
+        };
+
+        for (idx, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("line {}: expected `key = value`", idx + 1))?;
+            let (key, value) = (key.trim(), value.trim());
+
+            if key == "name" {
+                layout.name = value.trim_matches('"').to_string();
+            } else if let Some(field) = layout.field_mut(key) {
+                *field = value
+                    .parse()
+                    .map_err(|_| anyhow!("line {}: `{}` is not a column", idx + 1, value))?;
+            } else if let Some(field) = layout.optional_field_mut(key) {
+                *field = if value == "none" {
+                    None
+                } else {
+                    Some(value.parse().map_err(|_| {
+                        anyhow!("line {}: `{}` is neither a column nor none", idx + 1, value)
+                    })?)
+                };
+            } else {
+                bail!("line {}: unknown key `{}`", idx + 1, key);
+            }
+        }
+
+        layout.check()?;
+        Ok(layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::encode;
+    use crate::parser::{Code, LineNo};
+    use crate::structures::StructuredInstruction as I;
+
+    // the layout of risc0 0.19 with the micro and macro selectors swapped, and the Poseidon
+    // full and partial ones, so that only the content of the rows tells them apart
+    const SWAPPED: &str = "name = swapped
+select_micro_ops = 2
+select_macro_ops = 1
+select_poseidon_full = 5
+select_poseidon_partial = 4
+";
+
+    // two more columns, for the write address and the selectors, in front of the others
+    const WIDER: &str = "name = wider
+row_width = 23
+write_addr = 2
+select_micro_ops = 3
+select_macro_ops = 4
+select_poseidon_load = 5
+select_poseidon_full = 6
+select_poseidon_partial = 7
+select_poseidon_store = 8
+macro_nop = 9
+macro_wom_init = 10
+macro_wom_fini = 11
+macro_bit_and_elem = 12
+macro_bit_op_shorts = 13
+macro_sha_init = 14
+macro_sha_fini = 15
+macro_sha_load = 16
+macro_sha_mix = 17
+macro_set_global = 18
+macro_operand_0 = 19
+macro_operand_1 = 20
+macro_operand_2 = 21
+micro_slot_0 = 9
+micro_slot_1 = 13
+micro_slot_2 = 17
+poseidon_do_mont = 9
+poseidon_load_keep_state = 10
+poseidon_load_add_consts = 11
+poseidon_load_g0 = 12
+poseidon_load_g1 = 13
+poseidon_load_g2 = 14
+poseidon_load_operands = 15
+";
+
+    fn program() -> Code {
+        let r = |v: u32| v.into();
+        let mut code = Code::default();
+        code.push(I::WOM_INIT, LineNo::new(1, 0));
+        code.push(I::CONST(1, 5, 0), LineNo::new(2, 0));
+        code.push(I::READ_IOP_HEADER(1, 0), LineNo::new(2, 1));
+        code.push(I::READ_IOP_BODY(3), LineNo::new(2, 2));
+        code.push(I::ADD(4, r(1), r(3)), LineNo::new(3, 0));
+        code.push(I::MUL(5, r(4), r(4)), LineNo::new(3, 1));
+        code.push(I::EQ(r(5), r(5)), LineNo::new(3, 2));
+        code.push(
+            I::POSEIDON_LOAD(0, 0, r(1), r(2), r(3), r(4), r(5), r(6), r(7), r(8)),
+            LineNo::new(4, 0),
+        );
+        for row in 5..9 {
+            code.push(I::POSEIDON_FULL, LineNo::new(row, 0));
+        }
+        code.push(I::POSEIDON_PARTIAL, LineNo::new(9, 0));
+        code.push(I::POSEIDON_STORE(0, 8), LineNo::new(10, 0));
+        code.push(I::BIT_AND_ELEM(16, r(8), r(9)), LineNo::new(11, 0));
+        code.push(
+            I::SET_GLOBAL(r(16), r(0), r(0), r(0), 0),
+            LineNo::new(12, 0),
+        );
+        code.push(I::WOM_FINI, LineNo::new(13, 0));
+        code
+    }
+
+    fn bytes(layout: &IsaLayout) -> Vec<u8> {
+        let words = encode(&program(), layout).unwrap();
+        words.iter().flat_map(|w| w.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn layout_files() {
+        let swapped: IsaLayout = SWAPPED.parse().unwrap();
+        assert_eq!(swapped.name, "swapped");
+        assert_eq!(swapped.select_micro_ops, 2);
+        assert_eq!(swapped.macro_operand_0, MACRO_OPERAND_0);
+
+        let wider: IsaLayout = WIDER.parse().unwrap();
+        assert_eq!(wider.row_bytes(), 92);
+        assert_eq!(wider.micro_slots, [9, 13, 17]);
+
+        assert!("row_width = 20".parse::<IsaLayout>().is_err());
+        assert!("select_micro = 1".parse::<IsaLayout>().is_err());
+        assert!("select_poseidon2_full = 99".parse::<IsaLayout>().is_err());
+    }
+
+    #[test]
+    fn detect_picks_the_layout_of_the_rows() {
+        let layouts = vec![
+            IsaLayout::default(),
+            SWAPPED.parse::<IsaLayout>().unwrap(),
+            WIDER.parse::<IsaLayout>().unwrap(),
+        ];
+        for layout in layouts.iter() {
+            let value = bytes(layout);
+            assert!(validate(&words_from_bytes(&value, layout).unwrap(), layout).is_empty());
+            for order in [layouts.clone(), layouts.iter().rev().cloned().collect()] {
+                assert_eq!(IsaLayout::detect_among(&value, order).name, layout.name);
+            }
+        }
+        assert_eq!(
+            IsaLayout::detect(&bytes(&IsaLayout::default())),
+            IsaLayout::risc0_v0_19()
+        );
+        // a length that fits no layout falls back to the default one
+        assert_eq!(IsaLayout::detect(&[0u8; 5]), IsaLayout::default());
+    }
+
+    #[test]
+    fn resolve_specs() {
+        let path = std::env::temp_dir().join(format!("ll0-layout-{}.txt", std::process::id()));
+        std::fs::write(&path, SWAPPED).unwrap();
+        let file = path.to_str().unwrap();
+
+        let swapped = SWAPPED.parse::<IsaLayout>().unwrap();
+        let value = bytes(&swapped);
+        assert_eq!(
+            IsaLayout::resolve("auto", &value).unwrap().name,
+            "risc0-0.19"
+        );
+        assert_eq!(
+            IsaLayout::resolve(&format!("auto:{}", file), &value).unwrap(),
+            swapped
+        );
+        assert_eq!(IsaLayout::resolve(file, &[]).unwrap(), swapped);
+        assert_eq!(
+            IsaLayout::resolve("risc0-0.19", &value).unwrap(),
+            IsaLayout::default()
+        );
+        assert!(IsaLayout::resolve("auto:no-such-layout", &value).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
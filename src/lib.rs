@@ -1,7 +1,18 @@
+pub mod assembler;
+pub mod control_id;
+pub mod debugger;
+pub mod encoder;
+pub mod iop;
+pub mod layout;
 pub mod math;
 pub mod parser;
 pub mod pass;
+pub mod profile;
+pub mod provenance;
+pub mod sha;
 pub mod structures;
+pub mod vm;
+pub mod wom;
 
 pub const SELECT_MACRO_OPS: usize = 2;
 pub const SELECT_MICRO_OPS: usize = 1;
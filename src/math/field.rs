@@ -0,0 +1,380 @@
+use crate::math::{Fp, Fp4, P};
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// A prime field, the base field of the values that a machine holds.
+///
+/// The machine reads its constants, bit operations and addresses as integers, so an element
+/// converts to and from its canonical representative below `MODULUS`.
+pub trait Field:
+    Clone
+    + Debug
+    + Default
+    + Display
+    + Eq
+    + Hash
+    + Send
+    + Sync
+    + 'static
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Neg<Output = Self>
+{
+    const MODULUS: u64;
+    const ZERO: Self;
+    const ONE: Self;
+
+    /// `v` reduced modulo `MODULUS`.
+    fn from_u64(v: u64) -> Self;
+
+    /// The canonical representative, below `MODULUS`.
+    fn as_u64(&self) -> u64;
+
+    /// The inverse, or zero for zero, as the machine computes it.
+    fn inv(&self) -> Self;
+
+    fn is_zero(&self) -> bool {
+        self.as_u64() == 0
+    }
+}
+
+/// An extension of a `Field`, the values of the memory cells of a machine, which the instructions
+/// address as up to four components.
+pub trait ExtensionField:
+    Clone
+    + Debug
+    + Default
+    + Display
+    + Eq
+    + Send
+    + Sync
+    + 'static
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Neg<Output = Self>
+{
+    type Base: Field;
+
+    /// The degree over `Base`, at most four.
+    const DEGREE: usize;
+    const ZERO: Self;
+    const ONE: Self;
+
+    /// The element of the base field `v`.
+    fn from_base(v: Self::Base) -> Self;
+
+    /// The element with these coefficients, from the constant one up, the missing ones zero.
+    fn from_coeffs(coeffs: &[Self::Base]) -> Self;
+
+    /// The coefficient `idx`, zero at and above `DEGREE`.
+    fn coeff(&self, idx: usize) -> Self::Base;
+
+    /// The inverse, or zero for zero, as the machine computes it.
+    fn inv(&self) -> Self;
+
+    fn is_zero(&self) -> bool {
+        (0..Self::DEGREE).all(|i| self.coeff(i).is_zero())
+    }
+}
+
+impl Field for Fp {
+    const MODULUS: u64 = P as u64;
+    const ZERO: Self = Fp::ZERO;
+    const ONE: Self = Fp::ONE;
+
+    fn from_u64(v: u64) -> Self {
+        Fp::from(v)
+    }
+
+    fn as_u64(&self) -> u64 {
+        Fp::new(self.0).0 as u64
+    }
+
+    fn inv(&self) -> Self {
+        Fp::inv(self)
+    }
+}
+
+// the base field as an extension of degree 1, for a machine that has no extension
+impl ExtensionField for Fp {
+    type Base = Fp;
+
+    const DEGREE: usize = 1;
+    const ZERO: Self = Fp::ZERO;
+    const ONE: Self = Fp::ONE;
+
+    fn from_base(v: Fp) -> Self {
+        v
+    }
+
+    fn from_coeffs(coeffs: &[Fp]) -> Self {
+        coeffs.first().cloned().unwrap_or_default()
+    }
+
+    fn coeff(&self, idx: usize) -> Fp {
+        if idx == 0 {
+            self.clone()
+        } else {
+            Fp::ZERO
+        }
+    }
+
+    fn inv(&self) -> Self {
+        Fp::inv(self)
+    }
+}
+
+impl ExtensionField for Fp4 {
+    type Base = Fp;
+
+    const DEGREE: usize = 4;
+    const ZERO: Self = Fp4::ZERO;
+    const ONE: Self = Fp4::ONE;
+
+    fn from_base(v: Fp) -> Self {
+        Fp4::from_fp(v)
+    }
+
+    fn from_coeffs(coeffs: &[Fp]) -> Self {
+        let c = |i: usize| coeffs.get(i).cloned().unwrap_or_default();
+        Fp4(c(0), c(1), c(2), c(3))
+    }
+
+    fn coeff(&self, idx: usize) -> Fp {
+        match idx {
+            0 => self.0.clone(),
+            1 => self.1.clone(),
+            2 => self.2.clone(),
+            3 => self.3.clone(),
+            _ => Fp::ZERO,
+        }
+    }
+
+    fn inv(&self) -> Self {
+        Fp4::inv(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iop::IopSource;
+    use crate::parser::{Code, LineNo};
+    use crate::pass::manager::PassManager;
+    use crate::structures::StructuredInstruction as I;
+    use crate::vm::{Vm, VmError};
+    use std::fmt::Formatter;
+
+    // GF(97), and GF(97^2) as GF(97)[x] / (x^2 - 5), small enough to check by hand
+    #[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+    struct F97(u64);
+
+    impl Display for F97 {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            f.write_fmt(format_args!("{}", self.0))
+        }
+    }
+
+    impl Add for F97 {
+        type Output = Self;
+        fn add(self, rhs: Self) -> Self {
+            Self((self.0 + rhs.0) % 97)
+        }
+    }
+
+    impl Sub for F97 {
+        type Output = Self;
+        fn sub(self, rhs: Self) -> Self {
+            Self((self.0 + 97 - rhs.0) % 97)
+        }
+    }
+
+    impl Mul for F97 {
+        type Output = Self;
+        fn mul(self, rhs: Self) -> Self {
+            Self(self.0 * rhs.0 % 97)
+        }
+    }
+
+    impl Neg for F97 {
+        type Output = Self;
+        fn neg(self) -> Self {
+            Self((97 - self.0) % 97)
+        }
+    }
+
+    impl Field for F97 {
+        const MODULUS: u64 = 97;
+        const ZERO: Self = Self(0);
+        const ONE: Self = Self(1);
+
+        fn from_u64(v: u64) -> Self {
+            Self(v % 97)
+        }
+
+        fn as_u64(&self) -> u64 {
+            self.0
+        }
+
+        fn inv(&self) -> Self {
+            (0..95).fold(Self::ONE, |acc, _| acc * self.clone())
+        }
+    }
+
+    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    struct F97x2(F97, F97);
+
+    impl Display for F97x2 {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            f.write_fmt(format_args!("({}, {})", self.0, self.1))
+        }
+    }
+
+    impl Add for F97x2 {
+        type Output = Self;
+        fn add(self, rhs: Self) -> Self {
+            Self(self.0 + rhs.0, self.1 + rhs.1)
+        }
+    }
+
+    impl Sub for F97x2 {
+        type Output = Self;
+        fn sub(self, rhs: Self) -> Self {
+            Self(self.0 - rhs.0, self.1 - rhs.1)
+        }
+    }
+
+    impl Mul for F97x2 {
+        type Output = Self;
+        fn mul(self, rhs: Self) -> Self {
+            let (a, b) = (self, rhs);
+            Self(
+                a.0.clone() * b.0.clone() + F97(5) * a.1.clone() * b.1.clone(),
+                a.0 * b.1 + a.1 * b.0,
+            )
+        }
+    }
+
+    impl Neg for F97x2 {
+        type Output = Self;
+        fn neg(self) -> Self {
+            Self(-self.0, -self.1)
+        }
+    }
+
+    impl ExtensionField for F97x2 {
+        type Base = F97;
+
+        const DEGREE: usize = 2;
+        const ZERO: Self = Self(F97(0), F97(0));
+        const ONE: Self = Self(F97(1), F97(0));
+
+        fn from_base(v: F97) -> Self {
+            Self(v, F97(0))
+        }
+
+        fn from_coeffs(coeffs: &[F97]) -> Self {
+            let c = |i: usize| coeffs.get(i).cloned().unwrap_or_default();
+            Self(c(0), c(1))
+        }
+
+        fn coeff(&self, idx: usize) -> F97 {
+            match idx {
+                0 => self.0.clone(),
+                1 => self.1.clone(),
+                _ => F97(0),
+            }
+        }
+
+        // the conjugate over the norm, which is in GF(97)
+        fn inv(&self) -> Self {
+            let norm = self.0.clone() * self.0.clone() - F97(5) * self.1.clone() * self.1.clone();
+            let inv = norm.inv();
+            Self(self.0.clone() * inv.clone(), -self.1.clone() * inv)
+        }
+    }
+
+    fn program() -> Code<F97x2> {
+        let mut code = Code::default();
+        for (row, insn) in [
+            I::CONST(1, 3, 4),
+            I::CONST(2, 10, 0),
+            I::ADD(3, 1.into(), 2.into()),
+            I::MUL(4, 3.into(), 1.into()),
+            I::INV(5, 4.into()),
+            I::MUL(6, 5.into(), 4.into()),
+            I::CONST(7, 1, 0),
+            I::EQ(6.into(), 7.into()),
+            I::EXTRACT(8, 4.into(), 1),
+            I::SUB(9, 8.into(), 2.into()),
+            I::BIT_AND_ELEM(10, 8.into(), 2.into()),
+            I::NOT(11, 10.into()),
+            I::MIX_RNG(12, 1.into(), 2.into()),
+            I::SELECT(13, 11.into(), 3.into(), 4.into()),
+            I::SET_GLOBAL(4.into(), 9.into(), 12.into(), 13.into(), 0),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            code.push(insn, LineNo::new(row + 1, 0));
+        }
+        code
+    }
+
+    #[test]
+    fn small_field() {
+        // 5 is not a square modulo 97, so that x^2 - 5 is irreducible
+        assert!(!(1..97).any(|v| v * v % 97 == 5));
+
+        let mut vm: Vm<F97x2> = Vm::new(IopSource::default());
+        vm.run(&program()).unwrap();
+        let v = |c0, c1| F97x2(F97(c0), F97(c1));
+        // (13 + 4x)(3 + 4x) = 39 + 80 + 64x
+        assert_eq!(vm.get(4), Some(&v(22, 64)));
+        assert_eq!(vm.get(6), Some(&F97x2::ONE));
+        assert_eq!(vm.get(9), Some(&v(54, 0)));
+        // 64 & 10
+        assert_eq!(vm.get(10), Some(&v(0, 0)));
+        // ((4 * 2^16 + 3) * 2^16 + 0) * 2^16 + 10, with 2^16 = 61
+        assert_eq!(
+            vm.get(12),
+            Some(&v((((4 * 61 + 3) * 61 * 61) + 10) % 97, 0))
+        );
+        assert_eq!(vm.get(13), Some(&v(13, 4)));
+        let globals = vm.globals().clone();
+        assert_eq!(
+            globals.get(&0),
+            Some(
+                &[
+                    22,
+                    64,
+                    54,
+                    0,
+                    (((4 * 61 + 3) * 61 * 61) + 10) % 97,
+                    0,
+                    13,
+                    4
+                ]
+                .map(F97)
+            )
+        );
+
+        // the passes keep what the program computes
+        let mut code = program();
+        PassManager::<F97x2>::default().run(&mut code).unwrap();
+        let mut vm: Vm<F97x2> = Vm::new(IopSource::default());
+        vm.set_write_once(false);
+        vm.run(&code).unwrap();
+        assert_eq!(vm.globals(), &globals);
+
+        // the units of the BabyBear machine are not there
+        let mut vm: Vm<F97x2> = Vm::new(IopSource::default());
+        assert!(matches!(
+            vm.step(&I::POSEIDON_FULL, LineNo::new(1, 0)),
+            Err(VmError::Unsupported(..))
+        ));
+    }
+}
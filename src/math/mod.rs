@@ -1,85 +1,325 @@
-use std::fmt::{Display, Formatter};
-use std::ops::{Add, BitAnd, BitXor, Mul, Shl, Sub};
+pub mod field;
+pub mod poseidon;
+pub mod poseidon2;
+
+pub use crate::math::field::{ExtensionField, Field};
 
-#[derive(Default, Clone, PartialEq, Eq)]
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::iter::{Product, Sum};
+use std::ops::{
+    Add, AddAssign, BitAnd, BitXor, Div, DivAssign, Mul, MulAssign, Neg, Shl, Sub, SubAssign,
+};
+
+/// The BabyBear prime, `15 * 2^27 + 1`.
+pub const P: u32 = 2013265921;
+
+/// Multiplying by this moves an element into the Montgomery form, as the `*_TO_MONTGOMERY`
+/// instructions do.
+pub const TO_MONTGOMERY: Fp = Fp(268435454);
+
+/// Multiplying by this moves an element out of the Montgomery form.
+pub const FROM_MONTGOMERY: Fp = Fp(943718400);
+
+/// An element of the BabyBear field, normally kept below `P`. The operations also accept a
+/// value that is not, and reduce it, and elements compare and hash by their reduced value, so
+/// that `Fp(P) == Fp(0)`.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Fp(pub u32);
 
-#[derive(Default, Clone, PartialEq, Eq)]
+/// An element of the degree-4 extension `Fp[x] / (x^4 + 11)`, as its coefficients from the
+/// constant one up.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Fp4(pub Fp, pub Fp, pub Fp, pub Fp);
 
+// a generator of the multiplicative group, hence not a square
+const GENERATOR: Fp = Fp(31);
+
+// x^4 = NBETA in the extension
+const NBETA: Fp = Fp(P - 11);
+
+// NBETA^((P - 1) / 4), a fourth root of unity, so that the Frobenius map sends x to OMEGA * x
+const OMEGA: Fp = Fp(1728404513);
+
 impl Fp {
     pub const ZERO: Fp = Self(0u32);
+    pub const ONE: Fp = Self(1u32);
+
+    /// `v` reduced modulo `P`.
+    pub const fn new(v: u32) -> Self {
+        Self(v % P)
+    }
+
+    /// `v` as it is, or `None` if it is not below `P`.
+    pub fn from_canonical(v: u32) -> Option<Self> {
+        (v < P).then_some(Self(v))
+    }
+
+    /// The element whose Montgomery form is `v`, as the `*_FROM_MONTGOMERY` instructions read it.
+    pub fn from_montgomery(v: u32) -> Self {
+        Self::new(v) * FROM_MONTGOMERY
+    }
+
+    /// The Montgomery form of the element.
+    pub fn to_montgomery(&self) -> u32 {
+        (self * TO_MONTGOMERY).0
+    }
+
+    fn reduced(&self) -> u64 {
+        self.0 as u64 % P as u64
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.reduced() == 0
+    }
+
+    /// The element between `-(P - 1) / 2` and `(P - 1) / 2` that is equal to it, which is how
+    /// `{:#}` prints it.
+    pub fn signed(&self) -> i64 {
+        let v = self.reduced() as i64;
+        if v > (P / 2) as i64 {
+            v - P as i64
+        } else {
+            v
+        }
+    }
+
+    pub fn pow(&self, n: u64) -> Self {
+        let mut n = n;
+        let mut tot = Self::ONE;
+        let mut x = Self::new(self.0);
+        while n != 0 {
+            if n % 2 == 1 {
+                tot *= &x;
+            }
+            n /= 2;
+            x = &x * &x;
+        }
+        tot
+    }
+
+    /// The inverse, or zero for zero, as `pow(P - 2)` gives.
+    pub fn inv(&self) -> Self {
+        self.pow(P as u64 - 2)
+    }
+
+    /// The inverse, or `None` for zero.
+    pub fn try_inv(&self) -> Option<Self> {
+        (!self.is_zero()).then(|| self.inv())
+    }
+
+    pub fn is_square(&self) -> bool {
+        self.is_zero() || self.pow((P as u64 - 1) / 2) == Self::ONE
+    }
+
+    /// A square root, the smaller of the two, or `None` if the element is not a square. This is
+    /// Tonelli-Shanks, for `P - 1 = 15 * 2^27`.
+    pub fn sqrt(&self) -> Option<Self> {
+        if self.is_zero() {
+            return Some(Self::ZERO);
+        }
+        if !self.is_square() {
+            return None;
+        }
+
+        const Q: u64 = 15;
+        let mut m = 27;
+        let mut c = GENERATOR.pow(Q);
+        let mut t = self.pow(Q);
+        let mut r = self.pow(Q.div_ceil(2));
+        while t != Self::ONE {
+            // the least i such that t^(2^i) = 1
+            let mut i = 0;
+            let mut t2 = t.clone();
+            while t2 != Self::ONE {
+                t2 = &t2 * &t2;
+                i += 1;
+            }
+            let b = c.pow(1 << (m - i - 1));
+            m = i;
+            c = &b * &b;
+            t *= &c;
+            r *= &b;
+        }
+
+        let neg = -&r;
+        Some(if neg.0 < r.0 { neg } else { r })
+    }
 }
 
-impl Add<Fp> for &Fp {
-    type Output = Fp;
+impl PartialEq for Fp {
+    fn eq(&self, other: &Self) -> bool {
+        self.reduced() == other.reduced()
+    }
+}
+
+impl Eq for Fp {}
 
-    fn add(self, rhs: Fp) -> Self::Output {
-        Fp(((self.0 as u64 + rhs.0 as u64) % 2013265921u64) as u32)
+impl Hash for Fp {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.reduced().hash(state)
     }
 }
 
-impl Add<Fp> for Fp {
-    type Output = Fp;
+impl From<u32> for Fp {
+    fn from(v: u32) -> Self {
+        Self::new(v)
+    }
+}
 
-    fn add(self, rhs: Fp) -> Self::Output {
-        Fp(((self.0 as u64 + rhs.0 as u64) % 2013265921u64) as u32)
+impl From<u64> for Fp {
+    fn from(v: u64) -> Self {
+        Self((v % P as u64) as u32)
     }
 }
 
-impl Add for &Fp {
-    type Output = Fp;
+impl From<i32> for Fp {
+    fn from(v: i32) -> Self {
+        Self::from(v as i64)
+    }
+}
 
-    fn add(self, rhs: &Fp) -> Self::Output {
-        Fp(((self.0 as u64 + rhs.0 as u64) % 2013265921u64) as u32)
+impl From<i64> for Fp {
+    fn from(v: i64) -> Self {
+        Self(v.rem_euclid(P as i64) as u32)
     }
 }
 
-impl Sub for &Fp {
+fn fp_add(a: &Fp, b: &Fp) -> Fp {
+    Fp(((a.reduced() + b.reduced()) % P as u64) as u32)
+}
+
+fn fp_sub(a: &Fp, b: &Fp) -> Fp {
+    Fp(((P as u64 + a.reduced() - b.reduced()) % P as u64) as u32)
+}
+
+fn fp_mul(a: &Fp, b: &Fp) -> Fp {
+    Fp(((a.reduced() * b.reduced()) % P as u64) as u32)
+}
+
+fn fp_div(a: &Fp, b: &Fp) -> Fp {
+    fp_mul(a, &b.try_inv().expect("division of an Fp by zero"))
+}
+
+// the operator for every combination of owned and borrowed operands, and its assignment form
+macro_rules! impl_fp_op {
+    ($op:ident, $method:ident, $op_assign:ident, $method_assign:ident, $f:ident) => {
+        impl $op<Fp> for Fp {
+            type Output = Fp;
+
+            fn $method(self, rhs: Fp) -> Fp {
+                $f(&self, &rhs)
+            }
+        }
+
+        impl $op<&Fp> for Fp {
+            type Output = Fp;
+
+            fn $method(self, rhs: &Fp) -> Fp {
+                $f(&self, rhs)
+            }
+        }
+
+        impl $op<Fp> for &Fp {
+            type Output = Fp;
+
+            fn $method(self, rhs: Fp) -> Fp {
+                $f(self, &rhs)
+            }
+        }
+
+        impl $op<&Fp> for &Fp {
+            type Output = Fp;
+
+            fn $method(self, rhs: &Fp) -> Fp {
+                $f(self, rhs)
+            }
+        }
+
+        impl $op_assign<Fp> for Fp {
+            fn $method_assign(&mut self, rhs: Fp) {
+                *self = $f(self, &rhs);
+            }
+        }
+
+        impl $op_assign<&Fp> for Fp {
+            fn $method_assign(&mut self, rhs: &Fp) {
+                *self = $f(self, rhs);
+            }
+        }
+    };
+}
+
+impl_fp_op!(Add, add, AddAssign, add_assign, fp_add);
+impl_fp_op!(Sub, sub, SubAssign, sub_assign, fp_sub);
+impl_fp_op!(Mul, mul, MulAssign, mul_assign, fp_mul);
+impl_fp_op!(Div, div, DivAssign, div_assign, fp_div);
+
+impl Neg for Fp {
     type Output = Fp;
 
-    fn sub(self, rhs: Self) -> Self::Output {
-        Fp(((2013265921u64 + self.0 as u64 - rhs.0 as u64) % 2013265921u64) as u32)
+    fn neg(self) -> Fp {
+        fp_sub(&Fp::ZERO, &self)
     }
 }
 
-impl Mul for &Fp {
+impl Neg for &Fp {
     type Output = Fp;
 
-    fn mul(self, rhs: Self) -> Self::Output {
-        Fp(((self.0 as u64 * rhs.0 as u64) % 2013265921u64) as u32)
+    fn neg(self) -> Fp {
+        fp_sub(&Fp::ZERO, self)
     }
 }
 
-impl Mul<Fp> for &Fp {
-    type Output = Fp;
+impl Sum for Fp {
+    fn sum<I: Iterator<Item = Fp>>(iter: I) -> Fp {
+        iter.fold(Fp::ZERO, |acc, v| acc + v)
+    }
+}
 
-    fn mul(self, rhs: Fp) -> Self::Output {
-        Fp(((self.0 as u64 * rhs.0 as u64) % 2013265921u64) as u32)
+impl<'a> Sum<&'a Fp> for Fp {
+    fn sum<I: Iterator<Item = &'a Fp>>(iter: I) -> Fp {
+        iter.fold(Fp::ZERO, |acc, v| acc + v)
     }
 }
 
-impl Mul<Fp> for Fp {
-    type Output = Fp;
+impl Product for Fp {
+    fn product<I: Iterator<Item = Fp>>(iter: I) -> Fp {
+        iter.fold(Fp::ONE, |acc, v| acc * v)
+    }
+}
 
-    fn mul(self, rhs: Self) -> Self::Output {
-        Fp(((self.0 as u64 * rhs.0 as u64) % 2013265921u64) as u32)
+impl<'a> Product<&'a Fp> for Fp {
+    fn product<I: Iterator<Item = &'a Fp>>(iter: I) -> Fp {
+        iter.fold(Fp::ONE, |acc, v| acc * v)
     }
 }
 
+// multiplies by `2^rhs`, modulo `P`
 impl Shl<usize> for Fp {
     type Output = Fp;
 
     fn shl(self, rhs: usize) -> Self::Output {
-        Fp(self.0 << rhs)
+        &self << rhs
+    }
+}
+
+impl Shl<usize> for &Fp {
+    type Output = Fp;
+
+    fn shl(self, rhs: usize) -> Self::Output {
+        fp_mul(self, &Fp(2).pow(rhs as u64))
     }
 }
 
+// on the representations, which are only meaningful for the bit operations of the machine
 impl BitAnd for &Fp {
     type Output = Fp;
 
     fn bitand(self, rhs: Self) -> Self::Output {
-        Fp(self.0 & rhs.0)
+        Fp((self.reduced() & rhs.reduced()) as u32)
     }
 }
 
@@ -87,54 +327,127 @@ impl BitXor for &Fp {
     type Output = Fp;
 
     fn bitxor(self, rhs: Self) -> Self::Output {
-        Fp(self.0 ^ rhs.0)
+        Fp((self.reduced() ^ rhs.reduced()) as u32)
     }
 }
 
+// `{:#}` prints the signed value, such as -1 for `P - 1`
 impl Display for Fp {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        Display::fmt(&self.0, f)
+        if f.alternate() {
+            Display::fmt(&self.signed(), f)
+        } else {
+            Display::fmt(&self.0, f)
+        }
     }
 }
 
-impl Add for &Fp4 {
+fn fp4_add(a: &Fp4, b: &Fp4) -> Fp4 {
+    Fp4(&a.0 + &b.0, &a.1 + &b.1, &a.2 + &b.2, &a.3 + &b.3)
+}
+
+fn fp4_sub(a: &Fp4, b: &Fp4) -> Fp4 {
+    Fp4(&a.0 - &b.0, &a.1 - &b.1, &a.2 - &b.2, &a.3 - &b.3)
+}
+
+fn fp4_mul(a: &Fp4, b: &Fp4) -> Fp4 {
+    Fp4(
+        &a.0 * &b.0 + NBETA * (&a.1 * &b.3 + &a.2 * &b.2 + &a.3 * &b.1),
+        &a.0 * &b.1 + &a.1 * &b.0 + NBETA * (&a.2 * &b.3 + &a.3 * &b.2),
+        &a.0 * &b.2 + &a.1 * &b.1 + &a.2 * &b.0 + NBETA * (&a.3 * &b.3),
+        &a.0 * &b.3 + &a.1 * &b.2 + &a.2 * &b.1 + &a.3 * &b.0,
+    )
+}
+
+fn fp4_div(a: &Fp4, b: &Fp4) -> Fp4 {
+    fp4_mul(a, &b.try_inv().expect("division of an Fp4 by zero"))
+}
+
+// the operator for every combination of owned and borrowed operands, and its assignment form
+macro_rules! impl_fp4_op {
+    ($op:ident, $method:ident, $op_assign:ident, $method_assign:ident, $f:ident) => {
+        impl $op<Fp4> for Fp4 {
+            type Output = Fp4;
+
+            fn $method(self, rhs: Fp4) -> Fp4 {
+                $f(&self, &rhs)
+            }
+        }
+
+        impl $op<&Fp4> for Fp4 {
+            type Output = Fp4;
+
+            fn $method(self, rhs: &Fp4) -> Fp4 {
+                $f(&self, rhs)
+            }
+        }
+
+        impl $op<Fp4> for &Fp4 {
+            type Output = Fp4;
+
+            fn $method(self, rhs: Fp4) -> Fp4 {
+                $f(self, &rhs)
+            }
+        }
+
+        impl $op<&Fp4> for &Fp4 {
+            type Output = Fp4;
+
+            fn $method(self, rhs: &Fp4) -> Fp4 {
+                $f(self, rhs)
+            }
+        }
+
+        impl $op_assign<Fp4> for Fp4 {
+            fn $method_assign(&mut self, rhs: Fp4) {
+                *self = $f(self, &rhs);
+            }
+        }
+
+        impl $op_assign<&Fp4> for Fp4 {
+            fn $method_assign(&mut self, rhs: &Fp4) {
+                *self = $f(self, rhs);
+            }
+        }
+    };
+}
+
+impl_fp4_op!(Add, add, AddAssign, add_assign, fp4_add);
+impl_fp4_op!(Sub, sub, SubAssign, sub_assign, fp4_sub);
+impl_fp4_op!(Mul, mul, MulAssign, mul_assign, fp4_mul);
+impl_fp4_op!(Div, div, DivAssign, div_assign, fp4_div);
+
+impl Neg for Fp4 {
     type Output = Fp4;
 
-    fn add(self, rhs: Self) -> Fp4 {
-        Fp4(
-            &self.0 + &rhs.0,
-            &self.1 + &rhs.1,
-            &self.2 + &rhs.2,
-            &self.3 + &rhs.2,
-        )
+    fn neg(self) -> Fp4 {
+        -&self
     }
 }
 
-impl Sub for &Fp4 {
+impl Neg for &Fp4 {
     type Output = Fp4;
 
-    fn sub(self, rhs: Self) -> Self::Output {
-        Fp4(
-            &self.0 - &rhs.0,
-            &self.1 - &rhs.1,
-            &self.2 - &rhs.2,
-            &self.3 - &rhs.2,
-        )
+    fn neg(self) -> Fp4 {
+        Fp4(-&self.0, -&self.1, -&self.2, -&self.3)
     }
 }
 
-impl Mul for &Fp4 {
-    type Output = Fp4;
+// compares the coefficients modulo `P`
+impl PartialEq for Fp4 {
+    fn eq(&self, other: &Self) -> bool {
+        self.coeffs()
+            .iter()
+            .zip(other.coeffs().iter())
+            .all(|(a, b)| a == b)
+    }
+}
 
-    fn mul(self, rhs: Self) -> Self::Output {
-        const NBETA: Fp = Fp(2013265910);
+impl Eq for Fp4 {}
 
-        Fp4(
-            &self.0 * &rhs.0 + NBETA * (&self.1 * &rhs.3 + &self.2 * &rhs.2 + &self.3 * &rhs.1),
-            &self.0 * &rhs.1 + &self.1 * &rhs.0 + NBETA * (&self.2 * &rhs.3 + &self.3 * &rhs.2),
-            &self.0 * &rhs.2 + &self.1 * &rhs.1 + &self.2 * &rhs.0 + NBETA * (&self.3 * &rhs.3),
-            &self.0 * &rhs.3 + &self.1 * &rhs.2 + &self.2 * &rhs.1 + &self.3 * &rhs.0,
-        )
+impl From<Fp> for Fp4 {
+    fn from(v: Fp) -> Self {
+        Self::from_fp(v)
     }
 }
 
@@ -154,25 +467,359 @@ impl Display for Fp4 {
 }
 
 impl Fp4 {
+    pub const ZERO: Fp4 = Self(Fp::ZERO, Fp::ZERO, Fp::ZERO, Fp::ZERO);
+    pub const ONE: Fp4 = Self(Fp::ONE, Fp::ZERO, Fp::ZERO, Fp::ZERO);
+
     pub fn new(v1: Fp, v2: Fp, v3: Fp, v4: Fp) -> Self {
         Self(v1, v2, v3, v4)
     }
 
-    pub fn inv(&self) -> Self {
-        self.pow(2013265919usize)
+    /// The element of the base field `v`.
+    pub fn from_fp(v: Fp) -> Self {
+        Self(v, Fp::ZERO, Fp::ZERO, Fp::ZERO)
     }
 
-    fn pow(&self, n: usize) -> Self {
+    pub fn coeffs(&self) -> [&Fp; 4] {
+        [&self.0, &self.1, &self.2, &self.3]
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.coeffs().iter().all(|c| c.is_zero())
+    }
+
+    pub fn pow(&self, n: u64) -> Self {
+        self.pow_u128(n as u128)
+    }
+
+    // the exponents of the whole multiplicative group, of order `P^4 - 1`, need more than 64 bits
+    fn pow_u128(&self, n: u128) -> Self {
         let mut n = n;
-        let mut tot = Self(Fp(1), Fp::ZERO, Fp::ZERO, Fp::ZERO);
+        let mut tot = Self::ONE;
         let mut x = self.clone();
         while n != 0 {
             if n % 2 == 1 {
-                tot = &tot * &x;
+                tot *= &x;
             }
             n /= 2;
             x = &x * &x;
         }
         tot
     }
+
+    /// The Frobenius map, `a -> a^P`, which sends `x` to `OMEGA * x` for a fourth root of unity
+    /// `OMEGA` of the base field.
+    pub fn frobenius(&self) -> Self {
+        self.frobenius_pow(1)
+    }
+
+    /// The Frobenius map applied `k` times, `a -> a^(P^k)`.
+    pub fn frobenius_pow(&self, k: usize) -> Self {
+        let w = OMEGA.pow(k as u64 % 4);
+        let w2 = &w * &w;
+        let w3 = &w2 * &w;
+        Self(Fp::new(self.0 .0), &self.1 * w, &self.2 * w2, &self.3 * w3)
+    }
+
+    /// The norm down to the base field, the product of the conjugates of the element.
+    pub fn norm(&self) -> Fp {
+        let n = self * &self.conjugates();
+        debug_assert!(n.1.is_zero() && n.2.is_zero() && n.3.is_zero());
+        n.0
+    }
+
+    // the product of the conjugates other than the element itself
+    fn conjugates(&self) -> Self {
+        &(&self.frobenius_pow(1) * &self.frobenius_pow(2)) * &self.frobenius_pow(3)
+    }
+
+    /// The inverse, or zero for zero, as the machine computes it: the product of the other
+    /// conjugates, divided by the norm.
+    pub fn inv(&self) -> Self {
+        let conjugates = self.conjugates();
+        let n = (self * &conjugates).0.inv();
+        Self(
+            &conjugates.0 * &n,
+            &conjugates.1 * &n,
+            &conjugates.2 * &n,
+            &conjugates.3 * &n,
+        )
+    }
+
+    /// The inverse, or `None` for zero.
+    pub fn try_inv(&self) -> Option<Self> {
+        (!self.is_zero()).then(|| self.inv())
+    }
+
+    /// Whether the element is a square, which it is exactly when its norm is a square in the base
+    /// field.
+    pub fn is_square(&self) -> bool {
+        self.norm().is_square()
+    }
+
+    /// A square root, or `None` if the element is not a square. This is Tonelli-Shanks, for
+    /// `P^4 - 1 = q * 2^29`.
+    pub fn sqrt(&self) -> Option<Self> {
+        if self.is_zero() {
+            return Some(Self::ZERO);
+        }
+        if !self.is_square() {
+            return None;
+        }
+
+        let order = (P as u128).pow(4) - 1;
+        let mut m = order.trailing_zeros();
+        let q = order >> m;
+        let z = (0..)
+            .map(|k| Self(Fp(k), Fp::ONE, Fp::ZERO, Fp::ZERO))
+            .find(|z| !z.is_square())
+            .unwrap();
+        let mut c = z.pow_u128(q);
+        let mut t = self.pow_u128(q);
+        let mut r = self.pow_u128(q.div_ceil(2));
+        while t != Self::ONE {
+            // the least i such that t^(2^i) = 1
+            let mut i = 0;
+            let mut t2 = t.clone();
+            while t2 != Self::ONE {
+                t2 = &t2 * &t2;
+                i += 1;
+            }
+            let b = c.pow_u128(1 << (m - i - 1));
+            m = i;
+            c = &b * &b;
+            t *= &c;
+            r *= &b;
+        }
+        Some(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    // SplitMix64, for values spread over the whole range of the representation
+    struct Rng(u64);
+
+    impl Rng {
+        fn new(seed: u64) -> Self {
+            Self(seed)
+        }
+
+        fn next(&mut self) -> u64 {
+            self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^ (z >> 31)
+        }
+
+        // a canonical element, or the other representation below 2^32 of one, or an edge case
+        fn fp(&mut self) -> Fp {
+            const EDGES: [u32; 6] = [0, 1, P - 1, P, P + 1, u32::MAX];
+            match self.next() % 8 {
+                0 => Fp(EDGES[(self.next() % 6) as usize]),
+                1 => Fp(self.next() as u32),
+                _ => Fp((self.next() % P as u64) as u32),
+            }
+        }
+    }
+
+    fn hash_of(v: &Fp) -> u64 {
+        use std::hash::DefaultHasher;
+        let mut h = DefaultHasher::new();
+        v.hash(&mut h);
+        h.finish()
+    }
+
+    #[test]
+    fn canonical_eq_and_hash() {
+        assert_eq!(Fp(P), Fp::ZERO);
+        assert_eq!(Fp(P + 5), Fp(5));
+        assert_ne!(Fp(P - 1), Fp::ZERO);
+        assert_eq!(hash_of(&Fp(P + 5)), hash_of(&Fp(5)));
+        assert_eq!(HashSet::from([Fp(0), Fp(P), Fp::new(P)]).len(), 1);
+        assert_eq!(
+            Fp4(Fp(P), Fp(1), Fp(0), Fp(0)),
+            Fp4::new(Fp(0), Fp(P + 1), Fp(0), Fp(0))
+        );
+        assert_eq!(Field::as_u64(&Fp(P + 1)), 1);
+        // the bit operations act on the reduced values
+        assert_eq!((&Fp(P + 6) & &Fp(3)).0, 2);
+        assert_eq!((&Fp(P + 6) ^ &Fp(P + 3)).0, 5);
+
+        let mut rng = Rng::new(1);
+        for _ in 0..1000 {
+            let v = rng.next() as u32;
+            assert_eq!(Fp(v), Fp::new(v));
+            assert_eq!(hash_of(&Fp(v)), hash_of(&Fp(v % P)));
+        }
+    }
+
+    #[test]
+    fn field_axioms() {
+        let mut rng = Rng::new(2);
+        for _ in 0..2000 {
+            let (a, b, c) = (rng.fp(), rng.fp(), rng.fp());
+            assert_eq!(&a + &b, &b + &a);
+            assert_eq!(&a * &b, &b * &a);
+            assert_eq!((&a + &b) + &c, &a + (&b + &c));
+            assert_eq!((&a * &b) * &c, &a * (&b * &c));
+            assert_eq!(&a * (&b + &c), &a * &b + &a * &c);
+            assert_eq!(&a + Fp::ZERO, a);
+            assert_eq!(&a * Fp::ONE, a);
+            assert_eq!(&a + -&a, Fp::ZERO);
+            assert_eq!(&a - &b, &a + -&b);
+            assert!((&a * &b).0 < P && (&a + &b).0 < P && (&a - &b).0 < P);
+            match a.try_inv() {
+                Some(inv) => {
+                    assert_eq!(&a * &inv, Fp::ONE);
+                    assert_eq!(&b / &a * &a, b);
+                }
+                None => assert!(a.is_zero() && a.inv().is_zero()),
+            }
+        }
+    }
+
+    #[test]
+    fn powers_and_roots() {
+        let mut rng = Rng::new(3);
+        for _ in 0..200 {
+            let a = rng.fp();
+            let n = rng.next() % 64;
+            assert_eq!(a.pow(n), (0..n).map(|_| a.clone()).product::<Fp>());
+            assert_eq!(
+                a.pow(P as u64 - 1),
+                if a.is_zero() { Fp::ZERO } else { Fp::ONE }
+            );
+            assert_eq!(&a << 5, &a * Fp(32));
+
+            let square = &a * &a;
+            let root = square.sqrt().unwrap();
+            assert_eq!(&root * &root, square);
+            assert!(root.0 <= P / 2);
+            assert_eq!(a.is_square(), a.sqrt().is_some());
+        }
+        assert_eq!(GENERATOR.sqrt(), None);
+        assert_eq!(Fp::from(-1i64), Fp(P - 1));
+        assert_eq!(Fp(P - 1).signed(), -1);
+        assert_eq!(Fp::from_montgomery(Fp(7).to_montgomery()), Fp(7));
+    }
+
+    // the coefficients of an element of `F_p[x] / (x^4 + 11)`, computed on plain integers
+    type Poly = [u64; 4];
+
+    const PP: u64 = P as u64;
+
+    fn poly(a: &Fp4) -> Poly {
+        a.coeffs().map(|c| c.0 as u64 % PP)
+    }
+
+    fn poly_mul(a: &Poly, b: &Poly) -> Poly {
+        let mut product = [0u64; 7];
+        for i in 0..4 {
+            for j in 0..4 {
+                product[i + j] = (product[i + j] + a[i] * b[j]) % PP;
+            }
+        }
+        // x^(4 + k) = -11 x^k
+        for k in (4..7).rev() {
+            product[k - 4] = (product[k - 4] + (PP - 11) * product[k]) % PP;
+        }
+        [product[0], product[1], product[2], product[3]]
+    }
+
+    fn poly_pow(a: &Poly, n: u128) -> Poly {
+        let (mut n, mut tot, mut x) = (n, [1, 0, 0, 0], *a);
+        while n != 0 {
+            if n % 2 == 1 {
+                tot = poly_mul(&tot, &x);
+            }
+            n /= 2;
+            x = poly_mul(&x, &x);
+        }
+        tot
+    }
+
+    impl Rng {
+        fn fp4(&mut self) -> Fp4 {
+            Fp4::new(self.fp(), self.fp(), self.fp(), self.fp())
+        }
+    }
+
+    #[test]
+    fn fp4_against_polynomials() {
+        let mut rng = Rng::new(4);
+        for _ in 0..500 {
+            let (a, b) = (rng.fp4(), rng.fp4());
+            let (pa, pb) = (poly(&a), poly(&b));
+            let sum: Vec<u64> = pa.iter().zip(pb).map(|(x, y)| (x + y) % PP).collect();
+            let diff: Vec<u64> = pa.iter().zip(pb).map(|(x, y)| (x + PP - y) % PP).collect();
+            assert_eq!(poly(&(&a + &b)).to_vec(), sum);
+            assert_eq!(poly(&(&a - &b)).to_vec(), diff);
+            assert_eq!(poly(&(&a + -&a)), [0; 4]);
+            assert_eq!(poly(&(&a * &b)), poly_mul(&pa, &pb));
+            assert_eq!(poly(&a.pow(5)), poly_pow(&pa, 5));
+            assert_eq!(
+                Fp4::from_fp(a.0.clone()),
+                Fp4::new(a.0.clone(), Fp(P), Fp(0), Fp(0))
+            );
+        }
+    }
+
+    #[test]
+    fn fp4_inverse_and_frobenius() {
+        let order = (P as u128).pow(4) - 1;
+        let mut rng = Rng::new(5);
+        for _ in 0..100 {
+            let (a, b) = (rng.fp4(), rng.fp4());
+            let pa = poly(&a);
+            // the inverse is a^(P^4 - 2)
+            assert_eq!(poly(&a.inv()), poly_pow(&pa, order - 1));
+            match a.try_inv() {
+                Some(inv) => {
+                    assert_eq!(poly_mul(&pa, &poly(&inv)), [1, 0, 0, 0]);
+                    assert_eq!(&b / &a * &a, b);
+                }
+                None => assert!(a.is_zero()),
+            }
+
+            let mut frobenius = pa;
+            for k in 0..5 {
+                assert_eq!(poly(&a.frobenius_pow(k)), frobenius);
+                frobenius = poly_pow(&frobenius, P as u128);
+            }
+            assert_eq!(a.frobenius(), a.frobenius_pow(1));
+            let norm = poly_pow(&pa, order / (P as u128 - 1));
+            assert_eq!(norm, [a.norm().0 as u64, 0, 0, 0]);
+        }
+        assert!(Fp4::ZERO.inv().is_zero());
+        assert_eq!(Fp4::ZERO.try_inv(), None);
+    }
+
+    #[test]
+    fn fp4_roots() {
+        let half = ((P as u128).pow(4) - 1) / 2;
+        let mut rng = Rng::new(6);
+        let mut squares = 0;
+        for _ in 0..100 {
+            let a = rng.fp4();
+            let is_square = a.is_zero() || poly_pow(&poly(&a), half) == [1, 0, 0, 0];
+            assert_eq!(a.is_square(), is_square);
+            match a.sqrt() {
+                Some(root) => {
+                    assert_eq!(poly_mul(&poly(&root), &poly(&root)), poly(&a));
+                    squares += 1;
+                }
+                None => assert!(!is_square),
+            }
+
+            let square = &a * &a;
+            let root = square.sqrt().unwrap();
+            assert!(root == a || root == -&a);
+        }
+        // about half of the elements are squares
+        assert!((25..75).contains(&squares));
+    }
 }
@@ -0,0 +1,1337 @@
+use crate::math::Fp;
+
+pub const CELLS: usize = 24;
+
+pub const ROUNDS_HALF_FULL: usize = 4;
+
+pub const ROUNDS_PARTIAL: usize = 21;
+
+pub const ROUNDS: usize = 2 * ROUNDS_HALF_FULL + ROUNDS_PARTIAL;
+
+// the constants added to every cell at the start of each round, `CELLS` per round
+pub const ROUND_CONSTANTS: [Fp; ROUNDS * CELLS] = [
+    Fp(0x75c89df8),
+    Fp(0x0af90431),
+    Fp(0x39e877bf),
+    Fp(0x18a5a8cd),
+    Fp(0x588e9a95),
+    Fp(0x16760b26),
+    Fp(0x026ff4a4),
+    Fp(0x2326df32),
+    Fp(0x5a0b7c6f),
+    Fp(0x37d3973d),
+    Fp(0x1e5bc883),
+    Fp(0x59d3664a),
+    Fp(0x677f8a14),
+    Fp(0x64a3389e),
+    Fp(0x0d81d503),
+    Fp(0x77f51a78),
+    Fp(0x1fc66dca),
+    Fp(0x13a094e3),
+    Fp(0x5d410ff1),
+    Fp(0x2383e213),
+    Fp(0x4f1144c3),
+    Fp(0x1ee540c8),
+    Fp(0x097edb05),
+    Fp(0x2c17a521),
+    Fp(0x66927671),
+    Fp(0x76626203),
+    Fp(0x1de486e8),
+    Fp(0x440c19f5),
+    Fp(0x20bbb67a),
+    Fp(0x2d4f79b0),
+    Fp(0x55b64ceb),
+    Fp(0x6712fbb4),
+    Fp(0x24a3ec8a),
+    Fp(0x3b6062d5),
+    Fp(0x265abe23),
+    Fp(0x3b6fb81b),
+    Fp(0x43ddb4af),
+    Fp(0x49a5c4a8),
+    Fp(0x64581fc3),
+    Fp(0x77b795f1),
+    Fp(0x1e918db8),
+    Fp(0x550e2c6a),
+    Fp(0x6b3571c8),
+    Fp(0x3609bf61),
+    Fp(0x46ae6e8f),
+    Fp(0x41fe0f80),
+    Fp(0x20420c1a),
+    Fp(0x0e95ad8a),
+    Fp(0x1b48deb1),
+    Fp(0x122ac59f),
+    Fp(0x0252c4a7),
+    Fp(0x2696a2c4),
+    Fp(0x01b232cf),
+    Fp(0x5c26d56b),
+    Fp(0x405535be),
+    Fp(0x20cc2fe6),
+    Fp(0x1c16804d),
+    Fp(0x5fa5a4b8),
+    Fp(0x579729ad),
+    Fp(0x63e11b88),
+    Fp(0x5601d544),
+    Fp(0x3eeeaa1a),
+    Fp(0x4dfd202b),
+    Fp(0x4fa5da37),
+    Fp(0x2118e7fc),
+    Fp(0x3eb830dd),
+    Fp(0x169ee87b),
+    Fp(0x2bd97286),
+    Fp(0x22865c3e),
+    Fp(0x6aaa6429),
+    Fp(0x29e0f68d),
+    Fp(0x6aca023a),
+    Fp(0x4d02be5e),
+    Fp(0x4f170e62),
+    Fp(0x5cc04ba7),
+    Fp(0x20655f92),
+    Fp(0x6a216eea),
+    Fp(0x7685bdbd),
+    Fp(0x332f989c),
+    Fp(0x57a66733),
+    Fp(0x0719b431),
+    Fp(0x2f19a5c2),
+    Fp(0x5191f27f),
+    Fp(0x0e38bd91),
+    Fp(0x1c5abcda),
+    Fp(0x1da9f1bf),
+    Fp(0x42b01881),
+    Fp(0x5bf52e0f),
+    Fp(0x4dc17cb8),
+    Fp(0x1d4688d6),
+    Fp(0x18e0160d),
+    Fp(0x54841116),
+    Fp(0x6b381bc9),
+    Fp(0x5c4a838c),
+    Fp(0x0e3dc983),
+    Fp(0x71c18d85),
+    Fp(0x71902c5e),
+    Fp(0x1654566c),
+    Fp(0x38b6f3a0),
+    Fp(0x35a19789),
+    Fp(0x2adf05e2),
+    Fp(0x07d01f0d),
+    Fp(0x02677f73),
+    Fp(0x385f532a),
+    Fp(0x3e2fb2ac),
+    Fp(0x4e88c53b),
+    Fp(0x1e370587),
+    Fp(0x4e64e63c),
+    Fp(0x5942f756),
+    Fp(0x16944480),
+    Fp(0x711cc845),
+    Fp(0x76a93011),
+    Fp(0x6c978579),
+    Fp(0x68f3467f),
+    Fp(0x47df3684),
+    Fp(0x09001f22),
+    Fp(0x3c1d9746),
+    Fp(0x70796779),
+    Fp(0x3b0bcbc9),
+    Fp(0x64665dab),
+    Fp(0x55eb1733),
+    Fp(0x3a240c5c),
+    Fp(0x5a4445ac),
+    Fp(0x3623eb7e),
+    Fp(0x6c1a9a42),
+    Fp(0x0a024e58),
+    Fp(0x44097942),
+    Fp(0x240878ef),
+    Fp(0x3f507e57),
+    Fp(0x68b9393f),
+    Fp(0x43163825),
+    Fp(0x6d71852f),
+    Fp(0x5937ffff),
+    Fp(0x564b7092),
+    Fp(0x60d0dcb4),
+    Fp(0x414e0bf2),
+    Fp(0x754a4cd6),
+    Fp(0x4edd0db2),
+    Fp(0x341f2761),
+    Fp(0x6da77d03),
+    Fp(0x6f157bce),
+    Fp(0x436d5397),
+    Fp(0x5501d7ab),
+    Fp(0x310b522f),
+    Fp(0x0ab56164),
+    Fp(0x150e3635),
+    Fp(0x0a7e3bc1),
+    Fp(0x05fac2a2),
+    Fp(0x68f51ab3),
+    Fp(0x1f03a3cf),
+    Fp(0x398e133a),
+    Fp(0x0f5d1399),
+    Fp(0x5f29a526),
+    Fp(0x6cea6946),
+    Fp(0x1dca5af8),
+    Fp(0x5c06e542),
+    Fp(0x1665a8f2),
+    Fp(0x4b950256),
+    Fp(0x6ad5b666),
+    Fp(0x68e13aff),
+    Fp(0x3c118567),
+    Fp(0x70ef24e6),
+    Fp(0x08fe6bb2),
+    Fp(0x77610aee),
+    Fp(0x140b4090),
+    Fp(0x12a59e03),
+    Fp(0x43376c38),
+    Fp(0x5cc0ffc8),
+    Fp(0x1b05d461),
+    Fp(0x50cd7eb1),
+    Fp(0x320b07e2),
+    Fp(0x24cfbcfc),
+    Fp(0x12bb2eec),
+    Fp(0x33169cde),
+    Fp(0x5f7ea3ec),
+    Fp(0x3f690e4c),
+    Fp(0x2f47b8cd),
+    Fp(0x32d5e526),
+    Fp(0x3493e551),
+    Fp(0x07eac5d0),
+    Fp(0x111bfa27),
+    Fp(0x2f667258),
+    Fp(0x4c1c06d2),
+    Fp(0x6cd4b86c),
+    Fp(0x30702542),
+    Fp(0x53d64e17),
+    Fp(0x7143e320),
+    Fp(0x47e038ee),
+    Fp(0x6871b994),
+    Fp(0x4113c042),
+    Fp(0x240af147),
+    Fp(0x5cde3214),
+    Fp(0x600fd4c2),
+    Fp(0x17a79c40),
+    Fp(0x43600bcc),
+    Fp(0x158238e7),
+    Fp(0x23082895),
+    Fp(0x53b32037),
+    Fp(0x583dd7e9),
+    Fp(0x1c2966ec),
+    Fp(0x4f1c4aa2),
+    Fp(0x5e004513),
+    Fp(0x3b80fd92),
+    Fp(0x4cfed7fe),
+    Fp(0x62679264),
+    Fp(0x59abcafe),
+    Fp(0x4ddde884),
+    Fp(0x45535ec3),
+    Fp(0x6a446628),
+    Fp(0x238b231e),
+    Fp(0x307d9f5a),
+    Fp(0x1128d0a2),
+    Fp(0x6ff378d4),
+    Fp(0x2cd023e3),
+    Fp(0x2f1d7c11),
+    Fp(0x4c10f8f9),
+    Fp(0x6bd45d5c),
+    Fp(0x74c89ea9),
+    Fp(0x33cb1c8b),
+    Fp(0x2c94a9ce),
+    Fp(0x3816b43d),
+    Fp(0x0e65a71e),
+    Fp(0x5e38c082),
+    Fp(0x6427f754),
+    Fp(0x3bcce6ef),
+    Fp(0x6ea9c00e),
+    Fp(0x510a8434),
+    Fp(0x30063591),
+    Fp(0x06620981),
+    Fp(0x4beaf035),
+    Fp(0x4be4a698),
+    Fp(0x5ca330ec),
+    Fp(0x33d54bf7),
+    Fp(0x316d226b),
+    Fp(0x3fe68690),
+    Fp(0x54e0d88e),
+    Fp(0x197cc4e9),
+    Fp(0x23c98f88),
+    Fp(0x299beb0c),
+    Fp(0x1c598f09),
+    Fp(0x772a2f2d),
+    Fp(0x28f39a58),
+    Fp(0x15c6d916),
+    Fp(0x5482d960),
+    Fp(0x1bcfe651),
+    Fp(0x17da1dbc),
+    Fp(0x4ae31a82),
+    Fp(0x3d1fb87a),
+    Fp(0x10e59612),
+    Fp(0x59fff611),
+    Fp(0x7151fd1f),
+    Fp(0x04308067),
+    Fp(0x538af856),
+    Fp(0x31ec4b46),
+    Fp(0x5ca7e0e1),
+    Fp(0x014f24bc),
+    Fp(0x34801c56),
+    Fp(0x400f2575),
+    Fp(0x42c0be20),
+    Fp(0x3fef7fd5),
+    Fp(0x40e16c35),
+    Fp(0x39a29264),
+    Fp(0x5139e92e),
+    Fp(0x6243e0e4),
+    Fp(0x356f27cc),
+    Fp(0x022c022e),
+    Fp(0x47511780),
+    Fp(0x28cc6078),
+    Fp(0x358b8d3d),
+    Fp(0x45f840fa),
+    Fp(0x615d96d5),
+    Fp(0x4e68d893),
+    Fp(0x761e8520),
+    Fp(0x3ae0bc8d),
+    Fp(0x4d793ea9),
+    Fp(0x461c98dd),
+    Fp(0x0e6ae09e),
+    Fp(0x2473818a),
+    Fp(0x027d0ea1),
+    Fp(0x1e37b4fc),
+    Fp(0x5a9b918e),
+    Fp(0x1af57d91),
+    Fp(0x16b2e255),
+    Fp(0x3e968bb0),
+    Fp(0x025fff84),
+    Fp(0x4a0ae3b6),
+    Fp(0x196ea9cc),
+    Fp(0x492f54d7),
+    Fp(0x21dd4a08),
+    Fp(0x63d6a965),
+    Fp(0x0d8d0c0c),
+    Fp(0x4f797854),
+    Fp(0x1ddd249b),
+    Fp(0x48ea3e56),
+    Fp(0x2028b394),
+    Fp(0x59ba09f9),
+    Fp(0x73e9f78e),
+    Fp(0x14d3b6cb),
+    Fp(0x095b67e2),
+    Fp(0x5b97fccb),
+    Fp(0x4ae9fd70),
+    Fp(0x1c3d8daf),
+    Fp(0x592bb255),
+    Fp(0x459d8bb0),
+    Fp(0x53284952),
+    Fp(0x62d219ff),
+    Fp(0x085b04e1),
+    Fp(0x292a4f83),
+    Fp(0x33ab42f3),
+    Fp(0x3b4848d7),
+    Fp(0x35b3b4e3),
+    Fp(0x2b104210),
+    Fp(0x36bc80a0),
+    Fp(0x23bc9027),
+    Fp(0x5d3a8ec7),
+    Fp(0x0b24aed6),
+    Fp(0x0ede5278),
+    Fp(0x5e5305a8),
+    Fp(0x39ac900a),
+    Fp(0x05dc1627),
+    Fp(0x4294fabf),
+    Fp(0x6de5d842),
+    Fp(0x2a826d22),
+    Fp(0x1f30553c),
+    Fp(0x340b8c1b),
+    Fp(0x05b2b5d4),
+    Fp(0x752b7fd1),
+    Fp(0x1ee6d240),
+    Fp(0x63125d49),
+    Fp(0x09fe5b3e),
+    Fp(0x05f70546),
+    Fp(0x13eb2cbd),
+    Fp(0x0d833c10),
+    Fp(0x5a629eec),
+    Fp(0x5a7fd158),
+    Fp(0x0eb52380),
+    Fp(0x560ad5c4),
+    Fp(0x29645160),
+    Fp(0x1170c734),
+    Fp(0x1822aac9),
+    Fp(0x217db6d3),
+    Fp(0x2e21c8d3),
+    Fp(0x606c5e6a),
+    Fp(0x75faa8f5),
+    Fp(0x3094fa7e),
+    Fp(0x21b00a43),
+    Fp(0x4bb30480),
+    Fp(0x498991be),
+    Fp(0x28b20b19),
+    Fp(0x5c3d4d5f),
+    Fp(0x0b992d6a),
+    Fp(0x2607ef95),
+    Fp(0x24188fae),
+    Fp(0x3bf8a077),
+    Fp(0x33f06cbf),
+    Fp(0x2cd92b31),
+    Fp(0x057d5b1b),
+    Fp(0x344f5403),
+    Fp(0x74b78582),
+    Fp(0x1f3d6a54),
+    Fp(0x5f1e1ae8),
+    Fp(0x6136a9a2),
+    Fp(0x470c5e78),
+    Fp(0x02ad2fca),
+    Fp(0x4cd3a6bb),
+    Fp(0x05b4f5c1),
+    Fp(0x20436bea),
+    Fp(0x1bed9ec7),
+    Fp(0x1f29cc65),
+    Fp(0x1417eaa3),
+    Fp(0x0ba88347),
+    Fp(0x0a65070c),
+    Fp(0x03217e79),
+    Fp(0x3e5f5253),
+    Fp(0x60bb876e),
+    Fp(0x320e4cec),
+    Fp(0x53b2c52e),
+    Fp(0x3e1d6eff),
+    Fp(0x687a3e07),
+    Fp(0x112d32bc),
+    Fp(0x5af9296f),
+    Fp(0x2cded641),
+    Fp(0x6ecc492b),
+    Fp(0x45bd2f2c),
+    Fp(0x5068b70b),
+    Fp(0x2563dc0f),
+    Fp(0x47e5ec52),
+    Fp(0x6e316d9b),
+    Fp(0x770839b4),
+    Fp(0x4fecdeaa),
+    Fp(0x20e2248a),
+    Fp(0x257fa6ed),
+    Fp(0x21abc9d3),
+    Fp(0x4aaae270),
+    Fp(0x17e21621),
+    Fp(0x70d18ab2),
+    Fp(0x4a23443e),
+    Fp(0x169492c3),
+    Fp(0x627575d6),
+    Fp(0x4bcf6471),
+    Fp(0x070d29e0),
+    Fp(0x75fbfe05),
+    Fp(0x08e3b575),
+    Fp(0x582caf96),
+    Fp(0x0ad360ea),
+    Fp(0x53e6e93d),
+    Fp(0x5ce70e60),
+    Fp(0x68b4d9c8),
+    Fp(0x665572ef),
+    Fp(0x02d7409a),
+    Fp(0x645cdaf7),
+    Fp(0x1a6ffc30),
+    Fp(0x1cdb2768),
+    Fp(0x681b647b),
+    Fp(0x2744d691),
+    Fp(0x51749894),
+    Fp(0x1fa88ff1),
+    Fp(0x3b11764e),
+    Fp(0x71fec668),
+    Fp(0x12ad4534),
+    Fp(0x51ce85bf),
+    Fp(0x2fa22845),
+    Fp(0x4279a18a),
+    Fp(0x6436c1c4),
+    Fp(0x00ac710b),
+    Fp(0x64aa70d4),
+    Fp(0x6cb3f64d),
+    Fp(0x73215a9e),
+    Fp(0x1e0d8c6a),
+    Fp(0x43cba3a4),
+    Fp(0x207f2305),
+    Fp(0x3b06e336),
+    Fp(0x3e1b5bb9),
+    Fp(0x02392281),
+    Fp(0x18b48bd7),
+    Fp(0x0ed07feb),
+    Fp(0x50fd2ce1),
+    Fp(0x27016dbd),
+    Fp(0x57328674),
+    Fp(0x474cb312),
+    Fp(0x77168659),
+    Fp(0x6adbaa3a),
+    Fp(0x6910fad6),
+    Fp(0x1443e8c4),
+    Fp(0x263c7e3e),
+    Fp(0x034b3bcb),
+    Fp(0x1bdfbbff),
+    Fp(0x0d60038e),
+    Fp(0x57283b50),
+    Fp(0x06989f09),
+    Fp(0x53e02e93),
+    Fp(0x0f36babc),
+    Fp(0x1d11c437),
+    Fp(0x34c59005),
+    Fp(0x0e84eb31),
+    Fp(0x76503e52),
+    Fp(0x5172219b),
+    Fp(0x187801e0),
+    Fp(0x484aead8),
+    Fp(0x27658b1c),
+    Fp(0x29264a97),
+    Fp(0x3dce14e1),
+    Fp(0x35996177),
+    Fp(0x5d5371b7),
+    Fp(0x6ec08900),
+    Fp(0x5d816715),
+    Fp(0x20cfbffa),
+    Fp(0x613f7c59),
+    Fp(0x0569fcff),
+    Fp(0x0761a9cb),
+    Fp(0x2cfbcf11),
+    Fp(0x085eea02),
+    Fp(0x4860f9f0),
+    Fp(0x4d205005),
+    Fp(0x57f328bd),
+    Fp(0x1a650f78),
+    Fp(0x46ce7839),
+    Fp(0x770a894b),
+    Fp(0x1c0e9cb6),
+    Fp(0x6e0ca41a),
+    Fp(0x0552da59),
+    Fp(0x594dec6b),
+    Fp(0x2aab23ed),
+    Fp(0x60c6eb54),
+    Fp(0x033206e3),
+    Fp(0x0c7a8237),
+    Fp(0x56a98019),
+    Fp(0x5479f680),
+    Fp(0x0c51522a),
+    Fp(0x35d2c421),
+    Fp(0x69a6d90b),
+    Fp(0x5e7946e4),
+    Fp(0x6b48ddca),
+    Fp(0x728e2313),
+    Fp(0x14dad2af),
+    Fp(0x3947f321),
+    Fp(0x67bd011f),
+    Fp(0x60c4f777),
+    Fp(0x472591d6),
+    Fp(0x32731626),
+    Fp(0x70a23d5b),
+    Fp(0x151b6c47),
+    Fp(0x6a67fb25),
+    Fp(0x2fe0c49e),
+    Fp(0x54f4561b),
+    Fp(0x15704ee4),
+    Fp(0x16f90bb9),
+    Fp(0x3be1690c),
+    Fp(0x76c1febb),
+    Fp(0x50a35164),
+    Fp(0x509717e0),
+    Fp(0x0355f8d3),
+    Fp(0x668309fd),
+    Fp(0x54efe9b0),
+    Fp(0x49578bc5),
+    Fp(0x02fa220b),
+    Fp(0x2699c296),
+    Fp(0x5b07ec6a),
+    Fp(0x3df439cb),
+    Fp(0x6fa4b39c),
+    Fp(0x09e690d7),
+    Fp(0x0bbce5c4),
+    Fp(0x6a106fd9),
+    Fp(0x1c13ac7f),
+    Fp(0x1347d8fe),
+    Fp(0x4f5e5b3e),
+    Fp(0x6b5802b7),
+    Fp(0x62d98682),
+    Fp(0x5a98822d),
+    Fp(0x5f84702e),
+    Fp(0x3126f240),
+    Fp(0x5a1aff1a),
+    Fp(0x77b04e66),
+    Fp(0x48c685c3),
+    Fp(0x355f8209),
+    Fp(0x2e739ce1),
+    Fp(0x1adf9544),
+    Fp(0x1b327099),
+    Fp(0x0ce1a5a0),
+    Fp(0x1129ee7e),
+    Fp(0x5b334254),
+    Fp(0x4d1d8f66),
+    Fp(0x23c29e16),
+    Fp(0x63ac7ba7),
+    Fp(0x513cb4e4),
+    Fp(0x52edd435),
+    Fp(0x4580818e),
+    Fp(0x55516239),
+    Fp(0x628cf778),
+    Fp(0x5eebf087),
+    Fp(0x15def2ec),
+    Fp(0x581ee8ce),
+    Fp(0x6b4345c5),
+    Fp(0x62ab4957),
+    Fp(0x4af89e0f),
+    Fp(0x4424f30c),
+    Fp(0x23ad1768),
+    Fp(0x665a9fd6),
+    Fp(0x1c0b5ae1),
+    Fp(0x65abddb8),
+    Fp(0x7039a4b0),
+    Fp(0x21c65f02),
+    Fp(0x539f7cdc),
+    Fp(0x05ad0446),
+    Fp(0x4aae4ae6),
+    Fp(0x258bc9e4),
+    Fp(0x12e4cd47),
+    Fp(0x5a8e8499),
+    Fp(0x21ccbdef),
+    Fp(0x1aee7336),
+    Fp(0x622564eb),
+    Fp(0x5e6d5bbc),
+    Fp(0x48c2117a),
+    Fp(0x418c1333),
+    Fp(0x6afb386a),
+    Fp(0x0c5b7812),
+    Fp(0x565753d9),
+    Fp(0x59a7ea4f),
+    Fp(0x37dd5860),
+    Fp(0x5bf14251),
+    Fp(0x56398160),
+    Fp(0x20cbf536),
+    Fp(0x1e49ee8c),
+    Fp(0x30e66c4d),
+    Fp(0x32357c88),
+    Fp(0x595c244d),
+    Fp(0x7464a56d),
+    Fp(0x3a31384e),
+    Fp(0x3d3273be),
+    Fp(0x14d339cb),
+    Fp(0x5044a65c),
+    Fp(0x1cd80ac0),
+    Fp(0x2d6ae173),
+    Fp(0x5bfd70f8),
+    Fp(0x05a66375),
+    Fp(0x29500b8c),
+    Fp(0x08d07343),
+    Fp(0x45bec74b),
+    Fp(0x045faaef),
+    Fp(0x6e763fd3),
+    Fp(0x51aa9c1a),
+    Fp(0x6acf4d51),
+    Fp(0x35b040a0),
+    Fp(0x73bf91ea),
+    Fp(0x3177f4ab),
+    Fp(0x579e48cc),
+    Fp(0x241608ab),
+    Fp(0x333e6a9e),
+    Fp(0x0d495ddb),
+    Fp(0x6987b141),
+    Fp(0x18feffde),
+    Fp(0x2c91e2c2),
+    Fp(0x5dd6bff0),
+    Fp(0x5ce61d67),
+    Fp(0x779eb8b2),
+    Fp(0x54826cbb),
+    Fp(0x3001ecb4),
+    Fp(0x154bcd9a),
+    Fp(0x3be72d2e),
+    Fp(0x5455873b),
+    Fp(0x09ab84d6),
+    Fp(0x438c6a8b),
+    Fp(0x45ffda0e),
+    Fp(0x1ce4c9c7),
+    Fp(0x35c8f2f0),
+    Fp(0x1dc40835),
+    Fp(0x756cc6f6),
+    Fp(0x0e146d08),
+    Fp(0x0ad9b699),
+    Fp(0x0541f6fa),
+    Fp(0x77b884d0),
+    Fp(0x17db8c98),
+    Fp(0x59c7d04e),
+    Fp(0x3e0eba95),
+    Fp(0x2a28a66e),
+    Fp(0x60566879),
+    Fp(0x1ae1e397),
+    Fp(0x331e0750),
+    Fp(0x38d0f91e),
+    Fp(0x52fcf20d),
+    Fp(0x4508cf06),
+    Fp(0x4826d030),
+    Fp(0x321f7db2),
+    Fp(0x022452ff),
+    Fp(0x375adcb4),
+    Fp(0x69b19019),
+    Fp(0x47442ecd),
+    Fp(0x0222de3d),
+    Fp(0x3b30e99a),
+    Fp(0x5b4c7207),
+    Fp(0x71d04e99),
+    Fp(0x1f02c78e),
+    Fp(0x73fc0cdc),
+    Fp(0x4bb751f9),
+    Fp(0x2474d1bc),
+    Fp(0x637b9b71),
+    Fp(0x4766ad4f),
+    Fp(0x33060411),
+    Fp(0x2168b8b4),
+    Fp(0x4f15816e),
+    Fp(0x6ddacf2b),
+    Fp(0x0057c6ad),
+    Fp(0x5bfb91a9),
+    Fp(0x6027e02f),
+    Fp(0x5d967a56),
+    Fp(0x139d4629),
+    Fp(0x41f6cc04),
+    Fp(0x240c7304),
+    Fp(0x131eba30),
+    Fp(0x0cd4affb),
+    Fp(0x72b01bef),
+    Fp(0x4ef7a632),
+    Fp(0x09478952),
+    Fp(0x6814a0f0),
+    Fp(0x72a61a71),
+    Fp(0x39bd9d86),
+    Fp(0x3ed1fbfc),
+    Fp(0x653afc45),
+    Fp(0x4c4ddb5d),
+    Fp(0x656b8199),
+    Fp(0x686caf27),
+    Fp(0x5caf0289),
+    Fp(0x20e047b0),
+    Fp(0x736702e2),
+    Fp(0x1b46d6ed),
+    Fp(0x64cd8fdd),
+    Fp(0x475354f5),
+    Fp(0x59f199b8),
+    Fp(0x32d5d81d),
+    Fp(0x2732f5f5),
+    Fp(0x09538182),
+    Fp(0x1c3ac6b0),
+    Fp(0x1f754ae0),
+    Fp(0x442a0cb1),
+    Fp(0x74ccefcd),
+    Fp(0x43013839),
+];
+
+// the MDS matrix, by rows
+pub const MDS: [[Fp; CELLS]; CELLS] = [
+    [
+        Fp(0x19191a7d),
+        Fp(0x6cbe083c),
+        Fp(0x0b589971),
+        Fp(0x3be4046a),
+        Fp(0x2c837497),
+        Fp(0x61c4fa1e),
+        Fp(0x3d45f6db),
+        Fp(0x679624bc),
+        Fp(0x5f59f3da),
+        Fp(0x62cda2fe),
+        Fp(0x567b01b2),
+        Fp(0x389daccb),
+        Fp(0x5522ba84),
+        Fp(0x71e3475e),
+        Fp(0x5f147d86),
+        Fp(0x1d7e0433),
+        Fp(0x4450fc40),
+        Fp(0x6a1d0eee),
+        Fp(0x258885f3),
+        Fp(0x0ec7eb27),
+        Fp(0x584ae718),
+        Fp(0x3e4a6f51),
+        Fp(0x0671c21b),
+        Fp(0x418f03e4),
+    ],
+    [
+        Fp(0x61a34efe),
+        Fp(0x593f2d08),
+        Fp(0x5a5599f9),
+        Fp(0x246030d1),
+        Fp(0x1210627a),
+        Fp(0x2e5a37f0),
+        Fp(0x23c9e547),
+        Fp(0x02071da9),
+        Fp(0x49852008),
+        Fp(0x6e9fae9f),
+        Fp(0x26d98222),
+        Fp(0x13538a35),
+        Fp(0x61216f13),
+        Fp(0x3552bef4),
+        Fp(0x0bbe8d33),
+        Fp(0x0bf02cff),
+        Fp(0x0991e1c3),
+        Fp(0x73dccd52),
+        Fp(0x4f5a8c64),
+        Fp(0x2a9f0e56),
+        Fp(0x60786ad6),
+        Fp(0x5ce8c0fe),
+        Fp(0x3a9d048a),
+        Fp(0x3e1e5cd2),
+    ],
+    [
+        Fp(0x1866d4b4),
+        Fp(0x2e2f9a97),
+        Fp(0x127340e3),
+        Fp(0x6faf4fa8),
+        Fp(0x75575b7f),
+        Fp(0x447bb0f8),
+        Fp(0x069efa67),
+        Fp(0x610a18ac),
+        Fp(0x0edf2bd3),
+        Fp(0x559135f8),
+        Fp(0x5b912991),
+        Fp(0x43f2c76f),
+        Fp(0x73cd6b24),
+        Fp(0x13c3df30),
+        Fp(0x10a57d3d),
+        Fp(0x01603b41),
+        Fp(0x6e04d184),
+        Fp(0x40cdb676),
+        Fp(0x0134bac6),
+        Fp(0x0b88cf23),
+        Fp(0x31e557c7),
+        Fp(0x5a7cba48),
+        Fp(0x58fbabe3),
+        Fp(0x1b03e09e),
+    ],
+    [
+        Fp(0x4dc75c69),
+        Fp(0x63f7f41b),
+        Fp(0x27c89e9e),
+        Fp(0x09c1b7ad),
+        Fp(0x004a124f),
+        Fp(0x691a8125),
+        Fp(0x6924edbd),
+        Fp(0x29cdb1aa),
+        Fp(0x56bf4b5c),
+        Fp(0x2703c2c7),
+        Fp(0x19107ae4),
+        Fp(0x30c37517),
+        Fp(0x253c03bd),
+        Fp(0x623725c0),
+        Fp(0x37054961),
+        Fp(0x48ae028b),
+        Fp(0x52d40d06),
+        Fp(0x3e7d0eb6),
+        Fp(0x1d40d7dd),
+        Fp(0x29f924ee),
+        Fp(0x37083357),
+        Fp(0x59c995bc),
+        Fp(0x501e3fe0),
+        Fp(0x19024c7d),
+    ],
+    [
+        Fp(0x04377f31),
+        Fp(0x0a4a0017),
+        Fp(0x1f17ba47),
+        Fp(0x54276fdf),
+        Fp(0x1ad767cd),
+        Fp(0x4c397b7d),
+        Fp(0x45dad135),
+        Fp(0x1166cd59),
+        Fp(0x10032dd4),
+        Fp(0x26250b6c),
+        Fp(0x52802c2e),
+        Fp(0x53f6862f),
+        Fp(0x73fe56cf),
+        Fp(0x1f33e178),
+        Fp(0x02c41117),
+        Fp(0x2ccc0227),
+        Fp(0x44f9db11),
+        Fp(0x439fa653),
+        Fp(0x08c1efb8),
+        Fp(0x0dee8126),
+        Fp(0x157974cf),
+        Fp(0x3ed232d6),
+        Fp(0x2f2ad7ef),
+        Fp(0x2b3a4348),
+    ],
+    [
+        Fp(0x24802d4b),
+        Fp(0x0e3bf555),
+        Fp(0x094764ce),
+        Fp(0x1e5558cd),
+        Fp(0x300bd275),
+        Fp(0x41c2f5b5),
+        Fp(0x5fd9db22),
+        Fp(0x5681e3e6),
+        Fp(0x5e17625b),
+        Fp(0x2990a7d2),
+        Fp(0x146901e4),
+        Fp(0x09b26110),
+        Fp(0x451759ad),
+        Fp(0x4eb06038),
+        Fp(0x61c9ed3c),
+        Fp(0x074e4cb7),
+        Fp(0x51b17024),
+        Fp(0x507e4bb3),
+        Fp(0x487cdc37),
+        Fp(0x629c4f2c),
+        Fp(0x28f8531b),
+        Fp(0x271f4983),
+        Fp(0x038a6d28),
+        Fp(0x2c0aacd8),
+    ],
+    [
+        Fp(0x15545583),
+        Fp(0x101a363d),
+        Fp(0x15126e8a),
+        Fp(0x4e1ed8a5),
+        Fp(0x53d4f4d1),
+        Fp(0x3d07b7b3),
+        Fp(0x073ea0d9),
+        Fp(0x60998306),
+        Fp(0x00ffb607),
+        Fp(0x3e71b599),
+        Fp(0x730ea91f),
+        Fp(0x5fe87f4b),
+        Fp(0x49510355),
+        Fp(0x2ce9fd2a),
+        Fp(0x0b9451b8),
+        Fp(0x319c006d),
+        Fp(0x669ab96c),
+        Fp(0x71ee9e09),
+        Fp(0x5dc07b5e),
+        Fp(0x4611ee0d),
+        Fp(0x5022239a),
+        Fp(0x5a3ca2a3),
+        Fp(0x3f6830ca),
+        Fp(0x4928eae1),
+    ],
+    [
+        Fp(0x284e8831),
+        Fp(0x1cdef1a6),
+        Fp(0x3797153a),
+        Fp(0x42f4735d),
+        Fp(0x38cce46e),
+        Fp(0x1ef113c2),
+        Fp(0x5af0700d),
+        Fp(0x69ef808a),
+        Fp(0x3b9a08a3),
+        Fp(0x6aae8b15),
+        Fp(0x1bca8baf),
+        Fp(0x43fe3d1a),
+        Fp(0x3f08abbc),
+        Fp(0x13f6e4fa),
+        Fp(0x72948221),
+        Fp(0x53bd5821),
+        Fp(0x76f6675e),
+        Fp(0x3b26093c),
+        Fp(0x6e4ead9f),
+        Fp(0x11cd8332),
+        Fp(0x7035588c),
+        Fp(0x2575bb3d),
+        Fp(0x51f93a72),
+        Fp(0x22dad1b9),
+    ],
+    [
+        Fp(0x1120f193),
+        Fp(0x06ff8b82),
+        Fp(0x54b835cf),
+        Fp(0x3dc53b9e),
+        Fp(0x4d181e81),
+        Fp(0x7218c243),
+        Fp(0x11231c2b),
+        Fp(0x062fdc55),
+        Fp(0x2e2ae16d),
+        Fp(0x16d13882),
+        Fp(0x46f856e3),
+        Fp(0x3827488e),
+        Fp(0x7737cf0a),
+        Fp(0x620a9582),
+        Fp(0x38a1cae9),
+        Fp(0x150b8797),
+        Fp(0x506bd8aa),
+        Fp(0x47d6929c),
+        Fp(0x3f9205fd),
+        Fp(0x164ecbbf),
+        Fp(0x23d5093c),
+        Fp(0x0e9fde93),
+        Fp(0x562a2e48),
+        Fp(0x713a8433),
+    ],
+    [
+        Fp(0x5bab57ce),
+        Fp(0x005075f9),
+        Fp(0x3c9d0507),
+        Fp(0x2d25f9cd),
+        Fp(0x4cdf7499),
+        Fp(0x694cac91),
+        Fp(0x65c21198),
+        Fp(0x329c7d0b),
+        Fp(0x021d84c3),
+        Fp(0x6a61dfb1),
+        Fp(0x4ab97480),
+        Fp(0x19f2d1bd),
+        Fp(0x35381b5b),
+        Fp(0x0da575ff),
+        Fp(0x08afd461),
+        Fp(0x0fade176),
+        Fp(0x5b815cc2),
+        Fp(0x644290b2),
+        Fp(0x4ef463e7),
+        Fp(0x6ea5993e),
+        Fp(0x61b7b10b),
+        Fp(0x7081130d),
+        Fp(0x3d683910),
+        Fp(0x6860542b),
+    ],
+    [
+        Fp(0x1d7214bd),
+        Fp(0x367bf2b0),
+        Fp(0x48b48241),
+        Fp(0x1017b7be),
+        Fp(0x059453ab),
+        Fp(0x277337ec),
+        Fp(0x56277c87),
+        Fp(0x677bb976),
+        Fp(0x24190c2f),
+        Fp(0x2c717e0b),
+        Fp(0x59e2a73d),
+        Fp(0x4bf46e75),
+        Fp(0x270ebff5),
+        Fp(0x2e9a07a6),
+        Fp(0x47b4f2cc),
+        Fp(0x54d12ed3),
+        Fp(0x2bb16505),
+        Fp(0x745c060a),
+        Fp(0x3e0a397e),
+        Fp(0x2c5d842a),
+        Fp(0x03034fd5),
+        Fp(0x38cef2b1),
+        Fp(0x31927212),
+        Fp(0x423c33ef),
+    ],
+    [
+        Fp(0x4e7dfc7a),
+        Fp(0x037378f9),
+        Fp(0x1881ed25),
+        Fp(0x6af37772),
+        Fp(0x18ce1693),
+        Fp(0x4626f02f),
+        Fp(0x70732dcd),
+        Fp(0x0942f572),
+        Fp(0x65df735e),
+        Fp(0x5ad72db4),
+        Fp(0x699fe9cc),
+        Fp(0x35ab9cd7),
+        Fp(0x758a62eb),
+        Fp(0x0ae5ff35),
+        Fp(0x342e0c42),
+        Fp(0x1d3f9a7e),
+        Fp(0x2feedf99),
+        Fp(0x55e3734c),
+        Fp(0x56d75a3b),
+        Fp(0x040dfb91),
+        Fp(0x0361ad6c),
+        Fp(0x5a6aeb5d),
+        Fp(0x0de01972),
+        Fp(0x33328e03),
+    ],
+    [
+        Fp(0x13501f54),
+        Fp(0x204885fb),
+        Fp(0x43c85d92),
+        Fp(0x4a97c740),
+        Fp(0x424beb1b),
+        Fp(0x2012116a),
+        Fp(0x49694aa2),
+        Fp(0x247703cc),
+        Fp(0x4ba4bc45),
+        Fp(0x132cdb95),
+        Fp(0x3cafc24a),
+        Fp(0x241870e3),
+        Fp(0x1e582d31),
+        Fp(0x27ba0403),
+        Fp(0x1aa8fff0),
+        Fp(0x6eedc92c),
+        Fp(0x35d4decf),
+        Fp(0x2e722b3f),
+        Fp(0x36358015),
+        Fp(0x062d8da5),
+        Fp(0x4a0ff259),
+        Fp(0x41c55848),
+        Fp(0x67e4ea95),
+        Fp(0x40f69a47),
+    ],
+    [
+        Fp(0x0bba6386),
+        Fp(0x6f7efbff),
+        Fp(0x3d8fed85),
+        Fp(0x264b3587),
+        Fp(0x2c1d569b),
+        Fp(0x04b218e8),
+        Fp(0x3a939d97),
+        Fp(0x6b3a6c39),
+        Fp(0x29c25fb7),
+        Fp(0x6ffd0c02),
+        Fp(0x5e38ab0e),
+        Fp(0x0e3e636a),
+        Fp(0x324551bd),
+        Fp(0x19ac1d52),
+        Fp(0x6389c4b1),
+        Fp(0x15c77366),
+        Fp(0x3b746f85),
+        Fp(0x5e1eea00),
+        Fp(0x732b79a2),
+        Fp(0x73e81e4c),
+        Fp(0x2172727c),
+        Fp(0x58e8098c),
+        Fp(0x46c6e6c2),
+        Fp(0x07e9d643),
+    ],
+    [
+        Fp(0x5eca9a37),
+        Fp(0x304d75f1),
+        Fp(0x2294c0bd),
+        Fp(0x3d7ab743),
+        Fp(0x2636b5d5),
+        Fp(0x0f890758),
+        Fp(0x0afa5a3d),
+        Fp(0x41e4665b),
+        Fp(0x0b4c63fc),
+        Fp(0x1112be28),
+        Fp(0x0e9162b1),
+        Fp(0x6a00fcaa),
+        Fp(0x6cac103b),
+        Fp(0x153d5d75),
+        Fp(0x05ac5be5),
+        Fp(0x06519cce),
+        Fp(0x66941f1c),
+        Fp(0x1abeed57),
+        Fp(0x05f3b38b),
+        Fp(0x2dbe8560),
+        Fp(0x28c7ffb3),
+        Fp(0x06eed76a),
+        Fp(0x5404eed2),
+        Fp(0x28464e68),
+    ],
+    [
+        Fp(0x6937aa7d),
+        Fp(0x20cd555a),
+        Fp(0x41d20da1),
+        Fp(0x163d4a11),
+        Fp(0x4da2443d),
+        Fp(0x08c6343c),
+        Fp(0x5ec6eb45),
+        Fp(0x5e1b7c27),
+        Fp(0x29c7fd8e),
+        Fp(0x268f1b7f),
+        Fp(0x014ab25d),
+        Fp(0x1892edb1),
+        Fp(0x413222ca),
+        Fp(0x548a31a0),
+        Fp(0x765d9087),
+        Fp(0x64de0f9a),
+        Fp(0x3d0e6471),
+        Fp(0x7513a7ae),
+        Fp(0x3112d543),
+        Fp(0x2375ab22),
+        Fp(0x270250d0),
+        Fp(0x025b8b09),
+        Fp(0x574ca016),
+        Fp(0x02e4bb01),
+    ],
+    [
+        Fp(0x41786ccf),
+        Fp(0x41f0df6c),
+        Fp(0x43107975),
+        Fp(0x11ca0a05),
+        Fp(0x4b3d6afe),
+        Fp(0x5ad09060),
+        Fp(0x3da8de0a),
+        Fp(0x1573ad5b),
+        Fp(0x32384d13),
+        Fp(0x2e2cc2a4),
+        Fp(0x257be6c0),
+        Fp(0x246dd39b),
+        Fp(0x1d43bce2),
+        Fp(0x399670ed),
+        Fp(0x1c703658),
+        Fp(0x042b4e65),
+        Fp(0x2fcd523a),
+        Fp(0x58d576a5),
+        Fp(0x3f70e99e),
+        Fp(0x2c08c7e5),
+        Fp(0x048463f2),
+        Fp(0x30c41673),
+        Fp(0x327e05f6),
+        Fp(0x13476bbf),
+    ],
+    [
+        Fp(0x14971335),
+        Fp(0x2a9db91a),
+        Fp(0x48ceae5d),
+        Fp(0x0eab20e2),
+        Fp(0x627c3e30),
+        Fp(0x55697057),
+        Fp(0x0ab66b04),
+        Fp(0x51da9659),
+        Fp(0x4e7e6fba),
+        Fp(0x5c911a08),
+        Fp(0x093289b9),
+        Fp(0x5a95b9d6),
+        Fp(0x47a94672),
+        Fp(0x2b251d75),
+        Fp(0x334521f0),
+        Fp(0x32c685fb),
+        Fp(0x3431a4c5),
+        Fp(0x3a5c7f94),
+        Fp(0x319109af),
+        Fp(0x4c921429),
+        Fp(0x5dd16726),
+        Fp(0x60ea6e89),
+        Fp(0x47dc57e2),
+        Fp(0x2705784a),
+    ],
+    [
+        Fp(0x2eadb949),
+        Fp(0x6152ba98),
+        Fp(0x17e543ad),
+        Fp(0x1e65633a),
+        Fp(0x162f1877),
+        Fp(0x4b07c2b4),
+        Fp(0x5fc3caef),
+        Fp(0x45357bef),
+        Fp(0x65694da5),
+        Fp(0x4f35206b),
+        Fp(0x099d6860),
+        Fp(0x02ce0797),
+        Fp(0x7032c8f9),
+        Fp(0x21eb00e8),
+        Fp(0x5cb8810d),
+        Fp(0x1ae59be7),
+        Fp(0x48df9450),
+        Fp(0x620314f3),
+        Fp(0x37539abb),
+        Fp(0x477b9153),
+        Fp(0x72d3c7be),
+        Fp(0x6ca27c72),
+        Fp(0x767a7651),
+        Fp(0x191a449a),
+    ],
+    [
+        Fp(0x64e2a38c),
+        Fp(0x280cc6af),
+        Fp(0x28bf710a),
+        Fp(0x76bfe2c0),
+        Fp(0x40ed3108),
+        Fp(0x3beabed2),
+        Fp(0x18cfa693),
+        Fp(0x3672bd08),
+        Fp(0x3d0b66fb),
+        Fp(0x59af6345),
+        Fp(0x76d6cf74),
+        Fp(0x0b4f4043),
+        Fp(0x082538a9),
+        Fp(0x5680b41f),
+        Fp(0x73d88c5a),
+        Fp(0x1a038c65),
+        Fp(0x25b16b46),
+        Fp(0x40266a1f),
+        Fp(0x401fa170),
+        Fp(0x2c44ad35),
+        Fp(0x3c6e516d),
+        Fp(0x2d5ca9b3),
+        Fp(0x485ebe47),
+        Fp(0x3cbad540),
+    ],
+    [
+        Fp(0x1a317281),
+        Fp(0x03e74eb2),
+        Fp(0x1569d934),
+        Fp(0x0494659c),
+        Fp(0x034f41c3),
+        Fp(0x00b2f8a2),
+        Fp(0x5d7e821b),
+        Fp(0x2bb1d834),
+        Fp(0x06b36470),
+        Fp(0x2a42f149),
+        Fp(0x73e9fe13),
+        Fp(0x0d401689),
+        Fp(0x2f0b0fdc),
+        Fp(0x6cb6443c),
+        Fp(0x670e3987),
+        Fp(0x410c9579),
+        Fp(0x4b8fa592),
+        Fp(0x67d14ddc),
+        Fp(0x58c63aae),
+        Fp(0x33f6ac7b),
+        Fp(0x62496614),
+        Fp(0x5913b1ec),
+        Fp(0x338ed582),
+        Fp(0x4d4f4f02),
+    ],
+    [
+        Fp(0x19dfa11b),
+        Fp(0x005524e2),
+        Fp(0x5f7574d0),
+        Fp(0x5c5b3588),
+        Fp(0x0ee563ab),
+        Fp(0x05930821),
+        Fp(0x4c4c668e),
+        Fp(0x5a02d1c5),
+        Fp(0x33e4965c),
+        Fp(0x5b79d5c0),
+        Fp(0x730ca318),
+        Fp(0x2ce70f7b),
+        Fp(0x65611461),
+        Fp(0x6af41f50),
+        Fp(0x3a851c9d),
+        Fp(0x38225964),
+        Fp(0x45f49cd7),
+        Fp(0x0516405e),
+        Fp(0x63d9dbad),
+        Fp(0x4fbce612),
+        Fp(0x1f30cbb0),
+        Fp(0x47320ea7),
+        Fp(0x5c5a1db7),
+        Fp(0x344524d6),
+    ],
+    [
+        Fp(0x305f18aa),
+        Fp(0x4fefce1d),
+        Fp(0x2dd60db5),
+        Fp(0x37e7edb0),
+        Fp(0x33590038),
+        Fp(0x5ba76f30),
+        Fp(0x370a8395),
+        Fp(0x29114d66),
+        Fp(0x0ca70423),
+        Fp(0x64591365),
+        Fp(0x384f8781),
+        Fp(0x300190ae),
+        Fp(0x32abb521),
+        Fp(0x399cb85f),
+        Fp(0x5bb899ef),
+        Fp(0x68e000f3),
+        Fp(0x1ab3e268),
+        Fp(0x5cf5235f),
+        Fp(0x2b567559),
+        Fp(0x02e26480),
+        Fp(0x5df459ca),
+        Fp(0x2f379c3e),
+        Fp(0x5db69b04),
+        Fp(0x565b8f83),
+    ],
+    [
+        Fp(0x137f7ff7),
+        Fp(0x2dd419c1),
+        Fp(0x4c4af6e7),
+        Fp(0x49e58f6c),
+        Fp(0x16263dba),
+        Fp(0x694a7845),
+        Fp(0x01840406),
+        Fp(0x1f89c640),
+        Fp(0x1cd737aa),
+        Fp(0x58881e16),
+        Fp(0x462928d6),
+        Fp(0x2490dea3),
+        Fp(0x0bf92538),
+        Fp(0x37607a3d),
+        Fp(0x60321d26),
+        Fp(0x34118d9e),
+        Fp(0x1b9743bb),
+        Fp(0x018b4aad),
+        Fp(0x362d15da),
+        Fp(0x4cbb87c1),
+        Fp(0x384ae3c2),
+        Fp(0x48ce88f7),
+        Fp(0x27a25e4f),
+        Fp(0x47ef48c5),
+    ],
+];
@@ -0,0 +1,315 @@
+mod consts;
+
+pub use crate::math::poseidon::consts::{
+    CELLS, MDS, ROUNDS, ROUNDS_HALF_FULL, ROUNDS_PARTIAL, ROUND_CONSTANTS,
+};
+use crate::math::Fp;
+
+/// The number of cells that a single load or store moves, a third of the state.
+pub const GROUP_CELLS: usize = 8;
+
+// the rounds that a `POSEIDON_FULL` row runs
+const ROUNDS_PER_FULL: usize = 2;
+
+pub fn sbox(x: &Fp) -> Fp {
+    let x2 = x * x;
+    let x4 = &x2 * &x2;
+    let x6 = &x4 * &x2;
+    &x6 * x
+}
+
+pub fn add_round_constants(cells: &mut [Fp; CELLS], round: usize) {
+    for (cell, c) in cells
+        .iter_mut()
+        .zip(ROUND_CONSTANTS[round * CELLS..(round + 1) * CELLS].iter())
+    {
+        *cell = &*cell + c;
+    }
+}
+
+pub fn multiply_by_mds(cells: &mut [Fp; CELLS]) {
+    let old_cells = cells.clone();
+    for (cell, row) in cells.iter_mut().zip(MDS.iter()) {
+        *cell = row
+            .iter()
+            .zip(old_cells.iter())
+            .fold(Fp::ZERO, |acc, (m, x)| acc + m * x);
+    }
+}
+
+pub fn full_round(cells: &mut [Fp; CELLS], round: usize) {
+    add_round_constants(cells, round);
+    for cell in cells.iter_mut() {
+        *cell = sbox(cell);
+    }
+    multiply_by_mds(cells);
+}
+
+pub fn partial_round(cells: &mut [Fp; CELLS], round: usize) {
+    add_round_constants(cells, round);
+    cells[0] = sbox(&cells[0]);
+    multiply_by_mds(cells);
+}
+
+/// The whole Poseidon permutation over BabyBear with a width of 24, with 8 full and 21 partial
+/// rounds and an S-box of `x^7`.
+pub fn poseidon_mix(cells: &mut [Fp; CELLS]) {
+    for round in 0..ROUNDS {
+        if (ROUNDS_HALF_FULL..ROUNDS_HALF_FULL + ROUNDS_PARTIAL).contains(&round) {
+            partial_round(cells, round);
+        } else {
+            full_round(cells, round);
+        }
+    }
+}
+
+/// The Poseidon state of the recursion machine, driven by its load, round and store instructions.
+///
+/// The machine splits each round differently from `full_round`: the constants of a round are
+/// added at the end of the round before it, and those of the first round at the load that sets
+/// `add_consts`. A load that sets `add_consts`, two `full`, one `partial`, and two more `full`
+/// therefore compute `poseidon_mix`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Poseidon {
+    pub cells: [Fp; CELLS],
+    round: usize,
+}
+
+impl Poseidon {
+    /// Add `values` into the cells of `group`, after clearing the whole state unless `add` is set,
+    /// as `POSEIDON_ADD_LOAD` does. A load starts a new permutation.
+    pub fn load(
+        &mut self,
+        group: usize,
+        values: [Fp; GROUP_CELLS],
+        add: bool,
+        add_consts: bool,
+        from_montgomery: bool,
+    ) {
+        if !add {
+            self.cells = Default::default();
+        }
+        for (cell, value) in self.cells[group * GROUP_CELLS..(group + 1) * GROUP_CELLS]
+            .iter_mut()
+            .zip(values)
+        {
+            let value = if from_montgomery {
+                value * super::FROM_MONTGOMERY
+            } else {
+                value
+            };
+            *cell = &*cell + value;
+        }
+        if add_consts {
+            add_round_constants(&mut self.cells, 0);
+        }
+        self.round = 0;
+    }
+
+    /// The next round to run, `ROUNDS` once the permutation is complete.
+    pub fn round(&self) -> usize {
+        self.round
+    }
+
+    // one round as the machine runs it, with the constants of the next round added at the end
+    fn step(&mut self, partial: bool) {
+        if partial {
+            self.cells[0] = sbox(&self.cells[0]);
+        } else {
+            for cell in self.cells.iter_mut() {
+                *cell = sbox(cell);
+            }
+        }
+        multiply_by_mds(&mut self.cells);
+        self.round += 1;
+        if self.round < ROUNDS {
+            add_round_constants(&mut self.cells, self.round);
+        }
+    }
+
+    fn in_full_rounds(&self) -> bool {
+        self.round + ROUNDS_PER_FULL <= ROUNDS_HALF_FULL
+            || (self.round >= ROUNDS_HALF_FULL + ROUNDS_PARTIAL
+                && self.round + ROUNDS_PER_FULL <= ROUNDS)
+    }
+
+    /// Run the next two full rounds, as `POSEIDON_FULL` does. Returns `false` and leaves the
+    /// state unchanged if the next rounds are not full rounds.
+    pub fn full(&mut self) -> bool {
+        self.full_with(|_| {})
+    }
+
+    /// Like `full`, calling `on_round` with the state after each round.
+    pub fn full_with(&mut self, mut on_round: impl FnMut(&[Fp; CELLS])) -> bool {
+        if !self.in_full_rounds() {
+            return false;
+        }
+        for _ in 0..ROUNDS_PER_FULL {
+            self.step(false);
+            on_round(&self.cells);
+        }
+        true
+    }
+
+    /// Run all the partial rounds, as `POSEIDON_PARTIAL` does. Returns `false` and leaves the
+    /// state unchanged if the full rounds before them have not all run.
+    pub fn partial(&mut self) -> bool {
+        self.partial_with(|_| {})
+    }
+
+    /// Like `partial`, calling `on_round` with the state after each round.
+    pub fn partial_with(&mut self, mut on_round: impl FnMut(&[Fp; CELLS])) -> bool {
+        if self.round != ROUNDS_HALF_FULL {
+            return false;
+        }
+        for _ in 0..ROUNDS_PARTIAL {
+            self.step(true);
+            on_round(&self.cells);
+        }
+        true
+    }
+
+    /// Run the rounds that are left of the permutation.
+    pub fn permute(&mut self) {
+        self.permute_with(|_| {})
+    }
+
+    /// Like `permute`, calling `on_round` with the state after each round.
+    pub fn permute_with(&mut self, mut on_round: impl FnMut(&[Fp; CELLS])) {
+        while self.round < ROUNDS {
+            let round = self.round;
+            self.step((ROUNDS_HALF_FULL..ROUNDS_HALF_FULL + ROUNDS_PARTIAL).contains(&round));
+            on_round(&self.cells);
+        }
+    }
+
+    /// The cells of `group`, as `POSEIDON_STORE` writes them.
+    pub fn store(&self, group: usize, to_montgomery: bool) -> [Fp; GROUP_CELLS] {
+        let mut out: [Fp; GROUP_CELLS] = Default::default();
+        for (o, cell) in out
+            .iter_mut()
+            .zip(self.cells[group * GROUP_CELLS..(group + 1) * GROUP_CELLS].iter())
+        {
+            *o = if to_montgomery {
+                cell * super::TO_MONTGOMERY
+            } else {
+                cell.clone()
+            };
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::math::P;
+
+    /// The Grain LFSR that generates the constants of Poseidon and Poseidon2 over BabyBear, as
+    /// in `generate_parameters_grain.sage` of the Poseidon reference implementation.
+    pub(crate) struct Grain {
+        bits: std::collections::VecDeque<bool>,
+    }
+
+    impl Grain {
+        pub(crate) fn new(sbox: u32, rounds_full: u32, rounds_partial: u32) -> Self {
+            let mut bits = std::collections::VecDeque::new();
+            for (value, width) in [
+                (1, 2),
+                (sbox, 4),
+                (31, 12),
+                (CELLS as u32, 12),
+                (rounds_full, 10),
+                (rounds_partial, 10),
+                ((1 << 30) - 1, 30),
+            ] {
+                bits.extend((0..width).rev().map(|i| (value >> i) & 1 == 1));
+            }
+            let mut grain = Self { bits };
+            for _ in 0..160 {
+                grain.step();
+            }
+            grain
+        }
+
+        fn step(&mut self) -> bool {
+            let b = &self.bits;
+            let bit = b[62] ^ b[51] ^ b[38] ^ b[23] ^ b[13] ^ b[0];
+            self.bits.pop_front();
+            self.bits.push_back(bit);
+            bit
+        }
+
+        // the bits are taken in pairs, and the second of a pair is kept when the first is set
+        fn next_bit(&mut self) -> bool {
+            loop {
+                let keep = self.step();
+                let bit = self.step();
+                if keep {
+                    return bit;
+                }
+            }
+        }
+
+        pub(crate) fn next_u31(&mut self) -> u32 {
+            (0..31).fold(0, |acc, _| (acc << 1) | self.next_bit() as u32)
+        }
+
+        /// The next field element, drawing again while the bits are not below `P`.
+        pub(crate) fn next_fp(&mut self) -> Fp {
+            loop {
+                let v = self.next_u31();
+                if v < P {
+                    return Fp(v);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn round_constants_and_mds() {
+        let mut grain = Grain::new(1, 8, 21);
+        for (i, c) in ROUND_CONSTANTS.iter().enumerate() {
+            assert_eq!(grain.next_fp(), *c, "round constant {}", i);
+        }
+
+        // a Cauchy matrix, 1 / (x_i + y_j)
+        let xs: Vec<Fp> = (0..CELLS).map(|_| Fp(grain.next_u31() % P)).collect();
+        let ys: Vec<Fp> = (0..CELLS).map(|_| Fp(grain.next_u31() % P)).collect();
+        for (i, row) in MDS.iter().enumerate() {
+            for (j, m) in row.iter().enumerate() {
+                assert_eq!(*m, (&xs[i] + &ys[j]).inv(), "MDS[{}][{}]", i, j);
+            }
+        }
+    }
+
+    fn state(seed: u32) -> [Fp; CELLS] {
+        std::array::from_fn(|i| {
+            Fp::new(seed.wrapping_mul(0x9e3779b9) ^ (i as u32).wrapping_mul(0x85ebca6b))
+        })
+    }
+
+    #[test]
+    fn machine_rows_compute_the_permutation() {
+        for seed in 0..4 {
+            let mut cells = state(seed);
+            poseidon_mix(&mut cells);
+
+            let mut machine = Poseidon::default();
+            for group in 0..3 {
+                let values = state(seed)[group * GROUP_CELLS..(group + 1) * GROUP_CELLS]
+                    .to_vec()
+                    .try_into()
+                    .unwrap();
+                machine.load(group, values, group != 0, group == 2, false);
+            }
+            assert!(machine.full() && machine.full());
+            assert!(!machine.full());
+            assert!(machine.partial());
+            assert!(machine.full() && machine.full());
+            assert!(!machine.full());
+            assert_eq!(machine.round(), ROUNDS);
+            assert_eq!(machine.cells, cells);
+        }
+    }
+}
@@ -0,0 +1,737 @@
+use crate::math::Fp;
+
+pub const CELLS: usize = 24;
+
+pub const ROUNDS_HALF_FULL: usize = 4;
+
+pub const ROUNDS_PARTIAL: usize = 21;
+
+pub const ROUNDS: usize = 2 * ROUNDS_HALF_FULL + ROUNDS_PARTIAL;
+
+// `CELLS` constants per round, of which a partial round only uses the first
+pub const ROUND_CONSTANTS: [Fp; ROUNDS * CELLS] = [
+    Fp(0x0fa20c37),
+    Fp(0x0795bb97),
+    Fp(0x12c60b9c),
+    Fp(0x0eabd88e),
+    Fp(0x096485ca),
+    Fp(0x07093527),
+    Fp(0x1b1d4e50),
+    Fp(0x30a01ace),
+    Fp(0x3bd86f5a),
+    Fp(0x69af7c28),
+    Fp(0x3f94775f),
+    Fp(0x731560e8),
+    Fp(0x465a0ecd),
+    Fp(0x574ef807),
+    Fp(0x62fd4870),
+    Fp(0x52ccfe44),
+    Fp(0x14772b14),
+    Fp(0x4dedf371),
+    Fp(0x260acd7c),
+    Fp(0x1f51dc58),
+    Fp(0x75125532),
+    Fp(0x686a4d7b),
+    Fp(0x54bac179),
+    Fp(0x31947706),
+    Fp(0x29799d3b),
+    Fp(0x6e01ae90),
+    Fp(0x203a7a64),
+    Fp(0x4f7e25be),
+    Fp(0x72503f77),
+    Fp(0x45bd3b69),
+    Fp(0x769bd6b4),
+    Fp(0x5a867f08),
+    Fp(0x4fdba082),
+    Fp(0x251c4318),
+    Fp(0x28f06201),
+    Fp(0x6788c43a),
+    Fp(0x4c6d6a99),
+    Fp(0x357784a8),
+    Fp(0x2abaf051),
+    Fp(0x770f7de6),
+    Fp(0x1794b784),
+    Fp(0x4796c57a),
+    Fp(0x724b7a10),
+    Fp(0x449989a7),
+    Fp(0x64935cf1),
+    Fp(0x59e14aac),
+    Fp(0x0e620bb8),
+    Fp(0x3af5a33b),
+    Fp(0x4465cc0e),
+    Fp(0x019df68f),
+    Fp(0x4af8d068),
+    Fp(0x08784f82),
+    Fp(0x0cefdeae),
+    Fp(0x6337a467),
+    Fp(0x32fa7a16),
+    Fp(0x486f62d6),
+    Fp(0x386a7480),
+    Fp(0x20f17c4a),
+    Fp(0x54e50da8),
+    Fp(0x2012cf03),
+    Fp(0x5fe52950),
+    Fp(0x09afb6cd),
+    Fp(0x2523044e),
+    Fp(0x5c54d0ef),
+    Fp(0x71c01f3c),
+    Fp(0x60b2c4fb),
+    Fp(0x4050b379),
+    Fp(0x5e6a70a5),
+    Fp(0x418543f5),
+    Fp(0x71debe56),
+    Fp(0x1aad2994),
+    Fp(0x3368a483),
+    Fp(0x07a86f3a),
+    Fp(0x5ea43ff1),
+    Fp(0x2443780e),
+    Fp(0x4ce444f7),
+    Fp(0x146f9882),
+    Fp(0x3132b089),
+    Fp(0x197ea856),
+    Fp(0x667030c3),
+    Fp(0x2317d5dc),
+    Fp(0x0c2c48a7),
+    Fp(0x56b2df66),
+    Fp(0x67bd81e9),
+    Fp(0x4fcdfb19),
+    Fp(0x4baaef32),
+    Fp(0x0328d30a),
+    Fp(0x6235760d),
+    Fp(0x12432912),
+    Fp(0x0a49e258),
+    Fp(0x030e1b70),
+    Fp(0x48caeb03),
+    Fp(0x49e4d9e9),
+    Fp(0x1051b5c6),
+    Fp(0x6a36dbbe),
+    Fp(0x4cff27a5),
+    Fp(0x1da78ec2),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x730b0924),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x3eb56cf3),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x5bd93073),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x37204c97),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x51642d89),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x66e943e8),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x1a3e72de),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x70beb1e9),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x30ff3b3f),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x4240d1c4),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x12647b8d),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x65d86965),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x49ef4d7c),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x47785697),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x46b3969f),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x5c7b7a0e),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x7078fc60),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x4f22d482),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x482a9aee),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x6beb839d),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x00000000),
+    Fp(0x032959ad),
+    Fp(0x2b18af6a),
+    Fp(0x55d3dc8c),
+    Fp(0x43bd26c8),
+    Fp(0x0c41595f),
+    Fp(0x7048d2e2),
+    Fp(0x00db8983),
+    Fp(0x2af563d7),
+    Fp(0x6e84758f),
+    Fp(0x611d64e1),
+    Fp(0x1f9977e2),
+    Fp(0x64163a0a),
+    Fp(0x5c5fc27b),
+    Fp(0x02e22561),
+    Fp(0x3a2d75db),
+    Fp(0x1ba7b71a),
+    Fp(0x34343f64),
+    Fp(0x7406b35d),
+    Fp(0x19df8299),
+    Fp(0x6ff4480a),
+    Fp(0x514a81c8),
+    Fp(0x57ab52ce),
+    Fp(0x6ad69f52),
+    Fp(0x3e0c0e0d),
+    Fp(0x48126114),
+    Fp(0x2a9d62cc),
+    Fp(0x17441f23),
+    Fp(0x485762bb),
+    Fp(0x2f218674),
+    Fp(0x06fdc64a),
+    Fp(0x0861b7f2),
+    Fp(0x3b36eee6),
+    Fp(0x70a11040),
+    Fp(0x04b31737),
+    Fp(0x3722a872),
+    Fp(0x2a351c63),
+    Fp(0x623560dc),
+    Fp(0x62584ab2),
+    Fp(0x382c7c04),
+    Fp(0x3bf9edc7),
+    Fp(0x0e38fe51),
+    Fp(0x376f3b10),
+    Fp(0x5381e178),
+    Fp(0x3afc61c7),
+    Fp(0x5c1bcb4d),
+    Fp(0x6643ce1f),
+    Fp(0x2d0af1c1),
+    Fp(0x08f583cc),
+    Fp(0x5d6ff60f),
+    Fp(0x6324c1e5),
+    Fp(0x74412fb7),
+    Fp(0x70c0192e),
+    Fp(0x0b72f141),
+    Fp(0x4067a111),
+    Fp(0x57388c4f),
+    Fp(0x351009ec),
+    Fp(0x0974c159),
+    Fp(0x539a58b3),
+    Fp(0x038c0cff),
+    Fp(0x476c0392),
+    Fp(0x3f7bc15f),
+    Fp(0x4491dd2c),
+    Fp(0x4d1fef55),
+    Fp(0x04936ae3),
+    Fp(0x58214dd4),
+    Fp(0x683c6aad),
+    Fp(0x1b42f16b),
+    Fp(0x6dc79135),
+    Fp(0x2d4e71ec),
+    Fp(0x3e2946ea),
+    Fp(0x59dce8db),
+    Fp(0x6cee892a),
+    Fp(0x47f07350),
+    Fp(0x7106ce93),
+    Fp(0x3bd4a7a9),
+    Fp(0x2bfe636a),
+    Fp(0x430011e9),
+    Fp(0x001cd66a),
+    Fp(0x307faf5b),
+    Fp(0x0d9ef3fe),
+    Fp(0x6d40043a),
+    Fp(0x2e8f470c),
+    Fp(0x1b6865e8),
+    Fp(0x0c0e6c01),
+    Fp(0x4d41981f),
+    Fp(0x423b9d3d),
+    Fp(0x410408cc),
+    Fp(0x263f0884),
+    Fp(0x5311bbd0),
+    Fp(0x4dae58d8),
+    Fp(0x30401cea),
+    Fp(0x09afa575),
+    Fp(0x4b3d5b42),
+    Fp(0x63ac0b37),
+    Fp(0x5fe5bb14),
+    Fp(0x5244e9d4),
+];
+
+// the diagonal of the internal matrix, less the all-one matrix
+pub const M_INT_DIAG_HZN: [Fp; CELLS] = [
+    Fp(0x409133f0),
+    Fp(0x1667a8a1),
+    Fp(0x06a6c7b6),
+    Fp(0x6f53160e),
+    Fp(0x273b11d1),
+    Fp(0x03176c5d),
+    Fp(0x72f9bbf9),
+    Fp(0x73ceba91),
+    Fp(0x5cdef81d),
+    Fp(0x01393285),
+    Fp(0x46daee06),
+    Fp(0x065d7ba6),
+    Fp(0x52d72d6f),
+    Fp(0x05dd05e0),
+    Fp(0x3bab4b63),
+    Fp(0x6ada3842),
+    Fp(0x2fc5fbec),
+    Fp(0x770d61b0),
+    Fp(0x5715aae9),
+    Fp(0x03ef0e90),
+    Fp(0x75b6c770),
+    Fp(0x242adf5f),
+    Fp(0x00d0ca4c),
+    Fp(0x36c0e388),
+];
@@ -0,0 +1,329 @@
+mod consts;
+
+pub use crate::math::poseidon::{sbox, GROUP_CELLS};
+pub use crate::math::poseidon2::consts::{
+    CELLS, M_INT_DIAG_HZN, ROUNDS, ROUNDS_HALF_FULL, ROUNDS_PARTIAL, ROUND_CONSTANTS,
+};
+use crate::math::Fp;
+
+fn add_round_constants_full(cells: &mut [Fp; CELLS], round: usize) {
+    for (cell, c) in cells
+        .iter_mut()
+        .zip(ROUND_CONSTANTS[round * CELLS..(round + 1) * CELLS].iter())
+    {
+        *cell = &*cell + c;
+    }
+}
+
+fn add_round_constants_partial(cells: &mut [Fp; CELLS], round: usize) {
+    cells[0] = &cells[0] + &ROUND_CONSTANTS[round * CELLS];
+}
+
+pub fn multiply_by_m_int(cells: &mut [Fp; CELLS]) {
+    let sum = cells.iter().fold(Fp::ZERO, |acc, x| acc + x.clone());
+    for (cell, d) in cells.iter_mut().zip(M_INT_DIAG_HZN.iter()) {
+        *cell = &sum + d * &*cell;
+    }
+}
+
+fn multiply_by_4x4_circulant(x: [&Fp; 4]) -> [Fp; 4] {
+    let t0 = x[0] + x[1];
+    let t1 = x[2] + x[3];
+    let t2 = Fp(2) * x[1].clone() + t1.clone();
+    let t3 = Fp(2) * x[3].clone() + t0.clone();
+    let t4 = Fp(4) * t1 + t3.clone();
+    let t5 = Fp(4) * t0 + t2.clone();
+    let t6 = &t3 + &t5;
+    let t7 = &t2 + &t4;
+    [t6, t5, t7, t4]
+}
+
+pub fn multiply_by_m_ext(cells: &mut [Fp; CELLS]) {
+    let mut out: [Fp; CELLS] = Default::default();
+    let mut sums: [Fp; 4] = Default::default();
+    for (chunk, out) in cells.chunks_exact(4).zip(out.chunks_exact_mut(4)) {
+        let m = multiply_by_4x4_circulant([&chunk[0], &chunk[1], &chunk[2], &chunk[3]]);
+        for j in 0..4 {
+            sums[j] = &sums[j] + &m[j];
+            out[j] = m[j].clone();
+        }
+    }
+    for (i, cell) in cells.iter_mut().enumerate() {
+        *cell = &out[i] + &sums[i % 4];
+    }
+}
+
+pub fn full_round(cells: &mut [Fp; CELLS], round: usize) {
+    add_round_constants_full(cells, round);
+    for cell in cells.iter_mut() {
+        *cell = sbox(cell);
+    }
+    multiply_by_m_ext(cells);
+}
+
+pub fn partial_round(cells: &mut [Fp; CELLS], round: usize) {
+    add_round_constants_partial(cells, round);
+    cells[0] = sbox(&cells[0]);
+    multiply_by_m_int(cells);
+}
+
+/// The whole Poseidon2 permutation of RISC Zero, rephrased from `poseidon2_mix` in `risc0-zkp`.
+pub fn poseidon2_mix(cells: &mut [Fp; CELLS]) {
+    poseidon2_mix_with(cells, |_| {})
+}
+
+/// Like `poseidon2_mix`, calling `on_round` with the state after each round.
+pub fn poseidon2_mix_with(cells: &mut [Fp; CELLS], mut on_round: impl FnMut(&[Fp; CELLS])) {
+    multiply_by_m_ext(cells);
+    for round in 0..ROUNDS {
+        if (ROUNDS_HALF_FULL..ROUNDS_HALF_FULL + ROUNDS_PARTIAL).contains(&round) {
+            partial_round(cells, round);
+        } else {
+            full_round(cells, round);
+        }
+        on_round(cells);
+    }
+}
+
+/// The Poseidon2 state of the recursion machine, driven by its load, round and store
+/// instructions.
+///
+/// The rows are modelled on those of Poseidon: a `POSEIDON2_FULL` row runs one full round, the
+/// first of which starts with the external matrix that `poseidon2_mix` applies before the
+/// rounds, and a `POSEIDON2_PARTIAL` row runs all the partial rounds. A load, four `full`, one
+/// `partial` and four more `full` therefore compute `poseidon2_mix`. This model has not been
+/// checked against the rows of a release of the recursion circuit.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Poseidon2 {
+    pub cells: [Fp; CELLS],
+    round: usize,
+}
+
+impl Poseidon2 {
+    /// Add `values` into the cells of `group`. Unless `keep_state` is set, the state is cleared
+    /// first, all of it or, with `keep_upper_state`, only the first two groups. A load starts a
+    /// new permutation.
+    pub fn load(
+        &mut self,
+        group: usize,
+        values: [Fp; GROUP_CELLS],
+        keep_state: bool,
+        keep_upper_state: bool,
+        from_montgomery: bool,
+    ) {
+        if !keep_state {
+            let clear = if keep_upper_state { 16 } else { CELLS };
+            self.cells[..clear].fill(Fp::ZERO);
+        }
+        for (cell, value) in self.cells[group * GROUP_CELLS..(group + 1) * GROUP_CELLS]
+            .iter_mut()
+            .zip(values)
+        {
+            let value = if from_montgomery {
+                value * super::FROM_MONTGOMERY
+            } else {
+                value
+            };
+            *cell = &*cell + value;
+        }
+        self.round = 0;
+    }
+
+    /// The next round to run, `ROUNDS` once the permutation is complete.
+    pub fn round(&self) -> usize {
+        self.round
+    }
+
+    fn is_partial(round: usize) -> bool {
+        (ROUNDS_HALF_FULL..ROUNDS_HALF_FULL + ROUNDS_PARTIAL).contains(&round)
+    }
+
+    fn step(&mut self) {
+        if self.round == 0 {
+            multiply_by_m_ext(&mut self.cells);
+        }
+        if Self::is_partial(self.round) {
+            partial_round(&mut self.cells, self.round);
+        } else {
+            full_round(&mut self.cells, self.round);
+        }
+        self.round += 1;
+    }
+
+    /// Run the next full round, as `POSEIDON2_FULL` does. Returns `false` and leaves the state
+    /// unchanged if the next round is not a full round.
+    pub fn full(&mut self) -> bool {
+        self.full_with(|_| {})
+    }
+
+    /// Like `full`, calling `on_round` with the state after the round.
+    pub fn full_with(&mut self, mut on_round: impl FnMut(&[Fp; CELLS])) -> bool {
+        if self.round >= ROUNDS || Self::is_partial(self.round) {
+            return false;
+        }
+        self.step();
+        on_round(&self.cells);
+        true
+    }
+
+    /// Run all the partial rounds, as `POSEIDON2_PARTIAL` does. Returns `false` and leaves the
+    /// state unchanged if the full rounds before them have not all run.
+    pub fn partial(&mut self) -> bool {
+        self.partial_with(|_| {})
+    }
+
+    /// Like `partial`, calling `on_round` with the state after each round.
+    pub fn partial_with(&mut self, mut on_round: impl FnMut(&[Fp; CELLS])) -> bool {
+        if self.round != ROUNDS_HALF_FULL {
+            return false;
+        }
+        for _ in 0..ROUNDS_PARTIAL {
+            self.step();
+            on_round(&self.cells);
+        }
+        true
+    }
+
+    /// Run the rounds that are left of the permutation.
+    pub fn permute(&mut self) {
+        self.permute_with(|_| {})
+    }
+
+    /// Like `permute`, calling `on_round` with the state after each round.
+    pub fn permute_with(&mut self, mut on_round: impl FnMut(&[Fp; CELLS])) {
+        while self.round < ROUNDS {
+            self.step();
+            on_round(&self.cells);
+        }
+    }
+
+    /// The cells of `group`, as `POSEIDON2_STORE` writes them.
+    pub fn store(&self, group: usize, to_montgomery: bool) -> [Fp; GROUP_CELLS] {
+        let mut out: [Fp; GROUP_CELLS] = Default::default();
+        for (o, cell) in out
+            .iter_mut()
+            .zip(self.cells[group * GROUP_CELLS..(group + 1) * GROUP_CELLS].iter())
+        {
+            *o = if to_montgomery {
+                cell * super::TO_MONTGOMERY
+            } else {
+                cell.clone()
+            };
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::poseidon::tests::Grain;
+
+    #[test]
+    fn test_vector() {
+        // `test_poseidon2_test_vectors` in `risc0-zkp`
+        let mut cells: [Fp; CELLS] = std::array::from_fn(|i| Fp(i as u32));
+        poseidon2_mix(&mut cells);
+        let goal: [u32; CELLS] = [
+            0x2ed3e23d, 0x12921fb0, 0x0e659e79, 0x61d81dc9, 0x32bae33b, 0x62486ae3, 0x1e681b60,
+            0x24b91325, 0x2a2ef5b9, 0x50e8593e, 0x5bc818ec, 0x10691997, 0x35a14520, 0x2ba6a3c5,
+            0x279d47ec, 0x55014e81, 0x5953a67f, 0x2f403111, 0x6b8828ff, 0x1801301f, 0x2749207a,
+            0x3dc9cf21, 0x3c985ba2, 0x57a99864,
+        ];
+        assert_eq!(cells, goal.map(Fp));
+    }
+
+    #[test]
+    fn machine_rows_compute_the_permutation() {
+        let values: [Fp; CELLS] = std::array::from_fn(|i| Fp::new(i as u32 * 0x01000193 + 7));
+        let mut expected = values.clone();
+        poseidon2_mix(&mut expected);
+
+        let mut state = Poseidon2::default();
+        for group in 0..3 {
+            let group_values = std::array::from_fn(|i| values[group * GROUP_CELLS + i].clone());
+            state.load(group, group_values, group > 0, false, false);
+        }
+        assert!(!state.partial());
+        for _ in 0..ROUNDS_HALF_FULL {
+            assert!(state.full());
+        }
+        assert!(!state.full());
+        assert!(state.partial());
+        assert!(!state.partial());
+        for _ in 0..ROUNDS_HALF_FULL {
+            assert!(state.full());
+        }
+        assert!(!state.full());
+        assert_eq!(state.round(), ROUNDS);
+        assert_eq!(state.cells, expected);
+
+        // the rounds left after a load
+        let mut state = Poseidon2 {
+            cells: values.clone(),
+            ..Default::default()
+        };
+        assert!(state.full());
+        state.permute();
+        assert_eq!(state.cells, expected);
+    }
+
+    #[test]
+    fn round_constants() {
+        let mut grain = Grain::new(0, 8, 21);
+        for round in 0..ROUNDS {
+            let partial = (ROUNDS_HALF_FULL..ROUNDS_HALF_FULL + ROUNDS_PARTIAL).contains(&round);
+            for i in 0..CELLS {
+                let expected = if partial && i > 0 {
+                    Fp::ZERO
+                } else {
+                    grain.next_fp()
+                };
+                assert_eq!(
+                    ROUND_CONSTANTS[round * CELLS + i],
+                    expected,
+                    "round {}, cell {}",
+                    round,
+                    i
+                );
+            }
+        }
+    }
+
+    // the matrices of the linear layers, from the Poseidon2 paper, applied densely
+    #[test]
+    fn linear_layers() {
+        const M4: [[u32; 4]; 4] = [[5, 7, 1, 3], [4, 6, 1, 1], [1, 3, 5, 7], [1, 1, 4, 6]];
+        let m_ext = |i: usize, j: usize| {
+            let m = Fp(M4[i % 4][j % 4]);
+            if i / 4 == j / 4 {
+                Fp(2) * m
+            } else {
+                m
+            }
+        };
+        let m_int = |i: usize, j: usize| {
+            if i == j {
+                Fp(1) + M_INT_DIAG_HZN[i].clone()
+            } else {
+                Fp(1)
+            }
+        };
+        let dense = |m: &dyn Fn(usize, usize) -> Fp, cells: &[Fp; CELLS]| -> [Fp; CELLS] {
+            std::array::from_fn(|i| {
+                (0..CELLS).fold(Fp::ZERO, |acc, j| acc + m(i, j) * cells[j].clone())
+            })
+        };
+
+        for seed in 1..4u32 {
+            let cells: [Fp; CELLS] =
+                std::array::from_fn(|i| Fp::new(seed.wrapping_mul(0x9e3779b9) ^ i as u32));
+            let mut ext = cells.clone();
+            multiply_by_m_ext(&mut ext);
+            assert_eq!(ext, dense(&m_ext, &cells));
+            let mut int = cells.clone();
+            multiply_by_m_int(&mut int);
+            assert_eq!(int, dense(&m_int, &cells));
+        }
+    }
+}
@@ -1,15 +1,91 @@
+use crate::parser::validate::Violation;
 use std::fmt::{Debug, Display, Formatter};
+
+// where a row starts in the input, `row` counts from 1 like `LineNo::row`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub row: usize,
+    pub byte_offset: usize,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "row {} (byte offset {})",
+            self.row, self.byte_offset
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IllegalField {
+    // none of the row selectors is set
+    Selector,
+    // none of the macro op selectors is set
+    MacroSelector,
+    // the opcode of the micro op in the given slot (0, 1 or 2) is unknown
+    MicroOpcode(usize),
+    // the group columns of a Poseidon row are not flags of which at most one is set
+    PoseidonGroup,
+}
+
+impl Display for IllegalField {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IllegalField::Selector => f.write_str("no row selector is set"),
+            IllegalField::MacroSelector => f.write_str("no macro op selector is set"),
+            IllegalField::MicroOpcode(slot) => {
+                f.write_fmt(format_args!("unknown micro opcode in slot {}", slot))
+            }
+            IllegalField::PoseidonGroup => f.write_str("the Poseidon group flags are not valid"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum ParserError {
-    IllegalInstruction(Vec<u32>),
+    IllegalInstruction(Location, IllegalField, Vec<u32>),
+    // the location of the incomplete last row, how many bytes of it are present, and how many
+    // bytes a row has
+    TruncatedInput(Location, usize, usize),
+    // the location of the row being read, and what the reader reported
+    Io(Location, String),
+    // the location of the row, and the operand whose address computation overflows
+    ArithmeticOverflow(Location, &'static str),
+    // every row that does not have the expected shape, only reported in strict mode
+    StrictViolations(Vec<Violation>),
 }
 
 impl Display for ParserError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            ParserError::IllegalInstruction(line) => {
-                f.write_fmt(format_args!("Unknown instruction: {:?}", line))
+            ParserError::IllegalInstruction(location, field, line) => f.write_fmt(format_args!(
+                "Unknown instruction at {}, {}: {:?}",
+                location, field, line
+            )),
+            ParserError::TruncatedInput(location, len, row_bytes) => f.write_fmt(format_args!(
+                "Truncated input at {}: only {} of the {} bytes of the row are present",
+                location, len, row_bytes
+            )),
+            ParserError::Io(location, e) => {
+                f.write_fmt(format_args!("Cannot read {}: {}", location, e))
+            }
+            ParserError::ArithmeticOverflow(location, operand) => f.write_fmt(format_args!(
+                "Arithmetic overflow at {} when computing the {}",
+                location, operand
+            )),
+            ParserError::StrictViolations(violations) => {
+                f.write_fmt(format_args!(
+                    "{} violations of the expected row shape",
+                    violations.len()
+                ))?;
+                for violation in violations.iter() {
+                    f.write_fmt(format_args!("\n  {}", violation))?;
+                }
+                Ok(())
             }
         }
     }
 }
+
+impl std::error::Error for ParserError {}
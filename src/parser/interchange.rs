@@ -0,0 +1,141 @@
+use crate::parser::Code;
+
+/// JSON and CBOR forms of `Code`, which keep the `LineNo` of every instruction.
+impl Code {
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn from_json(text: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    pub fn to_cbor(&self) -> anyhow::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        ciborium::into_writer(self, &mut out)?;
+        Ok(out)
+    }
+
+    pub fn from_cbor(bytes: &[u8]) -> anyhow::Result<Self> {
+        Ok(ciborium::from_reader(bytes)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::tests::instructions;
+    use crate::parser::LineNo;
+    use crate::pass::manager::PassManager;
+    use crate::structures::StructuredInstruction as I;
+
+    fn check_round_trip(code: &Code) {
+        let json = code.to_json().unwrap();
+        let from_json = Code::from_json(&json).unwrap();
+        let from_cbor = Code::from_cbor(&code.to_cbor().unwrap()).unwrap();
+        for reloaded in [&from_json, &from_cbor] {
+            assert_eq!(reloaded.0.len(), code.0.len());
+            for ((insn, line_no), (expected, expected_line_no)) in reloaded.0.iter().zip(&code.0) {
+                assert!(insn == expected, "{} reads back as {}", expected, insn);
+                assert_eq!(line_no, expected_line_no);
+            }
+        }
+        assert_eq!(from_json.to_json().unwrap(), json);
+    }
+
+    #[test]
+    fn every_instruction() {
+        let mut code = Code::default();
+        for (i, insn) in instructions().into_iter().enumerate() {
+            let line_no = LineNo::new(i + 1, i % 3);
+            // a span on every other instruction
+            let line_no = if i % 2 == 0 {
+                line_no
+            } else {
+                line_no.to(&LineNo::new(i + 4, 2))
+            };
+            code.push(insn, line_no);
+        }
+        check_round_trip(&code);
+    }
+
+    fn program() -> Code {
+        let poseidon_load = |group| {
+            I::POSEIDON_ADD_LOAD(
+                (group == 2) as u32,
+                group,
+                1.into(),
+                2.into(),
+                3.into(),
+                4.into(),
+                5.into(),
+                6.into(),
+                7.into(),
+                8.into(),
+            )
+        };
+        let mut insns = vec![
+            I::WOM_INIT,
+            I::READ_IOP_HEADER(3, 4),
+            I::READ_IOP_BODY(1),
+            I::READ_IOP_BODY(2),
+            I::READ_IOP_BODY(3),
+            I::CONST(4, 3, 0),
+            I::ADD(5, 1.into(), 4.into()),
+            I::MUL(6, 4.into(), 4.into()),
+            I::CONST(7, 9, 0),
+            I::EQ(6.into(), 7.into()),
+            I::CONST(8, 1, 0),
+            poseidon_load(0),
+            poseidon_load(1),
+            poseidon_load(2),
+            I::POSEIDON_FULL,
+            I::POSEIDON_FULL,
+            I::POSEIDON_PARTIAL,
+            I::POSEIDON_FULL,
+            I::POSEIDON_FULL,
+            I::POSEIDON_STORE(0, 10),
+            I::SHA_INIT_START,
+        ];
+        insns.extend(vec![I::SHA_INIT_PADDING; 3]);
+        insns.extend((0..16).map(|i| I::SHA_LOAD((10 + i % 8).into())));
+        insns.extend(vec![I::SHA_MIX; 48]);
+        insns.push(I::SHA_FINI_START(20));
+        insns.extend(vec![I::SHA_FINI_PADDING; 3]);
+        insns.extend([
+            I::SET_GLOBAL(20.into(), 21.into(), 22.into(), 23.into(), 0),
+            I::WOM_FINI,
+        ]);
+
+        let mut code = Code::default();
+        for (row, insn) in insns.into_iter().enumerate() {
+            code.push(insn, LineNo::new(row + 1, 0));
+        }
+        code
+    }
+
+    #[test]
+    fn passes_on_reloaded_code() {
+        let code = program();
+        let manager = PassManager::default();
+        let mut expected = program();
+        manager.run(&mut expected).unwrap();
+        check_round_trip(&expected);
+        // the merging passes have all found something
+        for merged in [
+            "__READ_IOP_BODY_BATCH__",
+            "__SHA_MIX_48__",
+            "__POSEIDON_PERMUTE_STORE__",
+        ] {
+            assert!(expected.to_json().unwrap().contains(merged), "{}", merged);
+        }
+
+        for mut reloaded in [
+            Code::from_json(&code.to_json().unwrap()).unwrap(),
+            Code::from_cbor(&code.to_cbor().unwrap()).unwrap(),
+        ] {
+            manager.run(&mut reloaded).unwrap();
+            assert_eq!(reloaded.to_json().unwrap(), expected.to_json().unwrap());
+        }
+    }
+}
@@ -1,22 +1,46 @@
 mod error;
+#[cfg(feature = "serde")]
+mod interchange;
+mod reader;
+mod source_map;
+mod validate;
 
-use crate::parser::error::ParserError;
+use crate::layout::IsaLayout;
+use crate::math::{Fp4, P};
+pub use crate::parser::error::{IllegalField, Location, ParserError};
+pub use crate::parser::reader::ZkrReader;
+pub use crate::parser::source_map::{apply_source_map, write_listing, write_source_map};
+pub use crate::parser::validate::{validate, Violation, ViolationKind};
 use crate::structures::StructuredInstruction;
 use crate::{
-    MACRO_BIT_AND_ELEM, MACRO_BIT_OP_SHORTS, MACRO_OPERAND_0, MACRO_OPERAND_1, MACRO_OPERAND_2,
-    MACRO_SET_GLOBAL, MACRO_SHA_FINI, MACRO_SHA_INIT, MACRO_SHA_LOAD, MACRO_SHA_MIX,
-    MACRO_WOM_FINI, MACRO_WOM_INIT, MICRO_ADD, MICRO_CONST, MICRO_EQ, MICRO_EXTRACT, MICRO_INV,
-    MICRO_MIX_RNG, MICRO_MUL, MICRO_READ_IOP_BODY, MICRO_READ_IOP_HEADER, MICRO_SELECT, MICRO_SUB,
-    POSEIDON_DO_MONT, POSEIDON_LOAD_ADD_CONSTS, POSEIDON_LOAD_G1, POSEIDON_LOAD_G2,
-    POSEIDON_LOAD_KEEP_STATE, SELECT_MACRO_OPS, SELECT_MICRO_OPS, SELECT_POSEIDON_FULL,
-    SELECT_POSEIDON_LOAD, SELECT_POSEIDON_PARTIAL, SELECT_POSEIDON_STORE, WRITE_ADDR,
+    MICRO_ADD, MICRO_CONST, MICRO_EQ, MICRO_EXTRACT, MICRO_INV, MICRO_MIX_RNG, MICRO_MUL,
+    MICRO_READ_IOP_BODY, MICRO_READ_IOP_HEADER, MICRO_SELECT, MICRO_SUB,
 };
+use std::fmt::{Display, Formatter};
 
-#[derive(Default)]
-struct GlobalState {
+struct GlobalState<'a> {
+    layout: &'a IsaLayout,
     sha_init_pos: usize,
     sha_fini_pos: usize,
-    line_no: usize,
+    row: usize,
+    slot: usize,
+}
+
+impl GlobalState<'_> {
+    fn location(&self) -> Location {
+        Location {
+            row: self.row,
+            byte_offset: (self.row - 1) * self.layout.row_bytes(),
+        }
+    }
+
+    fn line_no(&self) -> LineNo {
+        LineNo::new(self.row, self.slot)
+    }
+
+    fn overflow(&self, operand: &'static str) -> ParserError {
+        ParserError::ArithmeticOverflow(self.location(), operand)
+    }
 }
 
 fn walk_macro(
@@ -24,90 +48,218 @@ fn walk_macro(
     out: &mut Code,
     insn: &[u32],
 ) -> Result<(), ParserError> {
-    if insn[MACRO_BIT_AND_ELEM] == 1 {
+    let layout = global_state.layout;
+    if insn[layout.macro_bit_and_elem] == 1 {
         out.push(
             StructuredInstruction::BIT_AND_ELEM(
-                insn[WRITE_ADDR],
-                insn[MACRO_OPERAND_0].into(),
-                insn[MACRO_OPERAND_1].into(),
+                insn[layout.write_addr],
+                insn[layout.macro_operand_0].into(),
+                insn[layout.macro_operand_1].into(),
             ),
-            global_state.line_no,
+            global_state.line_no(),
         );
-    } else if insn[MACRO_BIT_OP_SHORTS] == 1 {
-        if insn[MACRO_OPERAND_2] != 0 {
+    } else if insn[layout.macro_bit_op_shorts] == 1 {
+        if insn[layout.macro_operand_2] != 0 {
             out.push(
                 StructuredInstruction::BIT_AND_SHORTS(
-                    insn[WRITE_ADDR],
-                    insn[MACRO_OPERAND_0].into(),
-                    insn[MACRO_OPERAND_1].into(),
+                    insn[layout.write_addr],
+                    insn[layout.macro_operand_0].into(),
+                    insn[layout.macro_operand_1].into(),
                 ),
-                global_state.line_no,
+                global_state.line_no(),
             );
         } else {
             out.push(
                 StructuredInstruction::BIT_XOR_SHORTS(
-                    insn[WRITE_ADDR],
-                    insn[MACRO_OPERAND_0].into(),
-                    insn[MACRO_OPERAND_1].into(),
+                    insn[layout.write_addr],
+                    insn[layout.macro_operand_0].into(),
+                    insn[layout.macro_operand_1].into(),
                 ),
-                global_state.line_no,
+                global_state.line_no(),
             );
         }
-    } else if insn[MACRO_SHA_INIT] == 1 {
+    } else if insn[layout.macro_sha_init] == 1 {
         if global_state.sha_init_pos == 0 {
-            out.push(StructuredInstruction::SHA_INIT_START, global_state.line_no);
+            out.push(
+                StructuredInstruction::SHA_INIT_START,
+                global_state.line_no(),
+            );
         } else {
             out.push(
                 StructuredInstruction::SHA_INIT_PADDING,
-                global_state.line_no,
+                global_state.line_no(),
             );
         }
         global_state.sha_init_pos = (global_state.sha_init_pos + 1) % 4;
-    } else if insn[MACRO_SHA_LOAD] == 1 {
-        if insn[MACRO_OPERAND_2] == 0 {
+    } else if insn[layout.macro_sha_load] == 1 {
+        if insn[layout.macro_operand_2] == 0 {
             out.push(
-                StructuredInstruction::SHA_LOAD_FROM_MONTGOMERY(insn[MACRO_OPERAND_0].into()),
-                global_state.line_no,
+                StructuredInstruction::SHA_LOAD_FROM_MONTGOMERY(
+                    insn[layout.macro_operand_0].into(),
+                ),
+                global_state.line_no(),
             );
         } else {
             out.push(
-                StructuredInstruction::SHA_LOAD(insn[MACRO_OPERAND_0].into()),
-                global_state.line_no,
+                StructuredInstruction::SHA_LOAD(insn[layout.macro_operand_0].into()),
+                global_state.line_no(),
             );
         }
-    } else if insn[MACRO_SHA_MIX] == 1 {
-        out.push(StructuredInstruction::SHA_MIX, global_state.line_no);
-    } else if insn[MACRO_SHA_FINI] == 1 {
+    } else if insn[layout.macro_sha_mix] == 1 {
+        out.push(StructuredInstruction::SHA_MIX, global_state.line_no());
+    } else if insn[layout.macro_sha_fini] == 1 {
         if global_state.sha_fini_pos == 0 {
-            let out_addr = insn[MACRO_OPERAND_0] - 3;
+            let out_addr = insn[layout.macro_operand_0]
+                .checked_sub(3)
+                .ok_or_else(|| global_state.overflow("SHA_FINI output address"))?;
             out.push(
                 StructuredInstruction::SHA_FINI_START(out_addr),
-                global_state.line_no,
+                global_state.line_no(),
             );
         } else {
             out.push(
                 StructuredInstruction::SHA_FINI_PADDING,
-                global_state.line_no,
+                global_state.line_no(),
             );
         }
         global_state.sha_fini_pos = (global_state.sha_fini_pos + 1) % 4;
-    } else if insn[MACRO_WOM_INIT] == 1 {
-        out.push(StructuredInstruction::WOM_INIT, global_state.line_no);
-    } else if insn[MACRO_WOM_FINI] == 1 {
-        out.push(StructuredInstruction::WOM_FINI, global_state.line_no);
-    } else if insn[MACRO_SET_GLOBAL] == 1 {
+    } else if insn[layout.macro_wom_init] == 1 {
+        out.push(StructuredInstruction::WOM_INIT, global_state.line_no());
+    } else if insn[layout.macro_wom_fini] == 1 {
+        out.push(StructuredInstruction::WOM_FINI, global_state.line_no());
+    } else if insn[layout.macro_nop] == 1 {
+        out.push(StructuredInstruction::NOP, global_state.line_no());
+    } else if insn[layout.macro_set_global] == 1 {
+        if insn[layout.macro_operand_0].checked_add(3).is_none() {
+            return Err(global_state.overflow("SET_GLOBAL input address"));
+        }
         out.push(
             StructuredInstruction::SET_GLOBAL(
-                insn[MACRO_OPERAND_0].into(),
-                (insn[MACRO_OPERAND_0] + 1).into(),
-                (insn[MACRO_OPERAND_0] + 2).into(),
-                (insn[MACRO_OPERAND_0] + 3).into(),
-                insn[MACRO_OPERAND_1],
+                insn[layout.macro_operand_0].into(),
+                (insn[layout.macro_operand_0] + 1).into(),
+                (insn[layout.macro_operand_0] + 2).into(),
+                (insn[layout.macro_operand_0] + 3).into(),
+                insn[layout.macro_operand_1],
+            ),
+            global_state.line_no(),
+        );
+    } else {
+        return Err(ParserError::IllegalInstruction(
+            global_state.location(),
+            IllegalField::MacroSelector,
+            insn.to_vec(),
+        ));
+    }
+    Ok(())
+}
+
+// the value of an optional column, which reads as 0 when the layout does not have it
+fn optional(insn: &[u32], column: Option<usize>) -> u32 {
+    column.map_or(0, |c| insn[c])
+}
+
+// the group of the cells that a Poseidon or Poseidon2 row loads or stores, from its g1 and g2
+// columns, which are flags of which at most one is set
+fn poseidon_group(global_state: &GlobalState, insn: &[u32]) -> Result<u32, ParserError> {
+    let layout = global_state.layout;
+    match (insn[layout.poseidon_load_g1], insn[layout.poseidon_load_g2]) {
+        (0, 0) => Ok(0),
+        (1, 0) => Ok(1),
+        (0, 1) => Ok(2),
+        _ => Err(ParserError::IllegalInstruction(
+            global_state.location(),
+            IllegalField::PoseidonGroup,
+            insn.to_vec(),
+        )),
+    }
+}
+
+fn walk_poseidon2(
+    global_state: &GlobalState,
+    out: &mut Code,
+    insn: &[u32],
+) -> Result<(), ParserError> {
+    let layout = global_state.layout;
+    let group = poseidon_group(global_state, insn)?;
+    let do_mont = insn[layout.poseidon_do_mont] != 0;
+
+    if optional(insn, layout.select_poseidon2_load) == 1 {
+        let keep_upper_state = optional(insn, layout.poseidon2_load_keep_upper_state);
+        let prep_full = optional(insn, layout.poseidon2_load_prep_full);
+        let r = |i: usize| insn[layout.poseidon_load_operands + i].into();
+        let insn = match (insn[layout.poseidon_load_keep_state] == 1, do_mont) {
+            (false, true) => StructuredInstruction::POSEIDON2_LOAD_FROM_MONTGOMERY(
+                keep_upper_state,
+                prep_full,
+                group,
+                r(0),
+                r(1),
+                r(2),
+                r(3),
+                r(4),
+                r(5),
+                r(6),
+                r(7),
+            ),
+            (false, false) => StructuredInstruction::POSEIDON2_LOAD(
+                keep_upper_state,
+                prep_full,
+                group,
+                r(0),
+                r(1),
+                r(2),
+                r(3),
+                r(4),
+                r(5),
+                r(6),
+                r(7),
             ),
-            global_state.line_no,
+            (true, true) => StructuredInstruction::POSEIDON2_ADD_LOAD_FROM_MONTGOMERY(
+                prep_full,
+                group,
+                r(0),
+                r(1),
+                r(2),
+                r(3),
+                r(4),
+                r(5),
+                r(6),
+                r(7),
+            ),
+            (true, false) => StructuredInstruction::POSEIDON2_ADD_LOAD(
+                prep_full,
+                group,
+                r(0),
+                r(1),
+                r(2),
+                r(3),
+                r(4),
+                r(5),
+                r(6),
+                r(7),
+            ),
+        };
+        out.push(insn, global_state.line_no());
+    } else if optional(insn, layout.select_poseidon2_full) == 1 {
+        out.push(
+            StructuredInstruction::POSEIDON2_FULL,
+            global_state.line_no(),
+        );
+    } else if optional(insn, layout.select_poseidon2_partial) == 1 {
+        out.push(
+            StructuredInstruction::POSEIDON2_PARTIAL,
+            global_state.line_no(),
+        );
+    } else if do_mont {
+        out.push(
+            StructuredInstruction::POSEIDON2_STORE_TO_MONTGOMERY(group, insn[layout.write_addr]),
+            global_state.line_no(),
         );
     } else {
-        return Err(ParserError::IllegalInstruction(insn.to_vec()));
+        out.push(
+            StructuredInstruction::POSEIDON2_STORE(group, insn[layout.write_addr]),
+            global_state.line_no(),
+        );
     }
     Ok(())
 }
@@ -117,86 +269,90 @@ fn walk_micro(
     out: &mut Code,
     insn: &[u32],
 ) -> Result<(), ParserError> {
-    let group = [
-        [insn[7], insn[8], insn[9], insn[10]],
-        [insn[11], insn[12], insn[13], insn[14]],
-        [insn[15], insn[16], insn[17], insn[18]],
-    ];
-
-    for (i, row) in group.iter().enumerate() {
-        let write_addr = insn[WRITE_ADDR] + i as u32;
+    let layout = global_state.layout;
+    for (i, base) in layout.micro_slots.iter().enumerate() {
+        global_state.slot = i;
+        let row = &insn[*base..*base + 4];
+        let write_addr = insn[layout.write_addr]
+            .checked_add(i as u32)
+            .ok_or_else(|| global_state.overflow("micro op write address"))?;
         if row[0] == MICRO_CONST {
             out.push(
                 StructuredInstruction::CONST(write_addr, row[1], row[2]),
-                global_state.line_no,
+                global_state.line_no(),
             );
         } else if row[0] == MICRO_ADD {
             out.push(
                 StructuredInstruction::ADD(write_addr, row[1].into(), row[2].into()),
-                global_state.line_no,
+                global_state.line_no(),
             );
         } else if row[0] == MICRO_SUB {
             out.push(
                 StructuredInstruction::SUB(write_addr, row[1].into(), row[2].into()),
-                global_state.line_no,
+                global_state.line_no(),
             );
         } else if row[0] == MICRO_MUL {
             out.push(
                 StructuredInstruction::MUL(write_addr, row[1].into(), row[2].into()),
-                global_state.line_no,
+                global_state.line_no(),
             );
         } else if row[0] == MICRO_INV {
             if row[2] == 0 {
                 out.push(
                     StructuredInstruction::NOT(write_addr, row[1].into()),
-                    global_state.line_no,
+                    global_state.line_no(),
                 );
             } else {
                 out.push(
                     StructuredInstruction::INV(write_addr, row[1].into()),
-                    global_state.line_no,
+                    global_state.line_no(),
                 );
             }
         } else if row[0] == MICRO_EQ {
             out.push(
                 StructuredInstruction::EQ(row[1].into(), row[2].into()),
-                global_state.line_no,
+                global_state.line_no(),
             );
         } else if row[0] == MICRO_READ_IOP_HEADER {
             out.push(
                 StructuredInstruction::READ_IOP_HEADER(row[1], row[2]),
-                global_state.line_no,
+                global_state.line_no(),
             );
         } else if row[0] == MICRO_READ_IOP_BODY {
             out.push(
                 StructuredInstruction::READ_IOP_BODY(write_addr),
-                global_state.line_no,
+                global_state.line_no(),
             );
         } else if row[0] == MICRO_MIX_RNG {
             if row[3] != 0 {
+                let prev_addr = write_addr
+                    .checked_sub(1)
+                    .ok_or_else(|| global_state.overflow("MIX_RNG previous address"))?;
                 out.push(
                     StructuredInstruction::MIX_RNG_WITH_PERV(
                         write_addr,
                         row[3],
-                        (write_addr - 1).into(),
+                        prev_addr.into(),
                         row[1].into(),
                         row[2].into(),
                     ),
-                    global_state.line_no,
+                    global_state.line_no(),
                 );
             } else {
                 out.push(
                     StructuredInstruction::MIX_RNG(write_addr, row[1].into(), row[2].into()),
-                    global_state.line_no,
+                    global_state.line_no(),
                 );
             }
         } else if row[0] == MICRO_SELECT {
             let if_false = row[2];
-            let if_true = if row[3] >= 1006632960u32 {
-                row[2] - (2013265921 - row[3])
+            let if_true = if row[3] >= P / 2 {
+                P.checked_sub(row[3])
+                    .and_then(|diff| row[2].checked_sub(diff))
             } else {
-                row[2] + row[3]
-            };
+                row[2].checked_add(row[3])
+            }
+            .ok_or_else(|| global_state.overflow("SELECT address"))?;
             out.push(
                 StructuredInstruction::SELECT(
                     write_addr,
@@ -204,144 +360,535 @@ fn walk_micro(
                     if_true.into(),
                     if_false.into(),
                 ),
-                global_state.line_no,
+                global_state.line_no(),
             );
         } else if row[0] == MICRO_EXTRACT {
+            let idx = row[2]
+                .checked_mul(2)
+                .and_then(|v| v.checked_add(row[3]))
+                .ok_or_else(|| global_state.overflow("EXTRACT index"))?;
+            out.push(
+                StructuredInstruction::EXTRACT(write_addr, row[1].into(), idx),
+                global_state.line_no(),
+            );
+        } else {
+            return Err(ParserError::IllegalInstruction(
+                global_state.location(),
+                IllegalField::MicroOpcode(i),
+                insn.to_vec(),
+            ));
+        }
+    }
+    global_state.slot = 0;
+    Ok(())
+}
+
+fn walk_row(
+    global_state: &mut GlobalState,
+    out: &mut Code,
+    insn: &[u32],
+) -> Result<(), ParserError> {
+    let layout = global_state.layout;
+    if insn[layout.select_macro_ops] == 1 {
+        walk_macro(global_state, out, insn)?;
+    } else if insn[layout.select_micro_ops] == 1 {
+        walk_micro(global_state, out, insn)?;
+    } else if insn[layout.select_poseidon_load] == 1 {
+        let group = poseidon_group(global_state, insn)?;
+        if insn[layout.poseidon_load_keep_state] != 1 {
+            if insn[layout.poseidon_do_mont] != 0 {
+                out.push(
+                    StructuredInstruction::POSEIDON_LOAD_FROM_MONTGOMERY(
+                        insn[layout.poseidon_load_add_consts],
+                        group,
+                        insn[layout.poseidon_load_operands].into(),
+                        insn[layout.poseidon_load_operands + 1].into(),
+                        insn[layout.poseidon_load_operands + 2].into(),
+                        insn[layout.poseidon_load_operands + 3].into(),
+                        insn[layout.poseidon_load_operands + 4].into(),
+                        insn[layout.poseidon_load_operands + 5].into(),
+                        insn[layout.poseidon_load_operands + 6].into(),
+                        insn[layout.poseidon_load_operands + 7].into(),
+                    ),
+                    global_state.line_no(),
+                );
+            } else {
+                out.push(
+                    StructuredInstruction::POSEIDON_LOAD(
+                        insn[layout.poseidon_load_add_consts],
+                        group,
+                        insn[layout.poseidon_load_operands].into(),
+                        insn[layout.poseidon_load_operands + 1].into(),
+                        insn[layout.poseidon_load_operands + 2].into(),
+                        insn[layout.poseidon_load_operands + 3].into(),
+                        insn[layout.poseidon_load_operands + 4].into(),
+                        insn[layout.poseidon_load_operands + 5].into(),
+                        insn[layout.poseidon_load_operands + 6].into(),
+                        insn[layout.poseidon_load_operands + 7].into(),
+                    ),
+                    global_state.line_no(),
+                );
+            }
+        } else {
+            if insn[layout.poseidon_do_mont] != 0 {
+                out.push(
+                    StructuredInstruction::POSEIDON_ADD_LOAD_FROM_MONTGOMERY(
+                        insn[layout.poseidon_load_add_consts],
+                        group,
+                        insn[layout.poseidon_load_operands].into(),
+                        insn[layout.poseidon_load_operands + 1].into(),
+                        insn[layout.poseidon_load_operands + 2].into(),
+                        insn[layout.poseidon_load_operands + 3].into(),
+                        insn[layout.poseidon_load_operands + 4].into(),
+                        insn[layout.poseidon_load_operands + 5].into(),
+                        insn[layout.poseidon_load_operands + 6].into(),
+                        insn[layout.poseidon_load_operands + 7].into(),
+                    ),
+                    global_state.line_no(),
+                );
+            } else {
+                out.push(
+                    StructuredInstruction::POSEIDON_ADD_LOAD(
+                        insn[layout.poseidon_load_add_consts],
+                        group,
+                        insn[layout.poseidon_load_operands].into(),
+                        insn[layout.poseidon_load_operands + 1].into(),
+                        insn[layout.poseidon_load_operands + 2].into(),
+                        insn[layout.poseidon_load_operands + 3].into(),
+                        insn[layout.poseidon_load_operands + 4].into(),
+                        insn[layout.poseidon_load_operands + 5].into(),
+                        insn[layout.poseidon_load_operands + 6].into(),
+                        insn[layout.poseidon_load_operands + 7].into(),
+                    ),
+                    global_state.line_no(),
+                );
+            }
+        }
+    } else if insn[layout.select_poseidon_full] == 1 {
+        out.push(StructuredInstruction::POSEIDON_FULL, global_state.line_no());
+    } else if insn[layout.select_poseidon_partial] == 1 {
+        out.push(
+            StructuredInstruction::POSEIDON_PARTIAL,
+            global_state.line_no(),
+        );
+    } else if insn[layout.select_poseidon_store] == 1 {
+        let group = poseidon_group(global_state, insn)?;
+        if insn[layout.poseidon_do_mont] != 0 {
             out.push(
-                StructuredInstruction::EXTRACT(write_addr, row[1].into(), row[2] * 2 + row[3]),
-                global_state.line_no,
+                StructuredInstruction::POSEIDON_STORE_TO_MONTGOMERY(group, insn[layout.write_addr]),
+                global_state.line_no(),
             );
         } else {
-            return Err(ParserError::IllegalInstruction(insn.to_vec()));
+            out.push(
+                StructuredInstruction::POSEIDON_STORE(group, insn[layout.write_addr]),
+                global_state.line_no(),
+            );
         }
+    } else if layout.poseidon2_selectors().iter().any(|c| insn[*c] == 1) {
+        walk_poseidon2(global_state, out, insn)?;
+    } else if insn.iter().all(|v| *v == 0) {
+        // rows used to pad the program to a power-of-two length
+        out.push(StructuredInstruction::PADDING, global_state.line_no());
+    } else {
+        return Err(ParserError::IllegalInstruction(
+            global_state.location(),
+            IllegalField::Selector,
+            insn.to_vec(),
+        ));
     }
     Ok(())
 }
 
-pub type LineNo = usize;
+/// Where an instruction comes from: the row of the zkr, counting from 1, and the slot (0, 1 or
+/// 2) of a micro op within its row, 0 for the other instructions. An instruction that a pass
+/// merges from several others covers everything up to `end_row` and `end_slot`.
+///
+/// For code read back from `.ll0` text, the row is the line and the slot is the position of the
+/// statement on that line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LineNo {
+    pub row: usize,
+    pub slot: usize,
+    pub end_row: usize,
+    pub end_slot: usize,
+}
+
+impl LineNo {
+    pub fn new(row: usize, slot: usize) -> Self {
+        Self {
+            row,
+            slot,
+            end_row: row,
+            end_slot: slot,
+        }
+    }
 
+    /// The span from the start of `self` to the end of `other`.
+    pub fn to(&self, other: &LineNo) -> Self {
+        Self {
+            end_row: other.end_row,
+            end_slot: other.end_slot,
+            ..*self
+        }
+    }
+
+    pub fn is_span(&self) -> bool {
+        self.row != self.end_row || self.slot != self.end_slot
+    }
+}
+
+impl Display for LineNo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let write = |f: &mut Formatter<'_>, row: usize, slot: usize| {
+            if slot == 0 {
+                f.write_fmt(format_args!("{}", row))
+            } else {
+                f.write_fmt(format_args!("{}.{}", row, slot))
+            }
+        };
+        write(f, self.row, self.slot)?;
+        if self.is_span() {
+            f.write_str("..=")?;
+            write(f, self.end_row, self.end_slot)?;
+        }
+        Ok(())
+    }
+}
+
+/// The instructions of a program with their lines, over the extension field `E` of the machine,
+/// which is BabyBear's `Fp4` for the programs parsed from a zkr.
 #[derive(Default)]
-pub struct Code(pub Vec<(StructuredInstruction, LineNo)>);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Code<E = Fp4>(pub Vec<(StructuredInstruction<E>, LineNo)>);
 
-impl Code {
-    pub fn push(&mut self, insn: StructuredInstruction, line_no: usize) {
+impl<E> Code<E> {
+    pub fn push(&mut self, insn: StructuredInstruction<E>, line_no: LineNo) {
         self.0.push((insn, line_no));
     }
 }
 
+impl Code {
+    /// Parse in strict mode, which first checks every row with `validate` and fails with all
+    /// the violations found.
+    pub fn parse_strict(value: &[u32], layout: &IsaLayout) -> Result<Self, ParserError> {
+        let violations = validate(value, layout);
+        if !violations.is_empty() {
+            return Err(ParserError::StrictViolations(violations));
+        }
+        Code::parse(value, layout)
+    }
+
+    /// Parse rows whose columns are placed as `layout` describes.
+    pub fn parse(value: &[u32], layout: &IsaLayout) -> Result<Self, ParserError> {
+        let mut global_state = GlobalState {
+            layout,
+            sha_init_pos: 0,
+            sha_fini_pos: 0,
+            row: 0,
+            slot: 0,
+        };
+        let mut out = Code::default();
+
+        let rows = value.chunks_exact(layout.row_width);
+        if !rows.remainder().is_empty() {
+            global_state.row = value.len() / layout.row_width + 1;
+            return Err(ParserError::TruncatedInput(
+                global_state.location(),
+                rows.remainder().len() * 4,
+                layout.row_bytes(),
+            ));
+        }
+
+        for (idx, insn) in rows.enumerate() {
+            global_state.row = idx + 1;
+            walk_row(&mut global_state, &mut out, insn)?;
+        }
+
+        Ok(out)
+    }
+}
+
 impl TryFrom<&[u32]> for Code {
     type Error = ParserError;
 
     fn try_from(value: &[u32]) -> Result<Self, Self::Error> {
-        let mut global_state = GlobalState::default();
-        let mut out = Code::default();
+        Code::parse(value, &IsaLayout::default())
+    }
+}
 
-        for (idx, insn) in value.chunks_exact(21).enumerate() {
-            global_state.line_no = idx + 1;
-
-            if insn[SELECT_MACRO_OPS] == 1 {
-                walk_macro(&mut global_state, &mut out, insn)?;
-            } else if insn[SELECT_MICRO_OPS] == 1 {
-                walk_micro(&mut global_state, &mut out, insn)?;
-            } else if insn[SELECT_POSEIDON_LOAD] == 1 {
-                let group = insn[POSEIDON_LOAD_G1] + insn[POSEIDON_LOAD_G2] * 2;
-                if insn[POSEIDON_LOAD_KEEP_STATE] != 1 {
-                    if insn[POSEIDON_DO_MONT] != 0 {
-                        out.push(
-                            StructuredInstruction::POSEIDON_LOAD_FROM_MONTGOMERY(
-                                insn[POSEIDON_LOAD_ADD_CONSTS],
-                                group,
-                                insn[13].into(),
-                                insn[14].into(),
-                                insn[15].into(),
-                                insn[16].into(),
-                                insn[17].into(),
-                                insn[18].into(),
-                                insn[19].into(),
-                                insn[20].into(),
-                            ),
-                            global_state.line_no,
-                        );
-                    } else {
-                        out.push(
-                            StructuredInstruction::POSEIDON_LOAD(
-                                insn[POSEIDON_LOAD_ADD_CONSTS],
-                                group,
-                                insn[13].into(),
-                                insn[14].into(),
-                                insn[15].into(),
-                                insn[16].into(),
-                                insn[17].into(),
-                                insn[18].into(),
-                                insn[19].into(),
-                                insn[20].into(),
-                            ),
-                            global_state.line_no,
-                        );
-                    }
-                } else {
-                    if insn[POSEIDON_DO_MONT] != 0 {
-                        out.push(
-                            StructuredInstruction::POSEIDON_ADD_LOAD_FROM_MONTGOMERY(
-                                insn[POSEIDON_LOAD_ADD_CONSTS],
-                                group,
-                                insn[13].into(),
-                                insn[14].into(),
-                                insn[15].into(),
-                                insn[16].into(),
-                                insn[17].into(),
-                                insn[18].into(),
-                                insn[19].into(),
-                                insn[20].into(),
-                            ),
-                            global_state.line_no,
-                        );
-                    } else {
-                        out.push(
-                            StructuredInstruction::POSEIDON_ADD_LOAD(
-                                insn[POSEIDON_LOAD_ADD_CONSTS],
-                                group,
-                                insn[13].into(),
-                                insn[14].into(),
-                                insn[15].into(),
-                                insn[16].into(),
-                                insn[17].into(),
-                                insn[18].into(),
-                                insn[19].into(),
-                                insn[20].into(),
-                            ),
-                            global_state.line_no,
-                        );
+/// Split the raw bytes of a zkr file into its little-endian 32-bit words.
+pub fn words_from_bytes(value: &[u8], layout: &IsaLayout) -> Result<Vec<u32>, ParserError> {
+    let row_bytes = layout.row_bytes();
+    if value.len() % row_bytes != 0 {
+        let row = value.len() / row_bytes;
+        return Err(ParserError::TruncatedInput(
+            Location {
+                row: row + 1,
+                byte_offset: row * row_bytes,
+            },
+            value.len() % row_bytes,
+            row_bytes,
+        ));
+    }
+
+    Ok(value
+        .chunks_exact(4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect::<Vec<u32>>())
+}
+
+impl TryFrom<&[u8]> for Code {
+    type Error = ParserError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let layout = IsaLayout::default();
+        Code::parse(&words_from_bytes(value, &layout)?, &layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poseidon_row(layout: &IsaLayout, select: usize, g1: u32, g2: u32) -> Vec<u32> {
+        let mut row = vec![0u32; layout.row_width];
+        row[select] = 1;
+        row[layout.write_addr] = 8;
+        row[layout.poseidon_load_g1] = g1;
+        row[layout.poseidon_load_g2] = g2;
+        row
+    }
+
+    #[test]
+    fn poseidon_groups() {
+        let layout = IsaLayout::default();
+        for (select, is_load) in [
+            (layout.select_poseidon_load, true),
+            (layout.select_poseidon_store, false),
+        ] {
+            for (g1, g2, group) in [(0, 0, 0), (1, 0, 1), (0, 1, 2)] {
+                let code = Code::parse(&poseidon_row(&layout, select, g1, g2), &layout).unwrap();
+                let parsed = match code.0[0].0 {
+                    StructuredInstruction::POSEIDON_LOAD(_, group, ..) => Some((true, group)),
+                    StructuredInstruction::POSEIDON_STORE(group, _) => Some((false, group)),
+                    _ => None,
+                };
+                assert_eq!(parsed, Some((is_load, group)));
+            }
+        }
+    }
+
+    #[test]
+    fn poseidon2_rows() {
+        // no release is known to have these columns, so they are put after the others
+        let layout: IsaLayout = "row_width = 27
+select_poseidon2_load = 21
+select_poseidon2_full = 22
+select_poseidon2_partial = 23
+select_poseidon2_store = 24
+poseidon2_load_keep_upper_state = 25
+poseidon2_load_prep_full = 26"
+            .parse()
+            .unwrap();
+
+        let mut load = poseidon_row(&layout, 21, 1, 0);
+        load[25] = 1;
+        load[26] = 1;
+        for i in 0..8 {
+            load[layout.poseidon_load_operands + i] = i as u32 + 1;
+        }
+        let mut add_load = poseidon_row(&layout, 21, 0, 1);
+        add_load[layout.poseidon_load_keep_state] = 1;
+        add_load[layout.poseidon_do_mont] = 1;
+        let mut store = poseidon_row(&layout, 24, 0, 0);
+        store[layout.poseidon_do_mont] = 1;
+        let words = [
+            load,
+            add_load,
+            poseidon_row(&layout, 22, 0, 0),
+            poseidon_row(&layout, 23, 0, 0),
+            poseidon_row(&layout, 24, 1, 0),
+            store,
+        ]
+        .concat();
+
+        let r = |v: u32| v.into();
+        let expected = [
+            StructuredInstruction::POSEIDON2_LOAD(
+                1,
+                1,
+                1,
+                r(1),
+                r(2),
+                r(3),
+                r(4),
+                r(5),
+                r(6),
+                r(7),
+                r(8),
+            ),
+            StructuredInstruction::POSEIDON2_ADD_LOAD_FROM_MONTGOMERY(
+                0,
+                2,
+                r(0),
+                r(0),
+                r(0),
+                r(0),
+                r(0),
+                r(0),
+                r(0),
+                r(0),
+            ),
+            StructuredInstruction::POSEIDON2_FULL,
+            StructuredInstruction::POSEIDON2_PARTIAL,
+            StructuredInstruction::POSEIDON2_STORE(1, 8),
+            StructuredInstruction::POSEIDON2_STORE_TO_MONTGOMERY(0, 8),
+        ];
+        let code = Code::parse(&words, &layout).unwrap();
+        assert_eq!(code.0.len(), expected.len());
+        for ((insn, _), expected) in code.0.iter().zip(expected.iter()) {
+            assert!(insn == expected, "{} instead of {}", insn, expected);
+        }
+    }
+
+    #[test]
+    fn illegal_poseidon_groups() {
+        let layout = IsaLayout::default();
+        for select in [layout.select_poseidon_load, layout.select_poseidon_store] {
+            for (g1, g2) in [(1, 1), (2, 0), (0, 2), (0, u32::MAX), (u32::MAX, u32::MAX)] {
+                let mut words = vec![0u32; layout.row_width];
+                words[layout.select_macro_ops] = 1;
+                words[layout.macro_nop] = 1;
+                words.extend(poseidon_row(&layout, select, g1, g2));
+                match Code::parse(&words, &layout) {
+                    Err(ParserError::IllegalInstruction(location, field, _)) => {
+                        assert_eq!(location.row, 2);
+                        assert_eq!(location.byte_offset, layout.row_bytes());
+                        assert_eq!(field, IllegalField::PoseidonGroup);
                     }
+                    other => panic!("g1 = {}, g2 = {}: {:?}", g1, g2, other.err()),
                 }
-            } else if insn[SELECT_POSEIDON_FULL] == 1 {
-                out.push(StructuredInstruction::POSEIDON_FULL, global_state.line_no);
-            } else if insn[SELECT_POSEIDON_PARTIAL] == 1 {
-                out.push(
-                    StructuredInstruction::POSEIDON_PARTIAL,
-                    global_state.line_no,
-                );
-            } else if insn[SELECT_POSEIDON_STORE] == 1 {
-                let group = insn[POSEIDON_LOAD_G1] + insn[POSEIDON_LOAD_G2] * 2;
-                if insn[POSEIDON_DO_MONT] != 0 {
-                    out.push(
-                        StructuredInstruction::POSEIDON_STORE_TO_MONTGOMERY(
-                            group,
-                            insn[WRITE_ADDR],
-                        ),
-                        global_state.line_no,
-                    );
-                } else {
-                    out.push(
-                        StructuredInstruction::POSEIDON_STORE(group, insn[WRITE_ADDR]),
-                        global_state.line_no,
+            }
+        }
+    }
+
+    fn nop_row(layout: &IsaLayout) -> Vec<u32> {
+        let mut row = vec![0u32; layout.row_width];
+        row[layout.select_macro_ops] = 1;
+        row[layout.macro_nop] = 1;
+        row
+    }
+
+    #[test]
+    fn truncated_input() {
+        let layout = IsaLayout::default();
+        let bytes: Vec<u8> = [nop_row(&layout), nop_row(&layout)]
+            .concat()
+            .iter()
+            .flat_map(|w| w.to_le_bytes())
+            .collect();
+        // a partial last row, with or without a partial last word
+        for len in [10, 3, 84 + 40] {
+            let expected = Location {
+                row: len / 84 + 1,
+                byte_offset: len / 84 * 84,
+            };
+            match Code::try_from(&bytes[..len]) {
+                Err(ParserError::TruncatedInput(location, present, 84)) => {
+                    assert_eq!(location, expected);
+                    assert_eq!(present, len % 84);
+                }
+                other => panic!("{} bytes: {:?}", len, other.err()),
+            }
+        }
+
+        let words = [nop_row(&layout), nop_row(&layout)].concat();
+        match Code::parse(&words[..layout.row_width + 5], &layout) {
+            Err(ParserError::TruncatedInput(location, 20, 84)) => assert_eq!(
+                location,
+                Location {
+                    row: 2,
+                    byte_offset: 84
+                }
+            ),
+            other => panic!("{:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn arithmetic_overflow() {
+        let layout = IsaLayout::default();
+        let mut sha_fini = nop_row(&layout);
+        sha_fini[layout.macro_nop] = 0;
+        sha_fini[layout.macro_sha_fini] = 1;
+        sha_fini[layout.macro_operand_0] = 2;
+        let mut set_global = nop_row(&layout);
+        set_global[layout.macro_nop] = 0;
+        set_global[layout.macro_set_global] = 1;
+        set_global[layout.macro_operand_0] = u32::MAX - 2;
+        // the write address of the second slot is `u32::MAX + 1`
+        let mut micro = vec![0u32; layout.row_width];
+        micro[layout.select_micro_ops] = 1;
+        micro[layout.write_addr] = u32::MAX;
+
+        for (row, operand) in [
+            (sha_fini, "SHA_FINI output address"),
+            (set_global, "SET_GLOBAL input address"),
+            (micro, "micro op write address"),
+        ] {
+            let words = [nop_row(&layout), nop_row(&layout), row].concat();
+            match Code::parse(&words, &layout) {
+                Err(ParserError::ArithmeticOverflow(location, o)) => {
+                    assert_eq!(
+                        location,
+                        Location {
+                            row: 3,
+                            byte_offset: 2 * 84
+                        }
                     );
+                    assert_eq!(o, operand);
                 }
-            } else {
-                return Err(ParserError::IllegalInstruction(insn.to_vec()));
+                other => panic!("{}: {:?}", operand, other.err()),
             }
         }
 
-        return Ok(out);
+        // the smallest operands that do not overflow
+        let mut sha_fini = nop_row(&layout);
+        sha_fini[layout.macro_nop] = 0;
+        sha_fini[layout.macro_sha_fini] = 1;
+        sha_fini[layout.macro_operand_0] = 3;
+        let code = Code::parse(&sha_fini, &layout).unwrap();
+        assert!(code.0[0].0 == StructuredInstruction::SHA_FINI_START(0));
+    }
+
+    #[test]
+    fn nop_and_padding() {
+        let layout = IsaLayout::default();
+        let words = [nop_row(&layout), vec![0u32; layout.row_width]].concat();
+        let code = Code::parse(&words, &layout).unwrap();
+        assert_eq!(code.0.len(), 2);
+        assert!(code.0[0] == (StructuredInstruction::NOP, LineNo::new(1, 0)));
+        assert!(code.0[1] == (StructuredInstruction::PADDING, LineNo::new(2, 0)));
+
+        // the listing of both, assembled and decoded again
+        let text = format!("{}\n{}\n", code.0[0].0, code.0[1].0);
+        let assembled = Code::try_from(text.as_str()).unwrap();
+        let encoded = crate::encoder::encode(&assembled, &layout).unwrap();
+        assert_eq!(encoded, words);
+        let decoded = Code::parse(&encoded, &layout).unwrap();
+        assert!(decoded.0 == code.0);
+
+        // a row with a single column set and no selector is not padding
+        let mut words = vec![0u32; layout.row_width];
+        words[layout.macro_operand_0] = 1;
+        assert!(matches!(
+            Code::parse(&words, &layout),
+            Err(ParserError::IllegalInstruction(
+                _,
+                IllegalField::Selector,
+                _
+            ))
+        ));
     }
 }
@@ -0,0 +1,201 @@
+use crate::layout::IsaLayout;
+use crate::parser::{walk_row, Code, GlobalState, LineNo, ParserError};
+use crate::structures::StructuredInstruction;
+use std::io::{ErrorKind, Read};
+
+/// Decode the rows of a zkr file one at a time, as they are read from `R`.
+///
+/// Items are `(line_no, instruction)`, where a row of micro ops yields one item per op, each with
+/// its own slot. Only a single row is kept in memory, so a large file can be scanned straight
+/// from a zip entry. The iterator stops after the first error.
+pub struct ZkrReader<'a, R: Read> {
+    reader: R,
+    global_state: GlobalState<'a>,
+    bytes: Vec<u8>,
+    words: Vec<u32>,
+    pending: std::vec::IntoIter<(StructuredInstruction, LineNo)>,
+    done: bool,
+}
+
+impl<'a, R: Read> ZkrReader<'a, R> {
+    pub fn new(reader: R, layout: &'a IsaLayout) -> Self {
+        Self {
+            reader,
+            global_state: GlobalState {
+                layout,
+                sha_init_pos: 0,
+                sha_fini_pos: 0,
+                row: 0,
+                slot: 0,
+            },
+            bytes: vec![0u8; layout.row_bytes()],
+            words: vec![0u32; layout.row_width],
+            pending: Vec::new().into_iter(),
+            done: false,
+        }
+    }
+
+    // fill `self.bytes` with the next row, `false` if the input ends right before it
+    fn read_row(&mut self) -> Result<bool, ParserError> {
+        let mut len = 0;
+        while len < self.bytes.len() {
+            match self.reader.read(&mut self.bytes[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(ParserError::Io(self.global_state.location(), e.to_string())),
+            }
+        }
+
+        if len == 0 {
+            Ok(false)
+        } else if len < self.bytes.len() {
+            Err(ParserError::TruncatedInput(
+                self.global_state.location(),
+                len,
+                self.bytes.len(),
+            ))
+        } else {
+            Ok(true)
+        }
+    }
+
+    fn next_row(&mut self) -> Result<bool, ParserError> {
+        self.global_state.row += 1;
+        if !self.read_row()? {
+            return Ok(false);
+        }
+
+        for (word, b) in self.words.iter_mut().zip(self.bytes.chunks_exact(4)) {
+            *word = u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+        }
+
+        let mut out = Code::default();
+        walk_row(&mut self.global_state, &mut out, &self.words)?;
+        self.pending = out.0.into_iter();
+        Ok(true)
+    }
+}
+
+impl<R: Read> Iterator for ZkrReader<'_, R> {
+    type Item = Result<(LineNo, StructuredInstruction), ParserError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((insn, line_no)) = self.pending.next() {
+                return Some(Ok((line_no, insn)));
+            }
+            if self.done {
+                return None;
+            }
+            match self.next_row() {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.done = true;
+                    return None;
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read> std::iter::FusedIterator for ZkrReader<'_, R> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MICRO_ADD, MICRO_CONST, MICRO_MIX_RNG};
+
+    // hands out at most 7 bytes at a time, so that rows arrive in pieces
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(7).min(self.0.len());
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    fn macro_row(l: &IsaLayout, select: usize, operand_0: u32) -> Vec<u32> {
+        let mut row = vec![0u32; l.row_width];
+        row[l.select_macro_ops] = 1;
+        row[select] = 1;
+        row[l.macro_operand_0] = operand_0;
+        row
+    }
+
+    fn words(l: &IsaLayout) -> Vec<u32> {
+        let mut micro = vec![0u32; l.row_width];
+        micro[l.select_micro_ops] = 1;
+        micro[l.write_addr] = 4;
+        for (slot, base) in [
+            [MICRO_CONST, 3, 0, 0],
+            [MICRO_ADD, 4, 4, 0],
+            [MICRO_MIX_RNG, 4, 5, 1],
+        ]
+        .iter()
+        .zip(l.micro_slots)
+        {
+            micro[base..base + 4].copy_from_slice(slot);
+        }
+        let mut rows = vec![macro_row(l, l.macro_wom_init, 0), micro];
+        // the reader keeps the position within the init and fini rows across rows
+        rows.extend(vec![macro_row(l, l.macro_sha_init, 0); 4]);
+        rows.extend(vec![macro_row(l, l.macro_sha_fini, 10); 4]);
+        rows.push(vec![0u32; l.row_width]);
+        rows.concat()
+    }
+
+    fn bytes(words: &[u32]) -> Vec<u8> {
+        words.iter().flat_map(|w| w.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn same_items_as_parse() {
+        let l = IsaLayout::default();
+        let words = words(&l);
+        let code = Code::parse(&words, &l).unwrap();
+        let items: Vec<(LineNo, StructuredInstruction)> =
+            ZkrReader::new(Trickle(&bytes(&words)), &l)
+                .collect::<Result<_, _>>()
+                .unwrap();
+
+        assert_eq!(items.len(), code.0.len());
+        assert_eq!(items.len(), 1 + 3 + 4 + 4 + 1);
+        for ((line_no, insn), (expected, expected_line_no)) in items.iter().zip(code.0.iter()) {
+            assert_eq!(line_no, expected_line_no);
+            assert!(insn == expected, "{} instead of {}", insn, expected);
+        }
+        assert_eq!(items[3].0, LineNo::new(2, 2));
+        assert!(items[4].1 == StructuredInstruction::SHA_INIT_START);
+        assert!(items[8].1 == StructuredInstruction::SHA_FINI_START(7));
+        assert!(items[9].1 == StructuredInstruction::SHA_FINI_PADDING);
+    }
+
+    #[test]
+    fn truncated_tail() {
+        let l = IsaLayout::default();
+        let mut bytes = bytes(&words(&l));
+        let rows = bytes.len() / l.row_bytes();
+        bytes.extend([1, 2, 3, 4, 5]);
+
+        let mut reader = ZkrReader::new(Trickle(&bytes), &l);
+        let items: Vec<_> = reader.by_ref().collect();
+        assert_eq!(items.len(), 1 + 3 + 4 + 4 + 1 + 1);
+        assert!(items[..items.len() - 1].iter().all(|item| item.is_ok()));
+        match items.last().unwrap() {
+            Err(ParserError::TruncatedInput(location, 5, 84)) => {
+                assert_eq!(location.row, rows + 1);
+                assert_eq!(location.byte_offset, rows * l.row_bytes());
+            }
+            other => panic!("{:?}", other.as_ref().err()),
+        }
+        assert!(reader.next().is_none());
+    }
+}
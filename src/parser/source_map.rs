@@ -0,0 +1,209 @@
+use crate::math::ExtensionField;
+use crate::parser::{Code, LineNo};
+use std::io::Write;
+
+/// Write `code` as a listing with one numbered instruction per line, as `decompile` writes a
+/// .ll0 file.
+pub fn write_listing<E: ExtensionField>(
+    code: &Code<E>,
+    writer: &mut impl Write,
+) -> std::io::Result<()> {
+    for (i, (insn, _)) in code.0.iter().enumerate() {
+        writer.write_fmt(format_args!("{}: {}\n", i + 1, insn))?;
+    }
+    Ok(())
+}
+
+/// Write one line for each line of the listing of `code`, giving the rows and micro op slots of
+/// the zkr it comes from.
+pub fn write_source_map<E>(code: &Code<E>, writer: &mut impl Write) -> std::io::Result<()> {
+    writer.write_all(b"line,row,slot,end_row,end_slot\n")?;
+    for (i, (_, line_no)) in code.0.iter().enumerate() {
+        writer.write_fmt(format_args!(
+            "{},{},{},{},{}\n",
+            i + 1,
+            line_no.row,
+            line_no.slot,
+            line_no.end_row,
+            line_no.end_slot
+        ))?;
+    }
+    Ok(())
+}
+
+/// Give each instruction of a listing read back with the assembler the rows that the source map
+/// records for its line, so that it speaks of the same rows as the zkr.
+pub fn apply_source_map<E>(code: &mut Code<E>, map: &str) -> anyhow::Result<()> {
+    let mut rows = Vec::new();
+    for line in map.lines().skip(1) {
+        let fields = line
+            .split(',')
+            .map(str::parse)
+            .collect::<Result<Vec<usize>, _>>()?;
+        anyhow::ensure!(fields.len() == 5, "malformed source map line {}", line);
+        rows.push(LineNo {
+            row: fields[1],
+            slot: fields[2],
+            end_row: fields[3],
+            end_slot: fields[4],
+        });
+    }
+    for (_, line_no) in code.0.iter_mut() {
+        if let Some(mapped) = line_no.row.checked_sub(1).and_then(|i| rows.get(i)) {
+            *line_no = *mapped;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pass::manager::PassManager;
+    use crate::structures::StructuredInstruction as I;
+
+    #[test]
+    fn spans() {
+        let span = LineNo::new(3, 1).to(&LineNo::new(5, 2));
+        assert_eq!(
+            (span.row, span.slot, span.end_row, span.end_slot),
+            (3, 1, 5, 2)
+        );
+        assert!(span.is_span());
+        assert!(!LineNo::new(3, 1).is_span());
+        assert!(LineNo::new(3, 1).to(&LineNo::new(3, 2)).is_span());
+        assert!(!LineNo::new(3, 1).to(&LineNo::new(3, 1)).is_span());
+        // the end of a span is the end of the other span
+        assert_eq!(
+            LineNo::new(1, 0).to(&span),
+            LineNo::new(1, 0).to(&LineNo::new(5, 2))
+        );
+        assert_eq!(span.to_string(), "3.1..=5.2");
+        assert_eq!(LineNo::new(4, 0).to_string(), "4");
+    }
+
+    // the rows of a program as `Code::parse` gives them, with the micro ops in their slots
+    fn program() -> Code {
+        let load = |group| {
+            I::POSEIDON_ADD_LOAD(
+                0,
+                group,
+                1.into(),
+                2.into(),
+                3.into(),
+                4.into(),
+                5.into(),
+                6.into(),
+                7.into(),
+                8.into(),
+            )
+        };
+        let mut code = Code::default();
+        code.push(I::WOM_INIT, LineNo::new(1, 0));
+        code.push(I::READ_IOP_HEADER(4, 2), LineNo::new(2, 0));
+        code.push(I::READ_IOP_BODY(1), LineNo::new(2, 1));
+        code.push(I::READ_IOP_BODY(2), LineNo::new(2, 2));
+        code.push(I::READ_IOP_BODY(3), LineNo::new(3, 0));
+        code.push(I::READ_IOP_BODY(4), LineNo::new(3, 1));
+        code.push(I::CONST(5, 1, 0), LineNo::new(3, 2));
+        for group in 0..3 {
+            code.push(load(group), LineNo::new(4 + group as usize, 0));
+        }
+        // rows 7..=12
+        for (i, insn) in [
+            I::POSEIDON_FULL,
+            I::POSEIDON_FULL,
+            I::POSEIDON_PARTIAL,
+            I::POSEIDON_FULL,
+            I::POSEIDON_FULL,
+            I::POSEIDON_STORE(0, 10),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            code.push(insn, LineNo::new(7 + i, 0));
+        }
+        // rows 13..=60
+        for row in 13..=60 {
+            code.push(I::SHA_MIX, LineNo::new(row, 0));
+        }
+        code.push(I::WOM_FINI, LineNo::new(61, 0));
+        code
+    }
+
+    fn merged() -> Code {
+        let mut manager = PassManager::default();
+        manager
+            .set_pipeline(&["merge-iop", "sha", "poseidon"])
+            .unwrap();
+        let mut code = program();
+        manager.run(&mut code).unwrap();
+        code.0.retain(|(insn, _)| *insn != I::__DELETE__);
+        code
+    }
+
+    #[test]
+    fn merged_spans() {
+        let code = merged();
+        let expected = [
+            (I::WOM_INIT, LineNo::new(1, 0)),
+            (I::READ_IOP_HEADER(4, 2), LineNo::new(2, 0)),
+            // from the second slot of row 2 to the second slot of row 3
+            (
+                I::__READ_IOP_BODY_BATCH__(1, 5),
+                LineNo::new(2, 1).to(&LineNo::new(3, 1)),
+            ),
+            (I::CONST(5, 1, 0), LineNo::new(3, 2)),
+        ];
+        for (entry, expected) in code.0.iter().zip(expected.iter()) {
+            assert!(entry == expected, "{} at {}", entry.0, entry.1);
+        }
+        assert!(code.0[7].0 == I::__POSEIDON_PERMUTE_STORE__(0, 10));
+        assert_eq!(code.0[7].1, LineNo::new(7, 0).to(&LineNo::new(12, 0)));
+        assert!(code.0[8].0 == I::__SHA_MIX_48__);
+        assert_eq!(code.0[8].1, LineNo::new(13, 0).to(&LineNo::new(60, 0)));
+        assert_eq!(code.0.len(), 10);
+    }
+
+    #[test]
+    fn map_follows_listing() {
+        let code = merged();
+        let mut listing = Vec::new();
+        write_listing(&code, &mut listing).unwrap();
+        let mut map = Vec::new();
+        write_source_map(&code, &mut map).unwrap();
+        let (listing, map) = (
+            String::from_utf8(listing).unwrap(),
+            String::from_utf8(map).unwrap(),
+        );
+
+        let map_lines: Vec<&str> = map.lines().skip(1).collect();
+        assert_eq!(map_lines.len(), listing.lines().count());
+        for ((listing_line, map_line), (_, line_no)) in
+            listing.lines().zip(map_lines.iter()).zip(code.0.iter())
+        {
+            let (number, _) = listing_line.split_once(": ").unwrap();
+            assert_eq!(
+                *map_line,
+                format!(
+                    "{},{},{},{},{}",
+                    number, line_no.row, line_no.slot, line_no.end_row, line_no.end_slot
+                )
+            );
+        }
+
+        // the listing read back, with the rows of the zkr
+        let mut reloaded = Code::try_from(listing.as_str()).unwrap();
+        apply_source_map(&mut reloaded, &map).unwrap();
+        assert_eq!(reloaded.0.len(), code.0.len());
+        for ((insn, line_no), (expected, expected_line_no)) in reloaded.0.iter().zip(code.0.iter())
+        {
+            assert!(insn == expected, "{} instead of {}", insn, expected);
+            assert_eq!(line_no, expected_line_no);
+        }
+
+        assert!(
+            apply_source_map(&mut reloaded, "line,row,slot,end_row,end_slot\n1,2,3\n").is_err()
+        );
+    }
+}
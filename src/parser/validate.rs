@@ -0,0 +1,359 @@
+use crate::layout::IsaLayout;
+use crate::parser::error::Location;
+use crate::{MICRO_EXTRACT, MICRO_INV, MICRO_MIX_RNG, MICRO_READ_IOP_BODY, MICRO_SELECT};
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationKind {
+    // the number of row selectors set, which should be exactly one
+    SelectorCount(usize),
+    // the number of macro op selectors set, which should be exactly one
+    MacroSelectorCount(usize),
+    // a selector or flag column that holds something other than 0 or 1
+    NonBinaryFlag(usize, u32),
+    // a column that the instruction does not use but is not zero
+    NonZeroUnusedColumn(usize, u32),
+    // POSEIDON_LOAD_G0, POSEIDON_LOAD_G1, POSEIDON_LOAD_G2 do not select exactly one group
+    InconsistentPoseidonGroup(u32, u32, u32),
+    // the slot (0, 1 or 2) and the opcode found there
+    InvalidMicroOpcode(usize, u32),
+    // the slot (0, 1 or 2) of a micro op that cannot be in it, and its opcode
+    InvalidMicroSlot(usize, u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub location: Location,
+    pub kind: ViolationKind,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}: ", self.location))?;
+        match &self.kind {
+            ViolationKind::SelectorCount(n) => {
+                f.write_fmt(format_args!("{} row selectors are set instead of 1", n))
+            }
+            ViolationKind::MacroSelectorCount(n) => f.write_fmt(format_args!(
+                "{} macro op selectors are set instead of 1",
+                n
+            )),
+            ViolationKind::NonBinaryFlag(column, v) => {
+                f.write_fmt(format_args!("column {} is a flag but holds {}", column, v))
+            }
+            ViolationKind::NonZeroUnusedColumn(column, v) => {
+                f.write_fmt(format_args!("column {} is unused but holds {}", column, v))
+            }
+            ViolationKind::InconsistentPoseidonGroup(g0, g1, g2) => f.write_fmt(format_args!(
+                "Poseidon group flags ({}, {}, {}) do not select exactly one group",
+                g0, g1, g2
+            )),
+            ViolationKind::InvalidMicroOpcode(slot, op) => f.write_fmt(format_args!(
+                "slot {} holds the unknown micro opcode {}",
+                slot, op
+            )),
+            ViolationKind::InvalidMicroSlot(slot, op) => f.write_fmt(format_args!(
+                "slot {} cannot hold the micro opcode {}",
+                slot, op
+            )),
+        }
+    }
+}
+
+struct RowChecker<'a> {
+    layout: &'a IsaLayout,
+    insn: &'a [u32],
+    location: Location,
+    out: &'a mut Vec<Violation>,
+}
+
+impl RowChecker<'_> {
+    fn report(&mut self, kind: ViolationKind) {
+        self.out.push(Violation {
+            location: self.location,
+            kind,
+        });
+    }
+
+    fn flags(&mut self, columns: &[usize]) -> usize {
+        let mut count = 0;
+        for &column in columns {
+            match self.insn[column] {
+                0 => {}
+                1 => count += 1,
+                v => self.report(ViolationKind::NonBinaryFlag(column, v)),
+            }
+        }
+        count
+    }
+
+    fn unused(&mut self, columns: &[usize]) {
+        for &column in columns {
+            if self.insn[column] != 0 {
+                self.report(ViolationKind::NonZeroUnusedColumn(
+                    column,
+                    self.insn[column],
+                ));
+            }
+        }
+    }
+
+    // the columns every row of the given kind may use: the write address and the selectors
+    fn common_columns(&self, extra: &[usize]) -> Vec<usize> {
+        let mut used = vec![self.layout.write_addr];
+        used.extend(self.layout.selectors());
+        used.extend(extra);
+        used
+    }
+
+    fn check_macro(&mut self) {
+        let l = self.layout;
+        let count = self.flags(&l.macro_selectors());
+        if count != 1 {
+            self.report(ViolationKind::MacroSelectorCount(count));
+            return;
+        }
+
+        let mut used = self.common_columns(&l.macro_selectors());
+        used.extend([l.macro_operand_0, l.macro_operand_1, l.macro_operand_2]);
+        self.unused(&l.columns_outside(&used));
+        // only the bit operations write, the other macro ops leave the write address unused
+        if self.insn[l.macro_bit_and_elem] != 1 && self.insn[l.macro_bit_op_shorts] != 1 {
+            self.unused(&[l.write_addr]);
+        }
+        if self.insn[l.macro_bit_and_elem] == 1 || self.insn[l.macro_set_global] == 1 {
+            self.unused(&[l.macro_operand_2]);
+        } else if self.insn[l.macro_sha_load] == 1 {
+            self.unused(&[l.macro_operand_1]);
+            self.flags(&[l.macro_operand_2]);
+        } else if self.insn[l.macro_sha_fini] == 1 {
+            self.unused(&[l.macro_operand_1, l.macro_operand_2]);
+        } else if self.insn[l.macro_bit_op_shorts] == 1 {
+            self.flags(&[l.macro_operand_2]);
+        } else {
+            self.unused(&[l.macro_operand_0, l.macro_operand_1, l.macro_operand_2]);
+        }
+    }
+
+    fn check_micro(&mut self) {
+        let slots = self.layout.micro_slots;
+        let used = self.common_columns(
+            &slots
+                .iter()
+                .flat_map(|base| *base..*base + 4)
+                .collect::<Vec<usize>>(),
+        );
+        self.unused(&self.layout.columns_outside(&used));
+        for (slot, base) in slots.into_iter().enumerate() {
+            let op = self.insn[base];
+            if op > MICRO_EXTRACT {
+                self.report(ViolationKind::InvalidMicroOpcode(slot, op));
+            } else if op == MICRO_MIX_RNG && self.insn[base + 3] != 0 && slot == 0 {
+                // mixing in the previous value reads the output of the slot before, which the
+                // first slot does not have, wherever the row writes
+                self.report(ViolationKind::InvalidMicroSlot(slot, op));
+            } else if op == MICRO_READ_IOP_BODY {
+                self.unused(&[base + 1, base + 2, base + 3]);
+            } else if op == MICRO_EXTRACT {
+                self.flags(&[base + 2, base + 3]);
+            } else if op != MICRO_MIX_RNG && op != MICRO_SELECT {
+                if op == MICRO_INV {
+                    self.flags(&[base + 2]);
+                }
+                self.unused(&[base + 3]);
+            }
+        }
+    }
+
+    fn check_poseidon_group(&mut self) {
+        let l = self.layout;
+        let (g0, g1, g2) = (
+            self.insn[l.poseidon_load_g0],
+            self.insn[l.poseidon_load_g1],
+            self.insn[l.poseidon_load_g2],
+        );
+        if self.flags(&[l.poseidon_load_g0, l.poseidon_load_g1, l.poseidon_load_g2]) != 1 {
+            self.report(ViolationKind::InconsistentPoseidonGroup(g0, g1, g2));
+        }
+    }
+
+    fn check(&mut self) {
+        let l = self.layout;
+        let count = self.flags(&l.selectors());
+        if count == 0 && self.insn.iter().all(|v| *v == 0) {
+            // a padding row
+            return;
+        }
+        if count != 1 {
+            self.report(ViolationKind::SelectorCount(count));
+            return;
+        }
+
+        let group = [l.poseidon_load_g0, l.poseidon_load_g1, l.poseidon_load_g2];
+        let is_set = |column: Option<usize>| column.is_some_and(|c| self.insn[c] == 1);
+        let poseidon2_load = is_set(l.select_poseidon2_load);
+        let poseidon2_store = is_set(l.select_poseidon2_store);
+        if self.insn[l.select_macro_ops] == 1 {
+            self.check_macro();
+        } else if self.insn[l.select_micro_ops] == 1 {
+            self.check_micro();
+        } else if self.insn[l.select_poseidon_load] == 1 || poseidon2_load {
+            let mut flags = vec![l.poseidon_do_mont, l.poseidon_load_keep_state];
+            if poseidon2_load {
+                flags.extend(l.poseidon2_load_keep_upper_state);
+                flags.extend(l.poseidon2_load_prep_full);
+            } else {
+                flags.push(l.poseidon_load_add_consts);
+            }
+            self.flags(&flags);
+            let mut used = self.common_columns(&group);
+            used.extend(flags);
+            used.extend(l.poseidon_load_operands..l.poseidon_load_operands + 8);
+            self.unused(&l.columns_outside(&used));
+            self.check_poseidon_group();
+        } else if self.insn[l.select_poseidon_store] == 1 || poseidon2_store {
+            self.flags(&[l.poseidon_do_mont]);
+            let mut used = self.common_columns(&group);
+            used.push(l.poseidon_do_mont);
+            self.unused(&l.columns_outside(&used));
+            self.check_poseidon_group();
+        } else {
+            self.unused(&l.columns_outside(&self.common_columns(&[])));
+        }
+    }
+}
+
+/// Check that every row has the shape the decoder expects, and report every violation.
+///
+/// The regular decoder takes the first selector equal to 1 and ignores the columns it does
+/// not read, so these rows would decode without any warning.
+pub fn validate(value: &[u32], layout: &IsaLayout) -> Vec<Violation> {
+    let mut out = Vec::new();
+    for (idx, insn) in value.chunks_exact(layout.row_width).enumerate() {
+        RowChecker {
+            layout,
+            insn,
+            location: Location {
+                row: idx + 1,
+                byte_offset: idx * layout.row_bytes(),
+            },
+            out: &mut out,
+        }
+        .check();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Code, ParserError};
+    use crate::MICRO_CONST;
+
+    fn micro_row(l: &IsaLayout, write_addr: u32, slots: [[u32; 4]; 3]) -> Vec<u32> {
+        let mut row = vec![0u32; l.row_width];
+        row[l.write_addr] = write_addr;
+        row[l.select_micro_ops] = 1;
+        for (slot, base) in slots.iter().zip(l.micro_slots) {
+            row[base..base + 4].copy_from_slice(slot);
+        }
+        row
+    }
+
+    fn macro_row(l: &IsaLayout, select: usize, write_addr: u32) -> Vec<u32> {
+        let mut row = vec![0u32; l.row_width];
+        row[l.write_addr] = write_addr;
+        row[l.select_macro_ops] = 1;
+        row[select] = 1;
+        row
+    }
+
+    fn nop_row(l: &IsaLayout) -> Vec<u32> {
+        macro_row(l, l.macro_nop, 0)
+    }
+
+    fn poseidon_load_row(l: &IsaLayout, groups: [u32; 3]) -> Vec<u32> {
+        let mut row = vec![0u32; l.row_width];
+        row[l.select_poseidon_load] = 1;
+        row[l.poseidon_load_g0] = groups[0];
+        row[l.poseidon_load_g1] = groups[1];
+        row[l.poseidon_load_g2] = groups[2];
+        row
+    }
+
+    const CONST: [u32; 4] = [MICRO_CONST, 1, 0, 0];
+
+    #[test]
+    fn well_formed_rows() {
+        let l = IsaLayout::default();
+        let words = [
+            nop_row(&l),
+            micro_row(&l, 8, [CONST, [MICRO_MIX_RNG, 8, 8, 1], CONST]),
+            micro_row(&l, 0, [[MICRO_MIX_RNG, 8, 8, 0], CONST, CONST]),
+            macro_row(&l, l.macro_bit_and_elem, 20),
+            macro_row(&l, l.macro_bit_op_shorts, 21),
+            poseidon_load_row(&l, [0, 1, 0]),
+            vec![0u32; l.row_width],
+        ]
+        .concat();
+        assert!(validate(&words, &l).is_empty());
+        assert!(Code::parse_strict(&words, &l).is_ok());
+    }
+
+    #[test]
+    fn malformed_rows() {
+        let l = IsaLayout::default();
+        let mut two_selectors = nop_row(&l);
+        two_selectors[l.select_micro_ops] = 1;
+        let mut nop_with_operand = nop_row(&l);
+        nop_with_operand[l.macro_operand_0] = 5;
+        let mut micro_with_unused = micro_row(&l, 8, [CONST, CONST, CONST]);
+        micro_with_unused[l.row_width - 1] = 9;
+
+        let words = [
+            two_selectors,
+            nop_with_operand,
+            poseidon_load_row(&l, [0, 1, 1]),
+            micro_row(&l, 8, [CONST, [11, 0, 0, 0], CONST]),
+            micro_row(&l, 0, [[MICRO_MIX_RNG, 1, 2, 1], CONST, CONST]),
+            micro_row(&l, 8, [CONST, [MICRO_INV, 1, 2, 0], CONST]),
+            micro_with_unused,
+            // mixing in the previous value from the first slot, wherever the row writes
+            micro_row(&l, 11, [[MICRO_MIX_RNG, 8, 8, 1], CONST, CONST]),
+            // rows that do not write
+            macro_row(&l, l.macro_nop, 7),
+            macro_row(&l, l.macro_wom_init, 7),
+            macro_row(&l, l.macro_wom_fini, 7),
+            macro_row(&l, l.macro_sha_init, 7),
+            macro_row(&l, l.macro_sha_mix, 7),
+            macro_row(&l, l.macro_set_global, 7),
+        ]
+        .concat();
+
+        let at = |row: usize, kind: ViolationKind| Violation {
+            location: Location {
+                row,
+                byte_offset: (row - 1) * l.row_bytes(),
+            },
+            kind,
+        };
+        let mut expected = vec![
+            at(1, ViolationKind::SelectorCount(2)),
+            at(2, ViolationKind::NonZeroUnusedColumn(l.macro_operand_0, 5)),
+            at(3, ViolationKind::InconsistentPoseidonGroup(0, 1, 1)),
+            at(4, ViolationKind::InvalidMicroOpcode(1, 11)),
+            at(5, ViolationKind::InvalidMicroSlot(0, MICRO_MIX_RNG)),
+            at(6, ViolationKind::NonBinaryFlag(l.micro_slots[1] + 2, 2)),
+            at(7, ViolationKind::NonZeroUnusedColumn(l.row_width - 1, 9)),
+            at(8, ViolationKind::InvalidMicroSlot(0, MICRO_MIX_RNG)),
+        ];
+        expected.extend(
+            (9..=14).map(|row| at(row, ViolationKind::NonZeroUnusedColumn(l.write_addr, 7))),
+        );
+        assert_eq!(validate(&words, &l), expected);
+        match Code::parse_strict(&words, &l) {
+            Err(ParserError::StrictViolations(violations)) => assert_eq!(violations, expected),
+            other => panic!("{:?}", other.err()),
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::math::{Fp, Fp4};
+use crate::math::{ExtensionField, Field, Fp4};
 use crate::parser::Code;
 use crate::pass::Pass;
 use crate::structures::{ReadAddr, StructuredInstruction};
@@ -7,20 +7,29 @@ use std::collections::HashMap;
 use std::rc::Rc;
 
 #[derive(Clone)]
-pub enum RedirectionEntry {
-    Const(Fp4),
+pub enum RedirectionEntry<E = Fp4> {
+    Const(E),
     RefSub(u32, u32),
 }
 
 pub struct ConstPass;
 
-impl Pass for ConstPass {
-    fn pass(code: &mut Code) -> anyhow::Result<()> {
-        let mem = Rc::new(RefCell::new(HashMap::<u32, RedirectionEntry>::new()));
-        mem.borrow_mut()
-            .insert(0, RedirectionEntry::Const(Fp4::default()));
+// the element of the base field `v`, reduced
+fn base<E: ExtensionField>(v: u64) -> E {
+    E::from_base(E::Base::from_u64(v))
+}
+
+// the canonical value of a component of a constant, as the bit operations read it
+fn component<E: ExtensionField>(v: &E, idx: usize) -> u64 {
+    v.coeff(idx).as_u64()
+}
+
+impl<E: ExtensionField> Pass<E> for ConstPass {
+    fn pass(code: &mut Code<E>) -> anyhow::Result<()> {
+        let mem = Rc::new(RefCell::new(HashMap::<u32, RedirectionEntry<E>>::new()));
+        mem.borrow_mut().insert(0, RedirectionEntry::Const(E::ZERO));
 
-        let refresh_and_get_constant = |r: &mut ReadAddr| match r {
+        let refresh_and_get_constant = |r: &mut ReadAddr<E>| match r {
             ReadAddr::Ref(x) => {
                 if mem.borrow().contains_key(x) {
                     let m = mem.borrow().get(x).unwrap().clone();
@@ -43,17 +52,7 @@ impl Pass for ConstPass {
                     let m = (mem.borrow().get(x).unwrap()).clone();
                     match m {
                         RedirectionEntry::Const(val) => {
-                            let new_val = if *idx == 0 {
-                                Fp4::new(val.0.clone(), Fp::ZERO, Fp::ZERO, Fp::ZERO)
-                            } else if *idx == 1 {
-                                Fp4::new(val.1.clone(), Fp::ZERO, Fp::ZERO, Fp::ZERO)
-                            } else if *idx == 2 {
-                                Fp4::new(val.2.clone(), Fp::ZERO, Fp::ZERO, Fp::ZERO)
-                            } else {
-                                Fp4::new(val.3.clone(), Fp::ZERO, Fp::ZERO, Fp::ZERO)
-                            };
-
-                            *r = ReadAddr::Const(new_val);
+                            *r = ReadAddr::Const(E::from_base(val.coeff(*idx as usize)));
                             Some(val.clone())
                         }
                         RedirectionEntry::RefSub(val, val_idx) => {
@@ -61,8 +60,8 @@ impl Pass for ConstPass {
                                 *r = ReadAddr::RefSub(val, val_idx);
                                 None
                             } else {
-                                *r = ReadAddr::Const(Fp4::default());
-                                Some(Fp4::default())
+                                *r = ReadAddr::Const(E::ZERO);
+                                Some(E::ZERO)
                             }
                         }
                     }
@@ -78,17 +77,10 @@ impl Pass for ConstPass {
                 StructuredInstruction::BIT_AND_ELEM(w, r1, r2) => {
                     let d1 = refresh_and_get_constant(r1);
                     let d2 = refresh_and_get_constant(r2);
-                    if d1.is_some() && d2.is_some() {
-                        let d1 = d1.unwrap();
-                        let d2 = d2.unwrap();
+                    if let (Some(d1), Some(d2)) = (d1, d2) {
                         mem.borrow_mut().insert(
                             *w,
-                            RedirectionEntry::Const(Fp4::new(
-                                &d1.0 & &d2.0,
-                                Fp::ZERO,
-                                Fp::ZERO,
-                                Fp::ZERO,
-                            )),
+                            RedirectionEntry::Const(base(component(&d1, 0) & component(&d2, 0))),
                         );
                         *insn = StructuredInstruction::__DELETE__;
                     }
@@ -96,16 +88,12 @@ impl Pass for ConstPass {
                 StructuredInstruction::BIT_AND_SHORTS(w, r1, r2) => {
                     let d1 = refresh_and_get_constant(r1);
                     let d2 = refresh_and_get_constant(r2);
-                    if d1.is_some() && d2.is_some() {
-                        let d1 = d1.unwrap();
-                        let d2 = d2.unwrap();
+                    if let (Some(d1), Some(d2)) = (d1, d2) {
                         mem.borrow_mut().insert(
                             *w,
-                            RedirectionEntry::Const(Fp4::new(
-                                &(&d1.0 & &d2.0) + &((&d1.1 & &d2.1) << 16),
-                                Fp::ZERO,
-                                Fp::ZERO,
-                                Fp::ZERO,
+                            RedirectionEntry::Const(base(
+                                (component(&d1, 0) & component(&d2, 0))
+                                    + ((component(&d1, 1) & component(&d2, 1)) << 16),
                             )),
                         );
                         *insn = StructuredInstruction::__DELETE__;
@@ -114,17 +102,13 @@ impl Pass for ConstPass {
                 StructuredInstruction::BIT_XOR_SHORTS(w, r1, r2) => {
                     let d1 = refresh_and_get_constant(r1);
                     let d2 = refresh_and_get_constant(r2);
-                    if d1.is_some() && d2.is_some() {
-                        let d1 = d1.unwrap();
-                        let d2 = d2.unwrap();
+                    if let (Some(d1), Some(d2)) = (d1, d2) {
                         mem.borrow_mut().insert(
                             *w,
-                            RedirectionEntry::Const(Fp4::new(
-                                &d1.0 ^ &d2.0,
-                                &d1.1 ^ &d2.1,
-                                Fp::ZERO,
-                                Fp::ZERO,
-                            )),
+                            RedirectionEntry::Const(E::from_coeffs(&[
+                                E::Base::from_u64(component(&d1, 0) ^ component(&d2, 0)),
+                                E::Base::from_u64(component(&d1, 1) ^ component(&d2, 1)),
+                            ])),
                         );
                         *insn = StructuredInstruction::__DELETE__;
                     }
@@ -144,88 +128,64 @@ impl Pass for ConstPass {
                 StructuredInstruction::CONST(w, v1, v2) => {
                     mem.borrow_mut().insert(
                         *w,
-                        RedirectionEntry::Const(Fp4::new(Fp(*v1), Fp(*v2), Fp::ZERO, Fp::ZERO)),
+                        RedirectionEntry::Const(E::from_coeffs(&[
+                            E::Base::from_u64(*v1 as u64),
+                            E::Base::from_u64(*v2 as u64),
+                        ])),
                     );
                     *insn = StructuredInstruction::__DELETE__;
                 }
                 StructuredInstruction::ADD(w, r1, r2) => {
                     let d1 = refresh_and_get_constant(r1);
                     let d2 = refresh_and_get_constant(r2);
-                    if d1.is_some() && d2.is_some() {
-                        let d1 = d1.unwrap();
-                        let d2 = d2.unwrap();
+                    if let (Some(d1), Some(d2)) = (d1.clone(), d2.clone()) {
                         mem.borrow_mut()
-                            .insert(*w, RedirectionEntry::Const(&d1 + &d2));
+                            .insert(*w, RedirectionEntry::Const(d1 + d2));
                         *insn = StructuredInstruction::__DELETE__;
-                    } else if d1.is_some() && d1.unwrap() == Fp4::default() {
+                    } else if d1 == Some(E::ZERO) {
                         *insn = StructuredInstruction::__MOV__(*w, r2.clone());
-                    } else if d2.is_some() && d2.unwrap() == Fp4::default() {
+                    } else if d2 == Some(E::ZERO) {
                         *insn = StructuredInstruction::__MOV__(*w, r1.clone());
                     }
                 }
                 StructuredInstruction::SUB(w, r1, r2) => {
                     let d1 = refresh_and_get_constant(r1);
                     let d2 = refresh_and_get_constant(r2);
-                    if d1.is_some() && d2.is_some() {
-                        let d1 = d1.unwrap();
-                        let d2 = d2.unwrap();
+                    if let (Some(d1), Some(d2)) = (d1.clone(), d2.clone()) {
                         mem.borrow_mut()
-                            .insert(*w, RedirectionEntry::Const(&d1 - &d2));
+                            .insert(*w, RedirectionEntry::Const(d1 - d2));
                         *insn = StructuredInstruction::__DELETE__;
-                    } else if d2.is_some() && d2.unwrap() == Fp4::default() {
+                    } else if d2 == Some(E::ZERO) {
                         *insn = StructuredInstruction::__MOV__(*w, r1.clone());
                     }
                 }
                 StructuredInstruction::MUL(w, r1, r2) => {
                     let d1 = refresh_and_get_constant(r1);
                     let d2 = refresh_and_get_constant(r2);
-                    if d1.is_some() && d2.is_some() {
-                        let d1 = d1.unwrap();
-                        let d2 = d2.unwrap();
-                        mem.borrow_mut()
-                            .insert(*w, RedirectionEntry::Const(&d1 * &d2));
-                        *insn = StructuredInstruction::__DELETE__;
-                    } else if d1.is_some() && d1.unwrap() == Fp4::default() {
+                    if let (Some(d1), Some(d2)) = (d1.clone(), d2.clone()) {
                         mem.borrow_mut()
-                            .insert(*w, RedirectionEntry::Const(Fp4::default()));
+                            .insert(*w, RedirectionEntry::Const(d1 * d2));
                         *insn = StructuredInstruction::__DELETE__;
-                    } else if d2.is_some() && d2.unwrap() == Fp4::default() {
+                    } else if d1 == Some(E::ZERO) || d2 == Some(E::ZERO) {
                         mem.borrow_mut()
-                            .insert(*w, RedirectionEntry::Const(Fp4::default()));
+                            .insert(*w, RedirectionEntry::Const(E::ZERO));
                         *insn = StructuredInstruction::__DELETE__;
                     }
                 }
                 StructuredInstruction::NOT(w, r) => {
                     let d = refresh_and_get_constant(r);
-                    if d.is_some() {
-                        let d = d.unwrap();
-                        if d.0 .0 == 0 {
-                            mem.borrow_mut().insert(
-                                *w,
-                                RedirectionEntry::Const(Fp4::new(
-                                    Fp(1),
-                                    Fp::ZERO,
-                                    Fp::ZERO,
-                                    Fp::ZERO,
-                                )),
-                            );
+                    if let Some(d) = d {
+                        if d.coeff(0).is_zero() {
+                            mem.borrow_mut().insert(*w, RedirectionEntry::Const(E::ONE));
                         } else {
-                            mem.borrow_mut().insert(
-                                *w,
-                                RedirectionEntry::Const(Fp4::new(
-                                    Fp::ZERO,
-                                    Fp::ZERO,
-                                    Fp::ZERO,
-                                    Fp::ZERO,
-                                )),
-                            );
+                            mem.borrow_mut()
+                                .insert(*w, RedirectionEntry::Const(E::ZERO));
                         }
                     }
                 }
                 StructuredInstruction::INV(w, r) => {
                     let d = refresh_and_get_constant(r);
-                    if d.is_some() {
-                        let d = d.unwrap();
+                    if let Some(d) = d {
                         mem.borrow_mut()
                             .insert(*w, RedirectionEntry::Const(d.inv()));
                     }
@@ -233,9 +193,7 @@ impl Pass for ConstPass {
                 StructuredInstruction::EQ(r1, r2) => {
                     let d1 = refresh_and_get_constant(r1);
                     let d2 = refresh_and_get_constant(r2);
-                    if d1.is_some() && d2.is_some() {
-                        let d1 = d1.unwrap();
-                        let d2 = d2.unwrap();
+                    if let (Some(d1), Some(d2)) = (d1, d2) {
                         if d1 == d2 {
                             *insn = StructuredInstruction::__DELETE__;
                         } else {
@@ -247,42 +205,31 @@ impl Pass for ConstPass {
                     let d1 = refresh_and_get_constant(r1);
                     let d2 = refresh_and_get_constant(r2);
                     let d_prev = refresh_and_get_constant(prev);
-                    if d1.is_some() && d2.is_some() && d_prev.is_some() {
-                        let d1 = d1.unwrap();
-                        let d2 = d2.unwrap();
-                        let d_prev = d_prev.unwrap();
-
-                        let mut val = Fp(*fp) * d_prev.1;
-                        const SHIFT_WORD: Fp = Fp(1 << 16);
-                        val = val * SHIFT_WORD + d1.1;
-                        val = val * SHIFT_WORD + d1.0;
-                        val = val * SHIFT_WORD + d2.1;
-                        val = val * SHIFT_WORD + d2.0;
+                    if let (Some(d1), Some(d2), Some(d_prev)) = (d1, d2, d_prev) {
+                        let shift_word = E::Base::from_u64(1 << 16);
+                        let mut val = E::Base::from_u64(*fp as u64) * d_prev.coeff(1);
+                        val = val * shift_word.clone() + d1.coeff(1);
+                        val = val * shift_word.clone() + d1.coeff(0);
+                        val = val * shift_word.clone() + d2.coeff(1);
+                        val = val * shift_word + d2.coeff(0);
 
-                        mem.borrow_mut().insert(
-                            *w,
-                            RedirectionEntry::Const(Fp4::new(val, Fp::ZERO, Fp::ZERO, Fp::ZERO)),
-                        );
+                        mem.borrow_mut()
+                            .insert(*w, RedirectionEntry::Const(E::from_base(val)));
                         *insn = StructuredInstruction::__DELETE__;
                     }
                 }
                 StructuredInstruction::MIX_RNG(w, r1, r2) => {
                     let d1 = refresh_and_get_constant(r1);
                     let d2 = refresh_and_get_constant(r2);
-                    if d1.is_some() && d2.is_some() {
-                        let d1 = d1.unwrap();
-                        let d2 = d2.unwrap();
-
-                        const SHIFT_WORD: Fp = Fp(1 << 16);
-                        let mut val = d1.1;
-                        val = val * SHIFT_WORD + d1.0;
-                        val = val * SHIFT_WORD + d2.1;
-                        val = val * SHIFT_WORD + d2.0;
+                    if let (Some(d1), Some(d2)) = (d1, d2) {
+                        let shift_word = E::Base::from_u64(1 << 16);
+                        let mut val = d1.coeff(1);
+                        val = val * shift_word.clone() + d1.coeff(0);
+                        val = val * shift_word.clone() + d2.coeff(1);
+                        val = val * shift_word + d2.coeff(0);
 
-                        mem.borrow_mut().insert(
-                            *w,
-                            RedirectionEntry::Const(Fp4::new(val, Fp::ZERO, Fp::ZERO, Fp::ZERO)),
-                        );
+                        mem.borrow_mut()
+                            .insert(*w, RedirectionEntry::Const(E::from_base(val)));
                         *insn = StructuredInstruction::__DELETE__;
                     }
                 }
@@ -291,20 +238,17 @@ impl Pass for ConstPass {
                     let d1 = refresh_and_get_constant(r1);
                     let d2 = refresh_and_get_constant(r2);
 
-                    if s.is_some() {
-                        let s = s.unwrap();
-                        if s.0 == Fp(1) {
-                            if d1.is_some() {
-                                mem.borrow_mut()
-                                    .insert(*w, RedirectionEntry::Const(d1.unwrap()));
+                    if let Some(s) = s {
+                        if s.coeff(0) == E::Base::ONE {
+                            if let Some(d1) = d1 {
+                                mem.borrow_mut().insert(*w, RedirectionEntry::Const(d1));
                                 *insn = StructuredInstruction::__DELETE__;
                             } else {
                                 *insn = StructuredInstruction::__MOV__(*w, r1.clone());
                             }
                         } else {
-                            if d2.is_some() {
-                                mem.borrow_mut()
-                                    .insert(*w, RedirectionEntry::Const(d2.unwrap()));
+                            if let Some(d2) = d2 {
+                                mem.borrow_mut().insert(*w, RedirectionEntry::Const(d2));
                                 *insn = StructuredInstruction::__DELETE__;
                             } else {
                                 *insn = StructuredInstruction::__MOV__(*w, r2.clone());
@@ -314,20 +258,10 @@ impl Pass for ConstPass {
                 }
                 StructuredInstruction::EXTRACT(w, r, idx) => {
                     let d = refresh_and_get_constant(r);
-                    if d.is_some() {
-                        let d = d.unwrap();
-                        let sub = if *idx == 0 {
-                            d.0
-                        } else if *idx == 1 {
-                            d.1
-                        } else if *idx == 2 {
-                            d.2
-                        } else {
-                            d.3
-                        };
+                    if let Some(d) = d {
                         mem.borrow_mut().insert(
                             *w,
-                            RedirectionEntry::Const(Fp4::new(sub, Fp::ZERO, Fp::ZERO, Fp::ZERO)),
+                            RedirectionEntry::Const(E::from_base(d.coeff(*idx as usize))),
                         );
                         *insn = StructuredInstruction::__DELETE__;
                     } else {
@@ -343,7 +277,7 @@ impl Pass for ConstPass {
                                         .insert(*w, RedirectionEntry::RefSub(*v, *v_idx));
                                 } else {
                                     mem.borrow_mut()
-                                        .insert(*w, RedirectionEntry::Const(Fp4::default()));
+                                        .insert(*w, RedirectionEntry::Const(E::ZERO));
                                 }
                                 *insn = StructuredInstruction::__DELETE__;
                             }
@@ -413,11 +347,47 @@ impl Pass for ConstPass {
                     refresh_and_get_constant(r7);
                     refresh_and_get_constant(r8);
                 }
+                StructuredInstruction::POSEIDON2_LOAD_FROM_MONTGOMERY(
+                    _,
+                    _,
+                    _,
+                    r1,
+                    r2,
+                    r3,
+                    r4,
+                    r5,
+                    r6,
+                    r7,
+                    r8,
+                )
+                | StructuredInstruction::POSEIDON2_LOAD(_, _, _, r1, r2, r3, r4, r5, r6, r7, r8)
+                | StructuredInstruction::POSEIDON2_ADD_LOAD_FROM_MONTGOMERY(
+                    _,
+                    _,
+                    r1,
+                    r2,
+                    r3,
+                    r4,
+                    r5,
+                    r6,
+                    r7,
+                    r8,
+                )
+                | StructuredInstruction::POSEIDON2_ADD_LOAD(_, _, r1, r2, r3, r4, r5, r6, r7, r8) =>
+                {
+                    refresh_and_get_constant(r1);
+                    refresh_and_get_constant(r2);
+                    refresh_and_get_constant(r3);
+                    refresh_and_get_constant(r4);
+                    refresh_and_get_constant(r5);
+                    refresh_and_get_constant(r6);
+                    refresh_and_get_constant(r7);
+                    refresh_and_get_constant(r8);
+                }
                 StructuredInstruction::__MOV__(w, r) => {
                     let d = refresh_and_get_constant(r);
-                    if d.is_some() {
-                        mem.borrow_mut()
-                            .insert(*w, RedirectionEntry::Const(d.unwrap()));
+                    if let Some(d) = d {
+                        mem.borrow_mut().insert(*w, RedirectionEntry::Const(d));
                         *insn = StructuredInstruction::__DELETE__;
                     }
                 }
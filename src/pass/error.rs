@@ -0,0 +1,51 @@
+use crate::parser::LineNo;
+use std::fmt::{Debug, Display, Formatter};
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    // the pass after which the program behaves differently, the seed of the IOP input on which
+    // it does, the first line where the runs differ, and how they differ
+    Mismatch(String, u64, LineNo, String),
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::Mismatch(pass, seed, line_no, what) => f.write_fmt(format_args!(
+                "{} changes the program at line {} on the IOP input of seed {}: {}",
+                pass, line_no, seed, what
+            )),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PipelineError {
+    // a pass that is not registered, with the names of the registered ones
+    UnknownPass(String, Vec<String>),
+    // a pass that the pipeline gives twice
+    DuplicatePass(String),
+    // a pass, and the pass that it must run after but that comes later
+    OutOfOrder(String, String),
+}
+
+impl Display for PipelineError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PipelineError::UnknownPass(name, names) => f.write_fmt(format_args!(
+                "unknown pass {}, the passes are {}",
+                name,
+                names.join(", ")
+            )),
+            PipelineError::DuplicatePass(name) => {
+                f.write_fmt(format_args!("pass {} is given twice", name))
+            }
+            PipelineError::OutOfOrder(then, first) => {
+                f.write_fmt(format_args!("pass {} must run after {}", then, first))
+            }
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
@@ -1,3 +1,4 @@
+use crate::math::ExtensionField;
 use crate::parser::Code;
 use crate::pass::Pass;
 use crate::structures::{ReadAddr, StructuredInstruction, WriteAddr};
@@ -7,15 +8,15 @@ use std::rc::Rc;
 
 pub struct LiveVariableAnalysisPass;
 
-impl Pass for LiveVariableAnalysisPass {
-    fn pass(code: &mut Code) -> anyhow::Result<()> {
+impl<E: ExtensionField> Pass<E> for LiveVariableAnalysisPass {
+    fn pass(code: &mut Code<E>) -> anyhow::Result<()> {
         let last_use = Rc::new(RefCell::new(HashMap::<u32, usize>::new()));
         let line_number = Rc::new(RefCell::new(0usize));
 
         let u = |v: &WriteAddr| {
             last_use.borrow_mut().insert(*v, *line_number.borrow());
         };
-        let v = |v: &ReadAddr| match v {
+        let v = |v: &ReadAddr<E>| match v {
             ReadAddr::Ref(v) | ReadAddr::RefSub(v, _) => {
                 last_use.borrow_mut().insert(*v, *line_number.borrow());
             }
@@ -109,7 +110,35 @@ impl Pass for LiveVariableAnalysisPass {
                     r7,
                     r8,
                 )
-                | StructuredInstruction::POSEIDON_ADD_LOAD(_, _, r1, r2, r3, r4, r5, r6, r7, r8) => {
+                | StructuredInstruction::POSEIDON_ADD_LOAD(_, _, r1, r2, r3, r4, r5, r6, r7, r8)
+                | StructuredInstruction::POSEIDON2_LOAD_FROM_MONTGOMERY(
+                    _,
+                    _,
+                    _,
+                    r1,
+                    r2,
+                    r3,
+                    r4,
+                    r5,
+                    r6,
+                    r7,
+                    r8,
+                )
+                | StructuredInstruction::POSEIDON2_LOAD(_, _, _, r1, r2, r3, r4, r5, r6, r7, r8)
+                | StructuredInstruction::POSEIDON2_ADD_LOAD_FROM_MONTGOMERY(
+                    _,
+                    _,
+                    r1,
+                    r2,
+                    r3,
+                    r4,
+                    r5,
+                    r6,
+                    r7,
+                    r8,
+                )
+                | StructuredInstruction::POSEIDON2_ADD_LOAD(_, _, r1, r2, r3, r4, r5, r6, r7, r8) =>
+                {
                     v(r1);
                     v(r2);
                     v(r3);
@@ -120,7 +149,9 @@ impl Pass for LiveVariableAnalysisPass {
                     v(r8);
                 }
                 StructuredInstruction::POSEIDON_STORE_TO_MONTGOMERY(_, w)
-                | StructuredInstruction::POSEIDON_STORE(_, w) => {
+                | StructuredInstruction::POSEIDON_STORE(_, w)
+                | StructuredInstruction::POSEIDON2_STORE_TO_MONTGOMERY(_, w)
+                | StructuredInstruction::POSEIDON2_STORE(_, w) => {
                     u(w);
                     u(&(w + 1));
                     u(&(w + 2));
@@ -147,28 +178,20 @@ impl Pass for LiveVariableAnalysisPass {
 
         let remap = Rc::new(RefCell::new(HashMap::<u32, u32>::new()));
 
-        let remap_v = |r: &mut ReadAddr| match r {
-            ReadAddr::Ref(m) => {
-                if remap.borrow().contains_key(m) {
-                    *r = ReadAddr::Ref(*remap.borrow().get(m).unwrap());
-                }
+        let remap_v = |r: &mut ReadAddr<E>| match r {
+            ReadAddr::Ref(m) if remap.borrow().contains_key(m) => {
+                *r = ReadAddr::Ref(*remap.borrow().get(m).unwrap());
             }
-            ReadAddr::RefSub(m, idx) => {
-                if remap.borrow().contains_key(m) {
-                    *r = ReadAddr::RefSub(*remap.borrow().get(m).unwrap(), *idx);
-                }
+            ReadAddr::RefSub(m, idx) if remap.borrow().contains_key(m) => {
+                *r = ReadAddr::RefSub(*remap.borrow().get(m).unwrap(), *idx);
             }
             _ => {}
         };
 
-        let is_available = |r: &ReadAddr| match r {
+        let is_available = |r: &ReadAddr<E>| match r {
             ReadAddr::Ref(m) | ReadAddr::RefSub(m, _) => {
                 if let Some(v) = last_use.borrow().get(m) {
-                    if *v == *(line_number.borrow()) {
-                        true
-                    } else {
-                        false
-                    }
+                    *v == *(line_number.borrow())
                 } else {
                     false
                 }
@@ -176,12 +199,11 @@ impl Pass for LiveVariableAnalysisPass {
             _ => false,
         };
 
-        let remap_u = |w: &mut WriteAddr, r: &ReadAddr| match r {
-            ReadAddr::Ref(m) => {
+        let remap_u = |w: &mut WriteAddr, r: &ReadAddr<E>| {
+            if let ReadAddr::Ref(m) = r {
                 remap.borrow_mut().insert(*w, *m);
                 *w = *m;
             }
-            _ => {}
         };
 
         for (i, (insn, _)) in code.0.iter_mut().enumerate() {
@@ -296,7 +318,35 @@ impl Pass for LiveVariableAnalysisPass {
                     r7,
                     r8,
                 )
-                | StructuredInstruction::POSEIDON_ADD_LOAD(_, _, r1, r2, r3, r4, r5, r6, r7, r8) => {
+                | StructuredInstruction::POSEIDON_ADD_LOAD(_, _, r1, r2, r3, r4, r5, r6, r7, r8)
+                | StructuredInstruction::POSEIDON2_LOAD_FROM_MONTGOMERY(
+                    _,
+                    _,
+                    _,
+                    r1,
+                    r2,
+                    r3,
+                    r4,
+                    r5,
+                    r6,
+                    r7,
+                    r8,
+                )
+                | StructuredInstruction::POSEIDON2_LOAD(_, _, _, r1, r2, r3, r4, r5, r6, r7, r8)
+                | StructuredInstruction::POSEIDON2_ADD_LOAD_FROM_MONTGOMERY(
+                    _,
+                    _,
+                    r1,
+                    r2,
+                    r3,
+                    r4,
+                    r5,
+                    r6,
+                    r7,
+                    r8,
+                )
+                | StructuredInstruction::POSEIDON2_ADD_LOAD(_, _, r1, r2, r3, r4, r5, r6, r7, r8) =>
+                {
                     remap_v(r1);
                     remap_v(r2);
                     remap_v(r3);
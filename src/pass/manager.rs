@@ -0,0 +1,419 @@
+use crate::math::{ExtensionField, Fp4};
+use crate::parser::Code;
+use crate::pass::const_pass::ConstPass;
+use crate::pass::live_variable_analysis::LiveVariableAnalysisPass;
+use crate::pass::merge_iop_pass::MergeIOPPass;
+use crate::pass::poseidon2_pass::Poseidon2Pass;
+use crate::pass::poseidon_pass::PoseidonPass;
+use crate::pass::reorder_pass::ReorderPass;
+use crate::pass::sha_pass::ShaPass;
+use crate::pass::{Pass, PipelineError};
+use crate::structures::StructuredInstruction;
+use anyhow::Context;
+use std::fmt::{Display, Formatter};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// The passes that `PassManager::default` runs, in order.
+pub const DEFAULT_PIPELINE: [&str; 7] = [
+    "const",
+    "merge-iop",
+    "sha",
+    "poseidon",
+    "poseidon2",
+    "lva",
+    "reorder",
+];
+
+/// What a pass did to the code, and how long it took.
+///
+/// The instructions are compared with those at the same index before the pass, over the
+/// indices that the code has both before and after it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassStats {
+    pub name: String,
+    pub time: Duration,
+    // the instructions that the pass deleted
+    pub deleted: usize,
+    // the instructions that it replaced by different ones, other than by renaming addresses
+    pub rewritten: usize,
+    // the instructions that it left the same but for the addresses they read and write, as
+    // `lva` and `reorder` do
+    pub renamed: usize,
+    // the entries that it added to the code, or removed from it if negative
+    pub resized: isize,
+    // the instructions left after the pass
+    pub remaining: usize,
+}
+
+impl Display for PassStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "{:<10} {:>10.3?} {:>8} deleted {:>8} rewritten {:>8} renamed {:>8} left",
+            self.name, self.time, self.deleted, self.rewritten, self.renamed, self.remaining
+        ))?;
+        if self.resized != 0 {
+            f.write_fmt(format_args!(" ({:+} entries)", self.resized))?;
+        }
+        Ok(())
+    }
+}
+
+struct Registered<E: ExtensionField> {
+    name: &'static str,
+    run: fn(&mut Code<E>) -> anyhow::Result<()>,
+}
+
+/// A pipeline of passes, picked by name among the registered ones.
+///
+/// A pass may have to run after others, as `lva` does after the passes that merge instructions
+/// and after `const`, which expect the write-once memory that it gives up. The pipeline is
+/// checked against these constraints when it is set. Each pass is timed, what it changes in the
+/// code is counted, and the code may be dumped as a listing after each pass.
+pub struct PassManager<E: ExtensionField = Fp4> {
+    passes: Vec<Registered<E>>,
+    // (first, then): `then` cannot come before `first` when both are in the pipeline
+    constraints: Vec<(&'static str, &'static str)>,
+    pipeline: Vec<usize>,
+    dump_dir: Option<PathBuf>,
+}
+
+impl<E: ExtensionField> PassManager<E> {
+    /// A manager with no passes.
+    pub fn new() -> Self {
+        Self {
+            passes: Vec::new(),
+            constraints: Vec::new(),
+            pipeline: Vec::new(),
+            dump_dir: None,
+        }
+    }
+
+    /// Register the pass `P` under `name`, replacing the pass of that name if any.
+    pub fn register<P: Pass<E>>(&mut self, name: &'static str) {
+        let run = <P as Pass<E>>::pass;
+        match self.passes.iter_mut().find(|p| p.name == name) {
+            Some(p) => p.run = run,
+            None => self.passes.push(Registered { name, run }),
+        }
+    }
+
+    /// Require `then` to run after `first` in any pipeline that has both.
+    pub fn require_before(
+        &mut self,
+        first: &'static str,
+        then: &'static str,
+    ) -> Result<(), PipelineError> {
+        self.index(first)?;
+        self.index(then)?;
+        self.constraints.push((first, then));
+        Ok(())
+    }
+
+    /// The names of the registered passes, in the order they were registered.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.passes.iter().map(|p| p.name).collect()
+    }
+
+    /// The names of the passes of the pipeline, in order.
+    pub fn pipeline(&self) -> Vec<&'static str> {
+        self.pipeline.iter().map(|&i| self.passes[i].name).collect()
+    }
+
+    /// Set the passes to run, by name, after checking that each is registered, that none is
+    /// given twice and that they keep to the constraints.
+    pub fn set_pipeline<S: AsRef<str>>(&mut self, names: &[S]) -> Result<(), PipelineError> {
+        let mut pipeline = Vec::new();
+        for name in names.iter() {
+            let idx = self.index(name.as_ref())?;
+            if pipeline.contains(&idx) {
+                return Err(PipelineError::DuplicatePass(name.as_ref().to_string()));
+            }
+            pipeline.push(idx);
+        }
+
+        let position = |name: &str| pipeline.iter().position(|&i| self.passes[i].name == name);
+        for (first, then) in self.constraints.iter() {
+            if let (Some(f), Some(t)) = (position(first), position(then)) {
+                if t < f {
+                    return Err(PipelineError::OutOfOrder(
+                        then.to_string(),
+                        first.to_string(),
+                    ));
+                }
+            }
+        }
+
+        self.pipeline = pipeline;
+        Ok(())
+    }
+
+    /// Write the listing of the code after each pass to `dir`, as `<n>-<name>.ll0` for the `n`th
+    /// pass of the pipeline.
+    pub fn set_dump_dir(&mut self, dir: Option<PathBuf>) {
+        self.dump_dir = dir;
+    }
+
+    /// Run the pipeline over `code`.
+    pub fn run(&self, code: &mut Code<E>) -> anyhow::Result<Vec<PassStats>> {
+        self.run_with(code, |_, _| Ok(()))
+    }
+
+    /// Run the pipeline over `code`, and call `after` with the name of each pass and the code as
+    /// the pass leaves it, stopping at the first error.
+    pub fn run_with(
+        &self,
+        code: &mut Code<E>,
+        mut after: impl FnMut(&str, &Code<E>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<Vec<PassStats>> {
+        if let Some(dir) = &self.dump_dir {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("cannot create {}", dir.display()))?;
+        }
+
+        let mut stats = Vec::new();
+        for (n, &idx) in self.pipeline.iter().enumerate() {
+            let pass = &self.passes[idx];
+            let before: Vec<StructuredInstruction<E>> =
+                code.0.iter().map(|(insn, _)| insn.clone()).collect();
+
+            let start = Instant::now();
+            (pass.run)(code).with_context(|| format!("pass {} failed", pass.name))?;
+            let time = start.elapsed();
+
+            let (mut deleted, mut rewritten, mut renamed) = (0, 0, 0);
+            for (old, (new, _)) in before.iter().zip(code.0.iter()) {
+                if old == new {
+                    continue;
+                }
+                if *new == StructuredInstruction::__DELETE__ {
+                    deleted += 1;
+                } else if without_addresses(old) == without_addresses(new) {
+                    renamed += 1;
+                } else {
+                    rewritten += 1;
+                }
+            }
+            stats.push(PassStats {
+                name: pass.name.to_string(),
+                time,
+                deleted,
+                rewritten,
+                renamed,
+                resized: code.0.len() as isize - before.len() as isize,
+                remaining: live(code).count(),
+            });
+
+            if let Some(dir) = &self.dump_dir {
+                let path = dir.join(format!("{}-{}.ll0", n + 1, pass.name));
+                dump(code, &path).with_context(|| format!("cannot write {}", path.display()))?;
+            }
+            after(pass.name, code)?;
+        }
+        Ok(stats)
+    }
+
+    fn index(&self, name: &str) -> Result<usize, PipelineError> {
+        self.passes
+            .iter()
+            .position(|p| p.name == name)
+            .ok_or_else(|| {
+                PipelineError::UnknownPass(
+                    name.to_string(),
+                    self.names().iter().map(|n| n.to_string()).collect(),
+                )
+            })
+    }
+}
+
+impl<E: ExtensionField> Default for PassManager<E> {
+    /// The passes of this crate, with `DEFAULT_PIPELINE` as the pipeline.
+    fn default() -> Self {
+        let mut manager = Self::new();
+        manager.register::<ConstPass>("const");
+        manager.register::<MergeIOPPass>("merge-iop");
+        manager.register::<ShaPass>("sha");
+        manager.register::<PoseidonPass>("poseidon");
+        manager.register::<Poseidon2Pass>("poseidon2");
+        manager.register::<LiveVariableAnalysisPass>("lva");
+        manager.register::<ReorderPass>("reorder");
+
+        // lva reuses addresses: the constants of `const` would be overwritten, and the merging
+        // passes look for instructions that write to consecutive addresses or in a fixed shape
+        for first in ["const", "merge-iop", "sha", "poseidon", "poseidon2"] {
+            manager.require_before(first, "lva").unwrap();
+        }
+        // reorder fills the gaps that the others leave in the memory
+        for first in ["const", "merge-iop", "lva"] {
+            manager.require_before(first, "reorder").unwrap();
+        }
+
+        manager.set_pipeline(&DEFAULT_PIPELINE).unwrap();
+        manager
+    }
+}
+
+// the instructions that are not deleted
+fn live<E: ExtensionField>(code: &Code<E>) -> impl Iterator<Item = &StructuredInstruction<E>> {
+    code.0
+        .iter()
+        .map(|(insn, _)| insn)
+        .filter(|insn| !matches!(insn, StructuredInstruction::__DELETE__))
+}
+
+// the instruction as it prints, without the numbers of the `m[..]` that it reads or writes
+fn without_addresses<E: ExtensionField>(insn: &StructuredInstruction<E>) -> String {
+    let text = insn.to_string();
+    let mut out = String::with_capacity(text.len());
+    let mut in_memory = false;
+    let mut prev = [' ', ' '];
+    for c in text.chars() {
+        if c == '[' && prev[1] == 'm' && !(prev[0].is_alphanumeric() || prev[0] == '_') {
+            in_memory = true;
+        } else if c == ']' {
+            in_memory = false;
+        }
+        if !(in_memory && c.is_ascii_digit()) {
+            out.push(c);
+        }
+        prev = [prev[1], c];
+    }
+    out
+}
+
+// the listing of the code, as `decompile` writes it
+fn dump<E: ExtensionField>(code: &Code<E>, path: &Path) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(std::fs::File::create(path)?);
+    for (i, insn) in live(code).enumerate() {
+        writer.write_fmt(format_args!("{}: {}\n", i + 1, insn))?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::LineNo;
+    use StructuredInstruction as I;
+
+    fn program() -> Code {
+        let mut code = Code::default();
+        for (row, insn) in [
+            I::WOM_INIT,
+            I::READ_IOP_HEADER(1, 4),
+            I::READ_IOP_BODY(5),
+            I::CONST(9, 3, 0),
+            I::ADD(12, 5.into(), 9.into()),
+            I::EQ(12.into(), 5.into()),
+            I::WOM_FINI,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            code.push(insn, LineNo::new(row + 1, 0));
+        }
+        code
+    }
+
+    // a pass that subtracts instead of adding, and adds an assertion at the end
+    struct Grow;
+
+    impl Pass for Grow {
+        fn pass(code: &mut Code) -> anyhow::Result<()> {
+            code.0[4].0 = I::SUB(12, 5.into(), 9.into());
+            code.push(I::EQ(9.into(), 9.into()), LineNo::new(8, 0));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn pipelines() {
+        let mut manager = PassManager::<Fp4>::default();
+        assert_eq!(manager.pipeline(), DEFAULT_PIPELINE);
+
+        assert_eq!(
+            manager.set_pipeline(&["const", "fold"]),
+            Err(PipelineError::UnknownPass(
+                "fold".to_string(),
+                manager.names().iter().map(|n| n.to_string()).collect()
+            ))
+        );
+        assert_eq!(
+            manager.set_pipeline(&["const", "sha", "const"]),
+            Err(PipelineError::DuplicatePass("const".to_string()))
+        );
+        assert_eq!(
+            manager.set_pipeline(&["reorder", "lva"]),
+            Err(PipelineError::OutOfOrder(
+                "reorder".to_string(),
+                "lva".to_string()
+            ))
+        );
+        // a failed pipeline leaves the one before
+        assert_eq!(manager.pipeline(), DEFAULT_PIPELINE);
+
+        // passes without a constraint between them may come in any order
+        manager.set_pipeline(&["sha", "const"]).unwrap();
+        assert_eq!(manager.pipeline(), ["sha", "const"]);
+        assert_eq!(
+            manager.require_before("lva", "fold"),
+            Err(PipelineError::UnknownPass(
+                "fold".to_string(),
+                manager.names().iter().map(|n| n.to_string()).collect()
+            ))
+        );
+    }
+
+    #[test]
+    fn stats() {
+        let mut manager = PassManager::<Fp4>::default();
+        manager.register::<Grow>("grow");
+        manager.set_pipeline(&["grow", "const", "reorder"]).unwrap();
+        let mut code = program();
+        let stats = manager.run(&mut code).unwrap();
+
+        let counts: Vec<_> = stats
+            .iter()
+            .map(|s| {
+                (
+                    s.name.as_str(),
+                    s.deleted,
+                    s.rewritten,
+                    s.renamed,
+                    s.resized,
+                    s.remaining,
+                )
+            })
+            .collect();
+        assert_eq!(
+            counts,
+            vec![
+                ("grow", 0, 1, 0, 1, 8),
+                // the constant and the assertion that always holds, and the SUB of a constant
+                ("const", 2, 1, 0, 0, 6),
+                // the IOP read, the SUB and the assertion that is left
+                ("reorder", 0, 0, 3, 0, 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn addresses() {
+        let strip = |insn: I| without_addresses(&insn);
+        assert_eq!(
+            strip(I::ADD(12, 5.into(), 9.into())),
+            strip(I::ADD(1, 2.into(), 3.into()))
+        );
+        assert_ne!(strip(I::CONST(1, 2, 0)), strip(I::CONST(1, 3, 0)));
+        // the part of the state that a store reads is not an address of the memory
+        assert_eq!(
+            strip(I::POSEIDON_STORE(0, 8)),
+            strip(I::POSEIDON_STORE(0, 16))
+        );
+        assert_ne!(
+            strip(I::POSEIDON_STORE(0, 8)),
+            strip(I::POSEIDON_STORE(1, 8))
+        );
+    }
+}
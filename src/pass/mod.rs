@@ -1,13 +1,21 @@
 pub mod const_pass;
+mod error;
 pub mod live_variable_analysis;
+pub mod manager;
 pub mod merge_iop_pass;
+pub mod poseidon2_pass;
 pub mod poseidon_pass;
 pub mod reorder_pass;
 pub mod sha_pass;
+pub mod validation;
 
+use crate::math::{ExtensionField, Fp4};
 use crate::parser::Code;
+pub use crate::pass::error::{PipelineError, ValidationError};
 use anyhow::Result;
 
-pub trait Pass {
-    fn pass(code: &mut Code) -> Result<()>;
+/// A rewrite of `Code` that keeps what the program does, for the programs over the extension
+/// field `E`.
+pub trait Pass<E: ExtensionField = Fp4> {
+    fn pass(code: &mut Code<E>) -> Result<()>;
 }
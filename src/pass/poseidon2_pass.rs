@@ -0,0 +1,161 @@
+use crate::math::poseidon2::ROUNDS_HALF_FULL;
+use crate::math::ExtensionField;
+use crate::parser::Code;
+use crate::pass::Pass;
+use crate::structures::StructuredInstruction;
+
+pub struct Poseidon2Pass;
+
+// the number of rows in the rounds of a Poseidon2 permutation starting at `start`, which are
+// a row for each of the first full rounds, one row for all the partial rounds, and a row for each
+// of the last full rounds, as `Poseidon2` runs them
+fn permutation_len<E: ExtensionField>(code: &Code<E>, start: usize) -> Option<usize> {
+    let count = |from: usize, insn: StructuredInstruction<E>| {
+        code.0[from..]
+            .iter()
+            .take_while(|(cur, _)| *cur == insn)
+            .count()
+    };
+
+    let full = count(start, StructuredInstruction::POSEIDON2_FULL);
+    if full != ROUNDS_HALF_FULL
+        || count(start + full, StructuredInstruction::POSEIDON2_PARTIAL) == 0
+        || count(start + full + 1, StructuredInstruction::POSEIDON2_FULL) < full
+    {
+        return None;
+    }
+    Some(full * 2 + 1)
+}
+
+impl<E: ExtensionField> Pass<E> for Poseidon2Pass {
+    fn pass(code: &mut Code<E>) -> anyhow::Result<()> {
+        let len = code.0.len();
+        let mut cur = 0;
+
+        while cur < len {
+            let Some(rounds) = permutation_len(code, cur) else {
+                // a permutation starts with all of a run of full rounds, never within it
+                let full = code.0[cur].0 == StructuredInstruction::POSEIDON2_FULL;
+                cur += 1;
+                while full && cur < len && code.0[cur].0 == StructuredInstruction::POSEIDON2_FULL {
+                    cur += 1;
+                }
+                continue;
+            };
+
+            let last = cur + rounds;
+            let merged = match code.0.get(last).map(|(insn, _)| insn) {
+                Some(StructuredInstruction::POSEIDON2_STORE(idx, ws)) => Some(
+                    StructuredInstruction::__POSEIDON2_PERMUTE_STORE__(*idx, *ws),
+                ),
+                Some(StructuredInstruction::POSEIDON2_STORE_TO_MONTGOMERY(idx, ws)) => Some(
+                    StructuredInstruction::__POSEIDON2_PERMUTE_STORE_TO_MONTGOMERY__(*idx, *ws),
+                ),
+                _ => None,
+            };
+
+            match merged {
+                Some(insn) => {
+                    code.0[last].0 = insn;
+                    code.0[last].1 = code.0[cur].1.to(&code.0[last].1);
+                    for i in cur..last {
+                        code.0[i].0 = StructuredInstruction::__DELETE__;
+                    }
+                }
+                None => {
+                    code.0[last - 1].0 = StructuredInstruction::__POSEIDON2_PERMUTE__;
+                    code.0[last - 1].1 = code.0[cur].1.to(&code.0[last - 1].1);
+                    for i in cur..last - 1 {
+                        code.0[i].0 = StructuredInstruction::__DELETE__;
+                    }
+                }
+            }
+            cur = last;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::LineNo;
+    use StructuredInstruction as I;
+
+    fn code(insns: Vec<I>) -> Code {
+        let mut code = Code::default();
+        for (row, insn) in insns.into_iter().enumerate() {
+            code.push(insn, LineNo::new(row + 1, 0));
+        }
+        code
+    }
+
+    fn rounds(full: usize, partial: usize) -> Vec<I> {
+        let mut insns = vec![I::POSEIDON2_FULL; full];
+        insns.extend(vec![I::POSEIDON2_PARTIAL; partial]);
+        insns.extend(vec![I::POSEIDON2_FULL; full]);
+        insns
+    }
+
+    #[test]
+    fn merges_permutations() {
+        let mut insns = vec![I::POSEIDON2_LOAD(
+            0,
+            0,
+            0,
+            1.into(),
+            2.into(),
+            3.into(),
+            4.into(),
+            5.into(),
+            6.into(),
+            7.into(),
+            8.into(),
+        )];
+        // rows 2..=10
+        insns.extend(rounds(4, 1));
+        insns.push(I::POSEIDON2_STORE_TO_MONTGOMERY(1, 9));
+        // rows 12..=20, followed by a full round instead of a store
+        insns.extend(rounds(4, 1));
+        insns.push(I::POSEIDON2_FULL);
+        let mut code = code(insns);
+        Poseidon2Pass::pass(&mut code).unwrap();
+
+        let live: Vec<(&I, &LineNo)> = code
+            .0
+            .iter()
+            .filter(|(insn, _)| *insn != I::__DELETE__)
+            .map(|(insn, line_no)| (insn, line_no))
+            .collect();
+        assert_eq!(live.len(), 4);
+        assert!(matches!(live[0].0, I::POSEIDON2_LOAD(..)));
+        assert!(*live[1].0 == I::__POSEIDON2_PERMUTE_STORE_TO_MONTGOMERY__(1, 9));
+        assert_eq!((live[1].1.row, live[1].1.end_row), (2, 11));
+        assert!(*live[2].0 == I::__POSEIDON2_PERMUTE__);
+        assert_eq!((live[2].1.row, live[2].1.end_row), (12, 20));
+        assert!(*live[3].0 == I::POSEIDON2_FULL);
+    }
+
+    #[test]
+    fn leaves_incomplete_rounds() {
+        for insns in [
+            vec![
+                I::POSEIDON2_FULL,
+                I::POSEIDON2_FULL,
+                I::POSEIDON2_PARTIAL,
+                I::POSEIDON2_FULL,
+            ],
+            vec![I::POSEIDON2_FULL, I::POSEIDON2_FULL],
+            vec![I::POSEIDON2_PARTIAL, I::POSEIDON2_FULL],
+            // fewer full rounds than a permutation has
+            rounds(2, 1),
+            // a row for each partial round
+            rounds(4, 21),
+        ] {
+            let mut code = code(insns.clone());
+            Poseidon2Pass::pass(&mut code).unwrap();
+            assert!(code.0.iter().map(|(insn, _)| insn).eq(insns.iter()));
+        }
+    }
+}
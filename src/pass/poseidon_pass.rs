@@ -1,11 +1,12 @@
+use crate::math::ExtensionField;
 use crate::parser::Code;
 use crate::pass::Pass;
 use crate::structures::StructuredInstruction;
 
 pub struct PoseidonPass;
 
-impl Pass for PoseidonPass {
-    fn pass(code: &mut Code) -> anyhow::Result<()> {
+impl<E: ExtensionField> Pass<E> for PoseidonPass {
+    fn pass(code: &mut Code<E>) -> anyhow::Result<()> {
         let len = code.0.len();
         let mut cur = 5;
 
@@ -19,6 +20,7 @@ impl Pass for PoseidonPass {
                 match code.0[cur].0 {
                     StructuredInstruction::POSEIDON_STORE(idx, ws) => {
                         code.0[cur].0 = StructuredInstruction::__POSEIDON_PERMUTE_STORE__(idx, ws);
+                        code.0[cur].1 = code.0[cur - 5].1.to(&code.0[cur].1);
                         for i in 1..=5 {
                             code.0[cur - i].0 = StructuredInstruction::__DELETE__;
                         }
@@ -28,12 +30,14 @@ impl Pass for PoseidonPass {
                             StructuredInstruction::__POSEIDON_PERMUTE_STORE_TO_MONTGOMERY__(
                                 idx, ws,
                             );
+                        code.0[cur].1 = code.0[cur - 5].1.to(&code.0[cur].1);
                         for i in 1..=5 {
                             code.0[cur - i].0 = StructuredInstruction::__DELETE__;
                         }
                     }
                     _ => {
                         code.0[cur - 1].0 = StructuredInstruction::__POSEIDON_PERMUTE__;
+                        code.0[cur - 1].1 = code.0[cur - 5].1.to(&code.0[cur - 1].1);
                         for i in 2..=5 {
                             code.0[cur - i].0 = StructuredInstruction::__DELETE__;
                         }
@@ -1,3 +1,4 @@
+use crate::math::ExtensionField;
 use crate::parser::Code;
 use crate::pass::Pass;
 use crate::structures::{ReadAddr, StructuredInstruction, WriteAddr};
@@ -9,8 +10,8 @@ use std::rc::Rc;
 
 pub struct ReorderPass;
 
-impl Pass for ReorderPass {
-    fn pass(code: &mut Code) -> anyhow::Result<()> {
+impl<E: ExtensionField> Pass<E> for ReorderPass {
+    fn pass(code: &mut Code<E>) -> anyhow::Result<()> {
         let next_id = Rc::new(RefCell::new(1u32));
         let remap = Rc::new(RefCell::new(HashMap::new()));
 
@@ -50,6 +51,10 @@ impl Pass for ReorderPass {
                 | StructuredInstruction::POSEIDON_STORE(_, ws)
                 | StructuredInstruction::__POSEIDON_PERMUTE_STORE_TO_MONTGOMERY__(_, ws)
                 | StructuredInstruction::__POSEIDON_PERMUTE_STORE__(_, ws)
+                | StructuredInstruction::POSEIDON2_STORE_TO_MONTGOMERY(_, ws)
+                | StructuredInstruction::POSEIDON2_STORE(_, ws)
+                | StructuredInstruction::__POSEIDON2_PERMUTE_STORE_TO_MONTGOMERY__(_, ws)
+                | StructuredInstruction::__POSEIDON2_PERMUTE_STORE__(_, ws)
                 | StructuredInstruction::__SHA_FINI__(ws)
                 | StructuredInstruction::SHA_FINI_START(ws) => {
                     for i in *ws..*ws + 8 {
@@ -60,7 +65,7 @@ impl Pass for ReorderPass {
             }
         }
 
-        let remap_v = |r: &mut ReadAddr| match r {
+        let remap_v = |r: &mut ReadAddr<E>| match r {
             ReadAddr::Ref(m) => {
                 if remap.borrow().contains_key(m) {
                     *r = ReadAddr::Ref(*remap.borrow().get(m).unwrap());
@@ -160,7 +165,35 @@ impl Pass for ReorderPass {
                     r7,
                     r8,
                 )
-                | StructuredInstruction::POSEIDON_ADD_LOAD(_, _, r1, r2, r3, r4, r5, r6, r7, r8) => {
+                | StructuredInstruction::POSEIDON_ADD_LOAD(_, _, r1, r2, r3, r4, r5, r6, r7, r8)
+                | StructuredInstruction::POSEIDON2_LOAD_FROM_MONTGOMERY(
+                    _,
+                    _,
+                    _,
+                    r1,
+                    r2,
+                    r3,
+                    r4,
+                    r5,
+                    r6,
+                    r7,
+                    r8,
+                )
+                | StructuredInstruction::POSEIDON2_LOAD(_, _, _, r1, r2, r3, r4, r5, r6, r7, r8)
+                | StructuredInstruction::POSEIDON2_ADD_LOAD_FROM_MONTGOMERY(
+                    _,
+                    _,
+                    r1,
+                    r2,
+                    r3,
+                    r4,
+                    r5,
+                    r6,
+                    r7,
+                    r8,
+                )
+                | StructuredInstruction::POSEIDON2_ADD_LOAD(_, _, r1, r2, r3, r4, r5, r6, r7, r8) =>
+                {
                     remap_v(r1)?;
                     remap_v(r2)?;
                     remap_v(r3)?;
@@ -187,7 +220,11 @@ impl Pass for ReorderPass {
                 | StructuredInstruction::POSEIDON_STORE(_, ws)
                 | StructuredInstruction::__POSEIDON_PERMUTE_STORE_TO_MONTGOMERY__(_, ws)
                 | StructuredInstruction::__SHA_FINI__(ws)
-                | StructuredInstruction::__POSEIDON_PERMUTE_STORE__(_, ws) => {
+                | StructuredInstruction::__POSEIDON_PERMUTE_STORE__(_, ws)
+                | StructuredInstruction::POSEIDON2_STORE_TO_MONTGOMERY(_, ws)
+                | StructuredInstruction::POSEIDON2_STORE(_, ws)
+                | StructuredInstruction::__POSEIDON2_PERMUTE_STORE_TO_MONTGOMERY__(_, ws)
+                | StructuredInstruction::__POSEIDON2_PERMUTE_STORE__(_, ws) => {
                     remap_u(ws)?;
                 }
                 StructuredInstruction::__READ_IOP_BODY_BATCH__(ws, we) => {
@@ -199,13 +236,18 @@ impl Pass for ReorderPass {
                 StructuredInstruction::SHA_FINI_PADDING
                 | StructuredInstruction::WOM_INIT
                 | StructuredInstruction::WOM_FINI
+                | StructuredInstruction::NOP
+                | StructuredInstruction::PADDING
                 | StructuredInstruction::READ_IOP_HEADER(_, _)
                 | StructuredInstruction::POSEIDON_FULL
                 | StructuredInstruction::POSEIDON_PARTIAL
+                | StructuredInstruction::POSEIDON2_FULL
+                | StructuredInstruction::POSEIDON2_PARTIAL
                 | StructuredInstruction::__DELETE__
                 | StructuredInstruction::__PANIC__
                 | StructuredInstruction::__SHA_MIX_48__
                 | StructuredInstruction::__POSEIDON_PERMUTE__
+                | StructuredInstruction::__POSEIDON2_PERMUTE__
                 | StructuredInstruction::__SHA_INIT__
                 | StructuredInstruction::SHA_INIT_START
                 | StructuredInstruction::SHA_INIT_PADDING
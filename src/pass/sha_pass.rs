@@ -1,56 +1,49 @@
+use crate::math::ExtensionField;
 use crate::parser::Code;
 use crate::pass::Pass;
 use crate::structures::StructuredInstruction;
 
 pub struct ShaPass;
 
-impl Pass for ShaPass {
-    fn pass(code: &mut Code) -> anyhow::Result<()> {
+impl<E: ExtensionField> Pass<E> for ShaPass {
+    fn pass(code: &mut Code<E>) -> anyhow::Result<()> {
         let len = code.0.len();
         let mut cur = 0;
 
+        // whether the `n` instructions after `cur` are all `insn`
+        let followed_by =
+            |code: &Code<E>, cur: usize, n: usize, insn: &StructuredInstruction<E>| {
+                cur + n < len && code.0[cur + 1..=cur + n].iter().all(|(i, _)| i == insn)
+            };
+
         while cur < len {
-            if code.0[cur].0 == StructuredInstruction::SHA_MIX {
-                for i in 1..48 {
-                    if code.0[cur + i].0 != StructuredInstruction::SHA_MIX {
-                        cur += 1;
-                        continue;
-                    }
-                }
+            if code.0[cur].0 == StructuredInstruction::SHA_MIX
+                && followed_by(code, cur, 47, &StructuredInstruction::SHA_MIX)
+            {
                 code.0[cur].0 = StructuredInstruction::__SHA_MIX_48__;
+                code.0[cur].1 = code.0[cur].1.to(&code.0[cur + 47].1);
                 for i in 1..48 {
                     code.0[cur + i].0 = StructuredInstruction::__DELETE__;
                 }
             }
 
-            if code.0[cur].0 == StructuredInstruction::SHA_INIT_START {
-                for i in 1..=3 {
-                    if code.0[cur + i].0 != StructuredInstruction::SHA_INIT_PADDING {
-                        cur += 1;
-                        continue;
-                    }
-                }
+            if code.0[cur].0 == StructuredInstruction::SHA_INIT_START
+                && followed_by(code, cur, 3, &StructuredInstruction::SHA_INIT_PADDING)
+            {
                 code.0[cur].0 = StructuredInstruction::__SHA_INIT__;
+                code.0[cur].1 = code.0[cur].1.to(&code.0[cur + 3].1);
                 for i in 1..=3 {
                     code.0[cur + i].0 = StructuredInstruction::__DELETE__;
                 }
             }
 
-            if matches!(code.0[cur].0, StructuredInstruction::SHA_FINI_START(_)) {
-                for i in 1..=3 {
-                    if code.0[cur + i].0 != StructuredInstruction::SHA_FINI_PADDING {
-                        cur += 1;
-                        continue;
-                    }
-                }
-                match code.0[cur].0 {
-                    StructuredInstruction::SHA_FINI_START(ws) => {
-                        code.0[cur].0 = StructuredInstruction::__SHA_FINI__(ws);
+            if let StructuredInstruction::SHA_FINI_START(ws) = code.0[cur].0 {
+                if followed_by(code, cur, 3, &StructuredInstruction::SHA_FINI_PADDING) {
+                    code.0[cur].0 = StructuredInstruction::__SHA_FINI__(ws);
+                    code.0[cur].1 = code.0[cur].1.to(&code.0[cur + 3].1);
+                    for i in 1..=3 {
+                        code.0[cur + i].0 = StructuredInstruction::__DELETE__;
                     }
-                    _ => {}
-                }
-                for i in 1..=3 {
-                    code.0[cur + i].0 = StructuredInstruction::__DELETE__;
                 }
             }
 
@@ -60,3 +53,77 @@ impl Pass for ShaPass {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::LineNo;
+    use StructuredInstruction as I;
+
+    fn code(insns: Vec<I>) -> Code {
+        let mut code = Code::default();
+        for (row, insn) in insns.into_iter().enumerate() {
+            code.push(insn, LineNo::new(row + 1, 0));
+        }
+        code
+    }
+
+    fn live(code: &Code) -> Vec<(I, LineNo)> {
+        code.0
+            .iter()
+            .filter(|(insn, _)| *insn != I::__DELETE__)
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn merges_hashes() {
+        let mut insns = vec![I::SHA_INIT_START];
+        insns.extend(vec![I::SHA_INIT_PADDING; 3]);
+        insns.extend((1..=16).map(|addr| I::SHA_LOAD(addr.into())));
+        // rows 21..=68
+        insns.extend(vec![I::SHA_MIX; 48]);
+        insns.push(I::SHA_FINI_START(100));
+        insns.extend(vec![I::SHA_FINI_PADDING; 3]);
+        let mut code = code(insns);
+        ShaPass::pass(&mut code).unwrap();
+
+        let live = live(&code);
+        assert_eq!(live.len(), 1 + 16 + 1 + 1);
+        assert!(live[0] == (I::__SHA_INIT__, LineNo::new(1, 0).to(&LineNo::new(4, 0))));
+        assert!(
+            live[17]
+                == (
+                    I::__SHA_MIX_48__,
+                    LineNo::new(21, 0).to(&LineNo::new(68, 0))
+                )
+        );
+        assert!(
+            live[18]
+                == (
+                    I::__SHA_FINI__(100),
+                    LineNo::new(69, 0).to(&LineNo::new(72, 0))
+                )
+        );
+    }
+
+    #[test]
+    fn leaves_short_runs() {
+        // 47 mix rows, then a row of another kind and one more mix row
+        let mut insns = vec![I::SHA_MIX; 47];
+        insns.push(I::NOP);
+        insns.push(I::SHA_MIX);
+        // an init start followed by two paddings only
+        insns.push(I::SHA_INIT_START);
+        insns.extend(vec![I::SHA_INIT_PADDING; 2]);
+        insns.push(I::NOP);
+        // the same at the end of the code
+        insns.extend(vec![I::SHA_MIX; 47]);
+        insns.push(I::SHA_INIT_START);
+        insns.extend(vec![I::SHA_INIT_PADDING; 2]);
+        let mut code = code(insns);
+        let before = code.0.clone();
+        ShaPass::pass(&mut code).unwrap();
+        assert!(code.0 == before);
+    }
+}
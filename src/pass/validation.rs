@@ -0,0 +1,199 @@
+use crate::iop::{self, IopSource};
+use crate::parser::{Code, LineNo};
+use crate::pass::ValidationError;
+use crate::structures::StructuredInstruction;
+use crate::vm::{Globals, Vm};
+
+// what a run shows from outside: the globals, the assertions that fail, and the error that
+// stops it, if any
+#[derive(Debug, PartialEq, Eq)]
+struct Outcome {
+    globals: Globals,
+    failures: Vec<LineNo>,
+    error: Option<(LineNo, String)>,
+}
+
+fn run(code: &Code, words: &[u32]) -> Outcome {
+    let mut vm = Vm::new(IopSource::new(words.iter().copied()));
+    // the passes reuse addresses, and the random inputs fail the checks of the programs
+    vm.set_write_once(false);
+    vm.set_stop_on_failure(false);
+    let error = vm.run(code).err().map(|e| (e.line_no(), e.to_string()));
+    Outcome {
+        globals: vm.globals().clone(),
+        failures: vm.failures().to_vec(),
+        error,
+    }
+}
+
+// the first line where two outcomes differ, with how they differ
+fn compare(before: &Outcome, after: &Outcome, code: &Code) -> Option<(LineNo, String)> {
+    let mut diffs = Vec::new();
+
+    let mut failures = before.failures.iter().zip(after.failures.iter());
+    if let Some((b, a)) = failures.find(|(b, a)| b != a) {
+        diffs.push((
+            *b.min(a),
+            format!(
+                "the assertion at line {} fails instead of the one at line {}",
+                a, b
+            ),
+        ));
+    } else if before.failures.len() != after.failures.len() {
+        let (line_no, what) = if before.failures.len() > after.failures.len() {
+            (before.failures[after.failures.len()], "no longer fails")
+        } else {
+            (after.failures[before.failures.len()], "now fails")
+        };
+        diffs.push((
+            line_no,
+            format!("the assertion at line {} {}", line_no, what),
+        ));
+    }
+
+    match (&before.error, &after.error) {
+        (Some((b, _)), Some((a, _))) if a.row == b.row => {}
+        (None, None) => {}
+        (Some((line_no, e)), None) => {
+            diffs.push((*line_no, format!("no longer stops with: {}", e)))
+        }
+        (_, Some((line_no, e))) => diffs.push((*line_no, format!("now stops with: {}", e))),
+    }
+
+    for (idx, value) in before.globals.iter() {
+        if after.globals.get(idx) != Some(value) {
+            diffs.push((
+                set_global_line(code, *idx),
+                format!("global {} differs", idx),
+            ));
+        }
+    }
+    for idx in after.globals.keys() {
+        if !before.globals.contains_key(idx) {
+            diffs.push((
+                set_global_line(code, *idx),
+                format!("global {} differs", idx),
+            ));
+        }
+    }
+
+    diffs.into_iter().min_by_key(|(line_no, _)| *line_no)
+}
+
+fn set_global_line(code: &Code, idx: u32) -> LineNo {
+    code.0
+        .iter()
+        .rev()
+        .find(|(insn, _)| matches!(insn, StructuredInstruction::SET_GLOBAL(.., i) if *i == idx))
+        .map(|(_, line_no)| *line_no)
+        .unwrap_or_default()
+}
+
+/// Translation validation for the passes: the program is run before and after each pass, on
+/// random IOP inputs, and a pass is accepted only if the globals, the assertions that fail and
+/// the error that stops the program, if any, stay the same.
+///
+/// Assertions do not stop the runs, so that the whole program runs although random inputs fail
+/// its checks of the seal. The inputs are generated once, from the headers of the code given to
+/// `new`, which the passes keep.
+pub struct Validator {
+    inputs: Vec<(u64, Vec<u32>)>,
+    outcomes: Vec<Outcome>,
+}
+
+impl Validator {
+    /// Run `code`, before any pass, on the IOP input of each seed.
+    pub fn new(code: &Code, seeds: impl IntoIterator<Item = u64>) -> Self {
+        let inputs: Vec<(u64, Vec<u32>)> = seeds
+            .into_iter()
+            .map(|seed| (seed, iop::generate(code, seed)))
+            .collect();
+        let outcomes = inputs.iter().map(|(_, words)| run(code, words)).collect();
+        Self { inputs, outcomes }
+    }
+
+    /// Run `code` as it is after the pass `name`, and compare it with the run before the pass.
+    pub fn check(&mut self, name: &str, code: &Code) -> Result<(), ValidationError> {
+        for ((seed, words), before) in self.inputs.iter().zip(self.outcomes.iter_mut()) {
+            let after = run(code, words);
+            if let Some((line_no, what)) = compare(before, &after, code) {
+                return Err(ValidationError::Mismatch(
+                    name.to_string(),
+                    *seed,
+                    line_no,
+                    what,
+                ));
+            }
+            *before = after;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pass::const_pass::ConstPass;
+    use crate::pass::Pass;
+    use StructuredInstruction as I;
+
+    fn program() -> Code {
+        let mut code = Code::default();
+        for (row, insn) in [
+            I::WOM_INIT,
+            I::READ_IOP_HEADER(1, 4),
+            I::READ_IOP_BODY(1),
+            I::CONST(2, 3, 0),
+            I::ADD(3, 1.into(), 2.into()),
+            I::EQ(1.into(), 2.into()),
+            I::SET_GLOBAL(3.into(), 0.into(), 0.into(), 0.into(), 0),
+            I::WOM_FINI,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            code.push(insn, LineNo::new(row + 1, 0));
+        }
+        code
+    }
+
+    #[test]
+    fn accepts_a_sound_pass() {
+        let mut code = program();
+        let mut validator = Validator::new(&code, 0..4);
+        ConstPass::pass(&mut code).unwrap();
+        assert_eq!(validator.check("const", &code), Ok(()));
+    }
+
+    #[test]
+    fn catches_a_broken_pass() {
+        let code = program();
+        let mut validator = Validator::new(&code, 0..4);
+
+        // a pass that subtracts instead of adding
+        let mut broken = program();
+        broken.0[4].0 = I::SUB(3, 1.into(), 2.into());
+        assert_eq!(
+            validator.check("broken", &broken),
+            Err(ValidationError::Mismatch(
+                "broken".to_string(),
+                0,
+                LineNo::new(7, 0),
+                "global 0 differs".to_string()
+            ))
+        );
+
+        // a pass that drops an assertion that the random inputs fail
+        let mut broken = program();
+        broken.0[5].0 = I::__DELETE__;
+        assert_eq!(
+            validator.check("broken", &broken),
+            Err(ValidationError::Mismatch(
+                "broken".to_string(),
+                0,
+                LineNo::new(6, 0),
+                "the assertion at line 6 no longer fails".to_string()
+            ))
+        );
+    }
+}
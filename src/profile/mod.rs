@@ -0,0 +1,441 @@
+use crate::iop::header_words;
+use crate::parser::Code;
+use crate::structures::StructuredInstruction;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+
+/// What a row of a zkr spends itself on, as `profile` counts them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RowClass {
+    // a row of micro ops, none of which reads the IOP
+    Micro,
+    // a row of micro ops that reads an IOP header or body
+    Iop,
+    BitOp,
+    Sha,
+    PoseidonLoad,
+    PoseidonFull,
+    PoseidonPartial,
+    PoseidonStore,
+    Poseidon2Load,
+    Poseidon2Full,
+    Poseidon2Partial,
+    Poseidon2Store,
+    // nop, wom_init, wom_fini and set_global
+    Control,
+    Padding,
+}
+
+impl RowClass {
+    pub const ALL: [RowClass; 14] = [
+        RowClass::Micro,
+        RowClass::Iop,
+        RowClass::BitOp,
+        RowClass::Sha,
+        RowClass::PoseidonLoad,
+        RowClass::PoseidonFull,
+        RowClass::PoseidonPartial,
+        RowClass::PoseidonStore,
+        RowClass::Poseidon2Load,
+        RowClass::Poseidon2Full,
+        RowClass::Poseidon2Partial,
+        RowClass::Poseidon2Store,
+        RowClass::Control,
+        RowClass::Padding,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RowClass::Micro => "micro",
+            RowClass::Iop => "iop",
+            RowClass::BitOp => "bit op",
+            RowClass::Sha => "sha",
+            RowClass::PoseidonLoad => "poseidon load",
+            RowClass::PoseidonFull => "poseidon full",
+            RowClass::PoseidonPartial => "poseidon partial",
+            RowClass::PoseidonStore => "poseidon store",
+            RowClass::Poseidon2Load => "poseidon2 load",
+            RowClass::Poseidon2Full => "poseidon2 full",
+            RowClass::Poseidon2Partial => "poseidon2 partial",
+            RowClass::Poseidon2Store => "poseidon2 store",
+            RowClass::Control => "control",
+            RowClass::Padding => "padding",
+        }
+    }
+
+    fn of(insn: &StructuredInstruction) -> Self {
+        match insn {
+            StructuredInstruction::BIT_AND_ELEM(..)
+            | StructuredInstruction::BIT_AND_SHORTS(..)
+            | StructuredInstruction::BIT_XOR_SHORTS(..) => RowClass::BitOp,
+            StructuredInstruction::SHA_INIT_START
+            | StructuredInstruction::SHA_INIT_PADDING
+            | StructuredInstruction::SHA_LOAD_FROM_MONTGOMERY(_)
+            | StructuredInstruction::SHA_LOAD(_)
+            | StructuredInstruction::SHA_MIX
+            | StructuredInstruction::SHA_FINI_START(_)
+            | StructuredInstruction::SHA_FINI_PADDING => RowClass::Sha,
+            StructuredInstruction::WOM_INIT
+            | StructuredInstruction::WOM_FINI
+            | StructuredInstruction::NOP
+            | StructuredInstruction::SET_GLOBAL(..) => RowClass::Control,
+            StructuredInstruction::PADDING => RowClass::Padding,
+            StructuredInstruction::READ_IOP_HEADER(..)
+            | StructuredInstruction::READ_IOP_BODY(_) => RowClass::Iop,
+            StructuredInstruction::POSEIDON_LOAD_FROM_MONTGOMERY(..)
+            | StructuredInstruction::POSEIDON_LOAD(..)
+            | StructuredInstruction::POSEIDON_ADD_LOAD_FROM_MONTGOMERY(..)
+            | StructuredInstruction::POSEIDON_ADD_LOAD(..) => RowClass::PoseidonLoad,
+            StructuredInstruction::POSEIDON_FULL => RowClass::PoseidonFull,
+            StructuredInstruction::POSEIDON_PARTIAL => RowClass::PoseidonPartial,
+            StructuredInstruction::POSEIDON_STORE_TO_MONTGOMERY(..)
+            | StructuredInstruction::POSEIDON_STORE(..) => RowClass::PoseidonStore,
+            StructuredInstruction::POSEIDON2_LOAD_FROM_MONTGOMERY(..)
+            | StructuredInstruction::POSEIDON2_LOAD(..)
+            | StructuredInstruction::POSEIDON2_ADD_LOAD_FROM_MONTGOMERY(..)
+            | StructuredInstruction::POSEIDON2_ADD_LOAD(..) => RowClass::Poseidon2Load,
+            StructuredInstruction::POSEIDON2_FULL => RowClass::Poseidon2Full,
+            StructuredInstruction::POSEIDON2_PARTIAL => RowClass::Poseidon2Partial,
+            StructuredInstruction::POSEIDON2_STORE_TO_MONTGOMERY(..)
+            | StructuredInstruction::POSEIDON2_STORE(..) => RowClass::Poseidon2Store,
+            _ => RowClass::Micro,
+        }
+    }
+}
+
+impl Display for RowClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The cost of a program, or of a region of it, counted from its raw code.
+///
+/// A micro op `m[x] = (0, 0)` whose `m[x]` no instruction reads counts as an unused slot, which
+/// is how the rows that need fewer than three micro ops are filled; once read, it is a constant
+/// zero and counts as a micro op. A Poseidon or Poseidon2 permutation is counted at its first
+/// partial round, and a SHA-256 block at its first mix row after the loads.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub rows: usize,
+    // the rows of each class, in the order of `RowClass::ALL`
+    pub classes: [usize; 14],
+    // the micro rows, with or without IOP reads, by the number of micro ops they use
+    pub micro_ops: [usize; 4],
+    pub poseidon_permutations: usize,
+    pub poseidon2_permutations: usize,
+    pub sha_blocks: usize,
+    pub iop_words: usize,
+}
+
+impl Profile {
+    /// The rows of `class`.
+    pub fn count(&self, class: RowClass) -> usize {
+        self.classes[class as usize]
+    }
+
+    fn add(&mut self, row: &Row) {
+        self.rows += 1;
+        self.classes[row.class as usize] += 1;
+        if let Some(ops) = row.micro_ops {
+            self.micro_ops[ops] += 1;
+        }
+        match row.class {
+            RowClass::PoseidonPartial if row.starts => self.poseidon_permutations += 1,
+            RowClass::Poseidon2Partial if row.starts => self.poseidon2_permutations += 1,
+            RowClass::Sha if row.starts => self.sha_blocks += 1,
+            _ => {}
+        }
+        self.iop_words += row.iop_words;
+    }
+}
+
+impl Display for Profile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("rows: {}\n", self.rows))?;
+        for class in RowClass::ALL {
+            let count = self.count(class);
+            if count != 0 {
+                f.write_fmt(format_args!(
+                    "  {:<18} {:>9} ({:.1}%)\n",
+                    class.name(),
+                    count,
+                    count as f64 * 100.0 / self.rows as f64
+                ))?;
+            }
+        }
+        let micro_rows: usize = self.micro_ops.iter().sum();
+        if micro_rows != 0 {
+            let ops: usize = self.micro_ops.iter().enumerate().map(|(i, n)| i * n).sum();
+            f.write_fmt(format_args!(
+                "micro ops: {} in {} rows, {:.2} per row\n",
+                ops,
+                micro_rows,
+                ops as f64 / micro_rows as f64
+            ))?;
+            for (i, n) in self.micro_ops.iter().enumerate() {
+                if *n != 0 {
+                    f.write_fmt(format_args!("  {} ops {:>9}\n", i, n))?;
+                }
+            }
+        }
+        f.write_fmt(format_args!(
+            "poseidon permutations: {}\n",
+            self.poseidon_permutations
+        ))?;
+        f.write_fmt(format_args!(
+            "poseidon2 permutations: {}\n",
+            self.poseidon2_permutations
+        ))?;
+        f.write_fmt(format_args!("sha blocks: {}\n", self.sha_blocks))?;
+        f.write_fmt(format_args!("iop words read: {}\n", self.iop_words))
+    }
+}
+
+// what a single row counts for
+struct Row {
+    row: usize,
+    class: RowClass,
+    micro_ops: Option<usize>,
+    // whether the row starts a permutation or a SHA-256 block
+    starts: bool,
+    iop_words: usize,
+}
+
+fn rows(code: &Code) -> Vec<Row> {
+    let read: HashSet<u32> = code
+        .0
+        .iter()
+        .flat_map(|(insn, _)| insn.reads())
+        .filter_map(|r| r.addr())
+        .collect();
+    let mut out: Vec<Row> = Vec::new();
+    let mut prev_class = None;
+    // whether the last SHA-256 row before the current one loads a word
+    let mut after_load = false;
+
+    let mut cur = 0;
+    while cur < code.0.len() {
+        let row = code.0[cur].1.row;
+        let len = code.0[cur..]
+            .iter()
+            .take_while(|(_, line_no)| line_no.row == row)
+            .count();
+        let insns = &code.0[cur..cur + len];
+        cur += len;
+
+        let class = insns
+            .iter()
+            .map(|(insn, _)| RowClass::of(insn))
+            .find(|class| *class == RowClass::Iop)
+            .unwrap_or_else(|| RowClass::of(&insns[0].0));
+        let micro_ops = matches!(class, RowClass::Micro | RowClass::Iop).then(|| {
+            insns
+                .iter()
+                .filter(|(insn, _)| {
+                    !matches!(insn, StructuredInstruction::CONST(addr, 0, 0) if !read.contains(addr))
+                })
+                .count()
+        });
+
+        let starts = match &insns[0].0 {
+            StructuredInstruction::POSEIDON_PARTIAL | StructuredInstruction::POSEIDON2_PARTIAL => {
+                prev_class != Some(class)
+            }
+            StructuredInstruction::SHA_MIX => after_load,
+            _ => false,
+        };
+        if class == RowClass::Sha {
+            after_load = matches!(
+                insns[0].0,
+                StructuredInstruction::SHA_LOAD(_)
+                    | StructuredInstruction::SHA_LOAD_FROM_MONTGOMERY(_)
+            );
+        }
+
+        let iop_words = insns
+            .iter()
+            .map(|(insn, _)| match insn {
+                StructuredInstruction::READ_IOP_HEADER(count, k_and_flip_flag) => {
+                    header_words(*count, *k_and_flip_flag)
+                }
+                _ => 0,
+            })
+            .sum();
+
+        prev_class = Some(class);
+        out.push(Row {
+            row,
+            class,
+            micro_ops,
+            starts,
+            iop_words,
+        });
+    }
+    out
+}
+
+/// Profile raw `code`, as parsed from a zkr before any pass.
+pub fn profile(code: &Code) -> Profile {
+    let mut profile = Profile::default();
+    for row in rows(code).iter() {
+        profile.add(row);
+    }
+    profile
+}
+
+/// Profile raw `code` in regions of `window` rows, the last of which may be shorter.
+pub fn regions(code: &Code, window: usize) -> Vec<(Range<usize>, Profile)> {
+    let mut out: Vec<(Range<usize>, Profile)> = Vec::new();
+    for row in rows(code).iter() {
+        let start = (row.row - 1) / window * window + 1;
+        if out.last().is_none_or(|(range, _)| range.start != start) {
+            out.push((start..start + window, Profile::default()));
+        }
+        out.last_mut().unwrap().1.add(row);
+    }
+    if let Some((range, profile)) = out.last_mut() {
+        range.end = range.start + profile.rows;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::LineNo;
+    use crate::sha::{FINI_ROWS, INIT_ROWS, LOAD_ROWS, MIX_ROWS};
+    use StructuredInstruction as I;
+
+    // 87 rows: control, two micro rows and an IOP row, a Poseidon permutation, a SHA-256 block,
+    // a bit op, control and padding
+    fn program() -> Code {
+        let r = |v: u32| v.into();
+        let mut rows = vec![
+            vec![I::WOM_INIT],
+            // m[2] is a constant zero that is read, m[3] fills the row
+            vec![I::CONST(1, 5, 0), I::CONST(2, 0, 0), I::CONST(3, 0, 0)],
+            vec![
+                I::READ_IOP_HEADER(2, 4),
+                I::READ_IOP_BODY(4),
+                I::CONST(5, 0, 0),
+            ],
+            vec![
+                I::ADD(6, r(1), r(2)),
+                I::MUL(7, r(6), r(4)),
+                I::SUB(8, r(7), r(1)),
+            ],
+            vec![I::POSEIDON_LOAD(
+                0,
+                0,
+                r(1),
+                r(2),
+                r(4),
+                r(6),
+                r(7),
+                r(8),
+                r(0),
+                r(0),
+            )],
+            vec![I::POSEIDON_FULL],
+            vec![I::POSEIDON_FULL],
+            vec![I::POSEIDON_PARTIAL],
+            vec![I::POSEIDON_FULL],
+            vec![I::POSEIDON_FULL],
+            vec![I::POSEIDON_STORE(0, 16)],
+            vec![I::SHA_INIT_START],
+        ];
+        rows.extend(vec![vec![I::SHA_INIT_PADDING]; INIT_ROWS - 1]);
+        rows.extend((0..LOAD_ROWS as u32).map(|i| vec![I::SHA_LOAD(r(16 + i % 8))]));
+        rows.extend(vec![vec![I::SHA_MIX]; MIX_ROWS]);
+        rows.push(vec![I::SHA_FINI_START(24)]);
+        rows.extend(vec![vec![I::SHA_FINI_PADDING]; FINI_ROWS - 1]);
+        rows.push(vec![I::BIT_AND_ELEM(32, r(24), r(25))]);
+        rows.push(vec![I::SET_GLOBAL(r(32), r(0), r(0), r(0), 0)]);
+        rows.push(vec![I::WOM_FINI]);
+        rows.push(vec![I::PADDING]);
+
+        let mut code = Code::default();
+        for (row, insns) in rows.into_iter().enumerate() {
+            for (slot, insn) in insns.into_iter().enumerate() {
+                code.push(insn, LineNo::new(row + 1, slot));
+            }
+        }
+        code
+    }
+
+    fn sum(profiles: &[&Profile]) -> Profile {
+        let mut out = Profile::default();
+        for p in profiles {
+            out.rows += p.rows;
+            for (o, c) in out.classes.iter_mut().zip(p.classes) {
+                *o += c;
+            }
+            for (o, c) in out.micro_ops.iter_mut().zip(p.micro_ops) {
+                *o += c;
+            }
+            out.poseidon_permutations += p.poseidon_permutations;
+            out.poseidon2_permutations += p.poseidon2_permutations;
+            out.sha_blocks += p.sha_blocks;
+            out.iop_words += p.iop_words;
+        }
+        out
+    }
+
+    #[test]
+    fn whole_program() {
+        let p = profile(&program());
+        assert_eq!(p.rows, 87);
+        let expected = [
+            (RowClass::Micro, 2),
+            (RowClass::Iop, 1),
+            (RowClass::BitOp, 1),
+            (RowClass::Sha, 72),
+            (RowClass::PoseidonLoad, 1),
+            (RowClass::PoseidonFull, 4),
+            (RowClass::PoseidonPartial, 1),
+            (RowClass::PoseidonStore, 1),
+            (RowClass::Control, 3),
+            (RowClass::Padding, 1),
+        ];
+        for class in RowClass::ALL {
+            let count = expected
+                .iter()
+                .find(|(c, _)| *c == class)
+                .map_or(0, |e| e.1);
+            assert_eq!(p.count(class), count, "{}", class);
+        }
+        assert_eq!(p.micro_ops, [0, 0, 2, 1]);
+        assert_eq!(p.poseidon_permutations, 1);
+        assert_eq!(p.poseidon2_permutations, 0);
+        assert_eq!(p.sha_blocks, 1);
+        assert_eq!(p.iop_words, 2);
+    }
+
+    #[test]
+    fn windows() {
+        let code = program();
+        let whole = profile(&code);
+
+        let r = regions(&code, 10);
+        let ranges: Vec<Range<usize>> = r.iter().map(|(range, _)| range.clone()).collect();
+        let mut expected: Vec<Range<usize>> = (0..8).map(|i| i * 10 + 1..i * 10 + 11).collect();
+        // the last window is cut at the end of the program
+        expected.push(81..88);
+        assert_eq!(ranges, expected);
+        assert_eq!(r[0].1.rows, 10);
+        assert_eq!(r[0].1.poseidon_permutations, 1);
+        assert_eq!(r[0].1.micro_ops, [0, 0, 2, 1]);
+        assert_eq!(r[8].1.rows, 7);
+        assert_eq!(sum(&r.iter().map(|(_, p)| p).collect::<Vec<_>>()), whole);
+
+        for window in [87, 1000] {
+            assert_eq!(regions(&code, window), vec![(1..88, whole.clone())]);
+        }
+        let r = regions(&code, 86);
+        assert_eq!(r[1].0, 87..88);
+        assert_eq!(r[1].1.count(RowClass::Padding), 1);
+        assert!(regions(&Code::default(), 10).is_empty());
+    }
+}
@@ -0,0 +1,487 @@
+use crate::iop::{header_words, IopSource};
+use crate::math::Fp4;
+use crate::parser::{Code, LineNo};
+use crate::structures::{ReadAddr, StructuredInstruction};
+use crate::vm::{Vm, VmError};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+/// How a node of a provenance tree gets its value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    // an instruction that computes the value from its inputs
+    Computed,
+    // a constant operand, or an instruction that writes a constant
+    Constant,
+    // an element of the IOP input, with the positions in the stream of the words it is made of
+    Iop(Vec<usize>),
+    // an address that is read before any write, which reads as zero
+    Unwritten,
+    // an instruction already shown in the tree, whose inputs are not shown again
+    Repeated,
+    // an instruction at the depth limit, whose inputs are not shown
+    Truncated,
+}
+
+/// A node of the backward dependency tree of a value: the instruction that produces it, and
+/// the nodes of its inputs.
+///
+/// The inputs of a Poseidon or SHA-256 output are the loads into the state since it was last
+/// cleared, which appear as nodes of their own, with `addr` unset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    // the address whose value the node is, if any
+    pub addr: Option<u32>,
+    // the line of the instruction, unset for a constant operand or an unwritten address
+    pub line_no: Option<LineNo>,
+    pub insn: String,
+    // the value at `addr`, known after `Provenance::run`
+    pub value: Option<Fp4>,
+    pub origin: Origin,
+    pub inputs: Vec<Node>,
+}
+
+impl Node {
+    /// The positions of the IOP words that the value depends on.
+    pub fn iop_words(&self) -> BTreeSet<usize> {
+        let mut words = BTreeSet::new();
+        self.collect_iop_words(&mut words);
+        words
+    }
+
+    fn collect_iop_words(&self, words: &mut BTreeSet<usize>) {
+        if let Origin::Iop(w) = &self.origin {
+            words.extend(w.iter().copied());
+        }
+        for input in self.inputs.iter() {
+            input.collect_iop_words(words);
+        }
+    }
+
+    fn write(&self, f: &mut Formatter<'_>, depth: usize) -> std::fmt::Result {
+        f.write_fmt(format_args!("{:1$}", "", depth * 2))?;
+        match (&self.origin, self.line_no) {
+            (Origin::Constant, None) => f.write_fmt(format_args!("{} (constant)", self.insn))?,
+            (Origin::Unwritten, _) => f.write_fmt(format_args!(
+                "m[{}] is not written before, which reads as 0",
+                self.addr.unwrap_or_default()
+            ))?,
+            (_, line_no) => {
+                f.write_fmt(format_args!(
+                    "{}: {}",
+                    line_no.unwrap_or_default(),
+                    self.insn
+                ))?;
+                if let Some(addr) = self.addr {
+                    match &self.value {
+                        Some(value) => f.write_fmt(format_args!("  m[{}] = {}", addr, value))?,
+                        None => f.write_fmt(format_args!("  m[{}]", addr))?,
+                    }
+                }
+            }
+        }
+        match &self.origin {
+            Origin::Iop(words) => f.write_fmt(format_args!(
+                "  (iop words {})",
+                words
+                    .iter()
+                    .map(|w| w.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ))?,
+            Origin::Repeated => f.write_str("  (see above)")?,
+            Origin::Truncated => f.write_str("  (...)")?,
+            _ => {}
+        }
+        f.write_str("\n")?;
+
+        for input in self.inputs.iter() {
+            input.write(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Node {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+// the units that keep a state between instructions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unit {
+    Poseidon,
+    Poseidon2,
+    Sha,
+}
+
+// what an instruction does with the state of its unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    // adds its inputs to the state
+    Load,
+    // clears the state, and then loads its inputs, if any
+    Reset,
+    // writes the state, or a value computed from it, to memory
+    Output,
+}
+
+fn role(insn: &StructuredInstruction) -> Option<(Unit, Role)> {
+    match insn {
+        StructuredInstruction::POSEIDON_LOAD_FROM_MONTGOMERY(..)
+        | StructuredInstruction::POSEIDON_LOAD(..) => Some((Unit::Poseidon, Role::Reset)),
+        StructuredInstruction::POSEIDON_ADD_LOAD_FROM_MONTGOMERY(..)
+        | StructuredInstruction::POSEIDON_ADD_LOAD(..) => Some((Unit::Poseidon, Role::Load)),
+        StructuredInstruction::POSEIDON_STORE_TO_MONTGOMERY(..)
+        | StructuredInstruction::POSEIDON_STORE(..)
+        | StructuredInstruction::__POSEIDON_PERMUTE_STORE_TO_MONTGOMERY__(..)
+        | StructuredInstruction::__POSEIDON_PERMUTE_STORE__(..) => {
+            Some((Unit::Poseidon, Role::Output))
+        }
+        // the upper third of the state is kept from the previous permutation unless cleared
+        StructuredInstruction::POSEIDON2_LOAD_FROM_MONTGOMERY(keep_upper_state, ..)
+        | StructuredInstruction::POSEIDON2_LOAD(keep_upper_state, ..) => {
+            if *keep_upper_state == 0 {
+                Some((Unit::Poseidon2, Role::Reset))
+            } else {
+                Some((Unit::Poseidon2, Role::Load))
+            }
+        }
+        StructuredInstruction::POSEIDON2_ADD_LOAD_FROM_MONTGOMERY(..)
+        | StructuredInstruction::POSEIDON2_ADD_LOAD(..) => Some((Unit::Poseidon2, Role::Load)),
+        StructuredInstruction::POSEIDON2_STORE_TO_MONTGOMERY(..)
+        | StructuredInstruction::POSEIDON2_STORE(..)
+        | StructuredInstruction::__POSEIDON2_PERMUTE_STORE_TO_MONTGOMERY__(..)
+        | StructuredInstruction::__POSEIDON2_PERMUTE_STORE__(..) => {
+            Some((Unit::Poseidon2, Role::Output))
+        }
+        StructuredInstruction::SHA_INIT_START | StructuredInstruction::__SHA_INIT__ => {
+            Some((Unit::Sha, Role::Reset))
+        }
+        StructuredInstruction::SHA_LOAD_FROM_MONTGOMERY(_) | StructuredInstruction::SHA_LOAD(_) => {
+            Some((Unit::Sha, Role::Load))
+        }
+        StructuredInstruction::SHA_FINI_START(_) | StructuredInstruction::__SHA_FINI__(_) => {
+            Some((Unit::Sha, Role::Output))
+        }
+        _ => None,
+    }
+}
+
+// the positions in the stream of the words of element `i` of the header `(count, k_and_flip_flag)`
+// whose words start at `start`, as `IopSource` reads them
+fn element_words(start: usize, count: u32, k_and_flip_flag: u32, i: usize) -> Vec<usize> {
+    let k = k_and_flip_flag as usize / 2;
+    if k == 2 {
+        return vec![start + i];
+    }
+    (0..k.min(4))
+        .map(|j| {
+            if k_and_flip_flag & 1 == 1 {
+                start + i * k + j
+            } else {
+                start + j * count as usize + i
+            }
+        })
+        .collect()
+}
+
+/// Backward provenance of the values of a program: since memory is write-once, the value at an
+/// address comes from a single instruction, whose inputs come from earlier ones, down to the
+/// constants and the IOP reads.
+///
+/// After a pass that reuses addresses, an address read by an instruction is taken to come from
+/// the last instruction before it that writes the address.
+pub struct Provenance<'a> {
+    code: &'a Code,
+    // for each address, the instructions that write it, in order
+    writers: HashMap<u32, Vec<usize>>,
+    // for each IOP read, by instruction and address, the words of the element it reads
+    iop: HashMap<(usize, u32), Vec<usize>>,
+    // the values that each instruction writes, after `run`
+    values: Vec<Vec<Fp4>>,
+    max_depth: Option<usize>,
+}
+
+impl<'a> Provenance<'a> {
+    pub fn new(code: &'a Code) -> Self {
+        let mut writers: HashMap<u32, Vec<usize>> = HashMap::new();
+        let mut iop = HashMap::new();
+        // the first word, count, k_and_flip_flag of the last header, and the next element
+        let mut header = (0, 0, 0);
+        let mut element = 0;
+        let mut pos = 0;
+
+        for (idx, (insn, _)) in code.0.iter().enumerate() {
+            for addr in insn.writes() {
+                writers.entry(addr).or_default().push(idx);
+            }
+            match insn {
+                StructuredInstruction::READ_IOP_HEADER(count, k_and_flip_flag) => {
+                    header = (pos, *count, *k_and_flip_flag);
+                    element = 0;
+                    pos += header_words(*count, *k_and_flip_flag);
+                }
+                StructuredInstruction::READ_IOP_BODY(_)
+                | StructuredInstruction::__READ_IOP_BODY_BATCH__(..) => {
+                    for addr in insn.writes() {
+                        let (start, count, k_and_flip_flag) = header;
+                        iop.insert(
+                            (idx, addr),
+                            element_words(start, count, k_and_flip_flag, element),
+                        );
+                        element += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Self {
+            code,
+            writers,
+            iop,
+            values: Vec::new(),
+            max_depth: None,
+        }
+    }
+
+    /// Stop the trees at this depth, the root being at depth 0.
+    pub fn set_max_depth(&mut self, max_depth: Option<usize>) {
+        self.max_depth = max_depth;
+    }
+
+    /// Run the program on `iop` to give the nodes their values. Failed assertions do not stop
+    /// the run; on an error, the instructions from there on are left without values.
+    pub fn run(&mut self, iop: IopSource) -> Result<(), VmError> {
+        let mut vm = Vm::new(iop);
+        vm.set_write_once(false);
+        vm.set_stop_on_failure(false);
+        self.values.clear();
+        for (insn, line_no) in self.code.0.iter() {
+            vm.step(insn, *line_no)?;
+            self.values.push(
+                insn.writes()
+                    .map(|addr| vm.get(addr).cloned().unwrap_or_default())
+                    .collect(),
+            );
+        }
+        Ok(())
+    }
+
+    /// The tree of the value at `addr` as the instruction at index `before` reads it, or after
+    /// the last instruction if `before` is not given. `None` if no instruction writes `addr`
+    /// before.
+    pub fn of_addr(&self, addr: u32, before: Option<usize>) -> Option<Node> {
+        let idx = self.producer(addr, before.unwrap_or(self.code.0.len()))?;
+        Some(self.node(idx, Some(addr), 0, &mut HashSet::new()))
+    }
+
+    /// The tree of the last `set_global` of global `index`, whose inputs are the four values it
+    /// reads.
+    pub fn of_global(&self, index: u32) -> Option<Node> {
+        let idx = self.code.0.iter().rposition(
+            |(insn, _)| matches!(insn, StructuredInstruction::SET_GLOBAL(.., i) if *i == index),
+        )?;
+        Some(self.node(idx, None, 0, &mut HashSet::new()))
+    }
+
+    // the last instruction before `before` that writes `addr`
+    fn producer(&self, addr: u32, before: usize) -> Option<usize> {
+        let writers = self.writers.get(&addr)?;
+        let n = writers.partition_point(|idx| *idx < before);
+        n.checked_sub(1).map(|i| writers[i])
+    }
+
+    fn value(&self, idx: usize, addr: u32) -> Option<Fp4> {
+        let start = self.code.0[idx].0.writes().start;
+        self.values
+            .get(idx)
+            .and_then(|values| values.get((addr - start) as usize))
+            .cloned()
+    }
+
+    fn node(
+        &self,
+        idx: usize,
+        addr: Option<u32>,
+        depth: usize,
+        seen: &mut HashSet<(usize, Option<u32>)>,
+    ) -> Node {
+        let (insn, line_no) = &self.code.0[idx];
+        let mut node = Node {
+            addr,
+            line_no: Some(*line_no),
+            insn: insn.to_string(),
+            value: addr.and_then(|addr| self.value(idx, addr)),
+            origin: Origin::Computed,
+            inputs: Vec::new(),
+        };
+
+        if let Some(words) = addr.and_then(|addr| self.iop.get(&(idx, addr))) {
+            node.origin = Origin::Iop(words.clone());
+            return node;
+        }
+        if matches!(insn, StructuredInstruction::CONST(..)) {
+            node.origin = Origin::Constant;
+            return node;
+        }
+        if !seen.insert((idx, addr)) {
+            node.origin = Origin::Repeated;
+            return node;
+        }
+        if self.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            node.origin = Origin::Truncated;
+            return node;
+        }
+
+        for r in insn.reads() {
+            node.inputs.push(self.read_node(r, idx, depth + 1, seen));
+        }
+        if let Some((unit, Role::Output)) = role(insn) {
+            for load in self.loads(unit, idx) {
+                node.inputs.push(self.node(load, None, depth + 1, seen));
+            }
+        }
+        node
+    }
+
+    fn read_node(
+        &self,
+        r: &ReadAddr,
+        idx: usize,
+        depth: usize,
+        seen: &mut HashSet<(usize, Option<u32>)>,
+    ) -> Node {
+        let addr = match r {
+            ReadAddr::Ref(addr) | ReadAddr::RefSub(addr, _) => *addr,
+            ReadAddr::Const(value) => {
+                return Node {
+                    addr: None,
+                    line_no: None,
+                    insn: value.to_string(),
+                    value: Some(value.clone()),
+                    origin: Origin::Constant,
+                    inputs: Vec::new(),
+                }
+            }
+        };
+        match self.producer(addr, idx) {
+            Some(producer) => self.node(producer, Some(addr), depth, seen),
+            None => Node {
+                addr: Some(addr),
+                line_no: None,
+                insn: String::new(),
+                value: Some(Fp4::default()),
+                origin: Origin::Unwritten,
+                inputs: Vec::new(),
+            },
+        }
+    }
+
+    // the loads into the state of `unit` that the output at `idx` depends on, in order
+    fn loads(&self, unit: Unit, idx: usize) -> Vec<usize> {
+        let mut loads = Vec::new();
+        for j in (0..idx).rev() {
+            match role(&self.code.0[j].0) {
+                Some((u, Role::Load)) if u == unit => loads.push(j),
+                Some((u, Role::Reset)) if u == unit => {
+                    if !self.code.0[j].0.reads().is_empty() {
+                        loads.push(j);
+                    }
+                    break;
+                }
+                _ => {}
+            }
+        }
+        loads.reverse();
+        loads
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use StructuredInstruction as I;
+
+    fn program() -> Code {
+        let r = |v: u32| v.into();
+        let mut code = Code::default();
+        for (row, insns) in [
+            vec![I::WOM_INIT],
+            vec![
+                I::READ_IOP_HEADER(1, 4),
+                I::READ_IOP_BODY(1),
+                I::CONST(2, 3, 0),
+            ],
+            vec![
+                I::ADD(3, r(1), r(2)),
+                I::MUL(4, r(3), r(3)),
+                I::ADD(5, r(4), r(1)),
+            ],
+            vec![I::SET_GLOBAL(r(5), r(0), r(0), r(0), 0)],
+            vec![I::WOM_FINI],
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            for (slot, insn) in insns.into_iter().enumerate() {
+                code.push(insn, LineNo::new(row + 1, slot));
+            }
+        }
+        code
+    }
+
+    #[test]
+    fn tree_of_a_global() {
+        let code = program();
+        let mut provenance = Provenance::new(&code);
+        provenance.run(IopSource::new([0x0002_0001])).unwrap();
+        let tree = provenance.of_global(0).unwrap();
+        assert_eq!(
+            tree.to_string(),
+            "4: set_global((m[5].0, m[5].1, m[0].0, m[0].1, m[0].0, m[0].1, m[0].0, m[0].1), 0);
+  3.2: m[5] = m[4] + m[1];  m[5] = (17, 18, 4, 0)
+    3.1: m[4] = m[3] * m[3];  m[4] = (16, 16, 4, 0)
+      3: m[3] = m[1] + m[2];  m[3] = (4, 2)
+        2.1: m[1] = iop.pop();  m[1] = (1, 2)  (iop words 0)
+        2.2: m[2] = 3;  m[2] = 3
+      3: m[3] = m[1] + m[2];  m[3] = (4, 2)  (see above)
+    2.1: m[1] = iop.pop();  m[1] = (1, 2)  (iop words 0)
+  m[0] is not written before, which reads as 0
+  m[0] is not written before, which reads as 0
+  m[0] is not written before, which reads as 0
+"
+        );
+
+        let add = &tree.inputs[0].inputs[0].inputs[0];
+        assert_eq!(add.addr, Some(3));
+        assert_eq!(add.origin, Origin::Computed);
+        assert_eq!(add.inputs[0].origin, Origin::Iop(vec![0]));
+        assert_eq!(add.inputs[1].origin, Origin::Constant);
+        assert_eq!(tree.inputs[0].inputs[0].inputs[1].origin, Origin::Repeated);
+        assert_eq!(tree.inputs[1].origin, Origin::Unwritten);
+        assert_eq!(tree.iop_words(), BTreeSet::from([0]));
+    }
+
+    #[test]
+    fn depth_and_addresses() {
+        let code = program();
+        let mut provenance = Provenance::new(&code);
+        provenance.set_max_depth(Some(1));
+        let tree = provenance.of_addr(5, None).unwrap();
+        assert_eq!(tree.line_no, Some(LineNo::new(3, 2)));
+        assert_eq!(tree.value, None);
+        assert_eq!(tree.inputs.len(), 2);
+        assert_eq!(tree.inputs[0].origin, Origin::Truncated);
+        assert!(tree.inputs[0].inputs.is_empty());
+        // the IOP read is a leaf, whatever the depth
+        assert_eq!(tree.inputs[1].origin, Origin::Iop(vec![0]));
+
+        // m[3] is written at index 4, as the first micro op of row 3
+        assert!(provenance.of_addr(3, Some(4)).is_none());
+        assert!(provenance.of_addr(3, Some(5)).is_some());
+        assert!(provenance.of_global(1).is_none());
+    }
+}
@@ -0,0 +1,24 @@
+use crate::sha::{ShaPhase, ShaRow};
+use std::fmt::{Debug, Display, Formatter};
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShaError {
+    // the row found, and how far the hash had gone when it came
+    UnexpectedRow(ShaRow, ShaPhase),
+    // the program ends, or another instruction comes, while a hash is in this phase
+    Interrupted(ShaPhase),
+}
+
+impl Display for ShaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShaError::UnexpectedRow(row, phase) => {
+                f.write_fmt(format_args!("{} cannot come {}", row, phase))
+            }
+            ShaError::Interrupted(phase) => {
+                f.write_fmt(format_args!("SHA-256 is interrupted {}", phase))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ShaError {}
@@ -0,0 +1,314 @@
+mod error;
+
+pub use crate::sha::error::ShaError;
+use sha2::digest::generic_array::GenericArray;
+use std::fmt::{Display, Formatter};
+
+pub const INIT_ROWS: usize = 4;
+pub const LOAD_ROWS: usize = 16;
+pub const MIX_ROWS: usize = 48;
+pub const FINI_ROWS: usize = 4;
+
+pub const SHA256_INIT: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// The kinds of rows that take part in a SHA-256 compression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaRow {
+    InitStart,
+    InitPadding,
+    Load,
+    Mix,
+    FiniStart,
+    FiniPadding,
+}
+
+impl Display for ShaRow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ShaRow::InitStart => "SHA_INIT_START",
+            ShaRow::InitPadding => "SHA_INIT_PADDING",
+            ShaRow::Load => "SHA_LOAD",
+            ShaRow::Mix => "SHA_MIX",
+            ShaRow::FiniStart => "SHA_FINI_START",
+            ShaRow::FiniPadding => "SHA_FINI_PADDING",
+        })
+    }
+}
+
+/// How far a compression has gone, with the number of rows of the current step seen so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ShaPhase {
+    #[default]
+    Idle,
+    Init(usize),
+    Load(usize),
+    Mix(usize),
+    Fini(usize),
+}
+
+impl Display for ShaPhase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShaPhase::Idle => f.write_str("outside of a hash"),
+            ShaPhase::Init(n) => {
+                f.write_fmt(format_args!("after {} of {} init rows", n, INIT_ROWS))
+            }
+            ShaPhase::Load(n) => f.write_fmt(format_args!("after {} of {} loads", n, LOAD_ROWS)),
+            ShaPhase::Mix(n) => f.write_fmt(format_args!("after {} of {} mix rows", n, MIX_ROWS)),
+            ShaPhase::Fini(n) => {
+                f.write_fmt(format_args!("after {} of {} fini rows", n, FINI_ROWS))
+            }
+        }
+    }
+}
+
+impl ShaPhase {
+    // whether the machine accepts a row of another kind at this point, following the checks
+    // of `preflight.rs`
+    fn allows_other_rows(&self) -> bool {
+        match self {
+            ShaPhase::Idle | ShaPhase::Mix(_) => true,
+            ShaPhase::Init(n) => *n == INIT_ROWS,
+            ShaPhase::Load(n) => *n == LOAD_ROWS,
+            ShaPhase::Fini(_) => false,
+        }
+    }
+}
+
+/// The SHA-256 unit of the recursion machine, which compresses one or more 64-byte blocks per
+/// hash.
+///
+/// A hash takes 4 init rows, then 16 loads of a word each and 48 mix rows for each block, and 4
+/// fini rows, in this order; the rows of each step must be contiguous, except that other
+/// instructions can come between the steps and between the mix rows. The compression of a block
+/// itself happens at the first load of the next block or at the first fini row, as in
+/// `preflight.rs`. Each method checks that its row is in place and returns a
+/// `ShaError` otherwise, leaving the engine unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sha256 {
+    state: [u32; 8],
+    block: [u32; LOAD_ROWS],
+    phase: ShaPhase,
+}
+
+impl Sha256 {
+    pub fn phase(&self) -> ShaPhase {
+        self.phase
+    }
+
+    /// The eight words of the hash state, as updated by the last compression.
+    pub fn state(&self) -> [u32; 8] {
+        self.state
+    }
+
+    fn expect(&self, row: ShaRow, ok: bool) -> Result<(), ShaError> {
+        if ok {
+            Ok(())
+        } else {
+            Err(ShaError::UnexpectedRow(row, self.phase))
+        }
+    }
+
+    pub fn init_start(&mut self) -> Result<(), ShaError> {
+        self.expect(ShaRow::InitStart, self.phase == ShaPhase::Idle)?;
+        self.state = SHA256_INIT;
+        self.phase = ShaPhase::Init(1);
+        Ok(())
+    }
+
+    pub fn init_padding(&mut self) -> Result<(), ShaError> {
+        match self.phase {
+            ShaPhase::Init(n) if n < INIT_ROWS => {
+                self.phase = ShaPhase::Init(n + 1);
+                Ok(())
+            }
+            _ => Err(ShaError::UnexpectedRow(ShaRow::InitPadding, self.phase)),
+        }
+    }
+
+    /// Load the next word of the block, whose bytes are taken in little-endian order.
+    pub fn load(&mut self, word: u32) -> Result<(), ShaError> {
+        let pos = match self.phase {
+            ShaPhase::Init(INIT_ROWS) => 0,
+            ShaPhase::Load(n) if n < LOAD_ROWS => n,
+            // the first word of the next block
+            ShaPhase::Mix(MIX_ROWS) => {
+                self.compress();
+                0
+            }
+            _ => return Err(ShaError::UnexpectedRow(ShaRow::Load, self.phase)),
+        };
+        self.block[pos] = word;
+        self.phase = ShaPhase::Load(pos + 1);
+        Ok(())
+    }
+
+    // compress the loaded block into the state
+    fn compress(&mut self) {
+        let mut bytes = [0u8; 64];
+        for (b, word) in bytes.chunks_exact_mut(4).zip(self.block.iter()) {
+            b.copy_from_slice(&word.to_le_bytes());
+        }
+        sha2::compress256(&mut self.state, &[*GenericArray::from_slice(&bytes)]);
+    }
+
+    pub fn mix(&mut self) -> Result<(), ShaError> {
+        self.phase = match self.phase {
+            ShaPhase::Load(LOAD_ROWS) => ShaPhase::Mix(1),
+            ShaPhase::Mix(n) if n < MIX_ROWS => ShaPhase::Mix(n + 1),
+            _ => return Err(ShaError::UnexpectedRow(ShaRow::Mix, self.phase)),
+        };
+        Ok(())
+    }
+
+    /// Compress the block into the state and return the eight words that the machine writes
+    /// out, which, read as little-endian, give the bytes of the digest in order.
+    pub fn fini_start(&mut self) -> Result<[u32; 8], ShaError> {
+        self.expect(ShaRow::FiniStart, self.phase == ShaPhase::Mix(MIX_ROWS))?;
+        self.compress();
+        self.phase = ShaPhase::Fini(1);
+
+        Ok(self.state.map(u32::swap_bytes))
+    }
+
+    pub fn fini_padding(&mut self) -> Result<(), ShaError> {
+        self.phase = match self.phase {
+            ShaPhase::Fini(n) if n + 1 < FINI_ROWS => ShaPhase::Fini(n + 1),
+            ShaPhase::Fini(n) if n + 1 == FINI_ROWS => ShaPhase::Idle,
+            _ => return Err(ShaError::UnexpectedRow(ShaRow::FiniPadding, self.phase)),
+        };
+        Ok(())
+    }
+
+    /// Check that an instruction outside of the SHA-256 unit can run at this point.
+    pub fn other_row(&self) -> Result<(), ShaError> {
+        if self.phase.allows_other_rows() {
+            Ok(())
+        } else {
+            Err(ShaError::Interrupted(self.phase))
+        }
+    }
+
+    /// Check that no hash is left unfinished at the end of a program.
+    pub fn finish(&self) -> Result<(), ShaError> {
+        if self.phase == ShaPhase::Idle {
+            Ok(())
+        } else {
+            Err(ShaError::Interrupted(self.phase))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // hash `blocks`, already padded to a multiple of 64 bytes
+    fn hash_blocks(sha: &mut Sha256, blocks: &[u8]) -> Result<[u32; 8], ShaError> {
+        sha.init_start()?;
+        for _ in 1..INIT_ROWS {
+            sha.init_padding()?;
+        }
+        for block in blocks.chunks_exact(64) {
+            for word in block.chunks_exact(4) {
+                sha.load(u32::from_le_bytes(word.try_into().unwrap()))?;
+            }
+            for _ in 0..MIX_ROWS {
+                sha.mix()?;
+                sha.other_row()?;
+            }
+        }
+        let out = sha.fini_start()?;
+        for _ in 1..FINI_ROWS {
+            sha.fini_padding()?;
+        }
+        Ok(out)
+    }
+
+    #[test]
+    fn fips_180_4_abc() {
+        // "abc", padded to one block
+        let mut block = [0u8; 64];
+        block[..4].copy_from_slice(b"abc\x80");
+        block[63] = 24;
+
+        let mut sha = Sha256::default();
+        let out = hash_blocks(&mut sha, &block).unwrap();
+        let digest: Vec<u8> = out.iter().flat_map(|w| w.to_le_bytes()).collect();
+        assert_eq!(
+            digest,
+            [
+                0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae,
+                0x22, 0x23, 0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61,
+                0xf2, 0x00, 0x15, 0xad,
+            ]
+        );
+        assert_eq!(sha.phase(), ShaPhase::Idle);
+        assert!(sha.finish().is_ok());
+    }
+
+    #[test]
+    fn fips_180_4_two_blocks() {
+        // a 56-byte message, whose padding and length take a second block
+        let message = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+        let mut blocks = [0u8; 128];
+        blocks[..56].copy_from_slice(message);
+        blocks[56] = 0x80;
+        blocks[120..].copy_from_slice(&(56u64 * 8).to_be_bytes());
+
+        let mut sha = Sha256::default();
+        let out = hash_blocks(&mut sha, &blocks).unwrap();
+        let digest: Vec<u8> = out.iter().flat_map(|w| w.to_le_bytes()).collect();
+        assert_eq!(
+            digest,
+            [
+                0x24, 0x8d, 0x6a, 0x61, 0xd2, 0x06, 0x38, 0xb8, 0xe5, 0xc0, 0x26, 0x93, 0x0c, 0x3e,
+                0x60, 0x39, 0xa3, 0x3c, 0xe4, 0x59, 0x64, 0xff, 0x21, 0x67, 0xf6, 0xec, 0xed, 0xd4,
+                0x19, 0xdb, 0x06, 0xc1,
+            ]
+        );
+        assert_eq!(sha.phase(), ShaPhase::Idle);
+
+        // the next block cannot start before the mix rows of the last one are done
+        let mut sha = Sha256::default();
+        sha.init_start().unwrap();
+        for _ in 1..INIT_ROWS {
+            sha.init_padding().unwrap();
+        }
+        for _ in 0..LOAD_ROWS {
+            sha.load(0).unwrap();
+        }
+        for _ in 1..MIX_ROWS {
+            sha.mix().unwrap();
+        }
+        assert_eq!(
+            sha.load(0),
+            Err(ShaError::UnexpectedRow(
+                ShaRow::Load,
+                ShaPhase::Mix(MIX_ROWS - 1)
+            ))
+        );
+    }
+
+    #[test]
+    fn rows_out_of_place() {
+        let mut sha = Sha256::default();
+        assert_eq!(
+            sha.mix(),
+            Err(ShaError::UnexpectedRow(ShaRow::Mix, ShaPhase::Idle))
+        );
+        sha.init_start().unwrap();
+        assert_eq!(
+            sha.other_row(),
+            Err(ShaError::Interrupted(ShaPhase::Init(1)))
+        );
+        assert_eq!(
+            sha.load(0),
+            Err(ShaError::UnexpectedRow(ShaRow::Load, ShaPhase::Init(1)))
+        );
+        assert_eq!(sha.phase(), ShaPhase::Init(1));
+        assert_eq!(sha.finish(), Err(ShaError::Interrupted(ShaPhase::Init(1))));
+    }
+}
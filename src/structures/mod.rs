@@ -1,24 +1,26 @@
-use crate::math::{Fp, Fp4};
+use crate::math::{ExtensionField, Fp4};
 use std::fmt::{Display, Formatter};
 use std::format_args;
+use std::ops::Range;
 
 #[allow(non_camel_case_types)]
-#[derive(PartialEq, Eq)]
-pub enum StructuredInstruction {
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StructuredInstruction<E = Fp4> {
     // m[{}] = (m[{}].0 & m[{}].0)
-    BIT_AND_ELEM(WriteAddr, ReadAddr, ReadAddr),
+    BIT_AND_ELEM(WriteAddr, ReadAddr<E>, ReadAddr<E>),
     // m[{}] = (m[{}].0 & m[{}].0 + (m[{}].1 & m[{}].1) << 16)
-    BIT_AND_SHORTS(WriteAddr, ReadAddr, ReadAddr),
+    BIT_AND_SHORTS(WriteAddr, ReadAddr<E>, ReadAddr<E>),
     // m[{}] = (m[{}].0 ^ m[{}].0, m[{}].1 ^ m[{}].1)
-    BIT_XOR_SHORTS(WriteAddr, ReadAddr, ReadAddr),
+    BIT_XOR_SHORTS(WriteAddr, ReadAddr<E>, ReadAddr<E>),
     // sha_init_start()
     SHA_INIT_START,
     // sha_init_padding()
     SHA_INIT_PADDING,
     // sha_load_from_montgomery(m[{}].0)
-    SHA_LOAD_FROM_MONTGOMERY(ReadAddr),
+    SHA_LOAD_FROM_MONTGOMERY(ReadAddr<E>),
     // sha_load(m[{}].0 + m[{}].1 << 16)
-    SHA_LOAD(ReadAddr),
+    SHA_LOAD(ReadAddr<E>),
     // sha_mix()
     SHA_MIX,
     // sha_fini_start(&mut m[{}..={}])
@@ -29,85 +31,89 @@ pub enum StructuredInstruction {
     WOM_INIT,
     // wom_fini()
     WOM_FINI,
+    // nop()
+    NOP,
+    // padding()
+    PADDING,
     // set_global(({}, {}, {}, {}, {}, {}, {}, {}), {})
-    SET_GLOBAL(ReadAddr, ReadAddr, ReadAddr, ReadAddr, Index),
+    SET_GLOBAL(ReadAddr<E>, ReadAddr<E>, ReadAddr<E>, ReadAddr<E>, Index),
     // m[{}] = ({}, {})
     CONST(WriteAddr, Parameter, Parameter),
     // m[{}] = m[{}] + m[{}]
-    ADD(WriteAddr, ReadAddr, ReadAddr),
+    ADD(WriteAddr, ReadAddr<E>, ReadAddr<E>),
     // m[{}] = m[{}] - m[{}]
-    SUB(WriteAddr, ReadAddr, ReadAddr),
+    SUB(WriteAddr, ReadAddr<E>, ReadAddr<E>),
     // m[{}] = m[{}] * m[{}]
-    MUL(WriteAddr, ReadAddr, ReadAddr),
+    MUL(WriteAddr, ReadAddr<E>, ReadAddr<E>),
     // m[{}] = (!m[{}].0)
-    NOT(WriteAddr, ReadAddr),
+    NOT(WriteAddr, ReadAddr<E>),
     // m[{}] = 1 / m[{}]
-    INV(WriteAddr, ReadAddr),
+    INV(WriteAddr, ReadAddr<E>),
     // assert_eq!(m[{}], m[{}])
-    EQ(ReadAddr, ReadAddr),
+    EQ(ReadAddr<E>, ReadAddr<E>),
     // iop = read_iop(IOP_Header {{ count: {}, k_and_flip_flag: {}}})
     READ_IOP_HEADER(Parameter, Parameter),
     // m[{}] = iop.pop()
     READ_IOP_BODY(WriteAddr),
     // m[{}] = (({} * m[{}].0) << 64 + m[{}].1 << 48 + m[{}].0 << 32 + m[{}].1 << 16 + m[{}].0)
-    MIX_RNG_WITH_PERV(WriteAddr, Parameter, ReadAddr, ReadAddr, ReadAddr),
+    MIX_RNG_WITH_PERV(WriteAddr, Parameter, ReadAddr<E>, ReadAddr<E>, ReadAddr<E>),
     // m[{}] = (m[{}].1 << 48 + m[{}].0 << 32 + m[{}].1 << 16 + m[{}].0)
-    MIX_RNG(WriteAddr, ReadAddr, ReadAddr),
+    MIX_RNG(WriteAddr, ReadAddr<E>, ReadAddr<E>),
     // m[{}] = if m[{}].0 { m[{}] } else { m[{}] }
-    SELECT(WriteAddr, ReadAddr, ReadAddr, ReadAddr),
+    SELECT(WriteAddr, ReadAddr<E>, ReadAddr<E>, ReadAddr<E>),
     // m[{}] = (m[{}].{})
-    EXTRACT(WriteAddr, ReadAddr, Index),
+    EXTRACT(WriteAddr, ReadAddr<E>, Index),
     // poseidon.add_consts = {}; poseidon.state{} = to_montgomery!(m[{}].0, m[{}].0, m[{}].0, m[{}].0, m[{}].0, m[{}].0, m[{}].0, m[{}].0)
     POSEIDON_LOAD_FROM_MONTGOMERY(
         Parameter,
         Index,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
     ),
     // poseidon.add_consts = {}; poseidon.state{} = (m[{}].0, m[{}].0, m[{}].0, m[{}].0, m[{}].0, m[{}].0, m[{}].0, m[{}].0)
     POSEIDON_LOAD(
         Parameter,
         Index,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
     ),
     // poseidon.add_consts = {}; poseidon.state{} += to_montgomery!(m[{}].0, m[{}].0, m[{}].0, m[{}].0, m[{}].0, m[{}].0, m[{}].0, m[{}].0)
     POSEIDON_ADD_LOAD_FROM_MONTGOMERY(
         Parameter,
         Index,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
     ),
     // poseidon.add_consts = {}; poseidon.state{} += (m[{}].0, m[{}].0, m[{}].0, m[{}].0, m[{}].0, m[{}].0, m[{}].0, m[{}].0)
     POSEIDON_ADD_LOAD(
         Parameter,
         Index,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
-        ReadAddr,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
     ),
     // poseidon.full()
     POSEIDON_FULL,
@@ -117,12 +123,74 @@ pub enum StructuredInstruction {
     POSEIDON_STORE_TO_MONTGOMERY(Index, WriteStartAddr),
     // poseidon.store_state{}(&mut m[{}..={}])
     POSEIDON_STORE(Index, WriteStartAddr),
+    // poseidon2.prep_full = {}; poseidon2.state = [0u32; 24] (or only [0..=15] when keeping the upper state); poseidon2.state{} = from_montgomery!(m[{}].0, ...)
+    POSEIDON2_LOAD_FROM_MONTGOMERY(
+        Parameter,
+        Parameter,
+        Index,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+    ),
+    // poseidon2.prep_full = {}; poseidon2.state = [0u32; 24] (or only [0..=15] when keeping the upper state); poseidon2.state{} = (m[{}].0, ...)
+    POSEIDON2_LOAD(
+        Parameter,
+        Parameter,
+        Index,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+    ),
+    // poseidon2.prep_full = {}; poseidon2.state{} += from_montgomery!(m[{}].0, ...)
+    POSEIDON2_ADD_LOAD_FROM_MONTGOMERY(
+        Parameter,
+        Index,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+    ),
+    // poseidon2.prep_full = {}; poseidon2.state{} += (m[{}].0, ...)
+    POSEIDON2_ADD_LOAD(
+        Parameter,
+        Index,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+        ReadAddr<E>,
+    ),
+    // poseidon2.full()
+    POSEIDON2_FULL,
+    // poseidon2.partial()
+    POSEIDON2_PARTIAL,
+    // poseidon2.store_state{}_montgomery(&mut m[{}..={}])
+    POSEIDON2_STORE_TO_MONTGOMERY(Index, WriteStartAddr),
+    // poseidon2.store_state{}(&mut m[{}..={}])
+    POSEIDON2_STORE(Index, WriteStartAddr),
     // //delete
     __DELETE__,
     // panic!()
     __PANIC__,
     // m[{}] = m[{}]
-    __MOV__(WriteAddr, ReadAddr),
+    __MOV__(WriteAddr, ReadAddr<E>),
     // iop.write(m[{}..={}])
     __READ_IOP_BODY_BATCH__(WriteStartAddr, WriteEndAddr),
     // for _ in 0..48 { sha_mix(); }
@@ -133,13 +201,19 @@ pub enum StructuredInstruction {
     __POSEIDON_PERMUTE_STORE__(Index, WriteStartAddr),
     // poseidon.permute()
     __POSEIDON_PERMUTE__,
+    // poseidon2.permute_and_store_state{}_montgomery(&mut m[{}..={}])
+    __POSEIDON2_PERMUTE_STORE_TO_MONTGOMERY__(Index, WriteStartAddr),
+    // poseidon2.permute_and_store_state{}(&mut m[{}..={}])
+    __POSEIDON2_PERMUTE_STORE__(Index, WriteStartAddr),
+    // poseidon2.permute()
+    __POSEIDON2_PERMUTE__,
     // sha_init()
     __SHA_INIT__,
     // sha_fini(&mut m[{}..={}])
     __SHA_FINI__(WriteStartAddr),
 }
 
-impl Display for StructuredInstruction {
+impl<E: ExtensionField> Display for StructuredInstruction<E> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             StructuredInstruction::BIT_AND_ELEM(w, r1, r2) => {
@@ -190,6 +264,12 @@ impl Display for StructuredInstruction {
             StructuredInstruction::WOM_FINI => {
                 f.write_fmt(format_args!("wom_fini();"))
             }
+            StructuredInstruction::NOP => {
+                f.write_fmt(format_args!("nop();"))
+            }
+            StructuredInstruction::PADDING => {
+                f.write_fmt(format_args!("padding();"))
+            }
             StructuredInstruction::SET_GLOBAL(r1, r2, r3, r4, idx) => {
                 f.write_fmt(format_args!(
                     "set_global(({}, {}, {}, {}, {}, {}, {}, {}), {});",
@@ -320,6 +400,44 @@ impl Display for StructuredInstruction {
                     ws, ws + 8 - 1, idx * 8, idx * 8 + 8 - 1
                 ))
             }
+            StructuredInstruction::POSEIDON2_LOAD_FROM_MONTGOMERY(keep_upper_state, prep_full, idx, r1, r2, r3, r4, r5, r6, r7, r8) => {
+                f.write_fmt(format_args!("{}{} poseidon2.state[{}..={}] = from_montgomery!({}, {}, {}, {}, {}, {}, {}, {});",
+                                         PrepFull(*prep_full), Poseidon2Reset(*keep_upper_state), idx * 8, idx * 8 + 8 - 1, r1._0(), r2._0(), r3._0(), r4._0(), r5._0(), r6._0(), r7._0(), r8._0()
+                ))
+            }
+            StructuredInstruction::POSEIDON2_LOAD(keep_upper_state, prep_full, idx, r1, r2, r3, r4, r5, r6, r7, r8) => {
+                f.write_fmt(format_args!("{}{} poseidon2.state[{}..={}] = ({}, {}, {}, {}, {}, {}, {}, {});",
+                                         PrepFull(*prep_full), Poseidon2Reset(*keep_upper_state), idx * 8, idx * 8 + 8 - 1, r1._0(), r2._0(), r3._0(), r4._0(), r5._0(), r6._0(), r7._0(), r8._0()
+                ))
+            }
+            StructuredInstruction::POSEIDON2_ADD_LOAD_FROM_MONTGOMERY(prep_full, idx, r1, r2, r3, r4, r5, r6, r7, r8) => {
+                f.write_fmt(format_args!("{}poseidon2.state[{}..={}] += from_montgomery!({}, {}, {}, {}, {}, {}, {}, {});",
+                                         PrepFull(*prep_full), idx * 8, idx * 8 + 8 - 1, r1._0(), r2._0(), r3._0(), r4._0(), r5._0(), r6._0(), r7._0(), r8._0()
+                ))
+            }
+            StructuredInstruction::POSEIDON2_ADD_LOAD(prep_full, idx, r1, r2, r3, r4, r5, r6, r7, r8) => {
+                f.write_fmt(format_args!("{}poseidon2.state[{}..={}] += ({}, {}, {}, {}, {}, {}, {}, {});",
+                                         PrepFull(*prep_full), idx * 8, idx * 8 + 8 - 1, r1._0(), r2._0(), r3._0(), r4._0(), r5._0(), r6._0(), r7._0(), r8._0()
+                ))
+            }
+            StructuredInstruction::POSEIDON2_FULL => {
+                f.write_fmt(format_args!("poseidon2.full();"))
+            }
+            StructuredInstruction::POSEIDON2_PARTIAL => {
+                f.write_fmt(format_args!("poseidon2.partial();"))
+            }
+            StructuredInstruction::POSEIDON2_STORE_TO_MONTGOMERY(idx, ws) => {
+                f.write_fmt(format_args!(
+                    "m[{}..={}] = to_montgomery!(poseidon2.state[{}..={}]);",
+                   ws, ws + 8 - 1, idx * 8, idx * 8 + 8 - 1
+                ))
+            }
+            StructuredInstruction::POSEIDON2_STORE(idx, ws) => {
+                f.write_fmt(format_args!(
+                    "m[{}..={}] = poseidon2.state[{}..={}];",
+                    ws, ws + 8 - 1, idx * 8, idx * 8 + 8 - 1
+                ))
+            }
             StructuredInstruction::__DELETE__ => {
                 f.write_fmt(format_args!("// deleted"))
             }
@@ -358,6 +476,23 @@ impl Display for StructuredInstruction {
                     "poseidon.permute();"
                 )
             }
+            StructuredInstruction::__POSEIDON2_PERMUTE_STORE_TO_MONTGOMERY__(idx, ws) => {
+                f.write_fmt(format_args!(
+                    "poseidon2.permute(); m[{}..={}] = to_montgomery!(poseidon2.state[{}..={}])",
+                    ws, ws + 8 - 1, idx * 8, idx * 8 + 8 - 1
+                ))
+            }
+            StructuredInstruction::__POSEIDON2_PERMUTE_STORE__(idx, ws) => {
+                f.write_fmt(format_args!(
+                    "poseidon2.permute(); m[{}..={}] = poseidon2.state[{}..={}]",
+                    ws, ws + 8 - 1, idx * 8, idx * 8 + 8 - 1
+                ))
+            }
+            StructuredInstruction::__POSEIDON2_PERMUTE__ => {
+                f.write_str(
+                    "poseidon2.permute();"
+                )
+            }
             StructuredInstruction::__SHA_INIT__ => {
                 f.write_str(
                     "sha_init();"
@@ -374,13 +509,216 @@ impl Display for StructuredInstruction {
     }
 }
 
+impl<E> StructuredInstruction<E> {
+    /// The memory reads of the instruction, in the order of its operands, including the
+    /// constants that the passes have put in place of some reads.
+    pub fn reads(&self) -> Vec<&ReadAddr<E>> {
+        match self {
+            StructuredInstruction::BIT_AND_ELEM(_, r1, r2)
+            | StructuredInstruction::BIT_AND_SHORTS(_, r1, r2)
+            | StructuredInstruction::BIT_XOR_SHORTS(_, r1, r2)
+            | StructuredInstruction::ADD(_, r1, r2)
+            | StructuredInstruction::SUB(_, r1, r2)
+            | StructuredInstruction::MUL(_, r1, r2)
+            | StructuredInstruction::EQ(r1, r2)
+            | StructuredInstruction::MIX_RNG(_, r1, r2) => vec![r1, r2],
+            StructuredInstruction::SHA_LOAD_FROM_MONTGOMERY(r)
+            | StructuredInstruction::SHA_LOAD(r)
+            | StructuredInstruction::NOT(_, r)
+            | StructuredInstruction::INV(_, r)
+            | StructuredInstruction::EXTRACT(_, r, _)
+            | StructuredInstruction::__MOV__(_, r) => vec![r],
+            StructuredInstruction::SET_GLOBAL(r1, r2, r3, r4, _) => vec![r1, r2, r3, r4],
+            StructuredInstruction::MIX_RNG_WITH_PERV(_, _, r_p, r1, r2) => vec![r_p, r1, r2],
+            StructuredInstruction::SELECT(_, s, r1, r2) => vec![s, r1, r2],
+            StructuredInstruction::POSEIDON_LOAD_FROM_MONTGOMERY(
+                _,
+                _,
+                r1,
+                r2,
+                r3,
+                r4,
+                r5,
+                r6,
+                r7,
+                r8,
+            )
+            | StructuredInstruction::POSEIDON_LOAD(_, _, r1, r2, r3, r4, r5, r6, r7, r8)
+            | StructuredInstruction::POSEIDON_ADD_LOAD_FROM_MONTGOMERY(
+                _,
+                _,
+                r1,
+                r2,
+                r3,
+                r4,
+                r5,
+                r6,
+                r7,
+                r8,
+            )
+            | StructuredInstruction::POSEIDON_ADD_LOAD(_, _, r1, r2, r3, r4, r5, r6, r7, r8)
+            | StructuredInstruction::POSEIDON2_LOAD_FROM_MONTGOMERY(
+                _,
+                _,
+                _,
+                r1,
+                r2,
+                r3,
+                r4,
+                r5,
+                r6,
+                r7,
+                r8,
+            )
+            | StructuredInstruction::POSEIDON2_LOAD(_, _, _, r1, r2, r3, r4, r5, r6, r7, r8)
+            | StructuredInstruction::POSEIDON2_ADD_LOAD_FROM_MONTGOMERY(
+                _,
+                _,
+                r1,
+                r2,
+                r3,
+                r4,
+                r5,
+                r6,
+                r7,
+                r8,
+            )
+            | StructuredInstruction::POSEIDON2_ADD_LOAD(_, _, r1, r2, r3, r4, r5, r6, r7, r8) => {
+                vec![r1, r2, r3, r4, r5, r6, r7, r8]
+            }
+            StructuredInstruction::SHA_INIT_START
+            | StructuredInstruction::SHA_INIT_PADDING
+            | StructuredInstruction::SHA_MIX
+            | StructuredInstruction::SHA_FINI_START(_)
+            | StructuredInstruction::SHA_FINI_PADDING
+            | StructuredInstruction::WOM_INIT
+            | StructuredInstruction::WOM_FINI
+            | StructuredInstruction::NOP
+            | StructuredInstruction::PADDING
+            | StructuredInstruction::CONST(_, _, _)
+            | StructuredInstruction::READ_IOP_HEADER(_, _)
+            | StructuredInstruction::READ_IOP_BODY(_)
+            | StructuredInstruction::POSEIDON_FULL
+            | StructuredInstruction::POSEIDON_PARTIAL
+            | StructuredInstruction::POSEIDON_STORE_TO_MONTGOMERY(_, _)
+            | StructuredInstruction::POSEIDON_STORE(_, _)
+            | StructuredInstruction::POSEIDON2_FULL
+            | StructuredInstruction::POSEIDON2_PARTIAL
+            | StructuredInstruction::POSEIDON2_STORE_TO_MONTGOMERY(_, _)
+            | StructuredInstruction::POSEIDON2_STORE(_, _)
+            | StructuredInstruction::__DELETE__
+            | StructuredInstruction::__PANIC__
+            | StructuredInstruction::__READ_IOP_BODY_BATCH__(_, _)
+            | StructuredInstruction::__SHA_MIX_48__
+            | StructuredInstruction::__POSEIDON_PERMUTE_STORE_TO_MONTGOMERY__(_, _)
+            | StructuredInstruction::__POSEIDON_PERMUTE_STORE__(_, _)
+            | StructuredInstruction::__POSEIDON_PERMUTE__
+            | StructuredInstruction::__POSEIDON2_PERMUTE_STORE_TO_MONTGOMERY__(_, _)
+            | StructuredInstruction::__POSEIDON2_PERMUTE_STORE__(_, _)
+            | StructuredInstruction::__POSEIDON2_PERMUTE__
+            | StructuredInstruction::__SHA_INIT__
+            | StructuredInstruction::__SHA_FINI__(_) => vec![],
+        }
+    }
+
+    /// The addresses that the instruction writes, which are always consecutive.
+    pub fn writes(&self) -> Range<u32> {
+        match self {
+            StructuredInstruction::BIT_AND_ELEM(w, _, _)
+            | StructuredInstruction::BIT_AND_SHORTS(w, _, _)
+            | StructuredInstruction::BIT_XOR_SHORTS(w, _, _)
+            | StructuredInstruction::CONST(w, _, _)
+            | StructuredInstruction::ADD(w, _, _)
+            | StructuredInstruction::SUB(w, _, _)
+            | StructuredInstruction::MUL(w, _, _)
+            | StructuredInstruction::NOT(w, _)
+            | StructuredInstruction::INV(w, _)
+            | StructuredInstruction::READ_IOP_BODY(w)
+            | StructuredInstruction::MIX_RNG_WITH_PERV(w, _, _, _, _)
+            | StructuredInstruction::MIX_RNG(w, _, _)
+            | StructuredInstruction::SELECT(w, _, _, _)
+            | StructuredInstruction::EXTRACT(w, _, _)
+            | StructuredInstruction::__MOV__(w, _) => *w..*w + 1,
+            StructuredInstruction::SHA_FINI_START(ws)
+            | StructuredInstruction::POSEIDON_STORE_TO_MONTGOMERY(_, ws)
+            | StructuredInstruction::POSEIDON_STORE(_, ws)
+            | StructuredInstruction::POSEIDON2_STORE_TO_MONTGOMERY(_, ws)
+            | StructuredInstruction::POSEIDON2_STORE(_, ws)
+            | StructuredInstruction::__POSEIDON_PERMUTE_STORE_TO_MONTGOMERY__(_, ws)
+            | StructuredInstruction::__POSEIDON_PERMUTE_STORE__(_, ws)
+            | StructuredInstruction::__POSEIDON2_PERMUTE_STORE_TO_MONTGOMERY__(_, ws)
+            | StructuredInstruction::__POSEIDON2_PERMUTE_STORE__(_, ws)
+            | StructuredInstruction::__SHA_FINI__(ws) => *ws..*ws + 8,
+            StructuredInstruction::__READ_IOP_BODY_BATCH__(ws, we) => *ws..*we,
+            StructuredInstruction::SHA_INIT_START
+            | StructuredInstruction::SHA_INIT_PADDING
+            | StructuredInstruction::SHA_LOAD_FROM_MONTGOMERY(_)
+            | StructuredInstruction::SHA_LOAD(_)
+            | StructuredInstruction::SHA_MIX
+            | StructuredInstruction::SHA_FINI_PADDING
+            | StructuredInstruction::WOM_INIT
+            | StructuredInstruction::WOM_FINI
+            | StructuredInstruction::NOP
+            | StructuredInstruction::PADDING
+            | StructuredInstruction::SET_GLOBAL(_, _, _, _, _)
+            | StructuredInstruction::EQ(_, _)
+            | StructuredInstruction::READ_IOP_HEADER(_, _)
+            | StructuredInstruction::POSEIDON_LOAD_FROM_MONTGOMERY(..)
+            | StructuredInstruction::POSEIDON_LOAD(..)
+            | StructuredInstruction::POSEIDON_ADD_LOAD_FROM_MONTGOMERY(..)
+            | StructuredInstruction::POSEIDON_ADD_LOAD(..)
+            | StructuredInstruction::POSEIDON_FULL
+            | StructuredInstruction::POSEIDON_PARTIAL
+            | StructuredInstruction::POSEIDON2_LOAD_FROM_MONTGOMERY(..)
+            | StructuredInstruction::POSEIDON2_LOAD(..)
+            | StructuredInstruction::POSEIDON2_ADD_LOAD_FROM_MONTGOMERY(..)
+            | StructuredInstruction::POSEIDON2_ADD_LOAD(..)
+            | StructuredInstruction::POSEIDON2_FULL
+            | StructuredInstruction::POSEIDON2_PARTIAL
+            | StructuredInstruction::__DELETE__
+            | StructuredInstruction::__PANIC__
+            | StructuredInstruction::__SHA_MIX_48__
+            | StructuredInstruction::__POSEIDON_PERMUTE__
+            | StructuredInstruction::__POSEIDON2_PERMUTE__
+            | StructuredInstruction::__SHA_INIT__ => 0..0,
+        }
+    }
+}
+
 pub type WriteAddr = u32;
 
 #[derive(Clone, PartialEq, Eq)]
-pub enum ReadAddr {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReadAddr<E = Fp4> {
     Ref(u32),
     RefSub(u32, u32),
-    Const(Fp4),
+    Const(E),
+}
+
+// prints the `poseidon2.prep_full = {}; ` prefix of a Poseidon2 load, omitted when it is zero
+struct PrepFull(Parameter);
+
+impl Display for PrepFull {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.0 != 0 {
+            f.write_fmt(format_args!("poseidon2.prep_full = {}; ", self.0))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// prints how a Poseidon2 load clears the state, which keeps `[16..=23]` if asked to
+struct Poseidon2Reset(Parameter);
+
+impl Display for Poseidon2Reset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.0 != 0 {
+            f.write_str("poseidon2.state[0..=15] = [0u32; 16];")
+        } else {
+            f.write_str("poseidon2.state = [0u32; 24];")
+        }
+    }
 }
 
 pub type WriteStartAddr = u32;
@@ -388,66 +726,60 @@ pub type WriteEndAddr = u32;
 pub type Index = u32;
 pub type Parameter = u32;
 
-impl From<u32> for ReadAddr {
+impl<E> From<u32> for ReadAddr<E> {
     fn from(value: u32) -> Self {
         Self::Ref(value)
     }
 }
 
-impl Display for ReadAddr {
+impl<E: ExtensionField> Display for ReadAddr<E> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             ReadAddr::Ref(v) => f.write_fmt(format_args!("m[{}]", v)),
-            ReadAddr::Const(v) => {
-                if v.1 == Fp::ZERO && v.2 == Fp::ZERO && v.3 == Fp::ZERO {
-                    f.write_fmt(format_args!("{}", v.0))
-                } else if v.2 == Fp::ZERO && v.3 == Fp::ZERO {
-                    f.write_fmt(format_args!("({}, {})", v.0, v.1))
-                } else {
-                    f.write_fmt(format_args!("({}, {}, {}, {})", v.0, v.1, v.2, v.3))
-                }
-            }
+            ReadAddr::Const(v) => f.write_fmt(format_args!("{}", v)),
             ReadAddr::RefSub(v, idx) => f.write_fmt(format_args!("m[{}].{}", v, idx)),
         }
     }
 }
 
-impl ReadAddr {
-    pub fn _0(&self) -> ReadAddr {
+impl<E> ReadAddr<E> {
+    /// The address that is read, if this is not a constant.
+    pub fn addr(&self) -> Option<u32> {
+        match self {
+            ReadAddr::Ref(v) | ReadAddr::RefSub(v, _) => Some(*v),
+            ReadAddr::Const(_) => None,
+        }
+    }
+}
+
+impl<E: ExtensionField> ReadAddr<E> {
+    pub fn _0(&self) -> ReadAddr<E> {
         match self {
             ReadAddr::Ref(v) => ReadAddr::RefSub(*v, 0),
-            ReadAddr::Const(v) => {
-                ReadAddr::Const(Fp4::new(v.0.clone(), Fp::ZERO, Fp::ZERO, Fp::ZERO))
-            }
+            ReadAddr::Const(v) => ReadAddr::Const(E::from_base(v.coeff(0))),
             ReadAddr::RefSub(v, idx) => ReadAddr::RefSub(*v, *idx),
         }
     }
 
-    pub fn _1(&self) -> ReadAddr {
+    pub fn _1(&self) -> ReadAddr<E> {
         match self {
             ReadAddr::Ref(v) => ReadAddr::RefSub(*v, 1),
-            ReadAddr::Const(v) => {
-                ReadAddr::Const(Fp4::new(v.1.clone(), Fp::ZERO, Fp::ZERO, Fp::ZERO))
-            }
-            ReadAddr::RefSub(_, _) => ReadAddr::Const(Fp4::default()),
+            ReadAddr::Const(v) => ReadAddr::Const(E::from_base(v.coeff(1))),
+            ReadAddr::RefSub(_, _) => ReadAddr::Const(E::ZERO),
         }
     }
-    pub fn _2(&self) -> ReadAddr {
+    pub fn _2(&self) -> ReadAddr<E> {
         match self {
             ReadAddr::Ref(v) => ReadAddr::RefSub(*v, 2),
-            ReadAddr::Const(v) => {
-                ReadAddr::Const(Fp4::new(v.2.clone(), Fp::ZERO, Fp::ZERO, Fp::ZERO))
-            }
-            ReadAddr::RefSub(_, _) => ReadAddr::Const(Fp4::default()),
+            ReadAddr::Const(v) => ReadAddr::Const(E::from_base(v.coeff(2))),
+            ReadAddr::RefSub(_, _) => ReadAddr::Const(E::ZERO),
         }
     }
-    pub fn _3(&self) -> ReadAddr {
+    pub fn _3(&self) -> ReadAddr<E> {
         match self {
             ReadAddr::Ref(v) => ReadAddr::RefSub(*v, 3),
-            ReadAddr::Const(v) => {
-                ReadAddr::Const(Fp4::new(v.3.clone(), Fp::ZERO, Fp::ZERO, Fp::ZERO))
-            }
-            ReadAddr::RefSub(_, _) => ReadAddr::Const(Fp4::default()),
+            ReadAddr::Const(v) => ReadAddr::Const(E::from_base(v.coeff(3))),
+            ReadAddr::RefSub(_, _) => ReadAddr::Const(E::ZERO),
         }
     }
 }
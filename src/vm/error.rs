@@ -0,0 +1,86 @@
+use crate::iop::IopError;
+use crate::math::Fp4;
+use crate::parser::LineNo;
+use crate::sha::ShaError;
+use std::fmt::{Debug, Display, Formatter};
+#[derive(Debug, Clone)]
+pub enum VmError<E = Fp4> {
+    // the two values that an `EQ` found to differ
+    AssertionFailed(LineNo, E, E),
+    // an `EQ` that `ConstPass` has already found to fail
+    Panic(LineNo),
+    // the address that is read before anything is written to it
+    UninitializedRead(LineNo, u32),
+    // the address, the value it holds, and the different value written to it again
+    Rewrite(LineNo, u32, E, E),
+    // an IOP read that the IOP input cannot serve
+    Iop(LineNo, IopError),
+    // a component of a value, or a group of the Poseidon state, that does not exist
+    IndexOutOfRange(LineNo, u32),
+    // a SHA-256 row out of place
+    Sha(LineNo, ShaError),
+    // a Poseidon round instruction out of order, with the round that the permutation is at
+    PoseidonRound(LineNo, usize),
+    // an instruction that the interpreter cannot execute
+    Unsupported(LineNo, String),
+}
+
+impl<E> VmError<E> {
+    pub fn line_no(&self) -> LineNo {
+        match self {
+            VmError::AssertionFailed(line_no, _, _)
+            | VmError::Panic(line_no)
+            | VmError::UninitializedRead(line_no, _)
+            | VmError::Rewrite(line_no, _, _, _)
+            | VmError::Iop(line_no, _)
+            | VmError::IndexOutOfRange(line_no, _)
+            | VmError::Sha(line_no, _)
+            | VmError::PoseidonRound(line_no, _)
+            | VmError::Unsupported(line_no, _) => *line_no,
+        }
+    }
+}
+
+impl<E: Display> Display for VmError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VmError::AssertionFailed(line_no, a, b) => f.write_fmt(format_args!(
+                "Assertion failed at line {}: {} != {}",
+                line_no, a, b
+            )),
+            VmError::Panic(line_no) => {
+                f.write_fmt(format_args!("Assertion failed at line {}", line_no))
+            }
+            VmError::UninitializedRead(line_no, addr) => f.write_fmt(format_args!(
+                "Instruction at line {} reads m[{}], which has not been written",
+                line_no, addr
+            )),
+            VmError::Rewrite(line_no, addr, old, new) => f.write_fmt(format_args!(
+                "Instruction at line {} writes {} to m[{}], which already holds {}",
+                line_no, new, addr, old
+            )),
+            VmError::Iop(line_no, e) => f.write_fmt(format_args!(
+                "Instruction at line {} cannot read the IOP input: {}",
+                line_no, e
+            )),
+            VmError::IndexOutOfRange(line_no, idx) => f.write_fmt(format_args!(
+                "Instruction at line {} uses the index {}, which is out of range",
+                line_no, idx
+            )),
+            VmError::Sha(line_no, e) => f.write_fmt(format_args!(
+                "Instruction at line {} breaks the SHA-256 protocol: {}",
+                line_no, e
+            )),
+            VmError::PoseidonRound(line_no, round) => f.write_fmt(format_args!(
+                "Instruction at line {} is out of order, at round {} of the Poseidon permutation",
+                line_no, round
+            )),
+            VmError::Unsupported(line_no, insn) => f.write_fmt(format_args!(
+                "Instruction at line {} cannot be executed: {}",
+                line_no, insn
+            )),
+        }
+    }
+}
+
+impl<E: Debug + Display> std::error::Error for VmError<E> {}
@@ -0,0 +1,1121 @@
+mod error;
+pub mod trace;
+
+use crate::iop::IopSource;
+use crate::math::poseidon::{Poseidon, CELLS, GROUP_CELLS};
+use crate::math::poseidon2::Poseidon2;
+use crate::math::{ExtensionField, Field, Fp, Fp4, P, TO_MONTGOMERY};
+use crate::parser::{Code, LineNo};
+use crate::sha::{Sha256, ShaError, FINI_ROWS, INIT_ROWS, MIX_ROWS};
+use crate::structures::{Index, ReadAddr, StructuredInstruction};
+pub use crate::vm::error::VmError;
+use crate::vm::trace::{ShaTrace, TraceEntry, TraceFilter};
+use std::collections::{BTreeMap, HashMap};
+
+/// The values that a program writes with SET_GLOBAL, as the eight shorts of each global.
+pub type Globals<F = Fp> = BTreeMap<Index, [F; 8]>;
+
+/// An interpreter for `Code`, rephrased from `preflight.rs`.
+///
+/// It runs raw code as well as code after the passes. Memory is write-once, unless
+/// `set_write_once(false)` is called, which is needed for code after `LiveVariableAnalysisPass`
+/// since that pass reuses addresses. IOP reads take their elements from an `IopSource`.
+///
+/// With `set_stop_on_failure(false)`, a failed assertion is recorded instead of stopping the run,
+/// so that the rest of the program still runs on inputs that do not pass its checks.
+///
+/// After `trace`, the `Vm` also records what each instruction does, for the rows that the filter
+/// keeps, until `take_trace`.
+///
+/// The memory holds values of the extension field `E`. The IOP, SHA-256, Poseidon and Poseidon2
+/// units are those of the BabyBear machine, so their instructions are only supported over `Fp4`
+/// or another degree-4 extension of BabyBear.
+pub struct Vm<E: ExtensionField = Fp4> {
+    memory: HashMap<u32, E>,
+    write_once: bool,
+    iop: IopSource,
+    poseidon: Poseidon,
+    poseidon2: Poseidon2,
+    // which of the two states the store instructions read
+    poseidon2_used: bool,
+    sha: Sha256,
+    globals: Globals<E::Base>,
+    stop_on_failure: bool,
+    failures: Vec<LineNo>,
+    trace_filter: Option<TraceFilter>,
+    trace: Vec<TraceEntry<E>>,
+    // the Poseidon states after each round of the instruction being traced
+    rounds: Option<Vec<[Fp; CELLS]>>,
+}
+
+fn reduce<F: Field>(v: u64) -> F {
+    F::from_u64(v)
+}
+
+fn component<E: ExtensionField>(v: &E, idx: u32, line_no: LineNo) -> Result<E::Base, VmError<E>> {
+    if (idx as usize) < E::DEGREE {
+        Ok(v.coeff(idx as usize))
+    } else {
+        Err(VmError::IndexOutOfRange(line_no, idx))
+    }
+}
+
+// the BabyBear element of a base field element, for the units of the BabyBear machine
+fn to_fp<F: Field>(v: &F) -> Fp {
+    Fp(v.as_u64() as u32)
+}
+
+fn from_fp<F: Field>(v: &Fp) -> F {
+    F::from_u64(v.0 as u64)
+}
+
+fn is_sha<E>(insn: &StructuredInstruction<E>) -> bool {
+    matches!(
+        insn,
+        StructuredInstruction::SHA_INIT_START
+            | StructuredInstruction::SHA_INIT_PADDING
+            | StructuredInstruction::SHA_LOAD_FROM_MONTGOMERY(_)
+            | StructuredInstruction::SHA_LOAD(_)
+            | StructuredInstruction::SHA_MIX
+            | StructuredInstruction::SHA_FINI_START(_)
+            | StructuredInstruction::SHA_FINI_PADDING
+            | StructuredInstruction::__SHA_INIT__
+            | StructuredInstruction::__SHA_MIX_48__
+            | StructuredInstruction::__SHA_FINI__(_)
+    )
+}
+
+// whether the instruction runs on the IOP, SHA-256, Poseidon or Poseidon2 unit
+fn uses_units<E>(insn: &StructuredInstruction<E>) -> bool {
+    is_sha(insn)
+        || matches!(
+            insn,
+            StructuredInstruction::READ_IOP_HEADER(..)
+                | StructuredInstruction::READ_IOP_BODY(_)
+                | StructuredInstruction::__READ_IOP_BODY_BATCH__(..)
+                | StructuredInstruction::POSEIDON_LOAD_FROM_MONTGOMERY(..)
+                | StructuredInstruction::POSEIDON_LOAD(..)
+                | StructuredInstruction::POSEIDON_ADD_LOAD_FROM_MONTGOMERY(..)
+                | StructuredInstruction::POSEIDON_ADD_LOAD(..)
+                | StructuredInstruction::POSEIDON_FULL
+                | StructuredInstruction::POSEIDON_PARTIAL
+                | StructuredInstruction::POSEIDON_STORE_TO_MONTGOMERY(..)
+                | StructuredInstruction::POSEIDON_STORE(..)
+                | StructuredInstruction::POSEIDON2_LOAD_FROM_MONTGOMERY(..)
+                | StructuredInstruction::POSEIDON2_LOAD(..)
+                | StructuredInstruction::POSEIDON2_ADD_LOAD_FROM_MONTGOMERY(..)
+                | StructuredInstruction::POSEIDON2_ADD_LOAD(..)
+                | StructuredInstruction::POSEIDON2_FULL
+                | StructuredInstruction::POSEIDON2_PARTIAL
+                | StructuredInstruction::POSEIDON2_STORE_TO_MONTGOMERY(..)
+                | StructuredInstruction::POSEIDON2_STORE(..)
+                | StructuredInstruction::__POSEIDON_PERMUTE_STORE_TO_MONTGOMERY__(..)
+                | StructuredInstruction::__POSEIDON_PERMUTE_STORE__(..)
+                | StructuredInstruction::__POSEIDON_PERMUTE__
+                | StructuredInstruction::__POSEIDON2_PERMUTE_STORE_TO_MONTGOMERY__(..)
+                | StructuredInstruction::__POSEIDON2_PERMUTE_STORE__(..)
+                | StructuredInstruction::__POSEIDON2_PERMUTE__
+        )
+}
+
+// whether `E` is a degree-4 extension of BabyBear, over which the units exist
+fn has_units<E: ExtensionField>() -> bool {
+    E::Base::MODULUS == P as u64 && E::DEGREE == 4
+}
+
+fn record_round(rounds: &mut Option<Vec<[Fp; CELLS]>>, cells: &[Fp; CELLS]) {
+    if let Some(rounds) = rounds {
+        rounds.push(cells.clone());
+    }
+}
+
+impl<E: ExtensionField> Vm<E> {
+    pub fn new(iop: IopSource) -> Self {
+        let mut memory = HashMap::new();
+        // m[0] is the zero that the programs read as a constant
+        memory.insert(0, E::ZERO);
+
+        Self {
+            memory,
+            write_once: true,
+            iop,
+            poseidon: Poseidon::default(),
+            poseidon2: Poseidon2::default(),
+            poseidon2_used: false,
+            sha: Sha256::default(),
+            globals: Globals::new(),
+            stop_on_failure: true,
+            failures: Vec::new(),
+            trace_filter: None,
+            trace: Vec::new(),
+            rounds: None,
+        }
+    }
+
+    pub fn set_write_once(&mut self, write_once: bool) {
+        self.write_once = write_once;
+    }
+
+    pub fn set_stop_on_failure(&mut self, stop_on_failure: bool) {
+        self.stop_on_failure = stop_on_failure;
+    }
+
+    /// The lines of the assertions that have failed without stopping the run.
+    pub fn failures(&self) -> &[LineNo] {
+        &self.failures
+    }
+
+    /// The value at `addr`, if it has been written.
+    pub fn get(&self, addr: u32) -> Option<&E> {
+        self.memory.get(&addr)
+    }
+
+    pub fn globals(&self) -> &Globals<E::Base> {
+        &self.globals
+    }
+
+    pub fn poseidon(&self) -> &Poseidon {
+        &self.poseidon
+    }
+
+    pub fn poseidon2(&self) -> &Poseidon2 {
+        &self.poseidon2
+    }
+
+    pub fn sha(&self) -> &Sha256 {
+        &self.sha
+    }
+
+    /// Record the instructions that `filter` keeps from now on.
+    pub fn trace(&mut self, filter: TraceFilter) {
+        self.trace_filter = Some(filter);
+    }
+
+    /// The entries recorded so far, which are then cleared.
+    pub fn take_trace(&mut self) -> Vec<TraceEntry<E>> {
+        std::mem::take(&mut self.trace)
+    }
+
+    /// Run every instruction of `code` in order, stopping at the first error, and check that no
+    /// SHA-256 hash is left unfinished.
+    pub fn run(&mut self, code: &Code<E>) -> Result<(), VmError<E>> {
+        for (insn, line_no) in code.0.iter() {
+            self.step(insn, *line_no)?;
+        }
+        if let Some((_, line_no)) = code.0.last() {
+            self.finish(*line_no)?;
+        }
+        Ok(())
+    }
+
+    /// Check that no SHA-256 hash is left unfinished after the last instruction, at `line_no`.
+    pub fn finish(&self, line_no: LineNo) -> Result<(), VmError<E>> {
+        self.sha.finish().map_err(|e| VmError::Sha(line_no, e))
+    }
+
+    fn load(&self, addr: u32, line_no: LineNo) -> Result<&E, VmError<E>> {
+        self.memory
+            .get(&addr)
+            .ok_or(VmError::UninitializedRead(line_no, addr))
+    }
+
+    fn read(&self, r: &ReadAddr<E>, line_no: LineNo) -> Result<E, VmError<E>> {
+        match r {
+            ReadAddr::Ref(addr) => self.load(*addr, line_no).cloned(),
+            ReadAddr::RefSub(addr, idx) => Ok(E::from_base(component(
+                self.load(*addr, line_no)?,
+                *idx,
+                line_no,
+            )?)),
+            ReadAddr::Const(v) => Ok(v.clone()),
+        }
+    }
+
+    fn write(&mut self, addr: u32, val: E, line_no: LineNo) -> Result<(), VmError<E>> {
+        if self.write_once {
+            if let Some(old) = self.memory.get(&addr) {
+                // like the machine, accept writing the same value again
+                if *old != val {
+                    return Err(VmError::Rewrite(line_no, addr, old.clone(), val));
+                }
+            }
+        }
+        self.memory.insert(addr, val);
+        Ok(())
+    }
+
+    fn fail(&mut self, e: VmError<E>) -> Result<(), VmError<E>> {
+        if self.stop_on_failure {
+            return Err(e);
+        }
+        self.failures.push(e.line_no());
+        Ok(())
+    }
+
+    fn iop_body(&mut self, addr: u32, line_no: LineNo) -> Result<(), VmError<E>> {
+        let val = self.iop.read_body().map_err(|e| VmError::Iop(line_no, e))?;
+        let coeffs = [val.0, val.1, val.2, val.3].map(|c| from_fp(&c));
+        self.write(addr, E::from_coeffs(&coeffs), line_no)
+    }
+
+    // write the output of SHA_FINI as the 16-bit halves of each word
+    fn sha_fini(&mut self, ws: u32, line_no: LineNo) -> Result<(), VmError<E>> {
+        let out = self
+            .sha
+            .fini_start()
+            .map_err(|e| VmError::Sha(line_no, e))?;
+        for (i, word) in out.into_iter().enumerate() {
+            self.write(
+                ws + i as u32,
+                E::from_coeffs(&[reduce((word & 0xffff) as u64), reduce((word >> 16) as u64)]),
+                line_no,
+            )?;
+        }
+        Ok(())
+    }
+
+    // the group and the eight values of a Poseidon load
+    fn poseidon_inputs(
+        &self,
+        group: Index,
+        rs: [&ReadAddr<E>; 8],
+        line_no: LineNo,
+    ) -> Result<(usize, [Fp; GROUP_CELLS]), VmError<E>> {
+        if group > 2 {
+            return Err(VmError::IndexOutOfRange(line_no, group));
+        }
+        let mut values: [Fp; GROUP_CELLS] = Default::default();
+        for (value, r) in values.iter_mut().zip(rs) {
+            *value = to_fp(&self.read(r, line_no)?.coeff(0));
+        }
+        Ok((group as usize, values))
+    }
+
+    fn poseidon_store(
+        &mut self,
+        group: Index,
+        to_montgomery: bool,
+        ws: u32,
+        line_no: LineNo,
+    ) -> Result<(), VmError<E>> {
+        if group > 2 {
+            return Err(VmError::IndexOutOfRange(line_no, group));
+        }
+        let values = if self.poseidon2_used {
+            self.poseidon2.store(group as usize, to_montgomery)
+        } else {
+            self.poseidon.store(group as usize, to_montgomery)
+        };
+        for (i, val) in values.into_iter().enumerate() {
+            self.write(ws + i as u32, E::from_base(from_fp(&val)), line_no)?;
+        }
+        Ok(())
+    }
+
+    fn poseidon_permute(&mut self) {
+        let rounds = &mut self.rounds;
+        self.poseidon
+            .permute_with(|cells| record_round(rounds, cells));
+    }
+
+    fn poseidon2_permute(&mut self) {
+        let rounds = &mut self.rounds;
+        self.poseidon2
+            .permute_with(|cells| record_round(rounds, cells));
+    }
+
+    fn mix_rng(
+        &self,
+        mut val: E::Base,
+        r1: &ReadAddr<E>,
+        r2: &ReadAddr<E>,
+        line_no: LineNo,
+    ) -> Result<E, VmError<E>> {
+        let shift_word: E::Base = reduce(1 << 16);
+        let d1 = self.read(r1, line_no)?;
+        let d2 = self.read(r2, line_no)?;
+        val = val * shift_word.clone() + d1.coeff(1);
+        val = val * shift_word.clone() + d1.coeff(0);
+        val = val * shift_word.clone() + d2.coeff(1);
+        val = val * shift_word + d2.coeff(0);
+        Ok(E::from_base(val))
+    }
+
+    /// Execute a single instruction.
+    pub fn step(
+        &mut self,
+        insn: &StructuredInstruction<E>,
+        line_no: LineNo,
+    ) -> Result<(), VmError<E>> {
+        match &self.trace_filter {
+            Some(filter) if filter.keeps_row(&line_no) => {}
+            _ => return self.exec(insn, line_no),
+        }
+
+        let reads = insn
+            .reads()
+            .into_iter()
+            .filter_map(|r| r.addr())
+            .filter_map(|addr| Some((addr, self.memory.get(&addr)?.clone())))
+            .collect();
+        self.rounds = Some(Vec::new());
+        let res = self.exec(insn, line_no);
+
+        let entry = TraceEntry {
+            line_no,
+            insn: insn.to_string(),
+            reads,
+            writes: insn
+                .writes()
+                .filter_map(|addr| Some((addr, self.memory.get(&addr)?.clone())))
+                .collect(),
+            rounds: self.rounds.take().unwrap_or_default(),
+            sha: is_sha(insn).then(|| ShaTrace {
+                phase: self.sha.phase(),
+                state: self.sha.state(),
+            }),
+        };
+        if self.trace_filter.as_ref().is_some_and(|f| f.keeps(&entry)) {
+            self.trace.push(entry);
+        }
+        res
+    }
+
+    fn exec(&mut self, insn: &StructuredInstruction<E>, line_no: LineNo) -> Result<(), VmError<E>> {
+        let unsupported = || VmError::Unsupported(line_no, insn.to_string());
+        let sha_error = |e: ShaError| VmError::Sha(line_no, e);
+
+        if uses_units(insn) && !has_units::<E>() {
+            return Err(unsupported());
+        }
+
+        if !is_sha(insn) && !matches!(insn, StructuredInstruction::__DELETE__) {
+            self.sha.other_row().map_err(sha_error)?;
+        }
+
+        match insn {
+            StructuredInstruction::BIT_AND_ELEM(w, r1, r2) => {
+                let d1 = self.read(r1, line_no)?;
+                let d2 = self.read(r2, line_no)?;
+                let val = d1.coeff(0).as_u64() & d2.coeff(0).as_u64();
+                self.write(*w, E::from_base(reduce(val)), line_no)?;
+            }
+            StructuredInstruction::BIT_AND_SHORTS(w, r1, r2) => {
+                let d1 = self.read(r1, line_no)?;
+                let d2 = self.read(r2, line_no)?;
+                let val = (d1.coeff(0).as_u64() & d2.coeff(0).as_u64())
+                    + ((d1.coeff(1).as_u64() & d2.coeff(1).as_u64()) << 16);
+                self.write(*w, E::from_base(reduce(val)), line_no)?;
+            }
+            StructuredInstruction::BIT_XOR_SHORTS(w, r1, r2) => {
+                let d1 = self.read(r1, line_no)?;
+                let d2 = self.read(r2, line_no)?;
+                let val = E::from_coeffs(&[
+                    reduce(d1.coeff(0).as_u64() ^ d2.coeff(0).as_u64()),
+                    reduce(d1.coeff(1).as_u64() ^ d2.coeff(1).as_u64()),
+                ]);
+                self.write(*w, val, line_no)?;
+            }
+            StructuredInstruction::SHA_INIT_START => self.sha.init_start().map_err(sha_error)?,
+            StructuredInstruction::SHA_INIT_PADDING => {
+                self.sha.init_padding().map_err(sha_error)?
+            }
+            StructuredInstruction::__SHA_INIT__ => {
+                self.sha.init_start().map_err(sha_error)?;
+                for _ in 1..INIT_ROWS {
+                    self.sha.init_padding().map_err(sha_error)?;
+                }
+            }
+            StructuredInstruction::SHA_LOAD_FROM_MONTGOMERY(r) => {
+                let d = self.read(r, line_no)?;
+                self.sha
+                    .load((to_fp(&d.coeff(0)) * TO_MONTGOMERY).0)
+                    .map_err(sha_error)?;
+            }
+            StructuredInstruction::SHA_LOAD(r) => {
+                let d = self.read(r, line_no)?;
+                self.sha
+                    .load(
+                        to_fp(&d.coeff(0))
+                            .0
+                            .wrapping_add(to_fp(&d.coeff(1)).0.wrapping_shl(16)),
+                    )
+                    .map_err(sha_error)?;
+            }
+            StructuredInstruction::SHA_MIX => self.sha.mix().map_err(sha_error)?,
+            StructuredInstruction::__SHA_MIX_48__ => {
+                for _ in 0..MIX_ROWS {
+                    self.sha.mix().map_err(sha_error)?;
+                }
+            }
+            StructuredInstruction::SHA_FINI_START(ws) => self.sha_fini(*ws, line_no)?,
+            StructuredInstruction::SHA_FINI_PADDING => {
+                self.sha.fini_padding().map_err(sha_error)?
+            }
+            StructuredInstruction::__SHA_FINI__(ws) => {
+                self.sha_fini(*ws, line_no)?;
+                for _ in 1..FINI_ROWS {
+                    self.sha.fini_padding().map_err(sha_error)?;
+                }
+            }
+            StructuredInstruction::WOM_INIT
+            | StructuredInstruction::WOM_FINI
+            | StructuredInstruction::NOP
+            | StructuredInstruction::PADDING
+            | StructuredInstruction::__DELETE__ => {}
+            StructuredInstruction::SET_GLOBAL(r1, r2, r3, r4, idx) => {
+                let d1 = self.read(r1, line_no)?;
+                let d2 = self.read(r2, line_no)?;
+                let d3 = self.read(r3, line_no)?;
+                let d4 = self.read(r4, line_no)?;
+                self.globals.insert(
+                    *idx,
+                    [d1, d2, d3, d4]
+                        .map(|d| [d.coeff(0), d.coeff(1)])
+                        .concat()
+                        .try_into()
+                        .unwrap(),
+                );
+            }
+            StructuredInstruction::CONST(w, v1, v2) => {
+                let val = E::from_coeffs(&[reduce(*v1 as u64), reduce(*v2 as u64)]);
+                self.write(*w, val, line_no)?;
+            }
+            StructuredInstruction::ADD(w, r1, r2) => {
+                let val = self.read(r1, line_no)? + self.read(r2, line_no)?;
+                self.write(*w, val, line_no)?;
+            }
+            StructuredInstruction::SUB(w, r1, r2) => {
+                let val = self.read(r1, line_no)? - self.read(r2, line_no)?;
+                self.write(*w, val, line_no)?;
+            }
+            StructuredInstruction::MUL(w, r1, r2) => {
+                let val = self.read(r1, line_no)? * self.read(r2, line_no)?;
+                self.write(*w, val, line_no)?;
+            }
+            StructuredInstruction::NOT(w, r) => {
+                let val = if self.read(r, line_no)?.coeff(0).is_zero() {
+                    E::ONE
+                } else {
+                    E::ZERO
+                };
+                self.write(*w, val, line_no)?;
+            }
+            StructuredInstruction::INV(w, r) => {
+                let val = self.read(r, line_no)?.inv();
+                self.write(*w, val, line_no)?;
+            }
+            StructuredInstruction::EQ(r1, r2) => {
+                let d1 = self.read(r1, line_no)?;
+                let d2 = self.read(r2, line_no)?;
+                if d1 != d2 {
+                    self.fail(VmError::AssertionFailed(line_no, d1, d2))?;
+                }
+            }
+            StructuredInstruction::__PANIC__ => self.fail(VmError::Panic(line_no))?,
+            StructuredInstruction::READ_IOP_HEADER(count, k_and_flip_flag) => self
+                .iop
+                .read_header(*count, *k_and_flip_flag)
+                .map_err(|e| VmError::Iop(line_no, e))?,
+            StructuredInstruction::READ_IOP_BODY(w) => self.iop_body(*w, line_no)?,
+            StructuredInstruction::__READ_IOP_BODY_BATCH__(ws, we) => {
+                for addr in *ws..*we {
+                    self.iop_body(addr, line_no)?;
+                }
+            }
+            StructuredInstruction::MIX_RNG_WITH_PERV(w, fp, prev, r1, r2) => {
+                let val = reduce::<E::Base>(*fp as u64) * self.read(prev, line_no)?.coeff(0);
+                let val = self.mix_rng(val, r1, r2, line_no)?;
+                self.write(*w, val, line_no)?;
+            }
+            StructuredInstruction::MIX_RNG(w, r1, r2) => {
+                let val = self.mix_rng(E::Base::ZERO, r1, r2, line_no)?;
+                self.write(*w, val, line_no)?;
+            }
+            StructuredInstruction::SELECT(w, s, r1, r2) => {
+                let s = self.read(s, line_no)?.coeff(0);
+                let val = if s == E::Base::ONE {
+                    self.read(r1, line_no)?
+                } else if s.is_zero() {
+                    self.read(r2, line_no)?
+                } else if let (ReadAddr::Ref(if_true), ReadAddr::Ref(if_false)) = (r1, r2) {
+                    // the machine reads the address `if_false + s * (if_true - if_false)`
+                    let (if_true, if_false): (E::Base, E::Base) =
+                        (reduce(*if_true as u64), reduce(*if_false as u64));
+                    let addr = if_false.clone() + s * (if_true - if_false);
+                    let addr = u32::try_from(addr.as_u64()).map_err(|_| unsupported())?;
+                    self.load(addr, line_no)?.clone()
+                } else {
+                    return Err(unsupported());
+                };
+                self.write(*w, val, line_no)?;
+            }
+            StructuredInstruction::EXTRACT(w, r, idx) => {
+                let val = component(&self.read(r, line_no)?, *idx, line_no)?;
+                self.write(*w, E::from_base(val), line_no)?;
+            }
+            StructuredInstruction::__MOV__(w, r) => {
+                let val = self.read(r, line_no)?;
+                self.write(*w, val, line_no)?;
+            }
+            StructuredInstruction::POSEIDON_LOAD_FROM_MONTGOMERY(
+                add_consts,
+                group,
+                r1,
+                r2,
+                r3,
+                r4,
+                r5,
+                r6,
+                r7,
+                r8,
+            )
+            | StructuredInstruction::POSEIDON_LOAD(
+                add_consts,
+                group,
+                r1,
+                r2,
+                r3,
+                r4,
+                r5,
+                r6,
+                r7,
+                r8,
+            )
+            | StructuredInstruction::POSEIDON_ADD_LOAD_FROM_MONTGOMERY(
+                add_consts,
+                group,
+                r1,
+                r2,
+                r3,
+                r4,
+                r5,
+                r6,
+                r7,
+                r8,
+            )
+            | StructuredInstruction::POSEIDON_ADD_LOAD(
+                add_consts,
+                group,
+                r1,
+                r2,
+                r3,
+                r4,
+                r5,
+                r6,
+                r7,
+                r8,
+            ) => {
+                let (group, values) =
+                    self.poseidon_inputs(*group, [r1, r2, r3, r4, r5, r6, r7, r8], line_no)?;
+                let add = matches!(
+                    insn,
+                    StructuredInstruction::POSEIDON_ADD_LOAD_FROM_MONTGOMERY(..)
+                        | StructuredInstruction::POSEIDON_ADD_LOAD(..)
+                );
+                let from_montgomery = matches!(
+                    insn,
+                    StructuredInstruction::POSEIDON_LOAD_FROM_MONTGOMERY(..)
+                        | StructuredInstruction::POSEIDON_ADD_LOAD_FROM_MONTGOMERY(..)
+                );
+                self.poseidon
+                    .load(group, values, add, *add_consts != 0, from_montgomery);
+                self.poseidon2_used = false;
+            }
+            StructuredInstruction::POSEIDON2_LOAD_FROM_MONTGOMERY(
+                keep_upper_state,
+                _,
+                group,
+                r1,
+                r2,
+                r3,
+                r4,
+                r5,
+                r6,
+                r7,
+                r8,
+            )
+            | StructuredInstruction::POSEIDON2_LOAD(
+                keep_upper_state,
+                _,
+                group,
+                r1,
+                r2,
+                r3,
+                r4,
+                r5,
+                r6,
+                r7,
+                r8,
+            ) => {
+                let (group, values) =
+                    self.poseidon_inputs(*group, [r1, r2, r3, r4, r5, r6, r7, r8], line_no)?;
+                let from_montgomery = matches!(
+                    insn,
+                    StructuredInstruction::POSEIDON2_LOAD_FROM_MONTGOMERY(..)
+                );
+                self.poseidon2.load(
+                    group,
+                    values,
+                    false,
+                    *keep_upper_state == 1,
+                    from_montgomery,
+                );
+                self.poseidon2_used = true;
+            }
+            StructuredInstruction::POSEIDON2_ADD_LOAD_FROM_MONTGOMERY(
+                _,
+                group,
+                r1,
+                r2,
+                r3,
+                r4,
+                r5,
+                r6,
+                r7,
+                r8,
+            )
+            | StructuredInstruction::POSEIDON2_ADD_LOAD(_, group, r1, r2, r3, r4, r5, r6, r7, r8) =>
+            {
+                let (group, values) =
+                    self.poseidon_inputs(*group, [r1, r2, r3, r4, r5, r6, r7, r8], line_no)?;
+                let from_montgomery = matches!(
+                    insn,
+                    StructuredInstruction::POSEIDON2_ADD_LOAD_FROM_MONTGOMERY(..)
+                );
+                self.poseidon2
+                    .load(group, values, true, false, from_montgomery);
+                self.poseidon2_used = true;
+            }
+            StructuredInstruction::POSEIDON_FULL => {
+                let rounds = &mut self.rounds;
+                if !self.poseidon.full_with(|cells| record_round(rounds, cells)) {
+                    return Err(VmError::PoseidonRound(line_no, self.poseidon.round()));
+                }
+            }
+            StructuredInstruction::POSEIDON_PARTIAL => {
+                let rounds = &mut self.rounds;
+                if !self
+                    .poseidon
+                    .partial_with(|cells| record_round(rounds, cells))
+                {
+                    return Err(VmError::PoseidonRound(line_no, self.poseidon.round()));
+                }
+            }
+            StructuredInstruction::__POSEIDON_PERMUTE__ => self.poseidon_permute(),
+            StructuredInstruction::__POSEIDON_PERMUTE_STORE_TO_MONTGOMERY__(group, ws) => {
+                self.poseidon_permute();
+                self.poseidon_store(*group, true, *ws, line_no)?;
+            }
+            StructuredInstruction::__POSEIDON_PERMUTE_STORE__(group, ws) => {
+                self.poseidon_permute();
+                self.poseidon_store(*group, false, *ws, line_no)?;
+            }
+            StructuredInstruction::POSEIDON2_FULL => {
+                let rounds = &mut self.rounds;
+                if !self
+                    .poseidon2
+                    .full_with(|cells| record_round(rounds, cells))
+                {
+                    return Err(VmError::PoseidonRound(line_no, self.poseidon2.round()));
+                }
+            }
+            StructuredInstruction::POSEIDON2_PARTIAL => {
+                let rounds = &mut self.rounds;
+                if !self
+                    .poseidon2
+                    .partial_with(|cells| record_round(rounds, cells))
+                {
+                    return Err(VmError::PoseidonRound(line_no, self.poseidon2.round()));
+                }
+            }
+            StructuredInstruction::__POSEIDON2_PERMUTE__ => self.poseidon2_permute(),
+            StructuredInstruction::__POSEIDON2_PERMUTE_STORE_TO_MONTGOMERY__(group, ws) => {
+                self.poseidon2_permute();
+                self.poseidon_store(*group, true, *ws, line_no)?;
+            }
+            StructuredInstruction::__POSEIDON2_PERMUTE_STORE__(group, ws) => {
+                self.poseidon2_permute();
+                self.poseidon_store(*group, false, *ws, line_no)?;
+            }
+            StructuredInstruction::POSEIDON_STORE_TO_MONTGOMERY(group, ws)
+            | StructuredInstruction::POSEIDON2_STORE_TO_MONTGOMERY(group, ws) => {
+                self.poseidon_store(*group, true, *ws, line_no)?;
+            }
+            StructuredInstruction::POSEIDON_STORE(group, ws)
+            | StructuredInstruction::POSEIDON2_STORE(group, ws) => {
+                self.poseidon_store(*group, false, *ws, line_no)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Run `code` on a fresh `Vm` with the given IOP input, and return the globals it sets.
+pub fn execute<E: ExtensionField>(
+    code: &Code<E>,
+    iop: IopSource,
+) -> Result<Globals<E::Base>, VmError<E>> {
+    let mut vm = Vm::new(iop);
+    vm.run(code)?;
+    Ok(vm.globals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iop::IopError;
+    use crate::math::poseidon::{poseidon_mix, CELLS, ROUNDS_HALF_FULL};
+    use crate::math::poseidon2::poseidon2_mix;
+    use StructuredInstruction as I;
+
+    fn code(insns: Vec<I>) -> Code {
+        let mut code = Code::default();
+        for (row, insn) in insns.into_iter().enumerate() {
+            code.push(insn, LineNo::new(row + 1, 0));
+        }
+        code
+    }
+
+    fn run(insns: Vec<I>) -> Vm {
+        let mut vm: Vm = Vm::new(IopSource::new([]));
+        vm.run(&code(insns)).unwrap();
+        vm
+    }
+
+    fn run_with_iop(words: Vec<u32>, insns: Vec<I>) -> Vm {
+        let mut vm: Vm = Vm::new(IopSource::new(words));
+        vm.run(&code(insns)).unwrap();
+        vm
+    }
+
+    fn fp4(c0: u32, c1: u32) -> Fp4 {
+        Fp4::new(Fp::new(c0), Fp::new(c1), Fp::ZERO, Fp::ZERO)
+    }
+
+    #[test]
+    fn arithmetic() {
+        let vm = run(vec![
+            I::CONST(1, 5, 7),
+            I::CONST(2, 3, 0),
+            I::ADD(3, 1.into(), 2.into()),
+            I::SUB(4, 2.into(), 1.into()),
+            I::MUL(5, 1.into(), 2.into()),
+            I::NOT(6, 0.into()),
+            I::NOT(7, 1.into()),
+            I::INV(8, 1.into()),
+            I::MUL(9, 8.into(), 1.into()),
+            I::EXTRACT(10, 1.into(), 1),
+            I::__MOV__(11, 3.into()),
+            I::EQ(11.into(), 3.into()),
+        ]);
+        assert_eq!(vm.get(1), Some(&fp4(5, 7)));
+        assert_eq!(vm.get(3), Some(&fp4(8, 7)));
+        assert_eq!(vm.get(4), Some(&fp4(P - 2, P - 7)));
+        assert_eq!(vm.get(5), Some(&fp4(15, 21)));
+        assert_eq!(vm.get(6), Some(&Fp4::ONE));
+        assert_eq!(vm.get(7), Some(&Fp4::ZERO));
+        assert_eq!(vm.get(8), Some(&fp4(5, 7).inv()));
+        assert_eq!(vm.get(9), Some(&Fp4::ONE));
+        assert_eq!(vm.get(10), Some(&fp4(7, 0)));
+        assert_eq!(vm.get(11), Some(&fp4(8, 7)));
+
+        // a component that does not exist
+        let mut vm: Vm = Vm::new(IopSource::new([]));
+        assert!(matches!(
+            vm.step(&I::EXTRACT(1, 0.into(), 4), LineNo::new(1, 0)),
+            Err(VmError::IndexOutOfRange(_, 4))
+        ));
+    }
+
+    #[test]
+    fn mix_rng() {
+        let vm = run(vec![
+            I::CONST(1, 5, 7),
+            I::CONST(2, 3, 0),
+            I::MIX_RNG(3, 1.into(), 2.into()),
+            I::MIX_RNG_WITH_PERV(4, 2, 3.into(), 1.into(), 2.into()),
+        ]);
+        // the shorts of the operands, most significant first
+        let shift = Fp::new(1 << 16);
+        let mix = |val: Fp| {
+            [7, 5, 0, 3]
+                .into_iter()
+                .fold(val, |acc, short| acc * shift.clone() + Fp::new(short))
+        };
+        let first = mix(Fp::ZERO);
+        assert_eq!(vm.get(3), Some(&Fp4::from(first.clone())));
+        assert_eq!(vm.get(4), Some(&Fp4::from(mix(Fp::new(2) * first))));
+    }
+
+    #[test]
+    fn select() {
+        let vm = run(vec![
+            I::CONST(1, 1, 0),
+            I::CONST(2, 2, 0),
+            I::CONST(20, 100, 0),
+            I::CONST(21, 101, 0),
+            I::CONST(22, 102, 0),
+            I::SELECT(3, 0.into(), 21.into(), 20.into()),
+            I::SELECT(4, 1.into(), 21.into(), 20.into()),
+            // the machine reads `20 + 2 * (21 - 20)`
+            I::SELECT(5, 2.into(), 21.into(), 20.into()),
+        ]);
+        assert_eq!(vm.get(3), Some(&fp4(100, 0)));
+        assert_eq!(vm.get(4), Some(&fp4(101, 0)));
+        assert_eq!(vm.get(5), Some(&fp4(102, 0)));
+
+        // without addresses to interpolate
+        let mut vm: Vm = Vm::new(IopSource::new([]));
+        vm.step(&I::CONST(1, 2, 0), LineNo::new(1, 0)).unwrap();
+        let insn = I::SELECT(2, 1.into(), ReadAddr::Const(Fp4::ONE), 0.into());
+        assert!(matches!(
+            vm.step(&insn, LineNo::new(2, 0)),
+            Err(VmError::Unsupported(line_no, _)) if line_no == LineNo::new(2, 0)
+        ));
+    }
+
+    #[test]
+    fn bit_ops() {
+        let vm = run(vec![
+            I::CONST(1, 0b1100, 0xff00),
+            I::CONST(2, 0b1010, 0x0ff0),
+            I::BIT_AND_ELEM(3, 1.into(), 2.into()),
+            I::BIT_AND_SHORTS(4, 1.into(), 2.into()),
+            I::BIT_XOR_SHORTS(5, 1.into(), 2.into()),
+        ]);
+        assert_eq!(vm.get(3), Some(&fp4(0b1000, 0)));
+        assert_eq!(vm.get(4), Some(&fp4(0b1000 + (0x0f00 << 16), 0)));
+        assert_eq!(vm.get(5), Some(&fp4(0b0110, 0xf0f0)));
+    }
+
+    #[test]
+    fn no_ops_and_globals() {
+        let vm = run(vec![
+            I::WOM_INIT,
+            I::CONST(1, 1, 2),
+            I::CONST(2, 3, 4),
+            I::NOP,
+            I::SET_GLOBAL(1.into(), 2.into(), 1.into(), 0.into(), 3),
+            I::PADDING,
+            I::WOM_FINI,
+        ]);
+        assert_eq!(
+            vm.globals().get(&3),
+            Some(&[1, 2, 3, 4, 1, 2, 0, 0].map(Fp::new))
+        );
+        assert_eq!(vm.globals().len(), 1);
+        // m[0], m[1] and m[2]
+        assert_eq!((0..8).filter(|addr| vm.get(*addr).is_some()).count(), 3);
+    }
+
+    #[test]
+    fn rewrite() {
+        let mut vm: Vm = Vm::new(IopSource::new([]));
+        vm.step(&I::CONST(1, 5, 0), LineNo::new(1, 0)).unwrap();
+        // the same value is accepted
+        vm.step(&I::CONST(1, 5, 0), LineNo::new(2, 0)).unwrap();
+        let err = vm.step(&I::CONST(1, 6, 0), LineNo::new(3, 0)).unwrap_err();
+        assert!(matches!(
+            err,
+            VmError::Rewrite(line_no, 1, old, new)
+                if line_no == LineNo::new(3, 0) && old == fp4(5, 0) && new == fp4(6, 0)
+        ));
+        assert_eq!(vm.get(1), Some(&fp4(5, 0)));
+
+        vm.set_write_once(false);
+        vm.step(&I::CONST(1, 6, 0), LineNo::new(4, 0)).unwrap();
+        assert_eq!(vm.get(1), Some(&fp4(6, 0)));
+    }
+
+    #[test]
+    fn assertion_lines() {
+        let mut program = code(vec![I::CONST(1, 5, 0), I::CONST(2, 6, 0)]);
+        program.push(I::EQ(1.into(), 1.into()), LineNo::new(3, 0));
+        program.push(I::EQ(1.into(), 2.into()), LineNo::new(3, 1));
+        program.push(I::EQ(2.into(), 2.into()), LineNo::new(3, 2));
+        program.push(I::EQ(2.into(), 1.into()), LineNo::new(4, 2));
+
+        let mut vm: Vm = Vm::new(IopSource::new([]));
+        assert!(matches!(
+            vm.run(&program),
+            Err(VmError::AssertionFailed(line_no, d1, d2))
+                if line_no == LineNo::new(3, 1) && d1 == fp4(5, 0) && d2 == fp4(6, 0)
+        ));
+
+        let mut vm: Vm = Vm::new(IopSource::new([]));
+        vm.set_stop_on_failure(false);
+        vm.run(&program).unwrap();
+        assert_eq!(vm.failures(), [LineNo::new(3, 1), LineNo::new(4, 2)]);
+
+        let mut vm: Vm = Vm::new(IopSource::new([]));
+        assert!(matches!(
+            vm.step(&I::ADD(2, 1.into(), 0.into()), LineNo::new(5, 0)),
+            Err(VmError::UninitializedRead(line_no, 1)) if line_no == LineNo::new(5, 0)
+        ));
+    }
+
+    #[test]
+    fn iop_reads() {
+        let montgomery: Vec<u32> = (1..=6).map(|v| Fp::new(v).to_montgomery()).collect();
+        let mut words = vec![0x0003_0001, 0x0005_0004];
+        words.extend(&montgomery);
+        words.extend(&montgomery);
+        let vm = run_with_iop(
+            words,
+            vec![
+                // two elements of two shorts each
+                I::READ_IOP_HEADER(2, 4),
+                I::READ_IOP_BODY(1),
+                I::READ_IOP_BODY(2),
+                // two elements of three coefficients, each coefficient in turn
+                I::READ_IOP_HEADER(2, 6),
+                I::READ_IOP_BODY(3),
+                I::READ_IOP_BODY(4),
+                // the same, element by element
+                I::READ_IOP_HEADER(2, 7),
+                I::__READ_IOP_BODY_BATCH__(5, 7),
+            ],
+        );
+        let fp4_3 = |c0, c1, c2| Fp4::new(Fp::new(c0), Fp::new(c1), Fp::new(c2), Fp::ZERO);
+        assert_eq!(vm.get(1), Some(&fp4(1, 3)));
+        assert_eq!(vm.get(2), Some(&fp4(4, 5)));
+        assert_eq!(vm.get(3), Some(&fp4_3(1, 3, 5)));
+        assert_eq!(vm.get(4), Some(&fp4_3(2, 4, 6)));
+        assert_eq!(vm.get(5), Some(&fp4_3(1, 2, 3)));
+        assert_eq!(vm.get(6), Some(&fp4_3(4, 5, 6)));
+
+        let mut vm: Vm = Vm::new(IopSource::new([]));
+        assert!(matches!(
+            vm.step(&I::READ_IOP_BODY(1), LineNo::new(1, 0)),
+            Err(VmError::Iop(_, IopError::BodyExhausted))
+        ));
+    }
+
+    #[test]
+    fn sha() {
+        // "abc", padded to one block, as the shorts of its little-endian words
+        let mut block = [0u8; 64];
+        block[..4].copy_from_slice(b"abc\x80");
+        block[63] = 24;
+        let mut insns: Vec<I> = block
+            .chunks_exact(4)
+            .enumerate()
+            .map(|(i, word)| {
+                let word = u32::from_le_bytes(word.try_into().unwrap());
+                I::CONST(i as u32 + 1, word & 0xffff, word >> 16)
+            })
+            .collect();
+        insns.push(I::SHA_INIT_START);
+        insns.extend(vec![I::SHA_INIT_PADDING; 3]);
+        insns.extend((1..=16).map(|addr| I::SHA_LOAD(addr.into())));
+        insns.extend(vec![I::SHA_MIX; 48]);
+        insns.push(I::SHA_FINI_START(100));
+        insns.extend(vec![I::SHA_FINI_PADDING; 3]);
+        let vm = run(insns);
+
+        let digest: Vec<u8> = (100..108)
+            .flat_map(|addr| {
+                let word = vm.get(addr).unwrap();
+                (word.0 .0 + (word.1 .0 << 16)).to_le_bytes()
+            })
+            .collect();
+        assert_eq!(
+            digest,
+            [
+                0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae,
+                0x22, 0x23, 0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61,
+                0xf2, 0x00, 0x15, 0xad,
+            ]
+        );
+
+        // a row of another kind between the loads
+        let mut vm: Vm = Vm::new(IopSource::new([]));
+        vm.step(&I::CONST(1, 0, 0), LineNo::new(1, 0)).unwrap();
+        vm.step(&I::__SHA_INIT__, LineNo::new(2, 0)).unwrap();
+        vm.step(&I::SHA_LOAD(1.into()), LineNo::new(6, 0)).unwrap();
+        assert!(matches!(
+            vm.step(&I::CONST(2, 0, 0), LineNo::new(7, 0)),
+            Err(VmError::Sha(..))
+        ));
+    }
+
+    fn poseidon_loads(values: &[Fp; CELLS], v2: bool) -> Vec<I> {
+        let mut insns = Vec::new();
+        for group in 0..3 {
+            let base = 1 + 8 * group;
+            insns.extend((0..8).map(|i| I::CONST(base + i, values[(base + i - 1) as usize].0, 0)));
+            let r = |i: u32| ReadAddr::Ref(base + i);
+            insns.push(match (v2, group) {
+                // the constants of the first round are added with the last group
+                (false, 0) => {
+                    I::POSEIDON_LOAD(0, 0, r(0), r(1), r(2), r(3), r(4), r(5), r(6), r(7))
+                }
+                (false, _) => I::POSEIDON_ADD_LOAD(
+                    (group == 2) as u32,
+                    group,
+                    r(0),
+                    r(1),
+                    r(2),
+                    r(3),
+                    r(4),
+                    r(5),
+                    r(6),
+                    r(7),
+                ),
+                (true, 0) => {
+                    I::POSEIDON2_LOAD(0, 0, 0, r(0), r(1), r(2), r(3), r(4), r(5), r(6), r(7))
+                }
+                (true, _) => {
+                    I::POSEIDON2_ADD_LOAD(0, group, r(0), r(1), r(2), r(3), r(4), r(5), r(6), r(7))
+                }
+            });
+        }
+        insns
+    }
+
+    #[test]
+    fn poseidon() {
+        let values: [Fp; CELLS] = std::array::from_fn(|i| Fp::new(i as u32 * 0x01000193 + 7));
+        let mut expected = values.clone();
+        poseidon_mix(&mut expected);
+
+        let mut insns = poseidon_loads(&values, false);
+        insns.extend(vec![I::POSEIDON_FULL; 2]);
+        insns.push(I::POSEIDON_PARTIAL);
+        insns.extend(vec![I::POSEIDON_FULL; 2]);
+        insns.extend((0..3).map(|group| I::POSEIDON_STORE(group, 100 + 8 * group)));
+        let vm = run(insns);
+        for (i, cell) in expected.iter().enumerate() {
+            assert_eq!(vm.get(100 + i as u32), Some(&Fp4::from(cell.clone())));
+        }
+
+        // a partial row before the full rounds
+        let mut insns = poseidon_loads(&values, false);
+        insns.push(I::POSEIDON_PARTIAL);
+        let mut vm: Vm = Vm::new(IopSource::new([]));
+        assert!(matches!(
+            vm.run(&code(insns)),
+            Err(VmError::PoseidonRound(line_no, 0)) if line_no == LineNo::new(28, 0)
+        ));
+    }
+
+    #[test]
+    fn poseidon2() {
+        let values: [Fp; CELLS] = std::array::from_fn(|i| Fp::new(i as u32 * 0x01000193 + 7));
+        let mut expected = values.clone();
+        poseidon2_mix(&mut expected);
+
+        let mut insns = poseidon_loads(&values, true);
+        insns.extend(vec![I::POSEIDON2_FULL; ROUNDS_HALF_FULL]);
+        insns.push(I::POSEIDON2_PARTIAL);
+        insns.extend(vec![I::POSEIDON2_FULL; ROUNDS_HALF_FULL]);
+        insns.extend((0..3).map(|group| I::POSEIDON2_STORE_TO_MONTGOMERY(group, 100 + 8 * group)));
+        let vm = run(insns);
+        for (i, cell) in expected.iter().enumerate() {
+            let stored = Fp4::from(cell * TO_MONTGOMERY);
+            assert_eq!(vm.get(100 + i as u32), Some(&stored));
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use crate::math::poseidon::CELLS;
+use crate::math::{ExtensionField, Field, Fp, Fp4};
+use crate::parser::LineNo;
+use crate::sha::ShaPhase;
+use std::io::Write;
+use std::ops::Range;
+
+/// Which rows a `Vm` records. A row is kept if it starts within `rows`, and reads or writes an
+/// address within `addrs`; rows that touch no memory, such as the Poseidon rounds, are dropped
+/// when `addrs` is set.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceFilter {
+    pub rows: Option<Range<usize>>,
+    pub addrs: Option<Range<u32>>,
+}
+
+impl TraceFilter {
+    pub fn keeps_row(&self, line_no: &LineNo) -> bool {
+        self.rows
+            .as_ref()
+            .is_none_or(|rows| rows.contains(&line_no.row))
+    }
+
+    pub fn keeps<E>(&self, entry: &TraceEntry<E>) -> bool {
+        self.keeps_row(&entry.line_no)
+            && self.addrs.as_ref().is_none_or(|addrs| {
+                entry
+                    .reads
+                    .iter()
+                    .chain(entry.writes.iter())
+                    .any(|(addr, _)| addrs.contains(addr))
+            })
+    }
+}
+
+/// The SHA-256 unit after a SHA row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaTrace {
+    pub phase: ShaPhase,
+    pub state: [u32; 8],
+}
+
+/// What one instruction did, with the values as the machine holds them, that is, not in the
+/// Montgomery form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry<E = Fp4> {
+    pub line_no: LineNo,
+    pub insn: String,
+    // the addresses read, in the order of the operands, with their values before the instruction
+    pub reads: Vec<(u32, E)>,
+    // the addresses written, with their values after the instruction
+    pub writes: Vec<(u32, E)>,
+    // the Poseidon or Poseidon2 state after each round that the instruction runs
+    pub rounds: Vec<[Fp; CELLS]>,
+    pub sha: Option<ShaTrace>,
+}
+
+// `insn` is the only field that needs quoting, and the instructions print no control characters
+fn quote(s: &str, escaped_quote: &str) -> String {
+    format!("\"{}\"", s.replace('"', escaped_quote))
+}
+
+// the coefficients of a value
+fn coeffs<E: ExtensionField>(v: &E) -> Vec<String> {
+    (0..E::DEGREE)
+        .map(|i| v.coeff(i).as_u64().to_string())
+        .collect()
+}
+
+fn cells_json(cells: &[Fp]) -> String {
+    let cells: Vec<String> = cells.iter().map(|c| c.0.to_string()).collect();
+    format!("[{}]", cells.join(","))
+}
+
+fn accesses_json<E: ExtensionField>(accesses: &[(u32, E)]) -> String {
+    let accesses: Vec<String> = accesses
+        .iter()
+        .map(|(addr, v)| format!("[{},[{}]]", addr, coeffs(v).join(",")))
+        .collect();
+    format!("[{}]", accesses.join(","))
+}
+
+/// Write the entries as JSON Lines, one object per instruction, with the fields `row`, `line`,
+/// `insn`, `reads`, `writes`, `rounds` and `sha`. `row` is the first row of the instruction,
+/// which `preflight.rs` calls the cycle, and `line` is its full `LineNo`. Reads and writes are
+/// `[addr, [c0, c1, c2, c3]]`, with as many coefficients as the degree of the extension.
+pub fn write_jsonl<'a, E: ExtensionField>(
+    entries: impl IntoIterator<Item = &'a TraceEntry<E>>,
+    writer: &mut impl Write,
+) -> std::io::Result<()> {
+    for entry in entries {
+        let rounds: Vec<String> = entry.rounds.iter().map(|c| cells_json(c)).collect();
+        let sha = match &entry.sha {
+            Some(sha) => format!(
+                "{{\"phase\":{},\"state\":[{}]}}",
+                quote(&sha.phase.to_string(), "\\\""),
+                sha.state.map(|w| w.to_string()).join(",")
+            ),
+            None => String::from("null"),
+        };
+        writer.write_fmt(format_args!(
+            "{{\"row\":{},\"line\":\"{}\",\"insn\":{},\"reads\":{},\"writes\":{},\"rounds\":[{}],\"sha\":{}}}\n",
+            entry.line_no.row,
+            entry.line_no,
+            quote(&entry.insn, "\\\""),
+            accesses_json(&entry.reads),
+            accesses_json(&entry.writes),
+            rounds.join(","),
+            sha
+        ))?;
+    }
+    Ok(())
+}
+
+fn accesses_csv<E: ExtensionField>(accesses: &[(u32, E)]) -> String {
+    let accesses: Vec<String> = accesses
+        .iter()
+        .map(|(addr, v)| format!("{}={}", addr, coeffs(v).join(":")))
+        .collect();
+    accesses.join(" ")
+}
+
+/// Write the entries as CSV with the same columns as `write_jsonl`. Reads and writes are lists of
+/// `addr=c0:c1:c2:c3` separated by spaces, the Poseidon states are separated by `;` with their
+/// cells by spaces, and the SHA columns are empty outside of SHA rows.
+pub fn write_csv<'a, E: ExtensionField>(
+    entries: impl IntoIterator<Item = &'a TraceEntry<E>>,
+    writer: &mut impl Write,
+) -> std::io::Result<()> {
+    writer.write_all(b"row,line,insn,reads,writes,rounds,sha_phase,sha_state\n")?;
+    for entry in entries {
+        let rounds: Vec<String> = entry
+            .rounds
+            .iter()
+            .map(|cells| {
+                let cells: Vec<String> = cells.iter().map(|c| c.0.to_string()).collect();
+                cells.join(" ")
+            })
+            .collect();
+        let (sha_phase, sha_state) = match &entry.sha {
+            Some(sha) => (
+                quote(&sha.phase.to_string(), "\"\""),
+                sha.state.map(|w| w.to_string()).join(" "),
+            ),
+            None => (String::new(), String::new()),
+        };
+        writer.write_fmt(format_args!(
+            "{},{},{},{},{},{},{},{}\n",
+            entry.line_no.row,
+            entry.line_no,
+            quote(&entry.insn, "\"\""),
+            accesses_csv(&entry.reads),
+            accesses_csv(&entry.writes),
+            rounds.join(";"),
+            sha_phase,
+            sha_state
+        ))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iop::IopSource;
+    use crate::sha::SHA256_INIT;
+    use crate::structures::StructuredInstruction as I;
+    use crate::vm::Vm;
+
+    fn fp4(c0: u32, c1: u32) -> Fp4 {
+        Fp4::new(Fp::new(c0), Fp::new(c1), Fp::ZERO, Fp::ZERO)
+    }
+
+    fn trace(filter: TraceFilter, insns: Vec<I>) -> Vec<TraceEntry> {
+        let mut vm: Vm = Vm::new(IopSource::new([]));
+        vm.trace(filter);
+        for (row, insn) in insns.iter().enumerate() {
+            // keep the entry of a failing instruction as well
+            let _ = vm.step(insn, LineNo::new(row + 1, 0));
+        }
+        vm.take_trace()
+    }
+
+    fn program() -> Vec<I> {
+        let poseidon_load = I::POSEIDON_LOAD(
+            0,
+            0,
+            1.into(),
+            1.into(),
+            1.into(),
+            1.into(),
+            1.into(),
+            1.into(),
+            1.into(),
+            2.into(),
+        );
+        vec![
+            I::CONST(1, 5, 7),
+            I::ADD(2, 1.into(), 1.into()),
+            poseidon_load,
+            I::POSEIDON_FULL,
+            I::SHA_INIT_START,
+            I::SHA_INIT_PADDING,
+            I::SHA_INIT_PADDING,
+            I::SHA_INIT_PADDING,
+            I::EQ(1.into(), 2.into()),
+        ]
+    }
+
+    #[test]
+    fn records_instructions() {
+        let insns = program();
+        let entries = trace(TraceFilter::default(), insns.clone());
+        assert_eq!(entries.len(), insns.len());
+        for (row, (entry, insn)) in entries.iter().zip(insns.iter()).enumerate() {
+            assert_eq!(entry.line_no, LineNo::new(row + 1, 0));
+            assert_eq!(entry.insn, insn.to_string());
+        }
+
+        assert_eq!(entries[0].reads, []);
+        assert_eq!(entries[0].writes, [(1, fp4(5, 7))]);
+        assert_eq!(entries[1].reads, [(1, fp4(5, 7)), (1, fp4(5, 7))]);
+        assert_eq!(entries[1].writes, [(2, fp4(10, 14))]);
+        assert!(entries[1].rounds.is_empty() && entries[1].sha.is_none());
+
+        // the two rounds of a full row, the last of which is the state after it
+        assert!(entries[2].rounds.is_empty());
+        assert_eq!(entries[3].rounds.len(), 2);
+
+        assert_eq!(
+            entries[4].sha,
+            Some(ShaTrace {
+                phase: ShaPhase::Init(1),
+                state: SHA256_INIT,
+            })
+        );
+        assert_eq!(entries[7].sha.as_ref().unwrap().phase, ShaPhase::Init(4));
+
+        // a failed assertion, with the values it compared
+        assert_eq!(entries[8].reads, [(1, fp4(5, 7)), (2, fp4(10, 14))]);
+    }
+
+    #[test]
+    fn filters() {
+        let entries = trace(
+            TraceFilter {
+                rows: Some(2..4),
+                addrs: None,
+            },
+            program(),
+        );
+        let rows: Vec<usize> = entries.iter().map(|e| e.line_no.row).collect();
+        assert_eq!(rows, [2, 3]);
+
+        // rows that touch m[2], without the rounds and SHA rows that touch no memory
+        let entries = trace(
+            TraceFilter {
+                rows: None,
+                addrs: Some(2..3),
+            },
+            program(),
+        );
+        let rows: Vec<usize> = entries.iter().map(|e| e.line_no.row).collect();
+        assert_eq!(rows, [2, 3, 9]);
+
+        let entries = trace(
+            TraceFilter {
+                rows: Some(1..3),
+                addrs: Some(2..3),
+            },
+            program(),
+        );
+        let rows: Vec<usize> = entries.iter().map(|e| e.line_no.row).collect();
+        assert_eq!(rows, [2]);
+    }
+
+    fn entries() -> Vec<TraceEntry> {
+        vec![
+            TraceEntry {
+                line_no: LineNo::new(3, 1).to(&LineNo::new(4, 2)),
+                insn: String::from("say \"hi\""),
+                reads: vec![(1, fp4(5, 7))],
+                writes: vec![(2, fp4(1, 0)), (3, fp4(2, 0))],
+                rounds: vec![std::array::from_fn(|i| Fp::new(i as u32))],
+                sha: None,
+            },
+            TraceEntry {
+                line_no: LineNo::new(5, 0),
+                insn: String::from("sha_init_start()"),
+                reads: vec![],
+                writes: vec![],
+                rounds: vec![],
+                sha: Some(ShaTrace {
+                    phase: ShaPhase::Init(1),
+                    state: [1, 2, 3, 4, 5, 6, 7, 8],
+                }),
+            },
+        ]
+    }
+
+    fn cells(sep: &str) -> String {
+        (0..CELLS as u32)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(sep)
+    }
+
+    #[test]
+    fn jsonl() {
+        let mut out = Vec::new();
+        write_jsonl(&entries(), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "{}\n{}\n",
+                format_args!(
+                    "{{\"row\":3,\"line\":\"3.1..=4.2\",\"insn\":\"say \\\"hi\\\"\",\
+                     \"reads\":[[1,[5,7,0,0]]],\"writes\":[[2,[1,0,0,0]],[3,[2,0,0,0]]],\
+                     \"rounds\":[[{}]],\"sha\":null}}",
+                    cells(",")
+                ),
+                "{\"row\":5,\"line\":\"5\",\"insn\":\"sha_init_start()\",\"reads\":[],\
+                 \"writes\":[],\"rounds\":[],\"sha\":{\"phase\":\"after 1 of 4 init rows\",\
+                 \"state\":[1,2,3,4,5,6,7,8]}}"
+            )
+        );
+    }
+
+    #[test]
+    fn csv() {
+        let mut out = Vec::new();
+        write_csv(&entries(), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "row,line,insn,reads,writes,rounds,sha_phase,sha_state\n\
+                 3,3.1..=4.2,\"say \"\"hi\"\"\",1=5:7:0:0,2=1:0:0:0 3=2:0:0:0,{},,\n\
+                 5,5,\"sha_init_start()\",,,,\"after 1 of 4 init rows\",1 2 3 4 5 6 7 8\n",
+                cells(" ")
+            )
+        );
+    }
+}
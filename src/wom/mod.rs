@@ -0,0 +1,204 @@
+use crate::parser::{Code, LineNo};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+
+/// A conflict with the write-once memory of the machine, found by `check`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WomIssue {
+    // the address, the line that writes it first, and the line that writes it again
+    DoubleWrite(u32, LineNo, LineNo),
+    // the address, the line that reads it, and the line that writes it later, if any
+    ReadBeforeWrite(u32, LineNo, Option<LineNo>),
+    // the addresses that a write of several words shares with an earlier write, the earlier
+    // write, and the later one
+    OverlappingWrite(Range<u32>, LineNo, LineNo),
+}
+
+impl Display for WomIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WomIssue::DoubleWrite(addr, first, second) => f.write_fmt(format_args!(
+                "m[{}] is written at line {} and again at line {}",
+                addr, first, second
+            )),
+            WomIssue::ReadBeforeWrite(addr, read, Some(write)) => f.write_fmt(format_args!(
+                "m[{}] is read at line {} but only written at line {}",
+                addr, read, write
+            )),
+            WomIssue::ReadBeforeWrite(addr, read, None) => f.write_fmt(format_args!(
+                "m[{}] is read at line {} but never written",
+                addr, read
+            )),
+            WomIssue::OverlappingWrite(range, first, second) => f.write_fmt(format_args!(
+                "m[{}..={}] is written at line {} and again at line {}",
+                range.start,
+                range.end - 1,
+                first,
+                second
+            )),
+        }
+    }
+}
+
+// the instructions of one row, which write their addresses as a single unit
+struct Unit {
+    line_no: LineNo,
+    words: usize,
+}
+
+/// Check that raw `code` keeps to the write-once memory: every address is written at most once,
+/// and only read after it is written. The micro ops of a row count as one write of up to three
+/// words, like the `POSEIDON_STORE` and `SHA_FINI_START` writes of eight. An address that a
+/// write of several words shares with another write is reported once for the pair, as
+/// `OverlappingWrite`. `m[0]` reads as zero without being written.
+///
+/// The check is meant for code as it is parsed from a zkr, before any pass.
+pub fn check(code: &Code) -> Vec<WomIssue> {
+    let mut units: Vec<Unit> = Vec::new();
+    // for each address, the line and unit that write it
+    let mut written: HashMap<u32, (LineNo, usize)> = HashMap::new();
+    let mut unwritten_reads: Vec<(u32, LineNo)> = Vec::new();
+    // for each pair of units that write the same addresses, those addresses and the lines of
+    // the later unit that write them
+    let mut overlaps: BTreeMap<(usize, usize), Vec<(u32, LineNo)>> = BTreeMap::new();
+    let mut issues = Vec::new();
+
+    for (insn, line_no) in code.0.iter() {
+        match units.last_mut() {
+            Some(unit) if unit.line_no.row == line_no.row => {
+                unit.line_no = unit.line_no.to(line_no);
+            }
+            _ => units.push(Unit {
+                line_no: *line_no,
+                words: 0,
+            }),
+        }
+        let cur = units.len() - 1;
+
+        for r in insn.reads() {
+            if let Some(addr) = r.addr() {
+                if addr != 0 && !written.contains_key(&addr) {
+                    unwritten_reads.push((addr, *line_no));
+                }
+            }
+        }
+
+        let writes = insn.writes();
+        units[cur].words += writes.len();
+        for addr in writes {
+            match written.get(&addr) {
+                Some((_, prev)) if *prev != cur => {
+                    overlaps
+                        .entry((*prev, cur))
+                        .or_default()
+                        .push((addr, *line_no));
+                }
+                Some(_) => {}
+                None => {
+                    written.insert(addr, (*line_no, cur));
+                }
+            }
+        }
+    }
+
+    for ((prev, cur), addrs) in overlaps {
+        if units[prev].words == 1 && units[cur].words == 1 {
+            let (addr, second) = addrs[0];
+            issues.push(WomIssue::DoubleWrite(addr, written[&addr].0, second));
+        } else {
+            let start = addrs.iter().map(|(addr, _)| *addr).min().unwrap();
+            let end = addrs.iter().map(|(addr, _)| *addr).max().unwrap() + 1;
+            issues.push(WomIssue::OverlappingWrite(
+                start..end,
+                units[prev].line_no,
+                units[cur].line_no,
+            ));
+        }
+    }
+
+    for (addr, line_no) in unwritten_reads {
+        issues.push(WomIssue::ReadBeforeWrite(
+            addr,
+            line_no,
+            written.get(&addr).map(|(write, _)| *write),
+        ));
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structures::StructuredInstruction as I;
+
+    fn code(rows: Vec<Vec<I>>) -> Code {
+        let mut code = Code::default();
+        for (row, insns) in rows.into_iter().enumerate() {
+            for (slot, insn) in insns.into_iter().enumerate() {
+                code.push(insn, LineNo::new(row + 1, slot));
+            }
+        }
+        code
+    }
+
+    #[test]
+    fn write_once() {
+        let code = code(vec![
+            vec![
+                I::CONST(1, 2, 0),
+                I::CONST(2, 3, 0),
+                I::ADD(3, 1.into(), 2.into()),
+            ],
+            vec![I::MUL(4, 3.into(), 0.into())],
+            vec![I::POSEIDON_STORE(0, 8)],
+            vec![I::ADD(16, 8.into(), 15.into())],
+        ]);
+        assert_eq!(check(&code), vec![]);
+    }
+
+    #[test]
+    fn double_write() {
+        let code = code(vec![vec![I::CONST(5, 1, 0)], vec![I::CONST(5, 2, 0)]]);
+        assert_eq!(
+            check(&code),
+            vec![WomIssue::DoubleWrite(
+                5,
+                LineNo::new(1, 0),
+                LineNo::new(2, 0)
+            )]
+        );
+    }
+
+    #[test]
+    fn read_before_write() {
+        let code = code(vec![
+            vec![I::ADD(7, 9.into(), 11.into())],
+            vec![I::CONST(9, 1, 0)],
+        ]);
+        assert_eq!(
+            check(&code),
+            vec![
+                WomIssue::ReadBeforeWrite(9, LineNo::new(1, 0), Some(LineNo::new(2, 0))),
+                WomIssue::ReadBeforeWrite(11, LineNo::new(1, 0), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn overlapping_write() {
+        let code = code(vec![
+            vec![I::POSEIDON_STORE(0, 20)],
+            vec![I::CONST(22, 1, 0), I::CONST(23, 1, 0), I::CONST(30, 1, 0)],
+        ]);
+        assert_eq!(
+            check(&code),
+            vec![WomIssue::OverlappingWrite(
+                22..24,
+                LineNo::new(1, 0),
+                LineNo::new(2, 0).to(&LineNo::new(2, 2))
+            )]
+        );
+    }
+}